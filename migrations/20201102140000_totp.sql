@@ -0,0 +1,10 @@
+ALTER TABLE app_user
+ADD COLUMN totp_secret TEXT,
+ADD COLUMN totp_enabled BOOL NOT NULL DEFAULT FALSE;
+CREATE TABLE recovery_code(
+    id UUID NOT NULL PRIMARY KEY DEFAULT uuid_generate_v4(),
+    created TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    app_user_id UUID NOT NULL REFERENCES app_user(id),
+    code_hash TEXT NOT NULL,
+    used TIMESTAMPTZ
+);
@@ -0,0 +1,11 @@
+CREATE TABLE api_key(
+    id UUID NOT NULL PRIMARY KEY DEFAULT uuid_generate_v4(),
+    created TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    app_user_id UUID NOT NULL REFERENCES app_user(id),
+    key_name TEXT NOT NULL,
+    key_prefix TEXT NOT NULL,
+    key_hash TEXT NOT NULL UNIQUE,
+    scopes TEXT [] NOT NULL,
+    last_used TIMESTAMPTZ,
+    revoked TIMESTAMPTZ
+);
@@ -0,0 +1,15 @@
+CREATE TABLE user_identity(
+    id UUID NOT NULL PRIMARY KEY DEFAULT uuid_generate_v4(),
+    created TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    app_user_id UUID NOT NULL REFERENCES app_user(id),
+    issuer TEXT NOT NULL,
+    subject TEXT NOT NULL,
+    email TEXT,
+    UNIQUE (issuer, subject)
+);
+CREATE TABLE oidc_login(
+    oidc_state TEXT NOT NULL PRIMARY KEY,
+    created TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    code_verifier TEXT NOT NULL,
+    nonce TEXT NOT NULL
+);
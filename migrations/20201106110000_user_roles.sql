@@ -0,0 +1,8 @@
+ALTER TABLE app_user
+ADD COLUMN user_role TEXT NOT NULL DEFAULT 'user' CHECK(
+        user_role IN ('user', 'curator', 'moderator', 'admin')
+    );
+UPDATE app_user
+SET user_role = 'admin'
+WHERE is_admin;
+ALTER TABLE app_user DROP COLUMN is_admin;
@@ -0,0 +1,17 @@
+CREATE TABLE album(
+    id UUID NOT NULL PRIMARY KEY DEFAULT uuid_generate_v4(),
+    created TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    app_user_id UUID NOT NULL REFERENCES app_user(id),
+    album_name TEXT NOT NULL,
+    visibility TEXT NOT NULL DEFAULT 'private' CHECK(
+        visibility IN ('public', 'unlisted', 'private')
+    )
+);
+CREATE TABLE album_image(
+    album_id UUID NOT NULL REFERENCES album(id),
+    image_id UUID NOT NULL REFERENCES image(id),
+    position INTEGER NOT NULL,
+    added TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (album_id, image_id)
+);
+CREATE INDEX album_app_user_id_idx ON album(app_user_id);
@@ -0,0 +1,10 @@
+CREATE TABLE comment(
+    id UUID NOT NULL PRIMARY KEY DEFAULT uuid_generate_v4(),
+    created TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    edited TIMESTAMPTZ,
+    image_id UUID NOT NULL REFERENCES image(id),
+    app_user_id UUID NOT NULL REFERENCES app_user(id),
+    parent_id UUID REFERENCES comment(id) ON DELETE CASCADE,
+    body TEXT NOT NULL
+);
+CREATE INDEX comment_image_id_created_idx ON comment(image_id, created);
@@ -0,0 +1,12 @@
+CREATE TABLE tag(
+    id UUID NOT NULL PRIMARY KEY DEFAULT uuid_generate_v4(),
+    created TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    tag_name TEXT NOT NULL UNIQUE
+);
+CREATE TABLE image_tag(
+    image_id UUID NOT NULL REFERENCES image(id),
+    tag_id UUID NOT NULL REFERENCES tag(id),
+    PRIMARY KEY (image_id, tag_id)
+);
+CREATE INDEX image_tag_tag_id_idx ON image_tag(tag_id);
+CREATE INDEX tag_name_trgm_idx ON tag USING GIN (tag_name gin_trgm_ops);
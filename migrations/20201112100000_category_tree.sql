@@ -0,0 +1,30 @@
+ALTER TABLE category
+ADD COLUMN parent_id UUID REFERENCES category(id),
+    ADD COLUMN slug TEXT,
+    ADD COLUMN description TEXT,
+    ADD COLUMN cover_image_id UUID REFERENCES image(id);
+UPDATE category
+SET slug = TRIM(
+        BOTH '-'
+        FROM LOWER(
+                REGEXP_REPLACE(category_name, '[^A-Za-z0-9]+', '-', 'g')
+            )
+    );
+-- Names that only differ in case or punctuation would get the same slug.
+UPDATE category c
+SET slug = TRIM(
+        LEADING '-'
+        FROM c.slug || '-' || SUBSTR(c.id::TEXT, 1, 8)
+    )
+WHERE c.slug = ''
+    OR EXISTS (
+        SELECT 1
+        FROM category o
+        WHERE o.slug = c.slug
+            AND o.id < c.id
+    );
+ALTER TABLE category
+ALTER COLUMN slug
+SET NOT NULL,
+    ADD CONSTRAINT category_slug_key UNIQUE (slug);
+CREATE INDEX category_parent_id_idx ON category(parent_id);
@@ -0,0 +1,14 @@
+ALTER TABLE image
+ADD COLUMN visibility TEXT NOT NULL DEFAULT 'public' CHECK(
+        visibility IN ('public', 'unlisted', 'private')
+    );
+CREATE TABLE share_link(
+    id UUID NOT NULL PRIMARY KEY DEFAULT uuid_generate_v4(),
+    created TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    app_user_id UUID NOT NULL REFERENCES app_user(id),
+    image_id UUID NOT NULL REFERENCES image(id),
+    token TEXT NOT NULL UNIQUE,
+    expires TIMESTAMPTZ,
+    revoked TIMESTAMPTZ
+);
+CREATE INDEX share_link_image_id_idx ON share_link(image_id);
@@ -0,0 +1,15 @@
+CREATE TABLE image_metadata(
+    image_id UUID NOT NULL PRIMARY KEY REFERENCES image(id),
+    camera_make TEXT,
+    camera_model TEXT,
+    lens_model TEXT,
+    exposure_time TEXT,
+    f_number DOUBLE PRECISION,
+    iso INTEGER,
+    focal_length DOUBLE PRECISION,
+    captured TIMESTAMPTZ,
+    orientation SMALLINT
+);
+CREATE INDEX image_metadata_captured_idx ON image_metadata(captured);
+ALTER TABLE app_user
+ADD COLUMN keep_image_location BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,6 @@
+ALTER TABLE image
+ADD COLUMN latitude DOUBLE PRECISION CHECK(latitude BETWEEN -90 AND 90),
+ADD COLUMN longitude DOUBLE PRECISION CHECK(longitude BETWEEN -180 AND 180),
+ADD CONSTRAINT image_location_check CHECK((latitude IS NULL) = (longitude IS NULL));
+CREATE INDEX image_location_idx ON image(latitude, longitude)
+WHERE latitude IS NOT NULL;
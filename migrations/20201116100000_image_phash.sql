@@ -0,0 +1,5 @@
+ALTER TABLE image
+ADD COLUMN phash BIGINT,
+ADD COLUMN duplicate_of UUID REFERENCES image(id);
+CREATE INDEX image_phash_idx ON image(app_user_id)
+WHERE phash IS NOT NULL;
@@ -0,0 +1,13 @@
+CREATE TABLE upload_session(
+    id UUID NOT NULL PRIMARY KEY DEFAULT uuid_generate_v4(),
+    created TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    app_user_id UUID NOT NULL REFERENCES app_user(id),
+    image_id UUID NOT NULL REFERENCES image(id),
+    upload_length BIGINT NOT NULL CHECK(upload_length > 0),
+    upload_offset BIGINT NOT NULL DEFAULT 0 CHECK(
+        upload_offset BETWEEN 0 AND upload_length
+    ),
+    checksum TEXT NOT NULL,
+    file_extension TEXT NOT NULL
+);
+CREATE INDEX upload_session_image_id_idx ON upload_session(image_id);
@@ -0,0 +1,9 @@
+ALTER TABLE image
+ADD COLUMN file_size BIGINT CHECK (file_size >= 0);
+-- Limits set by admins, the configured defaults apply where they are NULL.
+CREATE TABLE user_quota (
+    app_user_id UUID PRIMARY KEY REFERENCES app_user(id) ON DELETE CASCADE,
+    max_bytes BIGINT CHECK (max_bytes >= 0),
+    max_images BIGINT CHECK (max_images >= 0),
+    updated TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,59 @@
+-- Deleting an image removes everything that belongs to it.
+ALTER TABLE image_category DROP CONSTRAINT image_category_image_id_fkey,
+ADD CONSTRAINT image_category_image_id_fkey FOREIGN KEY (image_id) REFERENCES image(id) ON DELETE CASCADE;
+ALTER TABLE rating DROP CONSTRAINT rating_image_id_fkey,
+ADD CONSTRAINT rating_image_id_fkey FOREIGN KEY (image_id) REFERENCES image(id) ON DELETE CASCADE;
+ALTER TABLE album_image DROP CONSTRAINT album_image_image_id_fkey,
+ADD CONSTRAINT album_image_image_id_fkey FOREIGN KEY (image_id) REFERENCES image(id) ON DELETE CASCADE;
+ALTER TABLE comment DROP CONSTRAINT comment_image_id_fkey,
+ADD CONSTRAINT comment_image_id_fkey FOREIGN KEY (image_id) REFERENCES image(id) ON DELETE CASCADE;
+ALTER TABLE image_tag DROP CONSTRAINT image_tag_image_id_fkey,
+ADD CONSTRAINT image_tag_image_id_fkey FOREIGN KEY (image_id) REFERENCES image(id) ON DELETE CASCADE;
+ALTER TABLE category DROP CONSTRAINT category_cover_image_id_fkey,
+ADD CONSTRAINT category_cover_image_id_fkey FOREIGN KEY (cover_image_id) REFERENCES image(id) ON DELETE SET NULL;
+ALTER TABLE share_link DROP CONSTRAINT share_link_image_id_fkey,
+ADD CONSTRAINT share_link_image_id_fkey FOREIGN KEY (image_id) REFERENCES image(id) ON DELETE CASCADE;
+ALTER TABLE image_metadata DROP CONSTRAINT image_metadata_image_id_fkey,
+ADD CONSTRAINT image_metadata_image_id_fkey FOREIGN KEY (image_id) REFERENCES image(id) ON DELETE CASCADE;
+ALTER TABLE image DROP CONSTRAINT image_duplicate_of_fkey,
+ADD CONSTRAINT image_duplicate_of_fkey FOREIGN KEY (duplicate_of) REFERENCES image(id) ON DELETE SET NULL;
+ALTER TABLE upload_session DROP CONSTRAINT upload_session_image_id_fkey,
+ADD CONSTRAINT upload_session_image_id_fkey FOREIGN KEY (image_id) REFERENCES image(id) ON DELETE CASCADE;
+-- Hidden images are left out of searches.
+ALTER TABLE image
+ADD COLUMN hidden BOOLEAN NOT NULL DEFAULT FALSE;
+-- Kept after the image is deleted, so there is no reference to it.
+CREATE TABLE moderation_action(
+    id UUID NOT NULL PRIMARY KEY DEFAULT uuid_generate_v4(),
+    created TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    moderator_id UUID NOT NULL REFERENCES app_user(id),
+    image_id UUID NOT NULL,
+    image_owner_id UUID REFERENCES app_user(id) ON DELETE SET NULL,
+    action TEXT NOT NULL CHECK(
+        action IN ('dismiss', 'hide', 'delete', 'warn')
+    ),
+    note TEXT
+);
+CREATE INDEX moderation_action_image_id_idx ON moderation_action(image_id);
+CREATE INDEX moderation_action_image_owner_id_idx ON moderation_action(image_owner_id);
+CREATE TABLE image_report(
+    id UUID NOT NULL PRIMARY KEY DEFAULT uuid_generate_v4(),
+    created TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    image_id UUID NOT NULL REFERENCES image(id) ON DELETE CASCADE,
+    app_user_id UUID NOT NULL REFERENCES app_user(id),
+    reason TEXT NOT NULL CHECK(
+        reason IN (
+            'spam',
+            'nudity',
+            'violence',
+            'harassment',
+            'copyright',
+            'other'
+        )
+    ),
+    details TEXT,
+    moderation_action_id UUID REFERENCES moderation_action(id)
+);
+-- A user can only have one open report of an image.
+CREATE UNIQUE INDEX image_report_open_idx ON image_report(image_id, app_user_id)
+WHERE moderation_action_id IS NULL;
@@ -0,0 +1,23 @@
+-- Security relevant changes, never updated or deleted.
+CREATE TABLE audit_event(
+    id UUID NOT NULL PRIMARY KEY DEFAULT uuid_generate_v4(),
+    created TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    -- Kept after the user is deleted, failed logins have no actor.
+    actor_id UUID,
+    action TEXT NOT NULL,
+    target_type TEXT NOT NULL,
+    target_id TEXT NOT NULL,
+    before JSONB,
+    after JSONB,
+    request_id TEXT
+);
+CREATE INDEX audit_event_created_idx ON audit_event(created);
+CREATE INDEX audit_event_actor_id_idx ON audit_event(actor_id);
+CREATE INDEX audit_event_action_idx ON audit_event(action);
+CREATE FUNCTION audit_event_append_only() RETURNS TRIGGER AS $$
+BEGIN
+    RAISE EXCEPTION 'audit events cannot be changed';
+END;
+$$ LANGUAGE plpgsql;
+CREATE TRIGGER audit_event_append_only BEFORE UPDATE OR DELETE OR TRUNCATE ON audit_event
+FOR EACH STATEMENT EXECUTE PROCEDURE audit_event_append_only();
@@ -0,0 +1,18 @@
+-- Deleted images and categories stay in the trash until they are purged.
+ALTER TABLE image
+ADD COLUMN deleted TIMESTAMPTZ,
+    ADD COLUMN deleted_by UUID REFERENCES app_user(id) ON DELETE SET NULL;
+CREATE INDEX image_deleted_idx ON image(deleted)
+WHERE deleted IS NOT NULL;
+ALTER TABLE category
+ADD COLUMN deleted TIMESTAMPTZ,
+    ADD COLUMN deleted_by UUID REFERENCES app_user(id) ON DELETE SET NULL;
+CREATE INDEX category_deleted_idx ON category(deleted)
+WHERE deleted IS NOT NULL;
+-- Names and slugs of deleted categories can be reused.
+ALTER TABLE category DROP CONSTRAINT category_category_name_key,
+    DROP CONSTRAINT category_slug_key;
+CREATE UNIQUE INDEX category_category_name_key ON category(category_name)
+WHERE deleted IS NULL;
+CREATE UNIQUE INDEX category_slug_key ON category(slug)
+WHERE deleted IS NULL;
@@ -0,0 +1,41 @@
+-- Deleting an account removes everything the user owns.
+ALTER TABLE image DROP CONSTRAINT image_app_user_id_fkey,
+    ADD CONSTRAINT image_app_user_id_fkey FOREIGN KEY (app_user_id) REFERENCES app_user(id) ON DELETE CASCADE;
+ALTER TABLE rating DROP CONSTRAINT rating_app_user_id_fkey,
+    ADD CONSTRAINT rating_app_user_id_fkey FOREIGN KEY (app_user_id) REFERENCES app_user(id) ON DELETE CASCADE;
+ALTER TABLE recovery_code DROP CONSTRAINT recovery_code_app_user_id_fkey,
+    ADD CONSTRAINT recovery_code_app_user_id_fkey FOREIGN KEY (app_user_id) REFERENCES app_user(id) ON DELETE CASCADE;
+ALTER TABLE api_key DROP CONSTRAINT api_key_app_user_id_fkey,
+    ADD CONSTRAINT api_key_app_user_id_fkey FOREIGN KEY (app_user_id) REFERENCES app_user(id) ON DELETE CASCADE;
+ALTER TABLE user_identity DROP CONSTRAINT user_identity_app_user_id_fkey,
+    ADD CONSTRAINT user_identity_app_user_id_fkey FOREIGN KEY (app_user_id) REFERENCES app_user(id) ON DELETE CASCADE;
+ALTER TABLE album DROP CONSTRAINT album_app_user_id_fkey,
+    ADD CONSTRAINT album_app_user_id_fkey FOREIGN KEY (app_user_id) REFERENCES app_user(id) ON DELETE CASCADE;
+ALTER TABLE album_image DROP CONSTRAINT album_image_album_id_fkey,
+    ADD CONSTRAINT album_image_album_id_fkey FOREIGN KEY (album_id) REFERENCES album(id) ON DELETE CASCADE;
+ALTER TABLE share_link DROP CONSTRAINT share_link_app_user_id_fkey,
+    ADD CONSTRAINT share_link_app_user_id_fkey FOREIGN KEY (app_user_id) REFERENCES app_user(id) ON DELETE CASCADE;
+ALTER TABLE upload_session DROP CONSTRAINT upload_session_app_user_id_fkey,
+    ADD CONSTRAINT upload_session_app_user_id_fkey FOREIGN KEY (app_user_id) REFERENCES app_user(id) ON DELETE CASCADE;
+-- Comments, reports and moderation actions are kept without the user.
+ALTER TABLE comment ALTER COLUMN app_user_id DROP NOT NULL,
+    DROP CONSTRAINT comment_app_user_id_fkey,
+    ADD CONSTRAINT comment_app_user_id_fkey FOREIGN KEY (app_user_id) REFERENCES app_user(id) ON DELETE SET NULL;
+ALTER TABLE image_report ALTER COLUMN app_user_id DROP NOT NULL,
+    DROP CONSTRAINT image_report_app_user_id_fkey,
+    ADD CONSTRAINT image_report_app_user_id_fkey FOREIGN KEY (app_user_id) REFERENCES app_user(id) ON DELETE SET NULL;
+ALTER TABLE moderation_action ALTER COLUMN moderator_id DROP NOT NULL,
+    DROP CONSTRAINT moderation_action_moderator_id_fkey,
+    ADD CONSTRAINT moderation_action_moderator_id_fkey FOREIGN KEY (moderator_id) REFERENCES app_user(id) ON DELETE SET NULL;
+-- ZIP archives of the data of a user, built in the background.
+CREATE TABLE account_export(
+    id UUID NOT NULL PRIMARY KEY DEFAULT uuid_generate_v4(),
+    created TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    app_user_id UUID NOT NULL REFERENCES app_user(id) ON DELETE CASCADE,
+    export_status TEXT NOT NULL DEFAULT 'pending' CHECK(
+        export_status IN ('pending', 'ready', 'failed')
+    ),
+    completed TIMESTAMPTZ,
+    file_size BIGINT
+);
+CREATE INDEX account_export_app_user_id_idx ON account_export(app_user_id);
@@ -0,0 +1,26 @@
+-- Background jobs, claimed by workers with SKIP LOCKED.
+CREATE TABLE job(
+    id UUID NOT NULL PRIMARY KEY DEFAULT uuid_generate_v4(),
+    created TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    job_type TEXT NOT NULL,
+    payload JSONB NOT NULL,
+    job_status TEXT NOT NULL DEFAULT 'queued' CHECK(
+        job_status IN ('queued', 'running', 'completed', 'dead')
+    ),
+    attempts INTEGER NOT NULL DEFAULT 0,
+    max_attempts INTEGER NOT NULL CHECK(max_attempts > 0),
+    run_after TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    -- Running jobs are claimed again after this, in case the worker died.
+    locked_until TIMESTAMPTZ,
+    last_error TEXT,
+    completed TIMESTAMPTZ
+);
+CREATE INDEX job_queued_idx ON job(run_after)
+WHERE job_status = 'queued';
+CREATE INDEX job_running_idx ON job(locked_until)
+WHERE job_status = 'running';
+-- Recurring jobs, enqueued by whichever worker finds them due first.
+CREATE TABLE job_schedule(
+    job_type TEXT NOT NULL PRIMARY KEY,
+    next_run TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,36 @@
+-- Events about the content of a user, e.g. ratings and comments of their images.
+CREATE TABLE notification(
+    id UUID NOT NULL PRIMARY KEY DEFAULT uuid_generate_v4(),
+    created TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    app_user_id UUID NOT NULL REFERENCES app_user(id) ON DELETE CASCADE,
+    -- Missing for moderator actions, or if the actor deleted their account.
+    actor_id UUID REFERENCES app_user(id) ON DELETE SET NULL,
+    notification_type TEXT NOT NULL CHECK(
+        notification_type IN (
+            'imageRated',
+            'imageCommented',
+            'commentReplied',
+            'imageModerated',
+            'commentDeleted'
+        )
+    ),
+    image_id UUID REFERENCES image(id) ON DELETE SET NULL,
+    comment_id UUID REFERENCES comment(id) ON DELETE SET NULL,
+    details JSONB,
+    read_date TIMESTAMPTZ
+);
+CREATE INDEX notification_app_user_id_created_idx ON notification(app_user_id, created);
+CREATE INDEX notification_unread_idx ON notification(app_user_id)
+WHERE read_date IS NULL;
+-- Connected clients are notified by whichever server listens on the channel.
+CREATE FUNCTION notification_publish() RETURNS TRIGGER AS $$
+BEGIN
+    PERFORM pg_notify(
+        'notification',
+        json_build_object('id', NEW.id, 'appUserId', NEW.app_user_id)::text
+    );
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+CREATE TRIGGER notification_publish AFTER INSERT ON notification
+FOR EACH ROW EXECUTE PROCEDURE notification_publish();
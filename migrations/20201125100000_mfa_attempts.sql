@@ -0,0 +1,5 @@
+-- The last accepted TOTP step, codes of earlier steps cannot be replayed.
+ALTER TABLE app_user
+ADD COLUMN totp_last_step BIGINT,
+ADD COLUMN mfa_failed_attempts INT NOT NULL DEFAULT 0,
+ADD COLUMN mfa_locked_until TIMESTAMPTZ;
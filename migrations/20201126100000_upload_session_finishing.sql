@@ -0,0 +1,3 @@
+-- Set while a request finishes the upload, so it cannot be finished twice at once.
+ALTER TABLE upload_session
+ADD COLUMN finishing BOOLEAN NOT NULL DEFAULT FALSE;
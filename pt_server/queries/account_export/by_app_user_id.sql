@@ -0,0 +1,8 @@
+SELECT
+	*
+FROM
+	account_export
+WHERE
+	app_user_id = $1
+ORDER BY
+	created DESC;
\ No newline at end of file
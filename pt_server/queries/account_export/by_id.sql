@@ -0,0 +1,6 @@
+SELECT
+	*
+FROM
+	account_export
+WHERE
+	id = $1;
\ No newline at end of file
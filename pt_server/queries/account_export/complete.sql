@@ -0,0 +1,5 @@
+UPDATE account_export
+SET export_status = $2,
+	completed = CURRENT_TIMESTAMP,
+	file_size = $3
+WHERE id = $1;
\ No newline at end of file
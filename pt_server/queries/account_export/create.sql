@@ -0,0 +1,3 @@
+INSERT INTO account_export(app_user_id)
+VALUES ($1)
+RETURNING *;
\ No newline at end of file
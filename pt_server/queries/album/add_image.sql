@@ -0,0 +1,10 @@
+INSERT INTO album_image (album_id, image_id, position)
+VALUES (
+		$1,
+		$2,
+		(
+			SELECT COALESCE(MAX(ai.position) + 1, 0)
+			FROM album_image ai
+			WHERE ai.album_id = $1
+		)
+	) ON CONFLICT DO NOTHING;
\ No newline at end of file
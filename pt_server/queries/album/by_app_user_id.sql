@@ -0,0 +1,4 @@
+SELECT *
+FROM album a
+WHERE a.app_user_id = $1
+ORDER BY a.created;
\ No newline at end of file
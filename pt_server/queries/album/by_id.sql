@@ -0,0 +1,3 @@
+SELECT *
+FROM album a
+WHERE a.id = $1;
\ No newline at end of file
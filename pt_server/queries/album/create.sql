@@ -0,0 +1,3 @@
+INSERT INTO album (app_user_id, album_name, visibility)
+VALUES ($1, $2, $3)
+RETURNING id;
\ No newline at end of file
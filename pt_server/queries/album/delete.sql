@@ -0,0 +1,2 @@
+DELETE FROM album
+WHERE album.id = $1;
\ No newline at end of file
@@ -0,0 +1,6 @@
+SELECT ai.image_id
+FROM album_image ai
+	JOIN image i ON i.id = ai.image_id
+WHERE ai.album_id = $1
+	AND i.deleted IS NULL
+ORDER BY ai.position;
\ No newline at end of file
@@ -0,0 +1,14 @@
+SELECT i.*
+FROM album_image ai
+	JOIN image i ON i.id = ai.image_id
+WHERE ai.album_id = $1
+	AND i.upload_date IS NOT NULL
+	AND NOT i.hidden
+	AND i.deleted IS NULL
+	AND (
+		i.visibility <> 'private'
+		OR i.app_user_id = $4
+	)
+ORDER BY ai.position
+OFFSET $2
+LIMIT $3;
\ No newline at end of file
@@ -0,0 +1,5 @@
+SELECT *
+FROM album a
+WHERE a.app_user_id = $1
+	AND a.visibility = 'public'
+ORDER BY a.created;
\ No newline at end of file
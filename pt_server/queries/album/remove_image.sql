@@ -0,0 +1,3 @@
+DELETE FROM album_image
+WHERE album_id = $1
+	AND image_id = $2;
\ No newline at end of file
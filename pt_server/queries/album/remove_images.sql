@@ -0,0 +1,2 @@
+DELETE FROM album_image
+WHERE album_id = $1;
\ No newline at end of file
@@ -0,0 +1,4 @@
+UPDATE album_image
+SET position = $3
+WHERE album_id = $1
+	AND image_id = $2;
\ No newline at end of file
@@ -0,0 +1,5 @@
+UPDATE album
+SET album_name = $2,
+	visibility = $3
+WHERE
+	id = $1;
\ No newline at end of file
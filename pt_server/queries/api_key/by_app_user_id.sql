@@ -0,0 +1,5 @@
+SELECT *
+FROM api_key k
+WHERE k.app_user_id = $1
+	AND k.revoked IS NULL
+ORDER BY k.created;
\ No newline at end of file
@@ -0,0 +1,3 @@
+SELECT *
+FROM api_key k
+WHERE k.id = $1;
\ No newline at end of file
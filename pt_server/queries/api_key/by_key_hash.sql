@@ -0,0 +1,4 @@
+SELECT *
+FROM api_key k
+WHERE k.key_hash = $1
+	AND k.revoked IS NULL;
\ No newline at end of file
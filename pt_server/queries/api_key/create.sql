@@ -0,0 +1,3 @@
+INSERT INTO api_key (app_user_id, key_name, key_prefix, key_hash, scopes)
+VALUES ($1, $2, $3, $4, $5)
+RETURNING id;
\ No newline at end of file
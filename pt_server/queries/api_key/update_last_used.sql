@@ -0,0 +1,3 @@
+UPDATE api_key
+SET last_used = CURRENT_TIMESTAMP
+WHERE id = $1;
\ No newline at end of file
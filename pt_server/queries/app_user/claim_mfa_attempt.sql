@@ -0,0 +1,14 @@
+UPDATE app_user
+SET mfa_failed_attempts = CASE
+		WHEN mfa_failed_attempts + 1 >= $2 THEN 0
+		ELSE mfa_failed_attempts + 1
+	END,
+	mfa_locked_until = CASE
+		WHEN mfa_failed_attempts + 1 >= $2 THEN $3
+		ELSE mfa_locked_until
+	END
+WHERE id = $1
+	AND (
+		mfa_locked_until IS NULL
+		OR mfa_locked_until <= CURRENT_TIMESTAMP
+	);
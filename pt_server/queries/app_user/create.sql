@@ -1,4 +1,4 @@
 INSERT
 	INTO
-	app_user (email, password_hash, is_admin)
+	app_user (email, password_hash, user_role)
 VALUES($1, $2, $3) RETURNING id;
\ No newline at end of file
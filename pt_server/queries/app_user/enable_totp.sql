@@ -0,0 +1,4 @@
+UPDATE app_user
+SET totp_enabled = TRUE,
+	totp_last_step = $2
+WHERE id = $1;
@@ -0,0 +1,4 @@
+UPDATE app_user
+SET mfa_failed_attempts = 0,
+	mfa_locked_until = NULL
+WHERE id = $1;
@@ -3,6 +3,9 @@ UPDATE
 SET
 	email = $2,
 	password_hash = $3,
-	is_admin = $4
+	user_role = $4,
+	totp_secret = $5,
+	totp_enabled = $6,
+	keep_image_location = $7
 WHERE
 	app_user.id = $1;
\ No newline at end of file
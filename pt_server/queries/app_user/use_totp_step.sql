@@ -0,0 +1,7 @@
+UPDATE app_user
+SET totp_last_step = $2
+WHERE id = $1
+	AND (
+		totp_last_step IS NULL
+		OR totp_last_step < $2
+	);
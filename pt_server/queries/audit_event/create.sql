@@ -0,0 +1,3 @@
+INSERT INTO audit_event (actor_id, action, target_type, target_id, before, after, request_id)
+VALUES ($1, $2, $3, $4, $5, $6, $7)
+RETURNING audit_event.id;
\ No newline at end of file
@@ -0,0 +1,25 @@
+SELECT
+	*
+FROM
+	audit_event e
+WHERE
+	(
+		$1::UUID IS NULL
+		OR e.actor_id = $1
+	)
+	AND (
+		$2::TEXT IS NULL
+		OR e.action = $2
+	)
+	AND (
+		$3::TIMESTAMPTZ IS NULL
+		OR e.created >= $3
+	)
+	AND (
+		$4::TIMESTAMPTZ IS NULL
+		OR e.created < $4
+	)
+ORDER BY
+	e.created DESC
+OFFSET $5
+LIMIT $6;
\ No newline at end of file
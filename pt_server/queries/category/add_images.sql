@@ -0,0 +1,6 @@
+INSERT INTO image_category (category_id, image_id)
+SELECT $1,
+	i.id
+FROM image i
+WHERE i.id = ANY($2)
+	AND i.deleted IS NULL ON CONFLICT DO NOTHING;
\ No newline at end of file
@@ -1 +1 @@
-SELECT * FROM category;
\ No newline at end of file
+SELECT * FROM category c WHERE c.deleted IS NULL;
\ No newline at end of file
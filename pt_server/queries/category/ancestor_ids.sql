@@ -0,0 +1,13 @@
+WITH RECURSIVE ancestors AS (
+	SELECT c.id,
+		c.parent_id
+	FROM category c
+	WHERE c.id = $1
+	UNION
+	SELECT c.id,
+		c.parent_id
+	FROM category c
+		JOIN ancestors a ON c.id = a.parent_id
+)
+SELECT a.id AS "id!"
+FROM ancestors a;
\ No newline at end of file
@@ -1,3 +1,4 @@
 SELECT *
 FROM category c
-WHERE c.id = $1;
\ No newline at end of file
+WHERE c.id = $1
+	AND c.deleted IS NULL;
\ No newline at end of file
@@ -1,8 +1,7 @@
-SELECT c.id,
-	c.created,
-	c.category_name
+SELECT c.*
 FROM category c
-WHERE EXISTS (
+WHERE c.deleted IS NULL
+	AND EXISTS (
 		SELECT ic.category_id
 		FROM image_category ic
 		WHERE ic.image_id = $1
@@ -0,0 +1,4 @@
+SELECT *
+FROM category c
+WHERE c.slug = $1
+	AND c.deleted IS NULL;
\ No newline at end of file
@@ -1,3 +1,3 @@
-INSERT INTO category (category_name)
-VALUES ($1)
+INSERT INTO category (category_name, slug, parent_id, description)
+VALUES ($1, $2, $3, $4)
 RETURNING category.id;
\ No newline at end of file
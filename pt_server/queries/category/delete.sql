@@ -1,2 +0,0 @@
-DELETE FROM category
-WHERE category.id = $1;
\ No newline at end of file
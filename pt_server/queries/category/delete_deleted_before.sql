@@ -0,0 +1,2 @@
+DELETE FROM category
+WHERE deleted < $1;
\ No newline at end of file
@@ -0,0 +1,6 @@
+SELECT EXISTS (
+		SELECT 1
+		FROM category c
+		WHERE c.parent_id = $1
+			AND c.deleted IS NULL
+	) AS "exists!";
\ No newline at end of file
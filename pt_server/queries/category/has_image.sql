@@ -0,0 +1,6 @@
+SELECT EXISTS (
+		SELECT 1
+		FROM image_category ic
+		WHERE ic.category_id = $1
+			AND ic.image_id = $2
+	) AS "exists!";
\ No newline at end of file
@@ -2,5 +2,7 @@ SELECT
    COUNT(*) 
 FROM 
    image_category ic
+   JOIN image i ON i.id = ic.image_id
 WHERE
-   ic.category_id = $1;
\ No newline at end of file
+   ic.category_id = $1
+   AND i.deleted IS NULL;
\ No newline at end of file
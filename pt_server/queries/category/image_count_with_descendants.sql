@@ -0,0 +1,18 @@
+WITH RECURSIVE descendants AS (
+	SELECT c.id
+	FROM category c
+	WHERE c.id = $1
+	UNION
+	SELECT c.id
+	FROM category c
+		JOIN descendants d ON c.parent_id = d.id
+	WHERE c.deleted IS NULL
+)
+SELECT COUNT(DISTINCT ic.image_id)
+FROM image_category ic
+	JOIN image i ON i.id = ic.image_id
+WHERE ic.category_id IN (
+		SELECT id
+		FROM descendants
+	)
+	AND i.deleted IS NULL;
\ No newline at end of file
@@ -0,0 +1,4 @@
+SELECT ic.image_id
+FROM image_category ic
+WHERE ic.category_id = $1
+	AND ic.image_id = ANY($2);
\ No newline at end of file
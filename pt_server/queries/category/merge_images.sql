@@ -0,0 +1,7 @@
+INSERT INTO image_category (category_id, image_id)
+SELECT $2,
+	ic.image_id
+FROM image_category ic
+	JOIN image i ON i.id = ic.image_id
+WHERE ic.category_id = $1
+	AND i.deleted IS NULL ON CONFLICT DO NOTHING;
\ No newline at end of file
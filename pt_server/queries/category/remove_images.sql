@@ -1,2 +1,3 @@
 DELETE FROM image_category
-WHERE category_id = $1;
\ No newline at end of file
+WHERE category_id = $1
+	AND image_id = ANY($2);
\ No newline at end of file
@@ -0,0 +1,6 @@
+DELETE FROM image_category
+WHERE category_id IN (
+		SELECT c.id
+		FROM category c
+		WHERE c.deleted < $1
+	);
\ No newline at end of file
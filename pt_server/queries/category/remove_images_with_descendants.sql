@@ -0,0 +1,14 @@
+WITH RECURSIVE descendants AS (
+	SELECT c.id
+	FROM category c
+	WHERE c.id = $1
+	UNION
+	SELECT c.id
+	FROM category c
+		JOIN descendants d ON c.parent_id = d.id
+)
+DELETE FROM image_category
+WHERE category_id IN (
+		SELECT id
+		FROM descendants
+	);
\ No newline at end of file
@@ -0,0 +1,3 @@
+UPDATE category
+SET parent_id = $2
+WHERE parent_id = $1;
\ No newline at end of file
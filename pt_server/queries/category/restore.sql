@@ -0,0 +1,4 @@
+UPDATE category
+SET deleted = NULL,
+	deleted_by = NULL
+WHERE id = ANY($1);
\ No newline at end of file
@@ -0,0 +1,5 @@
+UPDATE category
+SET deleted = CURRENT_TIMESTAMP,
+	deleted_by = $2
+WHERE
+	id = $1;
\ No newline at end of file
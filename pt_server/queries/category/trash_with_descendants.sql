@@ -0,0 +1,17 @@
+WITH RECURSIVE descendants AS (
+	SELECT c.id
+	FROM category c
+	WHERE c.id = $1
+	UNION
+	SELECT c.id
+	FROM category c
+		JOIN descendants d ON c.parent_id = d.id
+	WHERE c.deleted IS NULL
+)
+UPDATE category
+SET deleted = CURRENT_TIMESTAMP,
+	deleted_by = $2
+WHERE id IN (
+		SELECT id
+		FROM descendants
+	);
\ No newline at end of file
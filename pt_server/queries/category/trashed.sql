@@ -0,0 +1,4 @@
+SELECT *
+FROM category c
+WHERE c.deleted IS NOT NULL
+ORDER BY c.deleted DESC;
\ No newline at end of file
@@ -1,4 +1,7 @@
 UPDATE category
-SET category_name = $2
+SET category_name = $2,
+	parent_id = $3,
+	description = $4,
+	cover_image_id = $5
 WHERE
 	id = $1;
\ No newline at end of file
@@ -0,0 +1,3 @@
+SELECT *
+FROM comment c
+WHERE c.id = $1;
\ No newline at end of file
@@ -0,0 +1,12 @@
+SELECT *
+FROM comment c
+WHERE c.image_id = $1
+	AND EXISTS (
+		SELECT 1
+		FROM image i
+		WHERE i.id = c.image_id
+			AND i.deleted IS NULL
+	)
+ORDER BY c.created
+OFFSET $2
+LIMIT $3;
\ No newline at end of file
@@ -0,0 +1,3 @@
+SELECT COUNT(*)
+FROM comment c
+WHERE c.image_id = $1;
\ No newline at end of file
@@ -0,0 +1,3 @@
+INSERT INTO comment (image_id, app_user_id, parent_id, body)
+VALUES ($1, $2, $3, $4)
+RETURNING id;
\ No newline at end of file
@@ -0,0 +1,2 @@
+DELETE FROM comment
+WHERE comment.id = $1;
\ No newline at end of file
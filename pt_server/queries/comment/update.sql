@@ -0,0 +1,5 @@
+UPDATE comment
+SET body = $2,
+	edited = $3
+WHERE
+	id = $1;
\ No newline at end of file
@@ -0,0 +1,8 @@
+SELECT
+	*
+FROM
+	image i
+WHERE
+	i.app_user_id = $1
+ORDER BY
+	i.created;
\ No newline at end of file
@@ -3,4 +3,5 @@ SELECT
 FROM
 	image i
 WHERE
-	i.app_user_id = $1;
\ No newline at end of file
+	i.app_user_id = $1
+	AND i.deleted IS NULL;
\ No newline at end of file
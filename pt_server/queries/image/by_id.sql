@@ -1,3 +1,4 @@
 SELECT *
 FROM image i
-WHERE i.id = $1;
\ No newline at end of file
+WHERE i.id = $1
+	AND i.deleted IS NULL;
\ No newline at end of file
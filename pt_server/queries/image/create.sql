@@ -1,4 +1,4 @@
 INSERT
 	INTO
-	image (app_user_id, title, description)
-VALUES ($1, $2, $3) RETURNING id;
\ No newline at end of file
+	image (app_user_id, title, description, visibility)
+VALUES ($1, $2, $3, $4) RETURNING id;
\ No newline at end of file
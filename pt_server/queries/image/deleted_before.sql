@@ -0,0 +1,3 @@
+SELECT *
+FROM image i
+WHERE i.deleted < $1;
\ No newline at end of file
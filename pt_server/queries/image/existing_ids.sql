@@ -0,0 +1,4 @@
+SELECT i.id
+FROM image i
+WHERE i.id = ANY($1)
+	AND i.deleted IS NULL;
\ No newline at end of file
@@ -0,0 +1,17 @@
+INSERT INTO
+	image (
+		id,
+		created,
+		upload_date,
+		title,
+		description,
+		app_user_id,
+		visibility,
+		latitude,
+		longitude,
+		phash,
+		duplicate_of,
+		file_size
+	)
+VALUES
+	($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12);
\ No newline at end of file
@@ -0,0 +1,39 @@
+SELECT
+	FLOOR((i.latitude + 90) / $1)::INTEGER AS "cell_row!",
+	FLOOR((i.longitude + 180) / $1)::INTEGER AS "cell_column!",
+	COUNT(*) AS "count!",
+	AVG(i.latitude) AS "latitude!",
+	AVG(i.longitude) AS "longitude!"
+FROM
+	image i
+WHERE
+	i.latitude IS NOT NULL
+	AND i.upload_date IS NOT NULL
+	AND NOT i.hidden
+	AND i.deleted IS NULL
+	AND (
+		i.visibility = 'public'
+		OR i.app_user_id = $2
+	)
+	AND (
+		$3::DOUBLE PRECISION IS NULL
+		OR (
+			i.latitude BETWEEN $3 AND $5
+			AND (
+				(
+					$4::DOUBLE PRECISION <= $6::DOUBLE PRECISION
+					AND i.longitude BETWEEN $4 AND $6
+				)
+				OR (
+					$4 > $6
+					AND (
+						i.longitude >= $4
+						OR i.longitude <= $6
+					)
+				)
+			)
+		)
+	)
+GROUP BY
+	1,
+	2;
\ No newline at end of file
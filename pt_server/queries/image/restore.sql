@@ -0,0 +1,5 @@
+UPDATE image
+SET deleted = NULL,
+	deleted_by = NULL
+WHERE
+	id = $1;
\ No newline at end of file
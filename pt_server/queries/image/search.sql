@@ -3,8 +3,87 @@ SELECT
 FROM
 	image i
 WHERE
-	i.title % $1
-	OR i.description % $1
+	NOT i.hidden
+	AND i.deleted IS NULL
+	AND (
+		i.title % $1
+		OR i.description % $1
+	)
+	AND (
+		i.visibility = 'public'
+		OR i.app_user_id = $5
+	)
+	AND (
+		CARDINALITY($4::TEXT []) = 0
+		OR i.id IN (
+			SELECT
+				it.image_id
+			FROM
+				image_tag it
+				JOIN tag t ON t.id = it.tag_id
+			WHERE
+				t.tag_name = ANY($4)
+			GROUP BY
+				it.image_id
+			HAVING
+				COUNT(*) = CARDINALITY($4::TEXT [])
+		)
+	)
+	AND (
+		(
+			$6::TIMESTAMPTZ IS NULL
+			AND $7::TIMESTAMPTZ IS NULL
+		)
+		OR EXISTS (
+			SELECT
+				1
+			FROM
+				image_metadata im
+			WHERE
+				im.image_id = i.id
+				AND (
+					$6::TIMESTAMPTZ IS NULL
+					OR im.captured >= $6
+				)
+				AND (
+					$7::TIMESTAMPTZ IS NULL
+					OR im.captured < $7
+				)
+		)
+	)
+	AND (
+		$8::DOUBLE PRECISION IS NULL
+		OR (
+			i.latitude BETWEEN $8 AND $10
+			AND (
+				(
+					$9::DOUBLE PRECISION <= $11::DOUBLE PRECISION
+					AND i.longitude BETWEEN $9 AND $11
+				)
+				OR (
+					$9 > $11
+					AND (
+						i.longitude >= $9
+						OR i.longitude <= $11
+					)
+				)
+			)
+		)
+	)
+	AND (
+		$12::DOUBLE PRECISION IS NULL
+		OR (
+			i.latitude BETWEEN $12 - DEGREES($14::DOUBLE PRECISION / 6371000.0) AND $12 + DEGREES($14::DOUBLE PRECISION / 6371000.0)
+			AND 2 * 6371000.0 * ASIN(
+				LEAST(
+					1,
+					SQRT(
+						POWER(SIN(RADIANS(i.latitude - $12) / 2), 2) + COS(RADIANS($12)) * COS(RADIANS(i.latitude)) * POWER(SIN(RADIANS(i.longitude - $13) / 2), 2)
+					)
+				)
+			) <= $14
+		)
+	)
 ORDER BY
 	SIMILARITY(i.title, $1) DESC
 OFFSET $2
@@ -2,5 +2,83 @@ SELECT
 	*
 FROM
 	image i
+WHERE
+	NOT i.hidden
+	AND i.deleted IS NULL
+	AND (
+		i.visibility = 'public'
+		OR i.app_user_id = $4
+	)
+	AND (
+		CARDINALITY($3::TEXT []) = 0
+		OR i.id IN (
+			SELECT
+				it.image_id
+			FROM
+				image_tag it
+				JOIN tag t ON t.id = it.tag_id
+			WHERE
+				t.tag_name = ANY($3)
+			GROUP BY
+				it.image_id
+			HAVING
+				COUNT(*) = CARDINALITY($3::TEXT [])
+		)
+	)
+	AND (
+		(
+			$5::TIMESTAMPTZ IS NULL
+			AND $6::TIMESTAMPTZ IS NULL
+		)
+		OR EXISTS (
+			SELECT
+				1
+			FROM
+				image_metadata im
+			WHERE
+				im.image_id = i.id
+				AND (
+					$5::TIMESTAMPTZ IS NULL
+					OR im.captured >= $5
+				)
+				AND (
+					$6::TIMESTAMPTZ IS NULL
+					OR im.captured < $6
+				)
+		)
+	)
+	AND (
+		$7::DOUBLE PRECISION IS NULL
+		OR (
+			i.latitude BETWEEN $7 AND $9
+			AND (
+				(
+					$8::DOUBLE PRECISION <= $10::DOUBLE PRECISION
+					AND i.longitude BETWEEN $8 AND $10
+				)
+				OR (
+					$8 > $10
+					AND (
+						i.longitude >= $8
+						OR i.longitude <= $10
+					)
+				)
+			)
+		)
+	)
+	AND (
+		$11::DOUBLE PRECISION IS NULL
+		OR (
+			i.latitude BETWEEN $11 - DEGREES($13::DOUBLE PRECISION / 6371000.0) AND $11 + DEGREES($13::DOUBLE PRECISION / 6371000.0)
+			AND 2 * 6371000.0 * ASIN(
+				LEAST(
+					1,
+					SQRT(
+						POWER(SIN(RADIANS(i.latitude - $11) / 2), 2) + COS(RADIANS($11)) * COS(RADIANS(i.latitude)) * POWER(SIN(RADIANS(i.longitude - $12) / 2), 2)
+					)
+				)
+			) <= $13
+		)
+	)
 OFFSET $1
 LIMIT $2;
\ No newline at end of file
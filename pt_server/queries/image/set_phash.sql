@@ -0,0 +1,5 @@
+UPDATE image
+SET phash = $2,
+	duplicate_of = $3
+WHERE
+	id = $1;
@@ -0,0 +1,19 @@
+SELECT
+	i.id,
+	LENGTH(REPLACE((i.phash # $2)::BIT(64)::TEXT, '0', ''))::INTEGER AS "distance!"
+FROM
+	image i
+WHERE
+	i.phash IS NOT NULL
+	AND i.upload_date IS NOT NULL
+	AND i.deleted IS NULL
+	AND i.id <> $1
+	AND (
+		$5::UUID IS NULL
+		OR i.app_user_id = $5
+	)
+	AND LENGTH(REPLACE((i.phash # $2)::BIT(64)::TEXT, '0', '')) <= $3
+ORDER BY
+	2,
+	i.upload_date
+LIMIT $4;
\ No newline at end of file
@@ -0,0 +1,6 @@
+UPDATE image
+SET deleted = CURRENT_TIMESTAMP,
+	deleted_by = $2
+WHERE
+	id = $1
+	AND deleted IS NULL;
\ No newline at end of file
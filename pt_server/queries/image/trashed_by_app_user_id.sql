@@ -0,0 +1,11 @@
+SELECT
+	*
+FROM
+	image i
+WHERE
+	i.app_user_id = $1
+	AND i.deleted_by = $1
+ORDER BY
+	i.deleted DESC
+OFFSET $2
+LIMIT $3;
\ No newline at end of file
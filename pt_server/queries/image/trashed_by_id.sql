@@ -0,0 +1,4 @@
+SELECT *
+FROM image i
+WHERE i.id = $1
+	AND i.deleted IS NOT NULL;
\ No newline at end of file
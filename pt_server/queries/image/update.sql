@@ -1,6 +1,13 @@
 UPDATE image
 SET upload_date = $2,
 	title = $3,
-	description = $4
+	description = $4,
+	visibility = $5,
+	latitude = $6,
+	longitude = $7,
+	phash = $8,
+	duplicate_of = $9,
+	file_size = $10,
+	hidden = $11
 WHERE
 	id = $1;
\ No newline at end of file
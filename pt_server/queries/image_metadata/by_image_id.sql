@@ -0,0 +1,6 @@
+SELECT
+	*
+FROM
+	image_metadata
+WHERE
+	image_id = $1;
\ No newline at end of file
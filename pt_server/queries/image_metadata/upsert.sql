@@ -0,0 +1,26 @@
+INSERT INTO
+	image_metadata (
+		image_id,
+		camera_make,
+		camera_model,
+		lens_model,
+		exposure_time,
+		f_number,
+		iso,
+		focal_length,
+		captured,
+		orientation
+	)
+VALUES
+	($1, $2, $3, $4, $5, $6, $7, $8, $9, $10) ON CONFLICT (image_id) DO
+UPDATE
+SET
+	camera_make = $2,
+	camera_model = $3,
+	lens_model = $4,
+	exposure_time = $5,
+	f_number = $6,
+	iso = $7,
+	focal_length = $8,
+	captured = $9,
+	orientation = $10;
\ No newline at end of file
@@ -0,0 +1,4 @@
+INSERT INTO image_report (image_id, app_user_id, reason, details)
+VALUES ($1, $2, $3, $4) ON CONFLICT (image_id, app_user_id)
+WHERE moderation_action_id IS NULL DO NOTHING
+RETURNING image_report.id;
\ No newline at end of file
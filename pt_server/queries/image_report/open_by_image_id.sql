@@ -0,0 +1,9 @@
+SELECT
+	*
+FROM
+	image_report r
+WHERE
+	r.image_id = $1
+	AND r.moderation_action_id IS NULL
+ORDER BY
+	r.created;
\ No newline at end of file
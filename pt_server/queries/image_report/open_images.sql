@@ -0,0 +1,18 @@
+SELECT
+	r.image_id,
+	COUNT(*) AS "report_count!",
+	MIN(r.created) AS "first_reported!",
+	MAX(r.created) AS "last_reported!"
+FROM
+	image_report r
+	JOIN image i ON i.id = r.image_id
+WHERE
+	r.moderation_action_id IS NULL
+	AND i.deleted IS NULL
+GROUP BY
+	r.image_id
+ORDER BY
+	COUNT(*) DESC,
+	MIN(r.created)
+OFFSET $1
+LIMIT $2;
\ No newline at end of file
@@ -0,0 +1,7 @@
+UPDATE
+	image_report
+SET
+	moderation_action_id = $2
+WHERE
+	image_id = $1
+	AND moderation_action_id IS NULL;
\ No newline at end of file
@@ -0,0 +1,20 @@
+UPDATE job
+SET job_status = 'running',
+	attempts = attempts + 1,
+	locked_until = CURRENT_TIMESTAMP + make_interval(secs => $1)
+WHERE id = (
+		SELECT id
+		FROM job
+		WHERE (
+				job_status = 'queued'
+				AND run_after <= CURRENT_TIMESTAMP
+			)
+			OR (
+				job_status = 'running'
+				AND locked_until < CURRENT_TIMESTAMP
+			)
+		ORDER BY run_after
+		LIMIT 1
+		FOR UPDATE SKIP LOCKED
+	)
+RETURNING *;
\ No newline at end of file
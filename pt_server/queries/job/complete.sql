@@ -0,0 +1,7 @@
+UPDATE job
+SET job_status = 'completed',
+	locked_until = NULL,
+	completed = CURRENT_TIMESTAMP
+WHERE id = $1
+	AND job_status = 'running'
+	AND attempts = $2;
\ No newline at end of file
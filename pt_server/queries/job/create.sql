@@ -0,0 +1,3 @@
+INSERT INTO job(job_type, payload, max_attempts, run_after)
+VALUES ($1, $2, $3, $4)
+RETURNING id;
\ No newline at end of file
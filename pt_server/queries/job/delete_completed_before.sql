@@ -0,0 +1,3 @@
+DELETE FROM job
+WHERE job_status = 'completed'
+	AND completed < $1;
\ No newline at end of file
@@ -0,0 +1,5 @@
+UPDATE job
+SET locked_until = CURRENT_TIMESTAMP + make_interval(secs => $2)
+WHERE id = $1
+	AND job_status = 'running'
+	AND attempts = $3;
\ No newline at end of file
@@ -0,0 +1,8 @@
+UPDATE job
+SET job_status = 'dead',
+	locked_until = NULL,
+	last_error = $2,
+	completed = CURRENT_TIMESTAMP
+WHERE id = $1
+	AND job_status = 'running'
+	AND attempts = $3;
\ No newline at end of file
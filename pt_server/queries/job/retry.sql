@@ -0,0 +1,8 @@
+UPDATE job
+SET job_status = 'queued',
+	locked_until = NULL,
+	run_after = CURRENT_TIMESTAMP + make_interval(secs => $2),
+	last_error = $3
+WHERE id = $1
+	AND job_status = 'running'
+	AND attempts = $4;
\ No newline at end of file
@@ -0,0 +1,19 @@
+WITH due AS (
+	UPDATE job_schedule
+	SET next_run = CURRENT_TIMESTAMP + make_interval(secs => $2)
+	WHERE job_type = $1
+		AND next_run <= CURRENT_TIMESTAMP
+	RETURNING job_type
+)
+INSERT INTO job(job_type, payload, max_attempts)
+SELECT job_type,
+	$3,
+	$4
+FROM due
+WHERE NOT EXISTS (
+		SELECT 1
+		FROM job
+		WHERE job_type = $1
+			AND job_status IN ('queued', 'running')
+	)
+RETURNING id;
\ No newline at end of file
@@ -0,0 +1,2 @@
+INSERT INTO job_schedule(job_type)
+VALUES ($1) ON CONFLICT DO NOTHING;
\ No newline at end of file
@@ -0,0 +1,3 @@
+INSERT INTO moderation_action (moderator_id, image_id, image_owner_id, action, note)
+VALUES ($1, $2, $3, $4, $5)
+RETURNING moderation_action.id;
\ No newline at end of file
@@ -0,0 +1,11 @@
+SELECT
+	*
+FROM
+	moderation_action a
+WHERE
+	$1::UUID IS NULL
+	OR a.image_id = $1
+ORDER BY
+	a.created DESC
+OFFSET $2
+LIMIT $3;
\ No newline at end of file
@@ -0,0 +1,20 @@
+SELECT
+	*
+FROM
+	notification n
+WHERE
+	n.app_user_id = $1
+	AND (n.created, n.id) > (
+		SELECT
+			l.created,
+			l.id
+		FROM
+			notification l
+		WHERE
+			l.id = $2
+			AND l.app_user_id = $1
+	)
+ORDER BY
+	n.created,
+	n.id
+LIMIT $3;
\ No newline at end of file
@@ -0,0 +1,14 @@
+SELECT
+	*
+FROM
+	notification n
+WHERE
+	n.app_user_id = $1
+	AND (
+		NOT $2
+		OR n.read_date IS NULL
+	)
+ORDER BY
+	n.created DESC
+OFFSET $3
+LIMIT $4;
\ No newline at end of file
@@ -0,0 +1,6 @@
+SELECT
+	*
+FROM
+	notification n
+WHERE
+	n.id = $1;
\ No newline at end of file
@@ -0,0 +1,4 @@
+SELECT COUNT(*)
+FROM notification n
+WHERE n.app_user_id = $1
+	AND n.read_date IS NULL;
\ No newline at end of file
@@ -0,0 +1,10 @@
+INSERT INTO notification (
+		app_user_id,
+		actor_id,
+		notification_type,
+		image_id,
+		comment_id,
+		details
+	)
+VALUES ($1, $2, $3, $4, $5, $6)
+RETURNING notification.id;
\ No newline at end of file
@@ -0,0 +1,4 @@
+UPDATE notification
+SET read_date = CURRENT_TIMESTAMP
+WHERE notification.app_user_id = $1
+	AND notification.read_date IS NULL;
\ No newline at end of file
@@ -0,0 +1,4 @@
+UPDATE notification
+SET read_date = COALESCE(notification.read_date, CURRENT_TIMESTAMP)
+WHERE notification.id = $1
+	AND notification.app_user_id = $2;
\ No newline at end of file
@@ -0,0 +1,8 @@
+INSERT INTO oidc_login (oidc_state, code_verifier, nonce)
+SELECT $1,
+	$2,
+	$3
+WHERE (
+		SELECT COUNT(*)
+		FROM oidc_login
+	) < $4;
@@ -0,0 +1,2 @@
+DELETE FROM oidc_login
+WHERE created < $1;
\ No newline at end of file
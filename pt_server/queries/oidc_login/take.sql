@@ -0,0 +1,3 @@
+DELETE FROM oidc_login
+WHERE oidc_state = $1
+RETURNING *;
\ No newline at end of file
@@ -0,0 +1,6 @@
+SELECT
+	*
+FROM
+	rating
+WHERE
+	app_user_id = $1;
\ No newline at end of file
@@ -7,5 +7,9 @@ INNER JOIN image i ON
 	r.image_id = i.id
 INNER JOIN app_user au ON
 	au.id = i.app_user_id
+WHERE
+	NOT i.hidden
+	AND i.deleted IS NULL
+	AND i.visibility = 'public'
 GROUP BY email
 ORDER BY average_rating;
\ No newline at end of file
@@ -0,0 +1,3 @@
+INSERT INTO recovery_code (app_user_id, code_hash)
+VALUES ($1, $2)
+RETURNING id;
\ No newline at end of file
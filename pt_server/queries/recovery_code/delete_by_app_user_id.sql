@@ -0,0 +1,2 @@
+DELETE FROM recovery_code
+WHERE app_user_id = $1;
\ No newline at end of file
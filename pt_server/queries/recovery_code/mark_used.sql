@@ -0,0 +1,4 @@
+UPDATE recovery_code
+SET used = CURRENT_TIMESTAMP
+WHERE id = $1
+	AND used IS NULL;
@@ -0,0 +1,4 @@
+SELECT *
+FROM recovery_code rc
+WHERE rc.app_user_id = $1
+	AND rc.used IS NULL;
\ No newline at end of file
@@ -0,0 +1,3 @@
+SELECT *
+FROM share_link sl
+WHERE sl.id = $1;
\ No newline at end of file
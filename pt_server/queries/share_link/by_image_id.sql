@@ -0,0 +1,5 @@
+SELECT *
+FROM share_link sl
+WHERE sl.image_id = $1
+	AND sl.revoked IS NULL
+ORDER BY sl.created DESC;
\ No newline at end of file
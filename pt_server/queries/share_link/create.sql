@@ -0,0 +1,3 @@
+INSERT INTO share_link (app_user_id, image_id, token, expires)
+VALUES ($1, $2, $3, $4)
+RETURNING share_link.id;
\ No newline at end of file
@@ -0,0 +1,4 @@
+UPDATE share_link
+SET revoked = CURRENT_TIMESTAMP
+WHERE id = $1
+	AND revoked IS NULL;
\ No newline at end of file
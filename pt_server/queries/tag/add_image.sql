@@ -0,0 +1,2 @@
+INSERT INTO image_tag (image_id, tag_id)
+VALUES ($1, $2) ON CONFLICT DO NOTHING;
\ No newline at end of file
@@ -0,0 +1,16 @@
+SELECT t.id,
+	t.tag_name,
+	COUNT(i.id) AS image_count
+FROM tag t
+	LEFT JOIN image_tag it ON it.tag_id = t.id
+	LEFT JOIN image i ON i.id = it.image_id
+	AND NOT i.hidden
+	AND i.deleted IS NULL
+	AND i.visibility = 'public'
+WHERE t.tag_name % $1
+	OR t.tag_name LIKE $1 || '%'
+GROUP BY t.id
+ORDER BY (t.tag_name LIKE $1 || '%') DESC,
+	SIMILARITY(t.tag_name, $1) DESC,
+	image_count DESC
+LIMIT $2;
\ No newline at end of file
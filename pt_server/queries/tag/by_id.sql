@@ -0,0 +1,3 @@
+SELECT *
+FROM tag t
+WHERE t.id = $1;
\ No newline at end of file
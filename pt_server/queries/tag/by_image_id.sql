@@ -0,0 +1,5 @@
+SELECT t.*
+FROM tag t
+	JOIN image_tag it ON it.tag_id = t.id
+WHERE it.image_id = $1
+ORDER BY t.tag_name;
\ No newline at end of file
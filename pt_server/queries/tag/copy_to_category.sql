@@ -0,0 +1,7 @@
+INSERT INTO image_category (category_id, image_id)
+SELECT $2,
+	it.image_id
+FROM image_tag it
+	JOIN image i ON i.id = it.image_id
+WHERE it.tag_id = $1
+	AND i.deleted IS NULL ON CONFLICT DO NOTHING;
\ No newline at end of file
@@ -0,0 +1,2 @@
+DELETE FROM tag
+WHERE tag.id = $1;
\ No newline at end of file
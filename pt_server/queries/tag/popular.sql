@@ -0,0 +1,13 @@
+SELECT t.id,
+	t.tag_name,
+	COUNT(it.image_id) AS image_count
+FROM tag t
+	JOIN image_tag it ON it.tag_id = t.id
+	JOIN image i ON i.id = it.image_id
+WHERE NOT i.hidden
+	AND i.deleted IS NULL
+	AND i.visibility = 'public'
+GROUP BY t.id
+ORDER BY image_count DESC,
+	t.tag_name
+LIMIT $1;
\ No newline at end of file
@@ -0,0 +1,2 @@
+DELETE FROM image_tag
+WHERE image_id = $1;
\ No newline at end of file
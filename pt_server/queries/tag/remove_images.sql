@@ -0,0 +1,2 @@
+DELETE FROM image_tag
+WHERE tag_id = $1;
\ No newline at end of file
@@ -0,0 +1,5 @@
+INSERT INTO tag (tag_name)
+VALUES ($1) ON CONFLICT (tag_name) DO
+UPDATE
+SET tag_name = EXCLUDED.tag_name
+RETURNING id;
\ No newline at end of file
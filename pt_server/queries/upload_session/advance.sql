@@ -0,0 +1,8 @@
+UPDATE
+	upload_session
+SET
+	upload_offset = $3
+WHERE
+	id = $1
+	AND upload_offset = $2
+	AND NOT finishing;
\ No newline at end of file
@@ -0,0 +1,6 @@
+SELECT
+	*
+FROM
+	upload_session
+WHERE
+	app_user_id = $1;
\ No newline at end of file
@@ -0,0 +1,6 @@
+SELECT
+	*
+FROM
+	upload_session
+WHERE
+	id = $1;
\ No newline at end of file
@@ -0,0 +1,8 @@
+UPDATE
+	upload_session
+SET
+	finishing = TRUE
+WHERE
+	id = $1
+	AND NOT finishing
+	AND upload_offset = upload_length;
\ No newline at end of file
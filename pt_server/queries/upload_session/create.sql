@@ -0,0 +1,9 @@
+INSERT INTO upload_session (
+		app_user_id,
+		image_id,
+		upload_length,
+		checksum,
+		file_extension
+	)
+VALUES ($1, $2, $3, $4, $5)
+RETURNING id;
\ No newline at end of file
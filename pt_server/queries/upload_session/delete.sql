@@ -0,0 +1,4 @@
+DELETE FROM
+	upload_session
+WHERE
+	id = $1;
\ No newline at end of file
@@ -0,0 +1,7 @@
+UPDATE
+	upload_session
+SET
+	finishing = FALSE,
+	upload_offset = $2
+WHERE
+	id = $1;
\ No newline at end of file
@@ -0,0 +1,4 @@
+SELECT *
+FROM user_identity ui
+WHERE ui.issuer = $1
+	AND ui.subject = $2;
\ No newline at end of file
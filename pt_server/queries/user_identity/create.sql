@@ -0,0 +1,3 @@
+INSERT INTO user_identity (app_user_id, issuer, subject, email)
+VALUES ($1, $2, $3, $4)
+RETURNING id;
\ No newline at end of file
@@ -0,0 +1,6 @@
+SELECT
+	*
+FROM
+	user_quota q
+WHERE
+	q.app_user_id = $1;
\ No newline at end of file
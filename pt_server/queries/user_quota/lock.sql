@@ -0,0 +1,6 @@
+SELECT
+	id
+FROM
+	app_user
+WHERE
+	id = $1 FOR NO KEY UPDATE;
\ No newline at end of file
@@ -0,0 +1,9 @@
+INSERT INTO
+	user_quota (app_user_id, max_bytes, max_images)
+VALUES
+	($1, $2, $3) ON CONFLICT (app_user_id) DO
+UPDATE
+SET
+	max_bytes = $2,
+	max_images = $3,
+	updated = NOW();
\ No newline at end of file
@@ -0,0 +1,7 @@
+SELECT
+	COALESCE(SUM(i.file_size), 0)::BIGINT AS "bytes!",
+	COUNT(*) AS "images!"
+FROM
+	image i
+WHERE
+	i.app_user_id = $1;
\ No newline at end of file
@@ -0,0 +1,80 @@
+use sqlx::{query_file, query_file_as, PgPool};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+/// A ZIP archive of the data of a user, built in the background.
+pub struct AccountExport {
+    pub id: Uuid,
+    pub created: OffsetDateTime,
+    pub app_user_id: Uuid,
+    pub export_status: String,
+    pub completed: Option<OffsetDateTime>,
+    /// Size of the archive in bytes.
+    pub file_size: Option<i64>,
+}
+
+impl AccountExport {
+    pub async fn new(app_user_id: Uuid, pool: &PgPool) -> Result<AccountExport, sqlx::Error> {
+        query_file_as!(
+            AccountExport,
+            "queries/account_export/create.sql",
+            app_user_id
+        )
+        .fetch_one(pool)
+        .await
+    }
+
+    pub async fn by_id(id: Uuid, pool: &PgPool) -> Result<Option<AccountExport>, sqlx::Error> {
+        let res = query_file_as!(AccountExport, "queries/account_export/by_id.sql", id)
+            .fetch_one(pool)
+            .await;
+
+        match res {
+            Ok(e) => Ok(Some(e)),
+            Err(e) => match e {
+                sqlx::Error::RowNotFound => Ok(None),
+                _ => Err(e),
+            },
+        }
+    }
+
+    /// The latest first.
+    pub async fn by_app_user_id(
+        app_user_id: Uuid,
+        pool: &PgPool,
+    ) -> Result<Vec<AccountExport>, sqlx::Error> {
+        query_file_as!(
+            AccountExport,
+            "queries/account_export/by_app_user_id.sql",
+            app_user_id
+        )
+        .fetch_all(pool)
+        .await
+    }
+}
+
+impl AccountExport {
+    pub async fn complete(
+        &self,
+        export_status: &str,
+        file_size: Option<i64>,
+        pool: &PgPool,
+    ) -> Result<(), sqlx::Error> {
+        query_file!(
+            "queries/account_export/complete.sql",
+            self.id,
+            export_status,
+            file_size
+        )
+        .execute(pool)
+        .await
+        .map(|_| ())
+    }
+
+    pub async fn delete(&self, pool: &PgPool) -> Result<(), sqlx::Error> {
+        query_file!("queries/account_export/delete.sql", self.id)
+            .execute(pool)
+            .await
+            .map(|_| ())
+    }
+}
@@ -0,0 +1,201 @@
+use sqlx::{query_file, query_file_as, Done, PgPool};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use super::image::Image;
+use crate::model::Visibility;
+
+/// A user-owned collection of images.
+pub struct Album {
+    pub id: Uuid,
+    pub created: OffsetDateTime,
+    pub app_user_id: Uuid,
+    pub album_name: String,
+    pub visibility: String,
+}
+
+impl Album {
+    pub async fn new(
+        app_user_id: Uuid,
+        name: &str,
+        visibility: &str,
+        pool: &PgPool,
+    ) -> Result<Uuid, sqlx::Error> {
+        query_file!("queries/album/create.sql", app_user_id, name, visibility)
+            .fetch_one(pool)
+            .await
+            .map(|res| res.id)
+    }
+
+    pub async fn by_id(id: Uuid, pool: &PgPool) -> Result<Option<Album>, sqlx::Error> {
+        let res = query_file_as!(Album, "queries/album/by_id.sql", id)
+            .fetch_one(pool)
+            .await;
+
+        match res {
+            Ok(a) => Ok(Some(a)),
+            Err(e) => match e {
+                sqlx::Error::RowNotFound => Ok(None),
+                _ => Err(e),
+            },
+        }
+    }
+
+    pub async fn by_app_user_id(
+        app_user_id: Uuid,
+        pool: &PgPool,
+    ) -> Result<Vec<Album>, sqlx::Error> {
+        query_file_as!(Album, "queries/album/by_app_user_id.sql", app_user_id)
+            .fetch_all(pool)
+            .await
+    }
+
+    pub async fn public_by_app_user_id(
+        app_user_id: Uuid,
+        pool: &PgPool,
+    ) -> Result<Vec<Album>, sqlx::Error> {
+        query_file_as!(
+            Album,
+            "queries/album/public_by_app_user_id.sql",
+            app_user_id
+        )
+        .fetch_all(pool)
+        .await
+    }
+}
+
+impl Album {
+    pub async fn save(&self, pool: &PgPool) -> Result<(), sqlx::Error> {
+        query_file!(
+            "queries/album/update.sql",
+            &self.id,
+            &self.album_name,
+            &self.visibility
+        )
+        .execute(pool)
+        .await
+        .map(|_| ())
+    }
+
+    pub async fn delete(&self, pool: &PgPool) -> Result<(), sqlx::Error> {
+        let mut tx = pool.begin().await?;
+
+        query_file!("queries/album/remove_images.sql", self.id)
+            .execute(&mut tx)
+            .await?;
+
+        query_file!("queries/album/delete.sql", self.id)
+            .execute(&mut tx)
+            .await?;
+
+        tx.commit().await
+    }
+
+    /// The image is appended to the end, adding it again has no effect.
+    pub async fn add_image(&self, image_id: Uuid, pool: &PgPool) -> Result<(), sqlx::Error> {
+        query_file!("queries/album/add_image.sql", &self.id, image_id)
+            .execute(pool)
+            .await
+            .map(|_| ())
+    }
+
+    /// Returns whether the image was in the album.
+    pub async fn remove_image(&self, image_id: Uuid, pool: &PgPool) -> Result<bool, sqlx::Error> {
+        query_file!("queries/album/remove_image.sql", &self.id, image_id)
+            .execute(pool)
+            .await
+            .map(|res| res.rows_affected() > 0)
+    }
+
+    /// IDs of all the images in the album in order, including the ones not uploaded yet.
+    pub async fn image_ids(&self, pool: &PgPool) -> Result<Vec<Uuid>, sqlx::Error> {
+        query_file!("queries/album/image_ids.sql", &self.id)
+            .fetch_all(pool)
+            .await
+            .map(|rows| rows.into_iter().map(|r| r.image_id).collect())
+    }
+
+    /// Sets the order of the images to the given one,
+    /// it must contain every image of the album.
+    pub async fn reorder_images(
+        &self,
+        image_ids: &[Uuid],
+        pool: &PgPool,
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = pool.begin().await?;
+
+        for (position, image_id) in image_ids.iter().enumerate() {
+            query_file!(
+                "queries/album/set_image_position.sql",
+                &self.id,
+                image_id,
+                position as i32
+            )
+            .execute(&mut tx)
+            .await?;
+        }
+
+        tx.commit().await
+    }
+
+    /// Uploaded images in the album without the hidden ones,
+    /// private images are only included for their owners.
+    pub async fn images(
+        &self,
+        viewer_id: Uuid,
+        offset: Option<i64>,
+        limit: Option<i64>,
+        pool: &PgPool,
+    ) -> Result<Vec<Image>, sqlx::Error> {
+        query_file_as!(
+            Image,
+            "queries/album/images.sql",
+            &self.id,
+            offset.unwrap_or(0),
+            limit.unwrap_or(10),
+            viewer_id
+        )
+        .fetch_all(pool)
+        .await
+    }
+
+    /// Counts the same images as [`Album::images`].
+    pub async fn image_count(&self, viewer_id: Uuid, pool: &PgPool) -> Result<i64, sqlx::Error> {
+        query_file!("queries/album/image_count.sql", &self.id, viewer_id)
+            .fetch_one(pool)
+            .await
+            .map(|res| res.count.unwrap_or(0))
+    }
+
+    pub fn visibility(&self) -> Visibility {
+        self.visibility.parse().unwrap_or_default()
+    }
+
+    /// Private albums are only visible to their owners.
+    pub fn visible_to(&self, app_user_id: Uuid) -> bool {
+        self.app_user_id == app_user_id || self.visibility() != Visibility::Private
+    }
+}
+
+pub struct AlbumExt {
+    pub album: Album,
+    pub image_count: i64,
+}
+
+impl AlbumExt {
+    pub async fn from_albums(
+        albums: Vec<Album>,
+        viewer_id: Uuid,
+        pool: &PgPool,
+    ) -> Result<Vec<AlbumExt>, sqlx::Error> {
+        let mut albums_ext = Vec::with_capacity(albums.len());
+        for album in albums {
+            albums_ext.push(AlbumExt {
+                image_count: album.image_count(viewer_id, pool).await?,
+                album,
+            });
+        }
+
+        Ok(albums_ext)
+    }
+}
@@ -0,0 +1,100 @@
+use sqlx::{query_file, query_file_as, PgPool};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+/// A personal API key, only the hash of the key is stored.
+pub struct ApiKey {
+    pub id: Uuid,
+    pub created: OffsetDateTime,
+    pub app_user_id: Uuid,
+    pub key_name: String,
+    /// The first few characters of the key, so that users can tell them apart.
+    pub key_prefix: String,
+    pub key_hash: String,
+    pub scopes: Vec<String>,
+    pub last_used: Option<OffsetDateTime>,
+    pub revoked: Option<OffsetDateTime>,
+}
+
+/// New API key without ID
+pub struct NewApiKey<'a> {
+    pub key_name: &'a str,
+    pub key_prefix: &'a str,
+    pub key_hash: &'a str,
+    pub scopes: &'a [String],
+}
+
+impl ApiKey {
+    pub async fn new(
+        app_user_id: Uuid,
+        key: NewApiKey<'_>,
+        pool: &PgPool,
+    ) -> Result<Uuid, sqlx::Error> {
+        query_file!(
+            "queries/api_key/create.sql",
+            app_user_id,
+            key.key_name,
+            key.key_prefix,
+            key.key_hash,
+            key.scopes
+        )
+        .fetch_one(pool)
+        .await
+        .map(|res| res.id)
+    }
+
+    pub async fn by_id(id: Uuid, pool: &PgPool) -> Result<Option<ApiKey>, sqlx::Error> {
+        let res = query_file_as!(ApiKey, "queries/api_key/by_id.sql", id)
+            .fetch_one(pool)
+            .await;
+
+        match res {
+            Ok(k) => Ok(Some(k)),
+            Err(e) => match e {
+                sqlx::Error::RowNotFound => Ok(None),
+                _ => Err(e),
+            },
+        }
+    }
+
+    /// Only returns keys that were not revoked.
+    pub async fn by_key_hash(key_hash: &str, pool: &PgPool) -> Result<Option<ApiKey>, sqlx::Error> {
+        let res = query_file_as!(ApiKey, "queries/api_key/by_key_hash.sql", key_hash)
+            .fetch_one(pool)
+            .await;
+
+        match res {
+            Ok(k) => Ok(Some(k)),
+            Err(e) => match e {
+                sqlx::Error::RowNotFound => Ok(None),
+                _ => Err(e),
+            },
+        }
+    }
+
+    /// Only returns keys that were not revoked.
+    pub async fn by_app_user_id(
+        app_user_id: Uuid,
+        pool: &PgPool,
+    ) -> Result<Vec<ApiKey>, sqlx::Error> {
+        query_file_as!(ApiKey, "queries/api_key/by_app_user_id.sql", app_user_id)
+            .fetch_all(pool)
+            .await
+    }
+}
+
+impl ApiKey {
+    pub async fn revoke(&self, pool: &PgPool) -> Result<(), sqlx::Error> {
+        query_file!("queries/api_key/revoke.sql", self.id)
+            .execute(pool)
+            .await
+            .map(|_| ())
+    }
+
+    pub async fn update_last_used(&self, pool: &PgPool) -> Result<(), sqlx::Error> {
+        query_file!("queries/api_key/update_last_used.sql", self.id)
+            .execute(pool)
+            .await
+            .map(|_| ())
+    }
+}
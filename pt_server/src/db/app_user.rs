@@ -1,4 +1,4 @@
-use sqlx::{query_file, query_file_as, Error, PgPool};
+use sqlx::{query_file, query_file_as, Done, Error, PgPool};
 use uuid::Uuid;
 
 use super::image::{Image, NewImage};
@@ -8,7 +8,15 @@ pub struct AppUser {
     pub created: time::OffsetDateTime,
     pub email: String,
     pub password_hash: String,
-    pub is_admin: bool,
+    pub user_role: String,
+    pub totp_secret: Option<String>,
+    pub totp_enabled: bool,
+    /// Keep the GPS tags in the uploaded files.
+    pub keep_image_location: bool,
+    /// The step of the last accepted TOTP code.
+    pub totp_last_step: Option<i64>,
+    pub mfa_failed_attempts: i32,
+    pub mfa_locked_until: Option<time::OffsetDateTime>,
 }
 
 impl AppUser {
@@ -16,10 +24,10 @@ impl AppUser {
         pool: &PgPool,
         email: &str,
         password_hash: &str,
-        admin: bool,
+        user_role: &str,
     ) -> Result<Uuid, sqlx::Error> {
         Ok(
-            query_file!("queries/app_user/create.sql", email, password_hash, admin)
+            query_file!("queries/app_user/create.sql", email, password_hash, user_role)
                 .fetch_one(pool)
                 .await
                 .map(|v| v.id)?,
@@ -57,8 +65,13 @@ impl AppUser {
 
 /// Methods for an instance
 impl AppUser {
-    pub async fn add_image(&self, image: NewImage, pool: &PgPool) -> Result<Uuid, sqlx::Error> {
-        Image::new(self.id, image, pool).await
+    pub async fn add_image(
+        &self,
+        image: NewImage,
+        max_images: Option<i64>,
+        pool: &PgPool,
+    ) -> Result<Option<Uuid>, sqlx::Error> {
+        Image::new(self.id, image, max_images, pool).await
     }
 
     pub async fn save(&self, pool: &PgPool) -> Result<(), sqlx::Error> {
@@ -67,11 +80,102 @@ impl AppUser {
             &self.id,
             &self.email,
             &self.password_hash,
-            &self.is_admin
+            &self.user_role,
+            self.totp_secret.as_deref(),
+            &self.totp_enabled,
+            &self.keep_image_location
         )
         .execute(pool)
         .await?;
 
         Ok(())
     }
+
+    /// Counts a two-factor attempt before the code is checked, so that concurrent
+    /// attempts cannot exceed the limit. The user is locked out until `lock_until`
+    /// once the limit is reached.
+    ///
+    /// Returns false if the user is locked out.
+    pub async fn claim_mfa_attempt(
+        &self,
+        max_attempts: i32,
+        lock_until: time::OffsetDateTime,
+        pool: &PgPool,
+    ) -> Result<bool, sqlx::Error> {
+        query_file!(
+            "queries/app_user/claim_mfa_attempt.sql",
+            self.id,
+            max_attempts,
+            lock_until
+        )
+        .execute(pool)
+        .await
+        .map(|res| res.rows_affected() > 0)
+    }
+
+    pub async fn reset_mfa_attempts(&self, pool: &PgPool) -> Result<(), sqlx::Error> {
+        query_file!("queries/app_user/reset_mfa_attempts.sql", self.id)
+            .execute(pool)
+            .await
+            .map(|_| ())
+    }
+
+    /// Stores the step of an accepted TOTP code.
+    ///
+    /// Returns false if the same or a later step was already used.
+    pub async fn use_totp_step(&self, step: i64, pool: &PgPool) -> Result<bool, sqlx::Error> {
+        query_file!("queries/app_user/use_totp_step.sql", self.id, step)
+            .execute(pool)
+            .await
+            .map(|res| res.rows_affected() > 0)
+    }
+
+    /// Enables two-factor authentication, replacing the recovery codes with the given ones.
+    pub async fn enable_totp(
+        &self,
+        totp_step: i64,
+        recovery_code_hashes: &[String],
+        pool: &PgPool,
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = pool.begin().await?;
+
+        query_file!("queries/recovery_code/delete_by_app_user_id.sql", self.id)
+            .execute(&mut tx)
+            .await?;
+
+        for code_hash in recovery_code_hashes {
+            query_file!("queries/recovery_code/create.sql", self.id, code_hash)
+                .fetch_one(&mut tx)
+                .await?;
+        }
+
+        query_file!("queries/app_user/enable_totp.sql", self.id, totp_step)
+            .execute(&mut tx)
+            .await?;
+
+        tx.commit().await
+    }
+
+    /// Disables two-factor authentication and removes the secret and the recovery codes.
+    pub async fn disable_totp(&self, pool: &PgPool) -> Result<(), sqlx::Error> {
+        let mut tx = pool.begin().await?;
+
+        query_file!("queries/recovery_code/delete_by_app_user_id.sql", self.id)
+            .execute(&mut tx)
+            .await?;
+
+        query_file!("queries/app_user/disable_totp.sql", self.id)
+            .execute(&mut tx)
+            .await?;
+
+        tx.commit().await
+    }
+
+    /// Everything the user owns is deleted with it.
+    pub async fn delete(&self, pool: &PgPool) -> Result<(), sqlx::Error> {
+        query_file!("queries/app_user/delete.sql", self.id)
+            .execute(pool)
+            .await
+            .map(|_| ())
+    }
 }
@@ -0,0 +1,76 @@
+use sqlx::{query_file, query_file_as, PgPool};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+/// A recorded change, the table rejects updates and deletes.
+pub struct AuditEvent {
+    pub id: Uuid,
+    pub created: OffsetDateTime,
+    pub actor_id: Option<Uuid>,
+    pub action: String,
+    pub target_type: String,
+    pub target_id: String,
+    pub before: Option<serde_json::Value>,
+    pub after: Option<serde_json::Value>,
+    pub request_id: Option<String>,
+}
+
+pub struct NewAuditEvent<'a> {
+    pub actor_id: Option<Uuid>,
+    pub action: &'a str,
+    pub target_type: &'a str,
+    pub target_id: &'a str,
+    pub before: Option<serde_json::Value>,
+    pub after: Option<serde_json::Value>,
+    pub request_id: Option<&'a str>,
+}
+
+/// Filters for searching events, empty values are ignored.
+#[derive(Debug, Default, Clone)]
+pub struct AuditEventFilter {
+    pub actor_id: Option<Uuid>,
+    pub action: Option<String>,
+    /// Only events at or after this time.
+    pub from: Option<OffsetDateTime>,
+    /// Only events before this time.
+    pub to: Option<OffsetDateTime>,
+}
+
+impl AuditEvent {
+    pub async fn new(event: NewAuditEvent<'_>, pool: &PgPool) -> Result<Uuid, sqlx::Error> {
+        Ok(query_file!(
+            "queries/audit_event/create.sql",
+            event.actor_id,
+            event.action,
+            event.target_type,
+            event.target_id,
+            event.before,
+            event.after,
+            event.request_id
+        )
+        .fetch_one(pool)
+        .await?
+        .id)
+    }
+
+    /// The latest first.
+    pub async fn search(
+        filter: &AuditEventFilter,
+        offset: Option<i64>,
+        limit: Option<i64>,
+        pool: &PgPool,
+    ) -> Result<Vec<AuditEvent>, sqlx::Error> {
+        query_file_as!(
+            AuditEvent,
+            "queries/audit_event/search.sql",
+            filter.actor_id,
+            filter.action.as_deref(),
+            filter.from,
+            filter.to,
+            offset.unwrap_or(0),
+            limit.unwrap_or(10)
+        )
+        .fetch_all(pool)
+        .await
+    }
+}
@@ -1,4 +1,4 @@
-use sqlx::{query_file, query_file_as, PgPool};
+use sqlx::{query_file, query_file_as, Done, PgPool};
 use time::OffsetDateTime;
 use uuid::Uuid;
 
@@ -6,6 +6,37 @@ pub struct Category {
     pub id: Uuid,
     pub created: OffsetDateTime,
     pub category_name: String,
+    pub parent_id: Option<Uuid>,
+    pub slug: String,
+    pub description: Option<String>,
+    pub cover_image_id: Option<Uuid>,
+    /// When the category was moved to the trash.
+    pub deleted: Option<OffsetDateTime>,
+    pub deleted_by: Option<Uuid>,
+}
+
+pub struct NewCategory<'a> {
+    pub name: &'a str,
+    pub slug: &'a str,
+    pub parent_id: Option<Uuid>,
+    pub description: Option<&'a str>,
+}
+
+/// The result of merging a category into another one.
+pub struct MergedImages {
+    /// Images that were only in the merged category.
+    pub moved: u64,
+    /// Images that were already in the target category as well.
+    pub duplicates: u64,
+}
+
+/// What happens to the subcategories when a category is deleted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeleteChildren {
+    /// The children are moved to the parent of the deleted category.
+    Reparent,
+    /// The whole subtree is deleted.
+    Cascade,
 }
 
 impl Category {
@@ -23,17 +54,37 @@ impl Category {
         }
     }
 
+    pub async fn by_slug(slug: &str, pool: &PgPool) -> Result<Option<Category>, sqlx::Error> {
+        let res = query_file_as!(Category, "queries/category/by_slug.sql", slug)
+            .fetch_one(pool)
+            .await;
+
+        match res {
+            Ok(c) => Ok(Some(c)),
+            Err(e) => match e {
+                sqlx::Error::RowNotFound => Ok(None),
+                _ => Err(e),
+            },
+        }
+    }
+
     pub async fn by_image_id(id: Uuid, pool: &PgPool) -> Result<Vec<Category>, sqlx::Error> {
         query_file_as!(Category, "queries/category/by_image_id.sql", id)
             .fetch_all(pool)
             .await
     }
 
-    pub async fn new(name: &str, pool: &PgPool) -> Result<Uuid, sqlx::Error> {
-        query_file!("queries/category/create.sql", name)
-            .fetch_one(pool)
-            .await
-            .map(|res| res.id)
+    pub async fn new(category: NewCategory<'_>, pool: &PgPool) -> Result<Uuid, sqlx::Error> {
+        query_file!(
+            "queries/category/create.sql",
+            category.name,
+            category.slug,
+            category.parent_id,
+            category.description
+        )
+        .fetch_one(pool)
+        .await
+        .map(|res| res.id)
     }
 
     pub async fn all(pool: &PgPool) -> Result<Vec<Category>, sqlx::Error> {
@@ -42,22 +93,167 @@ impl Category {
             .await
     }
 
-    pub async fn delete(&self, pool: &PgPool) -> Result<(), sqlx::Error> {
+    /// Moves the category to the trash, the images stay linked to it.
+    ///
+    /// Subcategories deleted with it get the same deletion date,
+    /// so that they can be restored together.
+    pub async fn delete(
+        &self,
+        children: DeleteChildren,
+        deleted_by: Option<Uuid>,
+        pool: &PgPool,
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = pool.begin().await?;
+
+        match children {
+            DeleteChildren::Reparent => {
+                // Trashed subcategories are moved too, so that they are restored
+                // under a category that still exists.
+                query_file!(
+                    "queries/category/reparent_children.sql",
+                    self.id,
+                    self.parent_id
+                )
+                .execute(&mut tx)
+                .await?;
+
+                query_file!("queries/category/trash.sql", self.id, deleted_by)
+                    .execute(&mut tx)
+                    .await?;
+            }
+            DeleteChildren::Cascade => {
+                query_file!(
+                    "queries/category/trash_with_descendants.sql",
+                    self.id,
+                    deleted_by
+                )
+                .execute(&mut tx)
+                .await?;
+            }
+        }
+
+        tx.commit().await
+    }
+
+    /// Every category in the trash, the latest first.
+    pub async fn trashed(pool: &PgPool) -> Result<Vec<Category>, sqlx::Error> {
+        query_file_as!(Category, "queries/category/trashed.sql")
+            .fetch_all(pool)
+            .await
+    }
+
+    pub async fn restore(ids: &[Uuid], pool: &PgPool) -> Result<(), sqlx::Error> {
+        query_file!("queries/category/restore.sql", ids)
+            .execute(pool)
+            .await
+            .map(|_| ())
+    }
+
+    /// Deletes the categories that were moved to the trash before the given date,
+    /// returns the number of deleted categories.
+    pub async fn purge_deleted_before(
+        date: OffsetDateTime,
+        pool: &PgPool,
+    ) -> Result<u64, sqlx::Error> {
         let mut tx = pool.begin().await?;
 
-        query_file!("queries/category/remove_images.sql", self.id)
+        query_file!("queries/category/remove_images_deleted_before.sql", date)
             .execute(&mut tx)
             .await?;
 
-        query_file!("queries/category/delete.sql", self.id)
+        let deleted = query_file!("queries/category/delete_deleted_before.sql", date)
             .execute(&mut tx)
-            .await?;
+            .await?
+            .rows_affected();
 
-        tx.commit().await
+        tx.commit().await?;
+
+        Ok(deleted)
     }
 }
 
 impl Category {
+    /// Moves the images and subcategories to the target, then deletes this category.
+    ///
+    /// The caller must make sure that the target is not a descendant of this category.
+    pub async fn merge_into(
+        &self,
+        target_id: Uuid,
+        pool: &PgPool,
+    ) -> Result<MergedImages, sqlx::Error> {
+        let mut tx = pool.begin().await?;
+
+        let image_count = query_file!("queries/category/image_count.sql", self.id)
+            .fetch_one(&mut tx)
+            .await?
+            .count
+            .unwrap_or(0) as u64;
+
+        let moved = query_file!("queries/category/merge_images.sql", self.id, target_id)
+            .execute(&mut tx)
+            .await?
+            .rows_affected();
+
+        // Trashed subcategories are moved too, this category is deleted for good.
+        query_file!(
+            "queries/category/reparent_children.sql",
+            self.id,
+            Some(target_id)
+        )
+        .execute(&mut tx)
+        .await?;
+
+        query_file!(
+            "queries/category/remove_images_with_descendants.sql",
+            self.id
+        )
+        .execute(&mut tx)
+        .await?;
+
+        query_file!("queries/category/delete_with_descendants.sql", self.id)
+            .execute(&mut tx)
+            .await?;
+
+        tx.commit().await?;
+
+        Ok(MergedImages {
+            moved,
+            duplicates: image_count - moved,
+        })
+    }
+
+    /// The IDs out of the given ones that are in this category.
+    pub async fn linked_image_ids(
+        &self,
+        image_ids: &[Uuid],
+        pool: &PgPool,
+    ) -> Result<Vec<Uuid>, sqlx::Error> {
+        query_file!("queries/category/linked_image_ids.sql", &self.id, image_ids)
+            .fetch_all(pool)
+            .await
+            .map(|rows| rows.into_iter().map(|r| r.image_id).collect())
+    }
+
+    /// Returns the number of images that were added, missing images are skipped.
+    pub async fn add_images(&self, image_ids: &[Uuid], pool: &PgPool) -> Result<u64, sqlx::Error> {
+        query_file!("queries/category/add_images.sql", &self.id, image_ids)
+            .execute(pool)
+            .await
+            .map(|res| res.rows_affected())
+    }
+
+    /// Returns the number of images that were removed.
+    pub async fn remove_images(
+        &self,
+        image_ids: &[Uuid],
+        pool: &PgPool,
+    ) -> Result<u64, sqlx::Error> {
+        query_file!("queries/category/remove_images.sql", &self.id, image_ids)
+            .execute(pool)
+            .await
+            .map(|res| res.rows_affected())
+    }
+
     pub async fn image_count(&self, pool: &PgPool) -> Result<i64, sqlx::Error> {
         query_file!("queries/category/image_count.sql", &self.id)
             .fetch_one(pool)
@@ -65,6 +261,41 @@ impl Category {
             .map(|res| res.count.unwrap_or(0))
     }
 
+    /// Distinct images of this category and all of its subcategories.
+    pub async fn image_count_with_descendants(&self, pool: &PgPool) -> Result<i64, sqlx::Error> {
+        query_file!(
+            "queries/category/image_count_with_descendants.sql",
+            &self.id
+        )
+        .fetch_one(pool)
+        .await
+        .map(|res| res.count.unwrap_or(0))
+    }
+
+    pub async fn has_image(&self, image_id: Uuid, pool: &PgPool) -> Result<bool, sqlx::Error> {
+        query_file!("queries/category/has_image.sql", &self.id, image_id)
+            .fetch_one(pool)
+            .await
+            .map(|res| res.exists)
+    }
+
+    pub async fn has_children(&self, pool: &PgPool) -> Result<bool, sqlx::Error> {
+        query_file!("queries/category/has_children.sql", &self.id)
+            .fetch_one(pool)
+            .await
+            .map(|res| res.exists)
+    }
+
+    /// The IDs of this category and all of its ancestors.
+    ///
+    /// Trashed ancestors are included, the checks for cycles need the whole tree.
+    pub async fn ancestor_ids(&self, pool: &PgPool) -> Result<Vec<Uuid>, sqlx::Error> {
+        query_file!("queries/category/ancestor_ids.sql", &self.id)
+            .fetch_all(pool)
+            .await
+            .map(|rows| rows.into_iter().map(|r| r.id).collect())
+    }
+
     pub async fn add_image(&self, image_id: Uuid, pool: &PgPool) -> Result<(), sqlx::Error> {
         query_file!("queries/category/add_image.sql", &self.id, image_id)
             .execute(pool)
@@ -73,10 +304,17 @@ impl Category {
     }
 
     pub async fn save(&self, pool: &PgPool) -> Result<(), sqlx::Error> {
-        query_file!("queries/category/update.sql", &self.id, &self.category_name)
-            .execute(pool)
-            .await
-            .map(|_| ())
+        query_file!(
+            "queries/category/update.sql",
+            &self.id,
+            &self.category_name,
+            self.parent_id,
+            self.description.as_deref(),
+            self.cover_image_id
+        )
+        .execute(pool)
+        .await
+        .map(|_| ())
     }
 }
 
@@ -86,15 +324,25 @@ pub struct CategoryExt {
 }
 
 impl CategoryExt {
-    pub async fn all(pool: &PgPool) -> Result<Vec<CategoryExt>, sqlx::Error> {
+    /// With `include_descendants` the image counts include the images of the subcategories.
+    pub async fn all(
+        include_descendants: bool,
+        pool: &PgPool,
+    ) -> Result<Vec<CategoryExt>, sqlx::Error> {
         let categories: Vec<Category> = query_file_as!(Category, "queries/category/all.sql")
             .fetch_all(pool)
             .await?;
 
         let mut categories_ext = Vec::with_capacity(categories.len());
         for category in categories {
+            let image_count = if include_descendants {
+                category.image_count_with_descendants(pool).await?
+            } else {
+                category.image_count(pool).await?
+            };
+
             categories_ext.push(CategoryExt {
-                image_count: category.image_count(pool).await?,
+                image_count,
                 category,
             });
         }
@@ -0,0 +1,102 @@
+use sqlx::{query_file, query_file_as, PgPool};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+pub struct Comment {
+    pub id: Uuid,
+    pub created: OffsetDateTime,
+    pub edited: Option<OffsetDateTime>,
+    pub image_id: Uuid,
+    /// Missing if the author deleted their account.
+    pub app_user_id: Option<Uuid>,
+    /// The comment this one replies to.
+    pub parent_id: Option<Uuid>,
+    pub body: String,
+}
+
+/// New comment without ID
+pub struct NewComment<'a> {
+    pub image_id: Uuid,
+    pub parent_id: Option<Uuid>,
+    pub body: &'a str,
+}
+
+impl Comment {
+    pub async fn new(
+        app_user_id: Uuid,
+        comment: NewComment<'_>,
+        pool: &PgPool,
+    ) -> Result<Uuid, sqlx::Error> {
+        query_file!(
+            "queries/comment/create.sql",
+            comment.image_id,
+            app_user_id,
+            comment.parent_id,
+            comment.body
+        )
+        .fetch_one(pool)
+        .await
+        .map(|res| res.id)
+    }
+
+    pub async fn by_id(id: Uuid, pool: &PgPool) -> Result<Option<Comment>, sqlx::Error> {
+        let res = query_file_as!(Comment, "queries/comment/by_id.sql", id)
+            .fetch_one(pool)
+            .await;
+
+        match res {
+            Ok(c) => Ok(Some(c)),
+            Err(e) => match e {
+                sqlx::Error::RowNotFound => Ok(None),
+                _ => Err(e),
+            },
+        }
+    }
+
+    /// Oldest comments first, none if the image is in the trash.
+    pub async fn by_image_id(
+        image_id: Uuid,
+        offset: Option<i64>,
+        limit: Option<i64>,
+        pool: &PgPool,
+    ) -> Result<Vec<Comment>, sqlx::Error> {
+        query_file_as!(
+            Comment,
+            "queries/comment/by_image_id.sql",
+            image_id,
+            offset.unwrap_or(0),
+            limit.unwrap_or(10)
+        )
+        .fetch_all(pool)
+        .await
+    }
+
+    pub async fn count_by_image_id(image_id: Uuid, pool: &PgPool) -> Result<i64, sqlx::Error> {
+        query_file!("queries/comment/count_by_image_id.sql", image_id)
+            .fetch_one(pool)
+            .await
+            .map(|res| res.count.unwrap_or(0))
+    }
+}
+
+impl Comment {
+    pub async fn save(&self, pool: &PgPool) -> Result<(), sqlx::Error> {
+        query_file!(
+            "queries/comment/update.sql",
+            &self.id,
+            &self.body,
+            self.edited
+        )
+        .execute(pool)
+        .await
+        .map(|_| ())
+    }
+
+    /// Replies are deleted as well.
+    pub async fn delete(&self, pool: &PgPool) -> Result<(), sqlx::Error> {
+        query_file!("queries/comment/delete.sql", &self.id)
+            .execute(pool)
+            .await
+            .map(|_| ())
+    }
+}
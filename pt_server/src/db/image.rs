@@ -1,13 +1,113 @@
 use sqlx::{query_file, query_file_as, Error, PgPool};
+use std::str::FromStr;
 use time::OffsetDateTime;
 use uuid::Uuid;
 
-use super::{category::Category, rating::Rating};
+use super::{
+    category::Category, comment::Comment, image_metadata::ImageMetadata, rating::Rating, tag::Tag,
+    user_quota::StorageUsage,
+};
+use crate::model::{
+    role::{Permission, Role},
+    Visibility,
+};
 
 /// New image without ID
 pub struct NewImage {
     pub title: String,
     pub description: Option<String>,
+    pub visibility: Visibility,
+}
+
+/// Filters for searching images, empty values are ignored.
+#[derive(Debug, Default, Clone)]
+pub struct ImageFilter {
+    pub search: Option<String>,
+    /// Images must have every tag.
+    pub tags: Vec<String>,
+    /// Only public images are listed, except for the ones of this user.
+    pub viewer_id: Option<Uuid>,
+    /// Bounds of the Exif capture time, images without one are left out.
+    pub captured_after: Option<OffsetDateTime>,
+    pub captured_before: Option<OffsetDateTime>,
+    pub area: Option<GeoArea>,
+}
+
+/// An area on the map in degrees.
+///
+/// If `min_lon` is greater than `max_lon`, the box crosses the antimeridian.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundingBox {
+    pub min_lat: f64,
+    pub min_lon: f64,
+    pub max_lat: f64,
+    pub max_lon: f64,
+}
+
+impl BoundingBox {
+    pub fn is_valid(&self) -> bool {
+        valid_location(self.min_lat, self.min_lon)
+            && valid_location(self.max_lat, self.max_lon)
+            && self.min_lat <= self.max_lat
+    }
+}
+
+/// Parses the `minLon,minLat,maxLon,maxLat` format used by GeoJSON.
+impl FromStr for BoundingBox {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let values = s
+            .split(',')
+            .map(|v| v.trim().parse::<f64>().map_err(|_| ()))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        match values.as_slice() {
+            &[min_lon, min_lat, max_lon, max_lat] => Ok(BoundingBox {
+                min_lat,
+                min_lon,
+                max_lat,
+                max_lon,
+            }),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GeoArea {
+    BoundingBox(BoundingBox),
+    /// Images within the given distance in metres from a point.
+    Radius {
+        lat: f64,
+        lon: f64,
+        meters: f64,
+    },
+}
+
+impl GeoArea {
+    pub fn is_valid(&self) -> bool {
+        match *self {
+            GeoArea::BoundingBox(b) => b.is_valid(),
+            GeoArea::Radius { lat, lon, meters } => {
+                valid_location(lat, lon) && meters.is_finite() && meters > 0.0
+            }
+        }
+    }
+}
+
+pub fn valid_location(lat: f64, lon: f64) -> bool {
+    (-90.0..=90.0).contains(&lat) && (-180.0..=180.0).contains(&lon)
+}
+
+/// Located images in a single cell of the map grid.
+pub struct LocationCluster {
+    pub cell_row: i32,
+    pub cell_column: i32,
+    pub count: i64,
+    /// The average position of the images.
+    pub latitude: f64,
+    pub longitude: f64,
 }
 
 pub struct Image {
@@ -17,6 +117,26 @@ pub struct Image {
     pub title: String,
     pub description: Option<String>,
     pub app_user_id: Uuid,
+    pub visibility: String,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    /// Perceptual hash of the uploaded file.
+    pub phash: Option<i64>,
+    /// A similar image of the same user that was uploaded earlier.
+    pub duplicate_of: Option<Uuid>,
+    /// Size of the stored file in bytes.
+    pub file_size: Option<i64>,
+    /// Left out of searches by a moderator.
+    pub hidden: bool,
+    /// When the image was moved to the trash.
+    pub deleted: Option<OffsetDateTime>,
+    pub deleted_by: Option<Uuid>,
+}
+
+/// An image with the Hamming distance of its perceptual hash.
+pub struct SimilarImage {
+    pub id: Uuid,
+    pub distance: i32,
 }
 
 impl Image {
@@ -34,6 +154,50 @@ impl Image {
         }
     }
 
+    pub async fn trashed_by_id(id: Uuid, pool: &PgPool) -> Result<Option<Image>, sqlx::Error> {
+        let res = query_file_as!(Image, "queries/image/trashed_by_id.sql", id)
+            .fetch_one(pool)
+            .await;
+
+        match res {
+            Ok(image) => Ok(Some(image)),
+            Err(e) => match e {
+                Error::RowNotFound => Ok(None),
+                _ => Err(e),
+            },
+        }
+    }
+
+    /// Images the user moved to the trash, the latest first.
+    ///
+    /// Images deleted by moderators are left out.
+    pub async fn trashed_by_app_user_id(
+        app_user_id: Uuid,
+        offset: Option<i64>,
+        limit: Option<i64>,
+        pool: &PgPool,
+    ) -> Result<Vec<Image>, sqlx::Error> {
+        query_file_as!(
+            Image,
+            "queries/image/trashed_by_app_user_id.sql",
+            app_user_id,
+            offset.unwrap_or(0),
+            limit.unwrap_or(10)
+        )
+        .fetch_all(pool)
+        .await
+    }
+
+    /// Images that were moved to the trash before the given date.
+    pub async fn deleted_before(
+        date: OffsetDateTime,
+        pool: &PgPool,
+    ) -> Result<Vec<Image>, sqlx::Error> {
+        query_file_as!(Image, "queries/image/deleted_before.sql", date)
+            .fetch_all(pool)
+            .await
+    }
+
     pub async fn by_app_user_id(
         app_user_id: Uuid,
         pool: &PgPool,
@@ -45,35 +209,87 @@ impl Image {
         )
     }
 
+    /// Trashed images are included, the oldest first.
+    ///
+    /// Used for the export and the deletion of the account, which cover the trash too.
+    pub async fn all_by_app_user_id(
+        app_user_id: Uuid,
+        pool: &PgPool,
+    ) -> Result<Vec<Image>, sqlx::Error> {
+        query_file_as!(Image, "queries/image/all_by_app_user_id.sql", app_user_id)
+            .fetch_all(pool)
+            .await
+    }
+
+    /// `None` is returned if the user already has `max_images` images.
     pub async fn new(
         app_user_id: Uuid,
         image: NewImage,
+        max_images: Option<i64>,
         pool: &PgPool,
-    ) -> Result<Uuid, sqlx::Error> {
-        Ok(query_file!(
+    ) -> Result<Option<Uuid>, sqlx::Error> {
+        let mut tx = pool.begin().await?;
+
+        if !StorageUsage::reserve(app_user_id, 0, 1, None, max_images, &mut tx).await? {
+            return Ok(None);
+        }
+
+        let id = query_file!(
             "queries/image/create.sql",
             app_user_id,
             image.title,
-            image.description
+            image.description,
+            image.visibility.as_str()
         )
-        .fetch_one(pool)
-        .await
-        .map(|v| v.id)?)
+        .fetch_one(&mut tx)
+        .await?
+        .id;
+
+        tx.commit().await?;
+        Ok(Some(id))
+    }
+
+    /// The IDs out of the given ones that belong to an image.
+    pub async fn existing_ids(ids: &[Uuid], pool: &PgPool) -> Result<Vec<Uuid>, sqlx::Error> {
+        query_file!("queries/image/existing_ids.sql", ids)
+            .fetch_all(pool)
+            .await
+            .map(|rows| rows.into_iter().map(|r| r.id).collect())
     }
 
     pub async fn search(
-        s: Option<&str>,
+        filter: &ImageFilter,
         offset: Option<i64>,
         limit: Option<i64>,
         pool: &PgPool,
     ) -> Result<Vec<Image>, sqlx::Error> {
-        match s {
+        let bbox = match filter.area {
+            Some(GeoArea::BoundingBox(b)) => Some(b),
+            _ => None,
+        };
+        let radius = match filter.area {
+            Some(GeoArea::Radius { lat, lon, meters }) => Some((lat, lon, meters)),
+            _ => None,
+        };
+
+        match &filter.search {
             Some(s) => query_file_as!(
                 Image,
                 "queries/image/search.sql",
                 s,
                 offset.unwrap_or(0),
-                limit.unwrap_or(10)
+                limit.unwrap_or(10),
+                &filter.tags,
+                filter.viewer_id,
+                filter.captured_after,
+                filter.captured_before,
+                bbox.map(|b| b.min_lat),
+                bbox.map(|b| b.min_lon),
+                bbox.map(|b| b.max_lat),
+                bbox.map(|b| b.max_lon),
+                radius.map(|r| r.0),
+                radius.map(|r| r.1),
+                radius.map(|r| r.2)
             )
             .fetch_all(pool)
             .await
@@ -82,29 +298,288 @@ impl Image {
                 Image,
                 "queries/image/search_no_str.sql",
                 offset.unwrap_or(0),
-                limit.unwrap_or(10)
+                limit.unwrap_or(10),
+                &filter.tags,
+                filter.viewer_id,
+                filter.captured_after,
+                filter.captured_before,
+                bbox.map(|b| b.min_lat),
+                bbox.map(|b| b.min_lon),
+                bbox.map(|b| b.max_lat),
+                bbox.map(|b| b.max_lon),
+                radius.map(|r| r.0),
+                radius.map(|r| r.1),
+                radius.map(|r| r.2)
             )
             .fetch_all(pool)
             .await
             .map(|images| images.into_iter().map(Into::into).collect()),
         }
     }
+
+    /// Uploaded images with a perceptual hash within `max_distance` of the given one,
+    /// the closest first.
+    pub async fn similar(
+        phash: i64,
+        exclude_id: Uuid,
+        app_user_id: Option<Uuid>,
+        max_distance: i32,
+        limit: i64,
+        pool: &PgPool,
+    ) -> Result<Vec<SimilarImage>, sqlx::Error> {
+        query_file_as!(
+            SimilarImage,
+            "queries/image/similar.sql",
+            exclude_id,
+            phash,
+            max_distance,
+            limit,
+            app_user_id
+        )
+        .fetch_all(pool)
+        .await
+    }
+
+    /// Counts the located images in a grid of `cell_size` degrees.
+    pub async fn location_clusters(
+        cell_size: f64,
+        viewer_id: Option<Uuid>,
+        bbox: Option<BoundingBox>,
+        pool: &PgPool,
+    ) -> Result<Vec<LocationCluster>, sqlx::Error> {
+        query_file_as!(
+            LocationCluster,
+            "queries/image/location_clusters.sql",
+            cell_size,
+            viewer_id,
+            bbox.map(|b| b.min_lat),
+            bbox.map(|b| b.min_lon),
+            bbox.map(|b| b.max_lat),
+            bbox.map(|b| b.max_lon)
+        )
+        .fetch_all(pool)
+        .await
+    }
 }
 
 impl Image {
+    /// Inserts an image that was built in memory, together with
+    /// its categories and metadata in one transaction.
+    ///
+    /// `false` is returned if the image does not fit in the limits of the user.
+    pub async fn insert(
+        &self,
+        category_ids: &[Uuid],
+        metadata: Option<&ImageMetadata>,
+        max_bytes: Option<i64>,
+        max_images: Option<i64>,
+        pool: &PgPool,
+    ) -> Result<bool, sqlx::Error> {
+        let mut tx = pool.begin().await?;
+
+        let reserved = StorageUsage::reserve(
+            self.app_user_id,
+            self.file_size.unwrap_or(0),
+            1,
+            max_bytes,
+            max_images,
+            &mut tx,
+        )
+        .await?;
+
+        if !reserved {
+            return Ok(false);
+        }
+
+        query_file!(
+            "queries/image/insert.sql",
+            self.id,
+            self.created,
+            self.upload_date,
+            self.title,
+            self.description,
+            self.app_user_id,
+            &self.visibility,
+            self.latitude,
+            self.longitude,
+            self.phash,
+            self.duplicate_of,
+            self.file_size
+        )
+        .execute(&mut tx)
+        .await?;
+
+        for category_id in category_ids {
+            query_file!("queries/category/add_image.sql", category_id, self.id)
+                .execute(&mut tx)
+                .await?;
+        }
+
+        if let Some(m) = metadata {
+            query_file!(
+                "queries/image_metadata/upsert.sql",
+                m.image_id,
+                m.camera_make,
+                m.camera_model,
+                m.lens_model,
+                m.exposure_time,
+                m.f_number,
+                m.iso,
+                m.focal_length,
+                m.captured,
+                m.orientation
+            )
+            .execute(&mut tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    /// Saves the image after its file was uploaded, together with the metadata.
+    ///
+    /// `false` is returned if the file does not fit in the storage limit of the user.
+    pub async fn save_upload(
+        &self,
+        metadata: Option<&ImageMetadata>,
+        max_bytes: Option<i64>,
+        pool: &PgPool,
+    ) -> Result<bool, sqlx::Error> {
+        let mut tx = pool.begin().await?;
+
+        let reserved = StorageUsage::reserve(
+            self.app_user_id,
+            self.file_size.unwrap_or(0),
+            0,
+            max_bytes,
+            None,
+            &mut tx,
+        )
+        .await?;
+
+        if !reserved {
+            return Ok(false);
+        }
+
+        if let Some(m) = metadata {
+            query_file!(
+                "queries/image_metadata/upsert.sql",
+                m.image_id,
+                m.camera_make,
+                m.camera_model,
+                m.lens_model,
+                m.exposure_time,
+                m.f_number,
+                m.iso,
+                m.focal_length,
+                m.captured,
+                m.orientation
+            )
+            .execute(&mut tx)
+            .await?;
+        }
+
+        query_file!(
+            "queries/image/update.sql",
+            self.id,
+            self.upload_date,
+            self.title,
+            self.description,
+            &self.visibility,
+            self.latitude,
+            self.longitude,
+            self.phash,
+            self.duplicate_of,
+            self.file_size,
+            self.hidden
+        )
+        .execute(&mut tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(true)
+    }
+
     pub async fn save(&self, pool: &PgPool) -> Result<(), sqlx::Error> {
         query_file!(
             "queries/image/update.sql",
             self.id,
             self.upload_date,
             self.title,
-            self.description
+            self.description,
+            &self.visibility,
+            self.latitude,
+            self.longitude,
+            self.phash,
+            self.duplicate_of,
+            self.file_size,
+            self.hidden
         )
         .execute(pool)
         .await?;
         Ok(())
     }
 
+    /// Only updates the hash, other fields may have changed since the image was read.
+    pub async fn save_phash(&self, pool: &PgPool) -> Result<(), sqlx::Error> {
+        query_file!(
+            "queries/image/set_phash.sql",
+            self.id,
+            self.phash,
+            self.duplicate_of
+        )
+        .execute(pool)
+        .await
+        .map(|_| ())
+    }
+
+    /// Everything that belongs to the image is deleted with it, but not the file.
+    pub async fn delete(&self, pool: &PgPool) -> Result<(), sqlx::Error> {
+        query_file!("queries/image/delete.sql", self.id)
+            .execute(pool)
+            .await
+            .map(|_| ())
+    }
+
+    /// Moves the image to the trash, it is left out everywhere until it is restored.
+    pub async fn trash(&self, deleted_by: Uuid, pool: &PgPool) -> Result<(), sqlx::Error> {
+        query_file!("queries/image/trash.sql", self.id, deleted_by)
+            .execute(pool)
+            .await
+            .map(|_| ())
+    }
+
+    pub async fn restore(&self, pool: &PgPool) -> Result<(), sqlx::Error> {
+        query_file!("queries/image/restore.sql", self.id)
+            .execute(pool)
+            .await
+            .map(|_| ())
+    }
+
+    pub fn visibility(&self) -> Visibility {
+        self.visibility.parse().unwrap_or_default()
+    }
+
+    /// As `(latitude, longitude)`.
+    pub fn location(&self) -> Option<(f64, f64)> {
+        self.latitude.zip(self.longitude)
+    }
+
+    /// Private and hidden images are only visible to their owners,
+    /// `None` is an anonymous viewer.
+    pub fn visible_to(&self, app_user_id: Option<Uuid>) -> bool {
+        app_user_id == Some(self.app_user_id)
+            || (self.visibility() != Visibility::Private && !self.hidden)
+    }
+
+    /// Users who can hide images can also see the hidden images of others.
+    pub fn visible_to_role(&self, app_user_id: Uuid, role: Role) -> bool {
+        self.visible_to(Some(app_user_id))
+            || (self.visibility() != Visibility::Private
+                && role.has_permission(Permission::HideImages))
+    }
+
     pub async fn rate(&self, user_id: Uuid, rating: i32, pool: &PgPool) -> Result<(), sqlx::Error> {
         Rating::new(user_id, self.id, rating).save(pool).await
     }
@@ -116,4 +591,49 @@ impl Image {
     pub async fn categories(&self, pool: &PgPool) -> Result<Vec<Category>, sqlx::Error> {
         Category::by_image_id(self.id, pool).await
     }
+
+    pub async fn comment_count(&self, pool: &PgPool) -> Result<i64, sqlx::Error> {
+        Comment::count_by_image_id(self.id, pool).await
+    }
+
+    pub async fn tags(&self, pool: &PgPool) -> Result<Vec<Tag>, sqlx::Error> {
+        Tag::by_image_id(self.id, pool).await
+    }
+
+    pub async fn metadata(&self, pool: &PgPool) -> Result<Option<ImageMetadata>, sqlx::Error> {
+        ImageMetadata::by_image_id(self.id, pool).await
+    }
+}
+
+/// An image with everything needed for the API response.
+pub struct ImageExt {
+    pub image: Image,
+    pub categories: Vec<Category>,
+    pub tags: Vec<Tag>,
+    pub comment_count: i64,
+    pub metadata: Option<ImageMetadata>,
+}
+
+impl ImageExt {
+    pub async fn from_image(image: Image, pool: &PgPool) -> Result<ImageExt, sqlx::Error> {
+        Ok(ImageExt {
+            categories: image.categories(pool).await?,
+            tags: image.tags(pool).await?,
+            comment_count: image.comment_count(pool).await?,
+            metadata: image.metadata(pool).await?,
+            image,
+        })
+    }
+
+    pub async fn from_images(
+        images: Vec<Image>,
+        pool: &PgPool,
+    ) -> Result<Vec<ImageExt>, sqlx::Error> {
+        let mut images_ext = Vec::with_capacity(images.len());
+        for image in images {
+            images_ext.push(ImageExt::from_image(image, pool).await?);
+        }
+
+        Ok(images_ext)
+    }
 }
@@ -0,0 +1,79 @@
+use sqlx::{query_file, query_file_as, PgPool};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use crate::util::exif::ExifData;
+
+/// Exif data read from the uploaded file of an image.
+pub struct ImageMetadata {
+    pub image_id: Uuid,
+    pub camera_make: Option<String>,
+    pub camera_model: Option<String>,
+    pub lens_model: Option<String>,
+    pub exposure_time: Option<String>,
+    pub f_number: Option<f64>,
+    pub iso: Option<i32>,
+    pub focal_length: Option<f64>,
+    pub captured: Option<OffsetDateTime>,
+    pub orientation: Option<i16>,
+}
+
+impl ImageMetadata {
+    pub fn new(image_id: Uuid, exif: ExifData) -> Self {
+        Self {
+            image_id,
+            camera_make: exif.camera_make,
+            camera_model: exif.camera_model,
+            lens_model: exif.lens_model,
+            exposure_time: exif.exposure_time,
+            f_number: exif.f_number,
+            iso: exif.iso,
+            focal_length: exif.focal_length,
+            captured: exif.captured,
+            orientation: exif.orientation,
+        }
+    }
+
+    pub async fn by_image_id(
+        image_id: Uuid,
+        pool: &PgPool,
+    ) -> Result<Option<ImageMetadata>, sqlx::Error> {
+        let res = query_file_as!(
+            ImageMetadata,
+            "queries/image_metadata/by_image_id.sql",
+            image_id
+        )
+        .fetch_one(pool)
+        .await;
+
+        match res {
+            Ok(m) => Ok(Some(m)),
+            Err(e) => match e {
+                sqlx::Error::RowNotFound => Ok(None),
+                _ => Err(e),
+            },
+        }
+    }
+}
+
+impl ImageMetadata {
+    /// Replaces the metadata if the image already had any.
+    pub async fn save(&self, pool: &PgPool) -> Result<(), sqlx::Error> {
+        query_file!(
+            "queries/image_metadata/upsert.sql",
+            self.image_id,
+            self.camera_make,
+            self.camera_model,
+            self.lens_model,
+            self.exposure_time,
+            self.f_number,
+            self.iso,
+            self.focal_length,
+            self.captured,
+            self.orientation
+        )
+        .execute(pool)
+        .await
+        .map(|_| ())
+    }
+}
@@ -0,0 +1,99 @@
+use sqlx::{query_file, query_file_as, Done, PgPool};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+/// A report of an image by a user, open until a moderator acts on it.
+pub struct ImageReport {
+    pub id: Uuid,
+    pub created: OffsetDateTime,
+    pub image_id: Uuid,
+    /// Missing if the reporter deleted their account.
+    pub app_user_id: Option<Uuid>,
+    pub reason: String,
+    pub details: Option<String>,
+    /// The action that resolved the report.
+    pub moderation_action_id: Option<Uuid>,
+}
+
+/// An image with open reports.
+pub struct ReportedImage {
+    pub image_id: Uuid,
+    pub report_count: i64,
+    pub first_reported: OffsetDateTime,
+    pub last_reported: OffsetDateTime,
+}
+
+pub struct NewImageReport<'a> {
+    pub image_id: Uuid,
+    pub reason: &'a str,
+    pub details: Option<&'a str>,
+}
+
+impl ImageReport {
+    /// Returns `None` if the user already has an open report of the image.
+    pub async fn new(
+        app_user_id: Uuid,
+        report: NewImageReport<'_>,
+        pool: &PgPool,
+    ) -> Result<Option<Uuid>, sqlx::Error> {
+        query_file!(
+            "queries/image_report/create.sql",
+            report.image_id,
+            app_user_id,
+            report.reason,
+            report.details
+        )
+        .fetch_optional(pool)
+        .await
+        .map(|res| res.map(|r| r.id))
+    }
+
+    /// Oldest first.
+    pub async fn open_by_image_id(
+        image_id: Uuid,
+        pool: &PgPool,
+    ) -> Result<Vec<ImageReport>, sqlx::Error> {
+        query_file_as!(
+            ImageReport,
+            "queries/image_report/open_by_image_id.sql",
+            image_id
+        )
+        .fetch_all(pool)
+        .await
+    }
+
+    /// Marks the open reports of the image as resolved by the action,
+    /// returns the number of reports.
+    pub async fn resolve(
+        image_id: Uuid,
+        moderation_action_id: Uuid,
+        pool: &PgPool,
+    ) -> Result<u64, sqlx::Error> {
+        query_file!(
+            "queries/image_report/resolve.sql",
+            image_id,
+            moderation_action_id
+        )
+        .execute(pool)
+        .await
+        .map(|res| res.rows_affected())
+    }
+}
+
+impl ReportedImage {
+    /// The most reported images first.
+    pub async fn open(
+        offset: Option<i64>,
+        limit: Option<i64>,
+        pool: &PgPool,
+    ) -> Result<Vec<ReportedImage>, sqlx::Error> {
+        query_file_as!(
+            ReportedImage,
+            "queries/image_report/open_images.sql",
+            offset.unwrap_or(0),
+            limit.unwrap_or(10)
+        )
+        .fetch_all(pool)
+        .await
+    }
+}
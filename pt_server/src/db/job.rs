@@ -0,0 +1,163 @@
+use sqlx::{query_file, query_file_as, Done, PgPool};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+/// A background job in the queue.
+pub struct Job {
+    pub id: Uuid,
+    pub created: OffsetDateTime,
+    pub job_type: String,
+    pub payload: serde_json::Value,
+    pub job_status: String,
+    /// Including the one in progress.
+    ///
+    /// Every claim increments it, so it also tells which worker holds the lock.
+    pub attempts: i32,
+    pub max_attempts: i32,
+    pub run_after: OffsetDateTime,
+    pub locked_until: Option<OffsetDateTime>,
+    pub last_error: Option<String>,
+    pub completed: Option<OffsetDateTime>,
+}
+
+pub struct NewJob<'a> {
+    pub job_type: &'a str,
+    pub payload: serde_json::Value,
+    pub max_attempts: i32,
+    pub run_after: OffsetDateTime,
+}
+
+impl Job {
+    pub async fn new(job: NewJob<'_>, pool: &PgPool) -> Result<Uuid, sqlx::Error> {
+        query_file!(
+            "queries/job/create.sql",
+            job.job_type,
+            job.payload,
+            job.max_attempts,
+            job.run_after
+        )
+        .fetch_one(pool)
+        .await
+        .map(|res| res.id)
+    }
+
+    /// Locks the next job that is due for the given number of seconds.
+    ///
+    /// Running jobs whose lock has expired are claimed again.
+    pub async fn claim(lock_seconds: i64, pool: &PgPool) -> Result<Option<Job>, sqlx::Error> {
+        let res = query_file_as!(Job, "queries/job/claim.sql", lock_seconds as f64)
+            .fetch_one(pool)
+            .await;
+
+        match res {
+            Ok(j) => Ok(Some(j)),
+            Err(e) => match e {
+                sqlx::Error::RowNotFound => Ok(None),
+                _ => Err(e),
+            },
+        }
+    }
+
+    pub async fn delete_completed_before(
+        date: OffsetDateTime,
+        pool: &PgPool,
+    ) -> Result<u64, sqlx::Error> {
+        query_file!("queries/job/delete_completed_before.sql", date)
+            .execute(pool)
+            .await
+            .map(|res| res.rows_affected())
+    }
+}
+
+/// The updates of a claimed job return `false` if its lock
+/// has expired and another worker claimed it in the meantime.
+impl Job {
+    /// Keeps the job locked for the given number of seconds from now.
+    pub async fn extend_lock(&self, lock_seconds: i64, pool: &PgPool) -> Result<bool, sqlx::Error> {
+        query_file!(
+            "queries/job/extend_lock.sql",
+            self.id,
+            lock_seconds as f64,
+            self.attempts
+        )
+        .execute(pool)
+        .await
+        .map(|res| res.rows_affected() > 0)
+    }
+
+    pub async fn complete(&self, pool: &PgPool) -> Result<bool, sqlx::Error> {
+        query_file!("queries/job/complete.sql", self.id, self.attempts)
+            .execute(pool)
+            .await
+            .map(|res| res.rows_affected() > 0)
+    }
+
+    /// Queues the job again after the given number of seconds.
+    pub async fn retry(
+        &self,
+        delay_seconds: i64,
+        error: &str,
+        pool: &PgPool,
+    ) -> Result<bool, sqlx::Error> {
+        query_file!(
+            "queries/job/retry.sql",
+            self.id,
+            delay_seconds as f64,
+            error,
+            self.attempts
+        )
+        .execute(pool)
+        .await
+        .map(|res| res.rows_affected() > 0)
+    }
+
+    /// Moves the job to the dead letter state, it is not retried anymore.
+    pub async fn kill(&self, error: &str, pool: &PgPool) -> Result<bool, sqlx::Error> {
+        query_file!("queries/job/kill.sql", self.id, error, self.attempts)
+            .execute(pool)
+            .await
+            .map(|res| res.rows_affected() > 0)
+    }
+}
+
+/// A recurring job.
+pub struct JobSchedule;
+
+impl JobSchedule {
+    /// The first run is due immediately.
+    pub async fn register(job_type: &str, pool: &PgPool) -> Result<(), sqlx::Error> {
+        query_file!("queries/job_schedule/register.sql", job_type)
+            .execute(pool)
+            .await
+            .map(|_| ())
+    }
+
+    /// Queues the job if it is due, and schedules the next run.
+    ///
+    /// A run is skipped if the previous one has not finished yet.
+    pub async fn enqueue_due(
+        job_type: &str,
+        payload: serde_json::Value,
+        max_attempts: i32,
+        interval_seconds: i64,
+        pool: &PgPool,
+    ) -> Result<Option<Uuid>, sqlx::Error> {
+        let res = query_file!(
+            "queries/job_schedule/enqueue_due.sql",
+            job_type,
+            interval_seconds as f64,
+            payload,
+            max_attempts
+        )
+        .fetch_one(pool)
+        .await;
+
+        match res {
+            Ok(r) => Ok(Some(r.id)),
+            Err(e) => match e {
+                sqlx::Error::RowNotFound => Ok(None),
+                _ => Err(e),
+            },
+        }
+    }
+}
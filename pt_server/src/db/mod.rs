@@ -1,10 +1,26 @@
 use crate::config::Config;
 use sqlx::postgres::PgPoolOptions;
 
+pub mod account_export;
+pub mod album;
+pub mod api_key;
 pub mod app_user;
+pub mod audit_event;
 pub mod image;
+pub mod image_metadata;
+pub mod image_report;
+pub mod job;
 pub mod category;
+pub mod comment;
+pub mod moderation_action;
+pub mod notification;
 pub mod rating;
+pub mod recovery_code;
+pub mod share_link;
+pub mod tag;
+pub mod upload_session;
+pub mod user_identity;
+pub mod user_quota;
 
 pub async fn connect(config: &Config) -> anyhow::Result<sqlx::PgPool> {
     Ok(PgPoolOptions::new()
@@ -0,0 +1,71 @@
+use sqlx::{query_file, query_file_as, PgPool};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+/// What a moderator did with an image, kept after the image is deleted.
+pub struct ModerationAction {
+    pub id: Uuid,
+    pub created: OffsetDateTime,
+    /// Missing if the moderator deleted their account.
+    pub moderator_id: Option<Uuid>,
+    pub image_id: Uuid,
+    pub image_owner_id: Option<Uuid>,
+    pub action: String,
+    pub note: Option<String>,
+}
+
+pub struct NewModerationAction<'a> {
+    pub image_id: Uuid,
+    pub image_owner_id: Uuid,
+    pub action: &'a str,
+    pub note: Option<&'a str>,
+}
+
+impl ModerationAction {
+    /// Records the action and resolves the open reports of the image with it.
+    pub async fn new(
+        moderator_id: Uuid,
+        action: NewModerationAction<'_>,
+        pool: &PgPool,
+    ) -> Result<Uuid, sqlx::Error> {
+        let mut tx = pool.begin().await?;
+
+        let id = query_file!(
+            "queries/moderation_action/create.sql",
+            moderator_id,
+            action.image_id,
+            action.image_owner_id,
+            action.action,
+            action.note
+        )
+        .fetch_one(&mut tx)
+        .await?
+        .id;
+
+        query_file!("queries/image_report/resolve.sql", action.image_id, id)
+            .execute(&mut tx)
+            .await?;
+
+        tx.commit().await?;
+
+        Ok(id)
+    }
+
+    /// The latest first, optionally only the ones of an image.
+    pub async fn list(
+        image_id: Option<Uuid>,
+        offset: Option<i64>,
+        limit: Option<i64>,
+        pool: &PgPool,
+    ) -> Result<Vec<ModerationAction>, sqlx::Error> {
+        query_file_as!(
+            ModerationAction,
+            "queries/moderation_action/list.sql",
+            image_id,
+            offset.unwrap_or(0),
+            limit.unwrap_or(10)
+        )
+        .fetch_all(pool)
+        .await
+    }
+}
@@ -0,0 +1,127 @@
+use sqlx::{query_file, query_file_as, Done, PgPool};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+/// Something that happened to the content of a user.
+pub struct Notification {
+    pub id: Uuid,
+    pub created: OffsetDateTime,
+    /// The recipient.
+    pub app_user_id: Uuid,
+    pub actor_id: Option<Uuid>,
+    pub notification_type: String,
+    pub image_id: Option<Uuid>,
+    pub comment_id: Option<Uuid>,
+    pub details: Option<serde_json::Value>,
+    pub read_date: Option<OffsetDateTime>,
+}
+
+pub struct NewNotification<'a> {
+    pub app_user_id: Uuid,
+    pub actor_id: Option<Uuid>,
+    pub notification_type: &'a str,
+    pub image_id: Option<Uuid>,
+    pub comment_id: Option<Uuid>,
+    pub details: Option<serde_json::Value>,
+}
+
+impl Notification {
+    /// Connected clients of the user are notified by the database.
+    pub async fn new(
+        notification: NewNotification<'_>,
+        pool: &PgPool,
+    ) -> Result<Uuid, sqlx::Error> {
+        query_file!(
+            "queries/notification/create.sql",
+            notification.app_user_id,
+            notification.actor_id,
+            notification.notification_type,
+            notification.image_id,
+            notification.comment_id,
+            notification.details
+        )
+        .fetch_one(pool)
+        .await
+        .map(|res| res.id)
+    }
+
+    pub async fn by_id(id: Uuid, pool: &PgPool) -> Result<Option<Notification>, sqlx::Error> {
+        let res = query_file_as!(Notification, "queries/notification/by_id.sql", id)
+            .fetch_one(pool)
+            .await;
+
+        match res {
+            Ok(n) => Ok(Some(n)),
+            Err(e) => match e {
+                sqlx::Error::RowNotFound => Ok(None),
+                _ => Err(e),
+            },
+        }
+    }
+
+    /// The latest first.
+    pub async fn by_app_user_id(
+        app_user_id: Uuid,
+        unread_only: bool,
+        offset: Option<i64>,
+        limit: Option<i64>,
+        pool: &PgPool,
+    ) -> Result<Vec<Notification>, sqlx::Error> {
+        query_file_as!(
+            Notification,
+            "queries/notification/by_app_user_id.sql",
+            app_user_id,
+            unread_only,
+            offset.unwrap_or(0),
+            limit.unwrap_or(10)
+        )
+        .fetch_all(pool)
+        .await
+    }
+
+    /// The notifications created after the given one, the oldest first.
+    ///
+    /// None are returned if the user has no such notification.
+    pub async fn after(
+        app_user_id: Uuid,
+        id: Uuid,
+        limit: i64,
+        pool: &PgPool,
+    ) -> Result<Vec<Notification>, sqlx::Error> {
+        query_file_as!(
+            Notification,
+            "queries/notification/after.sql",
+            app_user_id,
+            id,
+            limit
+        )
+        .fetch_all(pool)
+        .await
+    }
+
+    pub async fn count_unread(app_user_id: Uuid, pool: &PgPool) -> Result<i64, sqlx::Error> {
+        query_file!("queries/notification/count_unread.sql", app_user_id)
+            .fetch_one(pool)
+            .await
+            .map(|res| res.count.unwrap_or(0))
+    }
+
+    /// Returns false if the user has no such notification.
+    pub async fn mark_read(
+        id: Uuid,
+        app_user_id: Uuid,
+        pool: &PgPool,
+    ) -> Result<bool, sqlx::Error> {
+        query_file!("queries/notification/mark_read.sql", id, app_user_id)
+            .execute(pool)
+            .await
+            .map(|res| res.rows_affected() > 0)
+    }
+
+    pub async fn mark_all_read(app_user_id: Uuid, pool: &PgPool) -> Result<u64, sqlx::Error> {
+        query_file!("queries/notification/mark_all_read.sql", app_user_id)
+            .execute(pool)
+            .await
+            .map(|res| res.rows_affected())
+    }
+}
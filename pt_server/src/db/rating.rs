@@ -21,6 +21,16 @@ impl Rating {
             .fetch_all(pool)
             .await
     }
+
+    /// The ratings the user gave.
+    pub async fn by_app_user_id(
+        app_user_id: Uuid,
+        pool: &PgPool,
+    ) -> Result<Vec<Rating>, sqlx::Error> {
+        query_file_as!(Rating, "queries/rating/all_by_app_user_id.sql", app_user_id)
+            .fetch_all(pool)
+            .await
+    }
 }
 
 impl Rating {
@@ -0,0 +1,54 @@
+use sqlx::{query_file, query_file_as, Done, PgPool};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+/// A hashed one-time recovery code for two-factor authentication.
+pub struct RecoveryCode {
+    pub id: Uuid,
+    pub created: OffsetDateTime,
+    pub app_user_id: Uuid,
+    pub code_hash: String,
+    pub used: Option<OffsetDateTime>,
+}
+
+impl RecoveryCode {
+    pub async fn new(app_user_id: Uuid, code_hash: &str, pool: &PgPool) -> Result<Uuid, sqlx::Error> {
+        query_file!("queries/recovery_code/create.sql", app_user_id, code_hash)
+            .fetch_one(pool)
+            .await
+            .map(|res| res.id)
+    }
+
+    pub async fn unused_by_app_user_id(
+        app_user_id: Uuid,
+        pool: &PgPool,
+    ) -> Result<Vec<RecoveryCode>, sqlx::Error> {
+        query_file_as!(
+            RecoveryCode,
+            "queries/recovery_code/unused_by_app_user_id.sql",
+            app_user_id
+        )
+        .fetch_all(pool)
+        .await
+    }
+
+    pub async fn delete_by_app_user_id(app_user_id: Uuid, pool: &PgPool) -> Result<(), sqlx::Error> {
+        query_file!(
+            "queries/recovery_code/delete_by_app_user_id.sql",
+            app_user_id
+        )
+        .execute(pool)
+        .await
+        .map(|_| ())
+    }
+}
+
+impl RecoveryCode {
+    /// Returns false if the code was used in the meantime.
+    pub async fn mark_used(&self, pool: &PgPool) -> Result<bool, sqlx::Error> {
+        query_file!("queries/recovery_code/mark_used.sql", self.id)
+            .execute(pool)
+            .await
+            .map(|res| res.rows_affected() > 0)
+    }
+}
@@ -0,0 +1,88 @@
+use sqlx::{query_file, query_file_as, PgPool};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+/// A link that gives anonymous read access to a single image.
+pub struct ShareLink {
+    pub id: Uuid,
+    pub created: OffsetDateTime,
+    pub app_user_id: Uuid,
+    pub image_id: Uuid,
+    pub token: String,
+    pub expires: Option<OffsetDateTime>,
+    pub revoked: Option<OffsetDateTime>,
+}
+
+impl ShareLink {
+    pub async fn new(
+        app_user_id: Uuid,
+        image_id: Uuid,
+        token: &str,
+        expires: Option<OffsetDateTime>,
+        pool: &PgPool,
+    ) -> Result<Uuid, sqlx::Error> {
+        query_file!(
+            "queries/share_link/create.sql",
+            app_user_id,
+            image_id,
+            token,
+            expires
+        )
+        .fetch_one(pool)
+        .await
+        .map(|res| res.id)
+    }
+
+    pub async fn by_id(id: Uuid, pool: &PgPool) -> Result<Option<ShareLink>, sqlx::Error> {
+        let res = query_file_as!(ShareLink, "queries/share_link/by_id.sql", id)
+            .fetch_one(pool)
+            .await;
+
+        match res {
+            Ok(l) => Ok(Some(l)),
+            Err(e) => match e {
+                sqlx::Error::RowNotFound => Ok(None),
+                _ => Err(e),
+            },
+        }
+    }
+
+    pub async fn by_token(token: &str, pool: &PgPool) -> Result<Option<ShareLink>, sqlx::Error> {
+        let res = query_file_as!(ShareLink, "queries/share_link/by_token.sql", token)
+            .fetch_one(pool)
+            .await;
+
+        match res {
+            Ok(l) => Ok(Some(l)),
+            Err(e) => match e {
+                sqlx::Error::RowNotFound => Ok(None),
+                _ => Err(e),
+            },
+        }
+    }
+
+    /// Only returns links that were not revoked.
+    pub async fn by_image_id(image_id: Uuid, pool: &PgPool) -> Result<Vec<ShareLink>, sqlx::Error> {
+        query_file_as!(ShareLink, "queries/share_link/by_image_id.sql", image_id)
+            .fetch_all(pool)
+            .await
+    }
+}
+
+impl ShareLink {
+    /// Not revoked and not expired.
+    pub fn is_active(&self) -> bool {
+        self.revoked.is_none()
+            && self
+                .expires
+                .map(|e| e > OffsetDateTime::now_utc())
+                .unwrap_or(true)
+    }
+
+    pub async fn revoke(&self, pool: &PgPool) -> Result<(), sqlx::Error> {
+        query_file!("queries/share_link/revoke.sql", self.id)
+            .execute(pool)
+            .await
+            .map(|_| ())
+    }
+}
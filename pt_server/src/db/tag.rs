@@ -0,0 +1,110 @@
+use sqlx::{query_file, query_file_as, PgPool};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+/// A free-form tag given by users, the names are normalised.
+pub struct Tag {
+    pub id: Uuid,
+    pub created: OffsetDateTime,
+    pub tag_name: String,
+}
+
+impl Tag {
+    pub async fn by_id(id: Uuid, pool: &PgPool) -> Result<Option<Tag>, sqlx::Error> {
+        let res = query_file_as!(Tag, "queries/tag/by_id.sql", id)
+            .fetch_one(pool)
+            .await;
+
+        match res {
+            Ok(t) => Ok(Some(t)),
+            Err(e) => match e {
+                sqlx::Error::RowNotFound => Ok(None),
+                _ => Err(e),
+            },
+        }
+    }
+
+    pub async fn by_image_id(image_id: Uuid, pool: &PgPool) -> Result<Vec<Tag>, sqlx::Error> {
+        query_file_as!(Tag, "queries/tag/by_image_id.sql", image_id)
+            .fetch_all(pool)
+            .await
+    }
+
+    /// Replaces the tags of the image, missing tags are created.
+    pub async fn set_image_tags(
+        image_id: Uuid,
+        tag_names: &[String],
+        pool: &PgPool,
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = pool.begin().await?;
+
+        query_file!("queries/tag/remove_by_image_id.sql", image_id)
+            .execute(&mut tx)
+            .await?;
+
+        for tag_name in tag_names {
+            let tag_id = query_file!("queries/tag/upsert.sql", tag_name)
+                .fetch_one(&mut tx)
+                .await?
+                .id;
+
+            query_file!("queries/tag/add_image.sql", image_id, tag_id)
+                .execute(&mut tx)
+                .await?;
+        }
+
+        tx.commit().await
+    }
+}
+
+impl Tag {
+    /// Adds the category to every image with this tag, then deletes the tag.
+    pub async fn promote(&self, category_id: Uuid, pool: &PgPool) -> Result<(), sqlx::Error> {
+        let mut tx = pool.begin().await?;
+
+        query_file!("queries/tag/copy_to_category.sql", self.id, category_id)
+            .execute(&mut tx)
+            .await?;
+
+        query_file!("queries/tag/remove_images.sql", self.id)
+            .execute(&mut tx)
+            .await?;
+
+        query_file!("queries/tag/delete.sql", self.id)
+            .execute(&mut tx)
+            .await?;
+
+        tx.commit().await
+    }
+}
+
+pub struct TagCount {
+    pub id: Uuid,
+    pub tag_name: String,
+    pub image_count: Option<i64>,
+}
+
+impl TagCount {
+    /// Tags starting with the given text come first, then the similar ones.
+    pub async fn autocomplete(
+        text: &str,
+        limit: Option<i64>,
+        pool: &PgPool,
+    ) -> Result<Vec<TagCount>, sqlx::Error> {
+        query_file_as!(
+            TagCount,
+            "queries/tag/autocomplete.sql",
+            text,
+            limit.unwrap_or(10)
+        )
+        .fetch_all(pool)
+        .await
+    }
+
+    /// The most used tags.
+    pub async fn popular(limit: Option<i64>, pool: &PgPool) -> Result<Vec<TagCount>, sqlx::Error> {
+        query_file_as!(TagCount, "queries/tag/popular.sql", limit.unwrap_or(10))
+            .fetch_all(pool)
+            .await
+    }
+}
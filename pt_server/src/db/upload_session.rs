@@ -0,0 +1,141 @@
+use sqlx::{query_file, query_file_as, Done, PgPool};
+use time::{Duration, OffsetDateTime};
+use uuid::Uuid;
+
+/// Sessions are abandoned after this long.
+pub const UPLOAD_SESSION_TTL_HOURS: i64 = 24;
+
+/// A resumable upload of the file of an image.
+pub struct UploadSession {
+    pub id: Uuid,
+    pub created: OffsetDateTime,
+    pub app_user_id: Uuid,
+    pub image_id: Uuid,
+    /// The size of the whole file in bytes.
+    pub upload_length: i64,
+    /// The bytes received so far.
+    pub upload_offset: i64,
+    /// Hex encoded SHA-256 of the whole file.
+    pub checksum: String,
+    pub file_extension: String,
+    /// Set while a request finishes the upload.
+    pub finishing: bool,
+}
+
+/// New upload session without ID
+pub struct NewUploadSession<'a> {
+    pub image_id: Uuid,
+    pub upload_length: i64,
+    pub checksum: &'a str,
+    pub file_extension: &'a str,
+}
+
+impl UploadSession {
+    pub async fn new(
+        app_user_id: Uuid,
+        session: NewUploadSession<'_>,
+        pool: &PgPool,
+    ) -> Result<Uuid, sqlx::Error> {
+        query_file!(
+            "queries/upload_session/create.sql",
+            app_user_id,
+            session.image_id,
+            session.upload_length,
+            session.checksum,
+            session.file_extension
+        )
+        .fetch_one(pool)
+        .await
+        .map(|res| res.id)
+    }
+
+    pub async fn by_id(id: Uuid, pool: &PgPool) -> Result<Option<UploadSession>, sqlx::Error> {
+        let res = query_file_as!(UploadSession, "queries/upload_session/by_id.sql", id)
+            .fetch_one(pool)
+            .await;
+
+        match res {
+            Ok(s) => Ok(Some(s)),
+            Err(e) => match e {
+                sqlx::Error::RowNotFound => Ok(None),
+                _ => Err(e),
+            },
+        }
+    }
+
+    pub async fn by_app_user_id(
+        app_user_id: Uuid,
+        pool: &PgPool,
+    ) -> Result<Vec<UploadSession>, sqlx::Error> {
+        query_file_as!(
+            UploadSession,
+            "queries/upload_session/by_app_user_id.sql",
+            app_user_id
+        )
+        .fetch_all(pool)
+        .await
+    }
+}
+
+impl UploadSession {
+    pub fn is_expired(&self) -> bool {
+        self.created + Duration::hours(UPLOAD_SESSION_TTL_HOURS) < OffsetDateTime::now_utc()
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.upload_offset == self.upload_length
+    }
+
+    /// Moves the offset forward, `false` is returned if another
+    /// request changed it in the meantime.
+    pub async fn advance(&mut self, offset: i64, pool: &PgPool) -> Result<bool, sqlx::Error> {
+        let res = query_file!(
+            "queries/upload_session/advance.sql",
+            self.id,
+            self.upload_offset,
+            offset
+        )
+        .execute(pool)
+        .await?;
+
+        if res.rows_affected() == 0 {
+            return Ok(false);
+        }
+
+        self.upload_offset = offset;
+        Ok(true)
+    }
+
+    /// Marks the complete session as being finished, `false` is returned
+    /// if it is incomplete or another request is finishing it.
+    pub async fn claim(&mut self, pool: &PgPool) -> Result<bool, sqlx::Error> {
+        let res = query_file!("queries/upload_session/claim.sql", self.id)
+            .execute(pool)
+            .await?;
+
+        if res.rows_affected() == 0 {
+            return Ok(false);
+        }
+
+        self.finishing = true;
+        Ok(true)
+    }
+
+    /// Lets the session be finished again, or continued from the given offset.
+    pub async fn release(&mut self, offset: i64, pool: &PgPool) -> Result<(), sqlx::Error> {
+        query_file!("queries/upload_session/release.sql", self.id, offset)
+            .execute(pool)
+            .await?;
+
+        self.finishing = false;
+        self.upload_offset = offset;
+        Ok(())
+    }
+
+    pub async fn delete(&self, pool: &PgPool) -> Result<(), sqlx::Error> {
+        query_file!("queries/upload_session/delete.sql", self.id)
+            .execute(pool)
+            .await
+            .map(|_| ())
+    }
+}
@@ -0,0 +1,109 @@
+use sqlx::{query_file, query_file_as, Done, PgPool};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+/// An identity at an external OpenID Connect provider linked to a user.
+pub struct UserIdentity {
+    pub id: Uuid,
+    pub created: OffsetDateTime,
+    pub app_user_id: Uuid,
+    pub issuer: String,
+    pub subject: String,
+    pub email: Option<String>,
+}
+
+impl UserIdentity {
+    pub async fn new(
+        app_user_id: Uuid,
+        issuer: &str,
+        subject: &str,
+        email: Option<&str>,
+        pool: &PgPool,
+    ) -> Result<Uuid, sqlx::Error> {
+        query_file!(
+            "queries/user_identity/create.sql",
+            app_user_id,
+            issuer,
+            subject,
+            email
+        )
+        .fetch_one(pool)
+        .await
+        .map(|res| res.id)
+    }
+
+    pub async fn by_issuer_subject(
+        issuer: &str,
+        subject: &str,
+        pool: &PgPool,
+    ) -> Result<Option<UserIdentity>, sqlx::Error> {
+        let res = query_file_as!(
+            UserIdentity,
+            "queries/user_identity/by_issuer_subject.sql",
+            issuer,
+            subject
+        )
+        .fetch_one(pool)
+        .await;
+
+        match res {
+            Ok(i) => Ok(Some(i)),
+            Err(e) => match e {
+                sqlx::Error::RowNotFound => Ok(None),
+                _ => Err(e),
+            },
+        }
+    }
+}
+
+/// A pending OpenID Connect login started by this server.
+pub struct OidcLogin {
+    pub oidc_state: String,
+    pub created: OffsetDateTime,
+    pub code_verifier: String,
+    pub nonce: String,
+}
+
+impl OidcLogin {
+    /// Returns false if there are already `max_pending` logins.
+    pub async fn new(
+        oidc_state: &str,
+        code_verifier: &str,
+        nonce: &str,
+        max_pending: i64,
+        pool: &PgPool,
+    ) -> Result<bool, sqlx::Error> {
+        query_file!(
+            "queries/oidc_login/create.sql",
+            oidc_state,
+            code_verifier,
+            nonce,
+            max_pending
+        )
+        .execute(pool)
+        .await
+        .map(|res| res.rows_affected() > 0)
+    }
+
+    /// Removes and returns the login, so that every state can only be used once.
+    pub async fn take(oidc_state: &str, pool: &PgPool) -> Result<Option<OidcLogin>, sqlx::Error> {
+        let res = query_file_as!(OidcLogin, "queries/oidc_login/take.sql", oidc_state)
+            .fetch_one(pool)
+            .await;
+
+        match res {
+            Ok(l) => Ok(Some(l)),
+            Err(e) => match e {
+                sqlx::Error::RowNotFound => Ok(None),
+                _ => Err(e),
+            },
+        }
+    }
+
+    pub async fn delete_older_than(date: OffsetDateTime, pool: &PgPool) -> Result<(), sqlx::Error> {
+        query_file!("queries/oidc_login/delete_older_than.sql", date)
+            .execute(pool)
+            .await
+            .map(|_| ())
+    }
+}
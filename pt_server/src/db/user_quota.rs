@@ -0,0 +1,98 @@
+use sqlx::{query_file, query_file_as, PgPool, Postgres, Transaction};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+/// Storage limits of a user set by an admin.
+///
+/// The configured defaults apply where a limit is missing.
+pub struct UserQuota {
+    pub app_user_id: Uuid,
+    pub max_bytes: Option<i64>,
+    pub max_images: Option<i64>,
+    pub updated: OffsetDateTime,
+}
+
+/// The total size of the stored files and the number of images of a user.
+///
+/// Images in the trash are counted until they are purged, their files are still stored.
+pub struct StorageUsage {
+    pub bytes: i64,
+    pub images: i64,
+}
+
+impl UserQuota {
+    pub async fn by_app_user_id(
+        app_user_id: Uuid,
+        pool: &PgPool,
+    ) -> Result<Option<UserQuota>, sqlx::Error> {
+        let res = query_file_as!(
+            UserQuota,
+            "queries/user_quota/by_app_user_id.sql",
+            app_user_id
+        )
+        .fetch_one(pool)
+        .await;
+
+        match res {
+            Ok(q) => Ok(Some(q)),
+            Err(e) => match e {
+                sqlx::Error::RowNotFound => Ok(None),
+                _ => Err(e),
+            },
+        }
+    }
+
+    /// Replaces the limits if the user already had any.
+    pub async fn set(
+        app_user_id: Uuid,
+        max_bytes: Option<i64>,
+        max_images: Option<i64>,
+        pool: &PgPool,
+    ) -> Result<(), sqlx::Error> {
+        query_file!(
+            "queries/user_quota/upsert.sql",
+            app_user_id,
+            max_bytes,
+            max_images
+        )
+        .execute(pool)
+        .await
+        .map(|_| ())
+    }
+}
+
+impl StorageUsage {
+    /// Trashed images are included, their files still take up the storage.
+    pub async fn by_app_user_id(
+        app_user_id: Uuid,
+        pool: &PgPool,
+    ) -> Result<StorageUsage, sqlx::Error> {
+        query_file_as!(StorageUsage, "queries/user_quota/usage.sql", app_user_id)
+            .fetch_one(pool)
+            .await
+    }
+
+    /// Checks whether `bytes` and `images` more fit in the limits, `None` means no limit.
+    ///
+    /// The user stays locked until the transaction ends, so that
+    /// concurrent uploads cannot exceed the limits together.
+    pub async fn reserve(
+        app_user_id: Uuid,
+        bytes: i64,
+        images: i64,
+        max_bytes: Option<i64>,
+        max_images: Option<i64>,
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> Result<bool, sqlx::Error> {
+        query_file!("queries/user_quota/lock.sql", app_user_id)
+            .fetch_one(&mut *tx)
+            .await?;
+
+        let usage = query_file_as!(StorageUsage, "queries/user_quota/usage.sql", app_user_id)
+            .fetch_one(&mut *tx)
+            .await?;
+
+        Ok(max_bytes.map_or(true, |m| usage.bytes + bytes <= m)
+            && max_images.map_or(true, |m| usage.images + images <= m))
+    }
+}
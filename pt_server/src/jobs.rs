@@ -0,0 +1,404 @@
+//! A job queue in Postgres for work outside of the requests.
+//!
+//! Workers claim jobs with `SELECT ... FOR UPDATE SKIP LOCKED`, so any number
+//! of them can run in the server or in separate `worker` processes.
+//! The lock of a job is extended while it runs, so it only expires if the worker dies.
+//! Failed jobs are retried with exponential backoff until they run out of attempts,
+//! then they are kept in the dead letter state.
+
+use crate::{
+    config::Config,
+    db::job::{Job as DbJob, JobSchedule, NewJob},
+    services::{
+        AccountService, DefaultAccountService, DefaultImageService, DefaultTrashService,
+        ImageService, TrashService,
+    },
+};
+use futures::future::{self, Either};
+use serde::{Deserialize, Serialize};
+use slog::{error, info, warn, Logger};
+use sqlx::PgPool;
+use std::time::Duration as StdDuration;
+use time::{Duration, OffsetDateTime};
+use uuid::Uuid;
+
+/// Claimed jobs are given to another worker after this, in case the worker died.
+const JOB_LOCK_SECONDS: i64 = 5 * 60;
+
+/// How often the lock of a running job is extended.
+const JOB_HEARTBEAT_SECONDS: u64 = 60;
+
+/// How often idle workers look for new jobs.
+const POLL_INTERVAL_SECONDS: u64 = 1;
+
+/// How often the schedules are checked.
+const SCHEDULE_INTERVAL_SECONDS: u64 = 30;
+
+/// Completed jobs are deleted after this, dead ones are kept.
+const COMPLETED_JOB_RETENTION_DAYS: i64 = 7;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum Job {
+    /// Deletes everything that has been in the trash for longer than the retention period.
+    PurgeTrash,
+    #[serde(rename_all = "camelCase")]
+    BuildAccountExport { export_id: Uuid },
+    /// Computes the perceptual hash of an uploaded file and marks the image
+    /// as a duplicate of the closest earlier one.
+    #[serde(rename_all = "camelCase")]
+    ProcessUpload { image_id: Uuid },
+    /// Deletes old completed jobs.
+    PurgeJobs,
+}
+
+impl Job {
+    pub fn job_type(&self) -> &'static str {
+        match self {
+            Job::PurgeTrash => "purgeTrash",
+            Job::BuildAccountExport { .. } => "buildAccountExport",
+            Job::ProcessUpload { .. } => "processUpload",
+            Job::PurgeJobs => "purgeJobs",
+        }
+    }
+
+    pub fn max_attempts(&self) -> i32 {
+        match self {
+            Job::PurgeTrash => 3,
+            Job::BuildAccountExport { .. } => 5,
+            Job::ProcessUpload { .. } => 3,
+            Job::PurgeJobs => 3,
+        }
+    }
+}
+
+/// Recurring jobs with the seconds between their runs.
+fn schedules() -> Vec<(Job, i64)> {
+    vec![(Job::PurgeTrash, 60 * 60), (Job::PurgeJobs, 24 * 60 * 60)]
+}
+
+/// The delay before the next attempt after the given number of failed ones.
+pub fn retry_delay_seconds(attempts: i32) -> i64 {
+    // 30 seconds doubled for every attempt, at most about 4 hours.
+    30 * 2i64.pow(attempts.max(1).min(10) as u32 - 1)
+}
+
+/// Queues the job to run as soon as a worker is free.
+pub async fn enqueue(job: &Job, pool: &PgPool) -> Result<Uuid, sqlx::Error> {
+    enqueue_at(job, OffsetDateTime::now_utc(), pool).await
+}
+
+/// Queues the job to run at the given time at the earliest.
+pub async fn enqueue_at(
+    job: &Job,
+    run_after: OffsetDateTime,
+    pool: &PgPool,
+) -> Result<Uuid, sqlx::Error> {
+    DbJob::new(
+        NewJob {
+            job_type: job.job_type(),
+            payload: serde_json::to_value(job).map_err(|e| sqlx::Error::Decode(e.into()))?,
+            max_attempts: job.max_attempts(),
+            run_after,
+        },
+        pool,
+    )
+    .await
+}
+
+pub struct Worker {
+    pool: PgPool,
+    logger: Logger,
+    account_service: Box<dyn AccountService>,
+    trash_service: Box<dyn TrashService>,
+    image_service: Box<dyn ImageService>,
+}
+
+impl Worker {
+    pub fn new(config: &Config, logger: Logger, pool: PgPool) -> Self {
+        Self {
+            account_service: Box::new(DefaultAccountService::new(
+                config,
+                logger.clone(),
+                pool.clone(),
+            )),
+            trash_service: Box::new(DefaultTrashService::new(
+                config,
+                logger.clone(),
+                pool.clone(),
+            )),
+            image_service: Box::new(DefaultImageService::new(
+                config,
+                logger.clone(),
+                pool.clone(),
+            )),
+            logger,
+            pool,
+        }
+    }
+
+    /// Processes jobs until the server stops.
+    pub async fn run(self) {
+        loop {
+            match DbJob::claim(JOB_LOCK_SECONDS, &self.pool).await {
+                Ok(Some(job)) => self.process(job).await,
+                Ok(None) => {
+                    actix_rt::time::delay_for(StdDuration::from_secs(POLL_INTERVAL_SECONDS)).await
+                }
+                Err(e) => {
+                    error!(&self.logger, "unexpected database error";
+                        "error" => e.to_string()
+                    );
+                    actix_rt::time::delay_for(StdDuration::from_secs(POLL_INTERVAL_SECONDS)).await
+                }
+            }
+        }
+    }
+
+    async fn process(&self, db_job: DbJob) {
+        let res = match serde_json::from_value::<Job>(db_job.payload.clone()) {
+            // The worker died during the last attempt.
+            Ok(_) if db_job.attempts > db_job.max_attempts => {
+                Err("the job was interrupted too many times".to_string())
+            }
+            Ok(job) => {
+                let execute = Box::pin(self.execute(&job, db_job.attempts >= db_job.max_attempts));
+
+                match future::select(execute, Box::pin(self.keep_locked(&db_job))).await {
+                    Either::Left((res, _)) => res.map_err(|e| e.to_string()),
+                    Either::Right(_) => {
+                        // Running it twice at once could do more harm than stopping here.
+                        warn!(&self.logger, "job lock lost, stopping the job";
+                            "job_id" => db_job.id.to_string(),
+                            "job_type" => &db_job.job_type
+                        );
+                        return;
+                    }
+                }
+            }
+            Err(e) => {
+                // Retrying would not help.
+                self.kill(&db_job, &format!("invalid payload: {}", e)).await;
+                return;
+            }
+        };
+
+        let res = match res {
+            Ok(_) => db_job.complete(&self.pool).await,
+            Err(e) if db_job.attempts >= db_job.max_attempts => {
+                self.kill(&db_job, &e).await;
+                return;
+            }
+            Err(e) => {
+                let delay = retry_delay_seconds(db_job.attempts);
+
+                warn!(&self.logger, "job failed, retrying later";
+                    "job_id" => db_job.id.to_string(),
+                    "job_type" => &db_job.job_type,
+                    "attempts" => db_job.attempts,
+                    "delay_seconds" => delay,
+                    "error" => &e
+                );
+
+                db_job.retry(delay, &e, &self.pool).await
+            }
+        };
+
+        match res {
+            Ok(true) => {}
+            Ok(false) => self.lock_lost(&db_job),
+            Err(e) => {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+            }
+        }
+    }
+
+    /// Extends the lock while the job runs, returns once another worker has claimed the job.
+    async fn keep_locked(&self, db_job: &DbJob) {
+        let mut interval = actix_rt::time::interval(StdDuration::from_secs(JOB_HEARTBEAT_SECONDS));
+
+        // The first tick is immediate, the job was just claimed.
+        interval.tick().await;
+
+        loop {
+            interval.tick().await;
+
+            match db_job.extend_lock(JOB_LOCK_SECONDS, &self.pool).await {
+                Ok(true) => {}
+                Ok(false) => return,
+                Err(e) => {
+                    // Tried again with the next tick, the lock is still good for a while.
+                    error!(&self.logger, "unexpected database error";
+                        "error" => e.to_string()
+                    );
+                }
+            }
+        }
+    }
+
+    fn lock_lost(&self, db_job: &DbJob) {
+        warn!(&self.logger, "job lock lost, the result is dropped";
+            "job_id" => db_job.id.to_string(),
+            "job_type" => &db_job.job_type
+        );
+    }
+
+    async fn kill(&self, db_job: &DbJob, error: &str) {
+        error!(&self.logger, "job failed for good";
+            "job_id" => db_job.id.to_string(),
+            "job_type" => &db_job.job_type,
+            "attempts" => db_job.attempts,
+            "error" => error
+        );
+
+        match db_job.kill(error, &self.pool).await {
+            Ok(true) => {}
+            Ok(false) => self.lock_lost(db_job),
+            Err(e) => {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+            }
+        }
+    }
+
+    async fn execute(&self, job: &Job, final_attempt: bool) -> anyhow::Result<()> {
+        match job {
+            Job::PurgeTrash => {
+                let purged = self.trash_service.purge_trash().await?;
+
+                if purged.images > 0 || purged.categories > 0 {
+                    info!(&self.logger, "purged the trash";
+                        "images" => purged.images,
+                        "categories" => purged.categories
+                    );
+                }
+            }
+            Job::BuildAccountExport { export_id } => {
+                self.account_service
+                    .build_export(*export_id, final_attempt)
+                    .await?;
+            }
+            Job::ProcessUpload { image_id } => {
+                self.image_service.process_upload(*image_id).await?;
+            }
+            Job::PurgeJobs => {
+                let date = OffsetDateTime::now_utc() - Duration::days(COMPLETED_JOB_RETENTION_DAYS);
+                DbJob::delete_completed_before(date, &self.pool).await?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Queues the recurring jobs when they are due.
+async fn run_schedules(pool: PgPool, logger: Logger) {
+    let schedules = schedules();
+
+    for (job, _) in &schedules {
+        if let Err(e) = JobSchedule::register(job.job_type(), &pool).await {
+            error!(logger, "unexpected database error";
+                "error" => e.to_string()
+            );
+        }
+    }
+
+    let mut interval = actix_rt::time::interval(StdDuration::from_secs(SCHEDULE_INTERVAL_SECONDS));
+
+    loop {
+        interval.tick().await;
+
+        for (job, interval_seconds) in &schedules {
+            let payload = match serde_json::to_value(job) {
+                Ok(p) => p,
+                Err(e) => {
+                    error!(logger, "invalid job payload";
+                        "job_type" => job.job_type(),
+                        "error" => e.to_string()
+                    );
+                    continue;
+                }
+            };
+
+            let res = JobSchedule::enqueue_due(
+                job.job_type(),
+                payload,
+                job.max_attempts(),
+                *interval_seconds,
+                &pool,
+            )
+            .await;
+
+            if let Err(e) = res {
+                error!(logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+            }
+        }
+    }
+}
+
+/// Starts the workers and the scheduler in the background,
+/// nothing is started if the count is 0.
+pub fn start_workers(config: &Config, logger: Logger, pool: PgPool, count: usize) {
+    if count == 0 {
+        return;
+    }
+
+    actix_rt::spawn(run_schedules(pool.clone(), logger.clone()));
+
+    for _ in 0..count {
+        actix_rt::spawn(Worker::new(config, logger.clone(), pool.clone()).run());
+    }
+}
+
+/// Runs the workers without the HTTP server until the process is stopped.
+pub async fn run(config: Config, logger: Logger, pool: PgPool) -> anyhow::Result<()> {
+    let count = config.worker_count.max(1);
+
+    info!(logger, "worker start";
+        "workers" => count
+    );
+
+    start_workers(&config, logger, pool, count);
+
+    actix_rt::signal::ctrl_c().await?;
+
+    Ok(())
+}
+
+#[test]
+fn test_retry_delay() {
+    assert_eq!(retry_delay_seconds(1), 30);
+    assert_eq!(retry_delay_seconds(2), 60);
+    assert_eq!(retry_delay_seconds(5), 480);
+    assert_eq!(retry_delay_seconds(50), retry_delay_seconds(10));
+}
+
+#[test]
+fn test_job_payload() {
+    let job = Job::BuildAccountExport {
+        export_id: Uuid::nil(),
+    };
+
+    let payload = serde_json::to_value(&job).unwrap();
+
+    assert_eq!(payload["type"], job.job_type());
+    assert_eq!(serde_json::from_value::<Job>(payload).unwrap(), job);
+    assert_eq!(
+        serde_json::to_value(&Job::PurgeTrash).unwrap()["type"],
+        Job::PurgeTrash.job_type()
+    );
+
+    let job = Job::ProcessUpload {
+        image_id: Uuid::nil(),
+    };
+
+    let payload = serde_json::to_value(&job).unwrap();
+
+    assert_eq!(payload["type"], job.job_type());
+    assert_eq!(payload["imageId"], Uuid::nil().to_string());
+}
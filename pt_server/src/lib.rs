@@ -3,6 +3,7 @@ pub mod logger;
 pub mod model;
 pub mod server;
 pub mod db;
+pub mod jobs;
 pub mod services;
 pub mod util;
 
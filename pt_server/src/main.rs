@@ -1,6 +1,6 @@
 use pt_server::{
     config::Config,
-    db,
+    db, jobs,
     logger::{create_logger, LoggerExt},
     server,
 };
@@ -9,6 +9,16 @@ use pt_server::{
 async fn main() -> anyhow::Result<()> {
     let config = Config::from_env()?;
     let db_pool = db::connect(&config).await?;
-    let log = create_logger(&config).with_scope("http-server");
-    server::run(config, log, db_pool).await
+
+    match std::env::args().nth(1).as_deref() {
+        None | Some("server") => {
+            let log = create_logger(&config).with_scope("http-server");
+            server::run(config, log, db_pool).await
+        }
+        Some("worker") => {
+            let log = create_logger(&config).with_scope("worker");
+            jobs::run(config, log, db_pool).await
+        }
+        Some(command) => Err(anyhow::anyhow!("unknown command: {}", command)),
+    }
 }
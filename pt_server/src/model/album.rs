@@ -0,0 +1,155 @@
+use super::{image::Image, Visibility};
+use aide::openapi::v3::macros::api;
+use thiserror::Error;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+#[api]
+#[serde(rename_all = "camelCase")]
+pub struct Album {
+    pub id: Uuid,
+    pub name: String,
+    pub owner: Uuid,
+    pub visibility: Visibility,
+    pub image_count: u32,
+    #[serde(serialize_with = "crate::util::serialize_rfc3339")]
+    #[serde(deserialize_with = "crate::util::deserialize_rfc3339")]
+    pub created: OffsetDateTime,
+}
+
+#[api]
+#[serde(rename_all = "camelCase")]
+pub struct CreateAlbumRequest {
+    pub name: String,
+    #[serde(default)]
+    pub visibility: Visibility,
+}
+
+#[api]
+#[serde(rename_all = "camelCase")]
+pub struct CreateAlbumResponse {
+    pub id: Uuid,
+}
+
+#[derive(Debug, Error)]
+pub enum CreateAlbumError {
+    #[error("the album name must be between 1 and {0} characters")]
+    InvalidName(usize),
+    #[error("there was an unexpected error")]
+    Unexpected,
+}
+
+#[api]
+#[serde(rename_all = "camelCase")]
+pub struct GetAlbumsQuery {
+    /// The owner of the albums, only public albums are returned for other users.
+    ///
+    /// Defaults to the current user.
+    pub user_id: Option<Uuid>,
+}
+
+#[api]
+#[serde(rename_all = "camelCase")]
+pub struct GetAlbumsResponse {
+    pub albums: Vec<Album>,
+}
+
+#[derive(Debug, Error)]
+pub enum GetAlbumsError {
+    #[error("there was an unexpected error")]
+    Unexpected,
+}
+
+#[api]
+#[serde(rename_all = "camelCase")]
+pub struct GetAlbumResponse {
+    pub album: Album,
+    pub images: Vec<Image>,
+}
+
+#[derive(Debug, Error)]
+pub enum GetAlbumError {
+    #[error("the album was not found")]
+    NotFound,
+    #[error("there was an unexpected error")]
+    Unexpected,
+}
+
+/// Omitted fields are left unchanged.
+#[api]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateAlbumRequest {
+    pub name: Option<String>,
+    pub visibility: Option<Visibility>,
+}
+
+#[derive(Debug, Error)]
+pub enum UpdateAlbumError {
+    #[error("the album was not found")]
+    NotFound,
+    #[error("only the owner can change the album")]
+    NotAllowed,
+    #[error("the album name must be between 1 and {0} characters")]
+    InvalidName(usize),
+    #[error("there was an unexpected error")]
+    Unexpected,
+}
+
+#[derive(Debug, Error)]
+pub enum DeleteAlbumError {
+    #[error("the album was not found")]
+    NotFound,
+    #[error("only the owner can delete the album")]
+    NotAllowed,
+    #[error("there was an unexpected error")]
+    Unexpected,
+}
+
+#[api]
+#[serde(rename_all = "camelCase")]
+pub struct AddAlbumImageRequest {
+    pub image_id: Uuid,
+}
+
+#[derive(Debug, Error)]
+pub enum AddAlbumImageError {
+    #[error("the album was not found")]
+    AlbumNotFound,
+    #[error("the image was not found")]
+    ImageNotFound,
+    #[error("only the owner can change the album")]
+    NotAllowed,
+    #[error("there was an unexpected error")]
+    Unexpected,
+}
+
+#[derive(Debug, Error)]
+pub enum RemoveAlbumImageError {
+    #[error("the album was not found")]
+    AlbumNotFound,
+    #[error("the image is not in the album")]
+    ImageNotFound,
+    #[error("only the owner can change the album")]
+    NotAllowed,
+    #[error("there was an unexpected error")]
+    Unexpected,
+}
+
+#[api]
+#[serde(rename_all = "camelCase")]
+pub struct ReorderAlbumImagesRequest {
+    /// Every image of the album in the new order.
+    pub image_ids: Vec<Uuid>,
+}
+
+#[derive(Debug, Error)]
+pub enum ReorderAlbumImagesError {
+    #[error("the album was not found")]
+    AlbumNotFound,
+    #[error("only the owner can change the album")]
+    NotAllowed,
+    #[error("the new order must contain every image of the album exactly once")]
+    ImagesMismatch,
+    #[error("there was an unexpected error")]
+    Unexpected,
+}
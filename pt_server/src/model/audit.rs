@@ -0,0 +1,130 @@
+use aide::openapi::v3::macros::api;
+use std::str::FromStr;
+use thiserror::Error;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+#[api]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum AuditAction {
+    CategoryCreate,
+    CategoryRename,
+    CategoryDelete,
+    CategoryRestore,
+    /// The role of a user was changed.
+    RoleChange,
+    Login,
+    /// A login with an incorrect password or two-factor code.
+    LoginFailed,
+    /// A user deleted their own account.
+    AccountDelete,
+}
+
+impl AuditAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditAction::CategoryCreate => "categoryCreate",
+            AuditAction::CategoryRename => "categoryRename",
+            AuditAction::CategoryDelete => "categoryDelete",
+            AuditAction::CategoryRestore => "categoryRestore",
+            AuditAction::RoleChange => "roleChange",
+            AuditAction::Login => "login",
+            AuditAction::LoginFailed => "loginFailed",
+            AuditAction::AccountDelete => "accountDelete",
+        }
+    }
+}
+
+impl FromStr for AuditAction {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "categoryCreate" => Ok(AuditAction::CategoryCreate),
+            "categoryRename" => Ok(AuditAction::CategoryRename),
+            "categoryDelete" => Ok(AuditAction::CategoryDelete),
+            "categoryRestore" => Ok(AuditAction::CategoryRestore),
+            "roleChange" => Ok(AuditAction::RoleChange),
+            "login" => Ok(AuditAction::Login),
+            "loginFailed" => Ok(AuditAction::LoginFailed),
+            "accountDelete" => Ok(AuditAction::AccountDelete),
+            _ => Err(()),
+        }
+    }
+}
+
+#[api]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum AuditExportFormat {
+    Json,
+    Csv,
+}
+
+impl Default for AuditExportFormat {
+    fn default() -> Self {
+        AuditExportFormat::Json
+    }
+}
+
+#[api]
+#[serde(rename_all = "camelCase")]
+pub struct AuditEvent {
+    pub id: Uuid,
+    #[serde(serialize_with = "crate::util::serialize_rfc3339")]
+    #[serde(deserialize_with = "crate::util::deserialize_rfc3339")]
+    pub created: OffsetDateTime,
+    /// Missing for failed logins of unknown users.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub actor_id: Option<Uuid>,
+    pub action: AuditAction,
+    pub target_type: String,
+    /// An ID, or the email address for logins.
+    pub target_id: String,
+    /// The state before the change as JSON.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub before: Option<String>,
+    /// The state after the change as JSON.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub after: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+}
+
+#[api]
+#[serde(rename_all = "camelCase")]
+pub struct GetAuditEventsQuery {
+    pub actor_id: Option<Uuid>,
+    pub action: Option<AuditAction>,
+    /// Only events at or after this time.
+    #[serde(default)]
+    #[serde(serialize_with = "crate::util::serialize_rfc3339_opt")]
+    #[serde(deserialize_with = "crate::util::deserialize_rfc3339_opt")]
+    pub from: Option<OffsetDateTime>,
+    /// Only events before this time.
+    #[serde(default)]
+    #[serde(serialize_with = "crate::util::serialize_rfc3339_opt")]
+    #[serde(deserialize_with = "crate::util::deserialize_rfc3339_opt")]
+    pub to: Option<OffsetDateTime>,
+    pub offset: Option<u64>,
+    pub limit: Option<u64>,
+    /// With `csv` the events are returned as a file download.
+    #[serde(default)]
+    pub format: AuditExportFormat,
+}
+
+#[api]
+#[serde(rename_all = "camelCase")]
+pub struct GetAuditEventsResponse {
+    /// The latest first.
+    pub events: Vec<AuditEvent>,
+}
+
+#[derive(Debug, Error)]
+pub enum GetAuditEventsError {
+    #[error("the time range is invalid")]
+    InvalidRange,
+    #[error("there was an unexpected error")]
+    Unexpected,
+}
@@ -1,5 +1,8 @@
 use aide::openapi::v3::macros::api;
+use std::str::FromStr;
 use thiserror::Error;
+use time::OffsetDateTime;
+use uuid::Uuid;
 
 #[api]
 #[serde(rename_all = "camelCase")]
@@ -12,6 +15,17 @@ pub struct LoginRequest {
 #[serde(rename_all = "camelCase")]
 pub struct LoginResponse {
     pub token: String,
+    /// If true, the token can only be used with `/auth/login/mfa`
+    /// along with a two-factor code.
+    pub mfa_required: bool,
+}
+
+#[api]
+#[serde(rename_all = "camelCase")]
+pub struct MfaLoginRequest {
+    pub token: String,
+    /// Either a TOTP code or an unused recovery code.
+    pub code: String,
 }
 
 #[api]
@@ -47,6 +61,12 @@ pub enum LoginError {
     UserNotFound,
     #[error("incorrect password")]
     IncorrectPassword,
+    #[error("the two-factor token is invalid or expired")]
+    InvalidMfaToken,
+    #[error("incorrect two-factor code")]
+    IncorrectMfaCode,
+    #[error("too many incorrect two-factor codes, try again later")]
+    MfaLocked,
     #[error("unexpected error")]
     Unexpected,
 }
@@ -59,3 +79,182 @@ pub enum PasswordError {
     #[error("password must not be longer than {0} characters")]
     TooLong(usize),
 }
+
+#[api]
+#[serde(rename_all = "camelCase")]
+pub struct EnrollTotpResponse {
+    pub secret: String,
+    pub otpauth_uri: String,
+}
+
+#[api]
+#[serde(rename_all = "camelCase")]
+pub struct ConfirmTotpRequest {
+    pub code: String,
+}
+
+#[api]
+#[serde(rename_all = "camelCase")]
+pub struct ConfirmTotpResponse {
+    /// One-time codes that can be used instead of TOTP codes,
+    /// these are only shown once.
+    pub recovery_codes: Vec<String>,
+}
+
+#[api]
+#[serde(rename_all = "camelCase")]
+pub struct DisableTotpRequest {
+    /// Either a TOTP code or an unused recovery code.
+    pub code: String,
+}
+
+#[api]
+#[derive(Debug, Error)]
+pub enum TotpError {
+    #[error("user was not found")]
+    UserNotFound,
+    #[error("two-factor authentication is already enabled")]
+    AlreadyEnabled,
+    #[error("two-factor authentication is not enabled")]
+    NotEnabled,
+    #[error("two-factor enrollment was not started")]
+    NotEnrolled,
+    #[error("incorrect two-factor code")]
+    IncorrectCode,
+    #[error("too many incorrect two-factor codes, try again later")]
+    Locked,
+    #[error("two-factor authentication cannot be managed with API keys")]
+    NotAllowed,
+    #[error("unexpected error")]
+    Unexpected,
+}
+
+/// Permissions that can be granted to API keys.
+#[api]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum ApiKeyScope {
+    Read,
+    Upload,
+    Rate,
+    Comment,
+    /// Also allows reading.
+    Admin,
+}
+
+impl ApiKeyScope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ApiKeyScope::Read => "read",
+            ApiKeyScope::Upload => "upload",
+            ApiKeyScope::Rate => "rate",
+            ApiKeyScope::Comment => "comment",
+            ApiKeyScope::Admin => "admin",
+        }
+    }
+}
+
+impl FromStr for ApiKeyScope {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "read" => Ok(ApiKeyScope::Read),
+            "upload" => Ok(ApiKeyScope::Upload),
+            "rate" => Ok(ApiKeyScope::Rate),
+            "comment" => Ok(ApiKeyScope::Comment),
+            "admin" => Ok(ApiKeyScope::Admin),
+            _ => Err(()),
+        }
+    }
+}
+
+#[api]
+#[serde(rename_all = "camelCase")]
+pub struct ApiKey {
+    pub id: Uuid,
+    pub name: String,
+    /// The first characters of the key.
+    pub prefix: String,
+    pub scopes: Vec<ApiKeyScope>,
+    #[serde(serialize_with = "crate::util::serialize_rfc3339")]
+    #[serde(deserialize_with = "crate::util::deserialize_rfc3339")]
+    pub created: OffsetDateTime,
+    #[serde(serialize_with = "crate::util::serialize_rfc3339_opt")]
+    #[serde(deserialize_with = "crate::util::deserialize_rfc3339_opt")]
+    pub last_used: Option<OffsetDateTime>,
+}
+
+#[api]
+#[serde(rename_all = "camelCase")]
+pub struct CreateApiKeyRequest {
+    pub name: String,
+    pub scopes: Vec<ApiKeyScope>,
+}
+
+#[api]
+#[serde(rename_all = "camelCase")]
+pub struct CreateApiKeyResponse {
+    pub id: Uuid,
+    /// The key itself, this is only shown once.
+    pub key: String,
+}
+
+#[api]
+#[serde(rename_all = "camelCase")]
+pub struct GetApiKeysResponse {
+    pub api_keys: Vec<ApiKey>,
+}
+
+#[derive(Debug, Error)]
+pub enum TokenError {
+    #[error(transparent)]
+    Invalid(#[from] jwt::errors::Error),
+    #[error("the user of the token does not exist anymore")]
+    UserNotFound,
+    #[error("unexpected error")]
+    Unexpected,
+}
+
+#[api]
+#[derive(Debug, Error)]
+pub enum ApiKeyError {
+    #[error("the API key was not found")]
+    NotFound,
+    #[error("the API key name must not be empty")]
+    InvalidName,
+    #[error("at least one scope is required")]
+    NoScopes,
+    #[error("the API key is invalid or revoked")]
+    Invalid,
+    #[error("API keys cannot be managed with API keys")]
+    NotAllowed,
+    #[error("unexpected error")]
+    Unexpected,
+}
+
+#[api]
+#[serde(rename_all = "camelCase")]
+pub struct OidcCallbackQuery {
+    pub code: String,
+    pub state: String,
+}
+
+#[api]
+#[derive(Debug, Error)]
+pub enum OidcError {
+    #[error("single sign-on is not configured")]
+    NotConfigured,
+    #[error("the login state is invalid or expired")]
+    InvalidState,
+    #[error("the identity provider returned an error")]
+    Provider,
+    #[error("the identity provider did not provide an e-mail address")]
+    MissingEmail,
+    #[error("the identity provider did not verify the e-mail address")]
+    UnverifiedEmail,
+    #[error("too many logins are in progress, try again later")]
+    TooManyLogins,
+    #[error("unexpected error")]
+    Unexpected,
+}
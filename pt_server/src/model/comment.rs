@@ -0,0 +1,94 @@
+use aide::openapi::v3::macros::api;
+use thiserror::Error;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+#[api]
+#[serde(rename_all = "camelCase")]
+pub struct Comment {
+    pub id: Uuid,
+    pub image_id: Uuid,
+    /// The comment this one replies to.
+    pub parent_id: Option<Uuid>,
+    /// Missing if the author deleted their account.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub author: Option<Uuid>,
+    pub body: String,
+    #[serde(serialize_with = "crate::util::serialize_rfc3339")]
+    #[serde(deserialize_with = "crate::util::deserialize_rfc3339")]
+    pub created: OffsetDateTime,
+    #[serde(serialize_with = "crate::util::serialize_rfc3339_opt")]
+    #[serde(deserialize_with = "crate::util::deserialize_rfc3339_opt")]
+    pub edited: Option<OffsetDateTime>,
+}
+
+#[api]
+#[serde(rename_all = "camelCase")]
+pub struct CreateCommentRequest {
+    pub body: String,
+    /// Replies to the given comment of the same image.
+    pub parent_id: Option<Uuid>,
+}
+
+#[api]
+#[serde(rename_all = "camelCase")]
+pub struct CreateCommentResponse {
+    pub id: Uuid,
+}
+
+#[derive(Debug, Error)]
+pub enum CreateCommentError {
+    #[error("the image was not found")]
+    ImageNotFound,
+    #[error("the parent comment was not found")]
+    ParentNotFound,
+    #[error("the comment must be between 1 and {0} characters")]
+    InvalidBody(usize),
+    #[error("there was an unexpected error")]
+    Unexpected,
+}
+
+#[api]
+#[serde(rename_all = "camelCase")]
+pub struct GetCommentsResponse {
+    /// Oldest first, replies have to be arranged by `parentId`.
+    pub comments: Vec<Comment>,
+}
+
+#[derive(Debug, Error)]
+pub enum GetCommentsError {
+    #[error("the image was not found")]
+    ImageNotFound,
+    #[error("there was an unexpected error")]
+    Unexpected,
+}
+
+#[api]
+#[serde(rename_all = "camelCase")]
+pub struct EditCommentRequest {
+    pub body: String,
+}
+
+#[derive(Debug, Error)]
+pub enum EditCommentError {
+    #[error("the comment was not found")]
+    NotFound,
+    #[error("only the author can edit the comment")]
+    NotAllowed,
+    #[error("comments can only be edited for {} minutes", .0.whole_minutes())]
+    EditWindowExpired(time::Duration),
+    #[error("the comment must be between 1 and {0} characters")]
+    InvalidBody(usize),
+    #[error("there was an unexpected error")]
+    Unexpected,
+}
+
+#[derive(Debug, Error)]
+pub enum DeleteCommentError {
+    #[error("the comment was not found")]
+    NotFound,
+    #[error("only the author, the owner of the image or a moderator can delete the comment")]
+    NotAllowed,
+    #[error("there was an unexpected error")]
+    Unexpected,
+}
@@ -1,3 +1,4 @@
+use super::Visibility;
 use aide::openapi::v3::macros::api;
 use thiserror::Error;
 use time::{Duration, OffsetDateTime};
@@ -9,6 +10,8 @@ pub struct CreateImageRequest {
     pub title: String,
     pub description: Option<String>,
     pub categories: Vec<Uuid>,
+    /// Public if not given.
+    pub visibility: Option<Visibility>,
 }
 
 #[api]
@@ -21,26 +24,97 @@ pub struct CreateImageResponse {
 pub enum CreateImageError {
     #[error(r#"the the image category "{0}" was not found"#)]
     CategoryNotFound(Uuid),
+    #[error("the image quota of the user was reached")]
+    QuotaExceeded,
     #[error("there was an unexpected error")]
     Unexpected,
 }
 
+impl CreateImageError {
+    /// Identifies the error in batch results.
+    pub fn code(&self) -> &'static str {
+        match self {
+            CreateImageError::CategoryNotFound(_) => "categoryNotFound",
+            CreateImageError::QuotaExceeded => "quotaExceeded",
+            CreateImageError::Unexpected => "unexpected",
+        }
+    }
+}
+
 #[api]
+#[serde(rename_all = "camelCase")]
 pub struct SearchImagesQuery {
     pub search: Option<String>,
+    /// Comma separated tags, images must have all of them.
+    pub tags: Option<String>,
     pub limit: Option<u64>,
     pub offset: Option<u64>,
+    /// Only images taken at or after this time.
+    #[serde(default)]
+    #[serde(serialize_with = "crate::util::serialize_rfc3339_opt")]
+    #[serde(deserialize_with = "crate::util::deserialize_rfc3339_opt")]
+    pub captured_after: Option<OffsetDateTime>,
+    /// Only images taken before this time.
+    #[serde(default)]
+    #[serde(serialize_with = "crate::util::serialize_rfc3339_opt")]
+    #[serde(deserialize_with = "crate::util::deserialize_rfc3339_opt")]
+    pub captured_before: Option<OffsetDateTime>,
+    /// Comma separated `minLon,minLat,maxLon,maxLat` in degrees.
+    pub bbox: Option<String>,
+    /// Center of a radius search, `lon` and `radius` are required too.
+    pub lat: Option<f64>,
+    pub lon: Option<f64>,
+    /// In metres.
+    pub radius: Option<f64>,
 }
 
 #[api]
+#[serde(rename_all = "camelCase")]
 pub struct Image {
     pub id: Uuid,
     pub title: String,
     pub description: Option<String>,
     pub categories: Vec<Uuid>,
+    pub tags: Vec<String>,
     #[serde(serialize_with = "crate::util::serialize_rfc3339")]
     #[serde(deserialize_with = "crate::util::deserialize_rfc3339")]
-    pub date: OffsetDateTime
+    pub date: OffsetDateTime,
+    pub comment_count: u32,
+    pub visibility: Visibility,
+    /// Read from the Exif data of the uploaded file.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<ImageMetadata>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub location: Option<GeoLocation>,
+    /// An earlier upload of the same user that looks the same.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duplicate_of: Option<Uuid>,
+}
+
+#[api]
+#[serde(rename_all = "camelCase")]
+pub struct GeoLocation {
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+#[api]
+#[serde(rename_all = "camelCase")]
+pub struct ImageMetadata {
+    pub camera_make: Option<String>,
+    pub camera_model: Option<String>,
+    pub lens_model: Option<String>,
+    /// In seconds, e.g. `1/250`.
+    pub exposure_time: Option<String>,
+    pub f_number: Option<f64>,
+    pub iso: Option<u32>,
+    /// In millimetres.
+    pub focal_length: Option<f64>,
+    #[serde(serialize_with = "crate::util::serialize_rfc3339_opt")]
+    #[serde(deserialize_with = "crate::util::deserialize_rfc3339_opt")]
+    pub captured: Option<OffsetDateTime>,
+    /// The Exif orientation, 1 is upright.
+    pub orientation: Option<u16>,
 }
 
 
@@ -56,6 +130,8 @@ pub struct GetImageResponse {
 
 #[derive(Debug, Error)]
 pub enum SearchImagesError {
+    #[error("the given area is invalid")]
+    InvalidArea,
     #[error("there was an unexpected error")]
     Unexpected,
 }
@@ -68,20 +144,307 @@ pub enum GetImageInfoError {
     Unexpected,
 }
 
+#[derive(Debug, Error)]
+pub enum DownloadImageError {
+    #[error("the image was not found")]
+    NotFound,
+    #[error("there was an unexpected error")]
+    Unexpected,
+}
+
+#[api]
+#[serde(rename_all = "camelCase")]
+pub struct SetImageVisibilityRequest {
+    pub visibility: Visibility,
+}
+
+#[derive(Debug, Error)]
+pub enum SetImageVisibilityError {
+    #[error("the image was not found")]
+    ImageNotFound,
+    #[error("only the owner can change the visibility of the image")]
+    NotAllowed,
+    #[error("there was an unexpected error")]
+    Unexpected,
+}
+
+#[api]
+#[serde(rename_all = "camelCase")]
+pub struct SetImageLocationRequest {
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+#[derive(Debug, Error)]
+pub enum SetImageLocationError {
+    #[error("the image was not found")]
+    ImageNotFound,
+    #[error("only the owner can change the location of the image")]
+    NotAllowed,
+    #[error("the location is out of range")]
+    InvalidLocation,
+    #[error("there was an unexpected error")]
+    Unexpected,
+}
+
+#[api]
+#[serde(rename_all = "camelCase")]
+pub struct GetImageClustersQuery {
+    /// Cells are `360 / 2^zoom` degrees wide and high.
+    pub zoom: u8,
+    /// Comma separated `minLon,minLat,maxLon,maxLat` in degrees.
+    pub bbox: Option<String>,
+}
+
+#[api]
+#[serde(rename_all = "camelCase")]
+pub struct ImageCluster {
+    pub count: u64,
+    /// The average position of the images in the cell.
+    pub latitude: f64,
+    pub longitude: f64,
+    /// Bounds of the cell.
+    pub min_latitude: f64,
+    pub min_longitude: f64,
+    pub max_latitude: f64,
+    pub max_longitude: f64,
+}
+
+#[api]
+pub struct GetImageClustersResponse {
+    pub clusters: Vec<ImageCluster>,
+}
+
+#[derive(Debug, Error)]
+pub enum GetImageClustersError {
+    #[error("the zoom level must be at most {0}")]
+    InvalidZoom(u8),
+    #[error("the given area is invalid")]
+    InvalidArea,
+    #[error("there was an unexpected error")]
+    Unexpected,
+}
+
+#[api]
+#[serde(rename_all = "camelCase")]
+pub struct GetSimilarImagesQuery {
+    /// Defaults to the duplicate threshold of the server.
+    pub max_distance: Option<u32>,
+    pub limit: Option<u64>,
+}
+
+#[api]
+#[serde(rename_all = "camelCase")]
+pub struct SimilarImage {
+    pub image: Image,
+    /// Differing bits of the perceptual hashes, 0 means identical.
+    pub distance: u32,
+}
+
+#[api]
+pub struct GetSimilarImagesResponse {
+    pub images: Vec<SimilarImage>,
+}
+
+#[derive(Debug, Error)]
+pub enum GetSimilarImagesError {
+    #[error("the image was not found")]
+    NotFound,
+    #[error("there was an unexpected error")]
+    Unexpected,
+}
+
+#[derive(Debug, Error)]
+pub enum ProcessUploadError {
+    #[error("the file of the image was not found")]
+    FileNotFound,
+    #[error("there was an unexpected error")]
+    Unexpected,
+}
+
 #[derive(Debug, Error)]
 pub enum UploadImageError {
     #[error("the given identifier is invalid")]
     InvalidId,
+    #[error("only the owner can upload the file of the image")]
+    NotAllowed,
     #[error("the image was already uploaded")]
     AlreadyUploaded,
     #[error("the image upload exceeded the {}s timeout, please create a new image", .0.whole_seconds())]
     TimeOut(Duration),
     #[error("expected a file, but got none")]
     ExpectedFile,
+    #[error("the image is a duplicate of {0}")]
+    Duplicate(Uuid),
+    #[error("the file is larger than the {0} bytes limit")]
+    TooLarge(u64),
+    #[error("the storage quota of the user was exceeded")]
+    QuotaExceeded,
+    #[error("the file is damaged or its metadata cannot be removed")]
+    UnsupportedFile,
     #[error("there was an unexpected error during the upload process")]
     Unexpected,
 }
 
+impl UploadImageError {
+    /// Identifies the error in batch results.
+    pub fn code(&self) -> &'static str {
+        match self {
+            UploadImageError::InvalidId => "invalidId",
+            UploadImageError::NotAllowed => "notAllowed",
+            UploadImageError::AlreadyUploaded => "alreadyUploaded",
+            UploadImageError::TimeOut(_) => "timeOut",
+            UploadImageError::ExpectedFile => "expectedFile",
+            UploadImageError::Duplicate(_) => "duplicate",
+            UploadImageError::TooLarge(_) => "tooLarge",
+            UploadImageError::QuotaExceeded => "quotaExceeded",
+            UploadImageError::UnsupportedFile => "unsupportedFile",
+            UploadImageError::Unexpected => "unexpected",
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum CreateAndUploadImageError {
+    #[error("expected the metadata of the image, but got none")]
+    ExpectedMetadata,
+    #[error("the metadata of the image is invalid: {0}")]
+    InvalidMetadata(String),
+    #[error(r#"unexpected part "{0}", only "metadata" and "file" are accepted"#)]
+    UnexpectedPart(String),
+    #[error(transparent)]
+    Create(#[from] CreateImageError),
+    #[error(transparent)]
+    Upload(#[from] UploadImageError),
+}
+
+impl CreateAndUploadImageError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            CreateAndUploadImageError::ExpectedMetadata => "expectedMetadata",
+            CreateAndUploadImageError::InvalidMetadata(_) => "invalidMetadata",
+            CreateAndUploadImageError::UnexpectedPart(_) => "unexpectedPart",
+            CreateAndUploadImageError::Create(err) => err.code(),
+            CreateAndUploadImageError::Upload(err) => err.code(),
+        }
+    }
+}
+
+/// Metadata of the files in a batch upload.
+///
+/// Given as the "defaults" part it applies to all following files,
+/// given as a "metadata" part only to the next file,
+/// overriding the defaults field by field.
+#[api]
+#[derive(Default)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchImageMetadata {
+    /// The name of the file without the extension if not given.
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub categories: Option<Vec<Uuid>>,
+    #[serde(default)]
+    pub visibility: Option<Visibility>,
+}
+
+#[api]
+#[serde(rename_all = "camelCase")]
+pub struct BatchUploadItem {
+    /// The position of the file in the request, starting from 0.
+    pub index: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filename: Option<String>,
+    /// The ID of the created image if the upload succeeded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<Uuid>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<BatchUploadItemError>,
+}
+
+#[api]
+#[serde(rename_all = "camelCase")]
+pub struct BatchUploadItemError {
+    /// The same codes as for single uploads, e.g. `categoryNotFound` or `tooLarge`.
+    pub code: String,
+    pub message: String,
+}
+
+#[api]
+#[serde(rename_all = "camelCase")]
+pub struct BatchUploadResponse {
+    pub succeeded: u64,
+    pub failed: u64,
+    /// In the order of the files.
+    pub items: Vec<BatchUploadItem>,
+}
+
+#[derive(Debug, Error)]
+pub enum BatchUploadError {
+    #[error("the defaults are invalid: {0}")]
+    InvalidDefaults(String),
+    #[error(r#"unexpected part "{0}", only "defaults", "metadata" and "file" are accepted"#)]
+    UnexpectedPart(String),
+    #[error("there was an unexpected error during the upload process")]
+    Unexpected,
+}
+
+#[api]
+#[serde(rename_all = "camelCase")]
+pub struct CreateUploadSessionRequest {
+    /// Size of the whole file in bytes.
+    pub length: u64,
+    /// Hex encoded SHA-256 hash of the whole file.
+    pub checksum: String,
+    /// Only the extension is kept.
+    pub filename: Option<String>,
+}
+
+#[api]
+#[serde(rename_all = "camelCase")]
+pub struct UploadSession {
+    pub id: Uuid,
+    pub image_id: Uuid,
+    pub length: u64,
+    /// The bytes received so far, the next chunk must start here.
+    pub offset: u64,
+    #[serde(serialize_with = "crate::util::serialize_rfc3339")]
+    #[serde(deserialize_with = "crate::util::deserialize_rfc3339")]
+    pub expires: OffsetDateTime,
+}
+
+#[derive(Debug, Error)]
+pub enum UploadSessionError {
+    #[error("the upload session was not found or it has expired")]
+    NotFound,
+    #[error("the image was not found")]
+    ImageNotFound,
+    #[error("only the owner can upload the file of the image")]
+    NotAllowed,
+    #[error("the image was already uploaded")]
+    AlreadyUploaded,
+    #[error("the length must be positive")]
+    InvalidLength,
+    #[error("the checksum must be a hex encoded SHA-256 hash")]
+    InvalidChecksum,
+    #[error("the upload offset does not match the received bytes")]
+    OffsetMismatch,
+    #[error("the chunk exceeds the length of the upload")]
+    ExceedsLength,
+    #[error("the upload is not complete yet")]
+    Incomplete,
+    #[error("the checksum of the uploaded file does not match, the upload has to be restarted")]
+    ChecksumMismatch,
+    #[error("the upload is already being finished")]
+    Finishing,
+    #[error(transparent)]
+    Upload(#[from] UploadImageError),
+    #[error("there was an unexpected error")]
+    Unexpected,
+}
+
 #[api]
 pub struct RateImageRequest {
     pub rating: u32,
@@ -114,10 +477,100 @@ pub enum GetImageRatingsError {
 }
 
 #[api]
+pub struct SetImageTagsRequest {
+    /// Replaces every tag of the image.
+    pub tags: Vec<String>,
+}
+
+#[api]
+pub struct SetImageTagsResponse {
+    /// The normalised tags.
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug, Error)]
+pub enum SetImageTagsError {
+    #[error("the image was not found")]
+    ImageNotFound,
+    #[error("only the owner can tag the image")]
+    NotAllowed,
+    #[error("an image can have at most {0} tags")]
+    TooManyTags(usize),
+    #[error(r#"the tag "{0}" is invalid"#)]
+    InvalidTag(String),
+    #[error("there was an unexpected error")]
+    Unexpected,
+}
+
+#[api]
+#[serde(rename_all = "camelCase")]
+pub struct Tag {
+    pub id: Uuid,
+    pub name: String,
+    pub image_count: u32,
+}
+
+#[api]
+pub struct AutocompleteTagsQuery {
+    /// The beginning of the tag.
+    pub q: String,
+    pub limit: Option<u64>,
+}
+
+#[api]
+pub struct PopularTagsQuery {
+    pub limit: Option<u64>,
+}
+
+#[api]
+pub struct GetTagsResponse {
+    pub tags: Vec<Tag>,
+}
+
+#[derive(Debug, Error)]
+pub enum GetTagsError {
+    #[error("there was an unexpected error")]
+    Unexpected,
+}
+
+#[api]
+#[serde(rename_all = "camelCase")]
+pub struct PromoteTagResponse {
+    pub category_id: Uuid,
+}
+
+#[derive(Debug, Error)]
+pub enum PromoteTagError {
+    #[error("the tag was not found")]
+    TagNotFound,
+    #[error("there was an unexpected error")]
+    Unexpected,
+}
+
+#[api]
+#[serde(rename_all = "camelCase")]
 pub struct Category {
     pub id: Uuid,
     pub name: String,
+    pub slug: String,
+    pub description: Option<String>,
+    pub parent_id: Option<Uuid>,
+    pub cover_image_id: Option<Uuid>,
     pub image_count: u32,
+    /// Only filled when the categories are requested as a tree.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<Category>,
+}
+
+#[api]
+#[serde(rename_all = "camelCase")]
+pub struct GetCategoriesQuery {
+    /// Return only the root categories with their subcategories nested in them.
+    #[serde(default)]
+    pub tree: bool,
+    /// Count the images of the subcategories as well.
+    #[serde(default)]
+    pub include_descendants: bool,
 }
 
 #[api]
@@ -132,8 +585,13 @@ pub enum GetCategoriesError {
 }
 
 #[api]
+#[serde(rename_all = "camelCase")]
 pub struct CreateCategoryRequest {
     pub name: String,
+    /// Generated from the name if not given.
+    pub slug: Option<String>,
+    pub parent_id: Option<Uuid>,
+    pub description: Option<String>,
 }
 
 #[api]
@@ -143,12 +601,16 @@ pub struct CreateCategoryResponse {
 
 #[derive(Debug, Error)]
 pub enum CreateCategoryError {
-    #[error("only admins are allowed to create categories")]
-    NotAllowed,
     #[error("the category name must match the following pattern: {0}")]
     InvalidName(String),
     #[error("the category already exists")]
     AlreadyExists,
+    #[error("the slug must match the following pattern: {0}")]
+    InvalidSlug(String),
+    #[error("the slug is already used by another category")]
+    SlugAlreadyExists,
+    #[error("the parent category was not found")]
+    ParentNotFound,
     #[error("there was an unexpected error")]
     Unexpected,
 }
@@ -160,8 +622,6 @@ pub struct RenameCategoryRequest {
 
 #[derive(Debug, Error)]
 pub enum RenameCategoryError {
-    #[error("only admins are allowed to create categories")]
-    NotAllowed,
     #[error("there category was not found")]
     CategoryNotFound,
     #[error("the category name must match the following pattern: {0}")]
@@ -172,12 +632,126 @@ pub enum RenameCategoryError {
     Unexpected,
 }
 
+#[api]
+#[serde(rename_all = "camelCase")]
+pub struct MoveCategoryRequest {
+    /// The category becomes a root category if not given.
+    pub parent_id: Option<Uuid>,
+}
+
+#[derive(Debug, Error)]
+pub enum MoveCategoryError {
+    #[error("there category was not found")]
+    CategoryNotFound,
+    #[error("the parent category was not found")]
+    ParentNotFound,
+    #[error("a category cannot be moved under itself or its subcategories")]
+    Cycle,
+    #[error("there was an unexpected error")]
+    Unexpected,
+}
+
+#[api]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateCategoryDetailsRequest {
+    pub description: Option<String>,
+    /// Must be an image in the category.
+    pub cover_image_id: Option<Uuid>,
+}
+
+#[derive(Debug, Error)]
+pub enum UpdateCategoryDetailsError {
+    #[error("there category was not found")]
+    CategoryNotFound,
+    #[error("the cover image must be in the category")]
+    CoverNotInCategory,
+    #[error("there was an unexpected error")]
+    Unexpected,
+}
+
+#[api]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum DeleteCategoryChildren {
+    /// Move the subcategories to the parent of the deleted category.
+    Reparent,
+    /// Delete the subcategories as well.
+    Cascade,
+}
+
+#[api]
+pub struct DeleteCategoryQuery {
+    /// Required if the category has subcategories.
+    pub children: Option<DeleteCategoryChildren>,
+}
+
 #[derive(Debug, Error)]
 pub enum DeleteCategoryError {
-    #[error("only admins are allowed to create categories")]
-    NotAllowed,
     #[error("there category was not found")]
     CategoryNotFound,
+    #[error("the category has subcategories, choose whether to reparent or cascade")]
+    HasChildren,
+    #[error("there was an unexpected error")]
+    Unexpected,
+}
+
+#[api]
+#[serde(rename_all = "camelCase")]
+pub struct MergeCategoriesResponse {
+    /// Images that were moved to the target category.
+    pub moved_images: u64,
+    /// Images that were already in the target category.
+    pub duplicate_images: u64,
+}
+
+#[derive(Debug, Error)]
+pub enum MergeCategoriesError {
+    #[error("there category was not found")]
+    CategoryNotFound,
+    #[error("the target category was not found")]
+    TargetNotFound,
+    #[error("a category cannot be merged into itself or its subcategories")]
+    InvalidTarget,
+    #[error("there was an unexpected error")]
+    Unexpected,
+}
+
+#[api]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum BulkCategoryAction {
+    Add,
+    Remove,
+}
+
+#[api]
+#[serde(rename_all = "camelCase")]
+pub struct BulkCategorizeRequest {
+    pub action: BulkCategoryAction,
+    pub image_ids: Vec<Uuid>,
+    /// Only report what would change.
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[api]
+#[serde(rename_all = "camelCase")]
+pub struct BulkCategorizeResponse {
+    pub dry_run: bool,
+    /// Images that were (or would be) added or removed.
+    pub affected: u64,
+    /// Images that were already in the requested state.
+    pub unchanged: u64,
+    /// IDs that do not belong to any image.
+    pub missing_image_ids: Vec<Uuid>,
+}
+
+#[derive(Debug, Error)]
+pub enum BulkCategorizeError {
+    #[error("there category was not found")]
+    CategoryNotFound,
+    #[error("at most {0} images can be given at once")]
+    TooManyImages(usize),
     #[error("there was an unexpected error")]
     Unexpected,
 }
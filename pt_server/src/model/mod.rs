@@ -1,8 +1,18 @@
 use aide::openapi::v3::macros::api;
+use std::str::FromStr;
 
+pub mod album;
+pub mod audit;
 pub mod auth;
+pub mod comment;
 pub mod error;
 pub mod image;
+pub mod moderation;
+pub mod notification;
+pub mod role;
+pub mod share_link;
+pub mod trash;
+pub mod user;
 
 #[api]
 #[derive(Debug)]
@@ -10,3 +20,45 @@ pub struct Pagination {
     pub limit: Option<usize>,
     pub offset: Option<usize>,
 }
+
+/// Who can see an item besides its owner.
+#[api]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum Visibility {
+    /// Listed and visible to everyone.
+    Public,
+    /// Visible to everyone who knows the ID, but not listed.
+    Unlisted,
+    /// Only visible to the owner.
+    Private,
+}
+
+impl Default for Visibility {
+    fn default() -> Self {
+        Visibility::Private
+    }
+}
+
+impl Visibility {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Visibility::Public => "public",
+            Visibility::Unlisted => "unlisted",
+            Visibility::Private => "private",
+        }
+    }
+}
+
+impl FromStr for Visibility {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "public" => Ok(Visibility::Public),
+            "unlisted" => Ok(Visibility::Unlisted),
+            "private" => Ok(Visibility::Private),
+            _ => Err(()),
+        }
+    }
+}
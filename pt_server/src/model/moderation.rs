@@ -0,0 +1,214 @@
+use aide::openapi::v3::macros::api;
+use std::str::FromStr;
+use thiserror::Error;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+#[api]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum ReportReason {
+    Spam,
+    Nudity,
+    Violence,
+    Harassment,
+    Copyright,
+    Other,
+}
+
+impl ReportReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReportReason::Spam => "spam",
+            ReportReason::Nudity => "nudity",
+            ReportReason::Violence => "violence",
+            ReportReason::Harassment => "harassment",
+            ReportReason::Copyright => "copyright",
+            ReportReason::Other => "other",
+        }
+    }
+}
+
+impl FromStr for ReportReason {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "spam" => Ok(ReportReason::Spam),
+            "nudity" => Ok(ReportReason::Nudity),
+            "violence" => Ok(ReportReason::Violence),
+            "harassment" => Ok(ReportReason::Harassment),
+            "copyright" => Ok(ReportReason::Copyright),
+            "other" => Ok(ReportReason::Other),
+            _ => Err(()),
+        }
+    }
+}
+
+#[api]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum ModerationActionKind {
+    /// Closes the reports without changing the image.
+    Dismiss,
+    /// Leaves the image out of searches.
+    Hide,
+    /// Moves the image to the trash, the owner cannot restore it.
+    Delete,
+    /// Warns the owner of the image without changing it.
+    Warn,
+}
+
+impl ModerationActionKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ModerationActionKind::Dismiss => "dismiss",
+            ModerationActionKind::Hide => "hide",
+            ModerationActionKind::Delete => "delete",
+            ModerationActionKind::Warn => "warn",
+        }
+    }
+}
+
+impl FromStr for ModerationActionKind {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dismiss" => Ok(ModerationActionKind::Dismiss),
+            "hide" => Ok(ModerationActionKind::Hide),
+            "delete" => Ok(ModerationActionKind::Delete),
+            "warn" => Ok(ModerationActionKind::Warn),
+            _ => Err(()),
+        }
+    }
+}
+
+#[api]
+#[serde(rename_all = "camelCase")]
+pub struct ReportImageRequest {
+    pub reason: ReportReason,
+    #[serde(default)]
+    pub details: Option<String>,
+}
+
+#[api]
+#[serde(rename_all = "camelCase")]
+pub struct ReportImageResponse {
+    pub id: Uuid,
+}
+
+#[derive(Debug, Error)]
+pub enum ReportImageError {
+    #[error("the image was not found")]
+    ImageNotFound,
+    #[error("the image was already reported by the user")]
+    AlreadyReported,
+    #[error("the details can be at most {0} characters")]
+    InvalidDetails(usize),
+    #[error("there was an unexpected error")]
+    Unexpected,
+}
+
+#[api]
+#[serde(rename_all = "camelCase")]
+pub struct Report {
+    pub id: Uuid,
+    /// Missing if the reporter deleted their account.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reporter: Option<Uuid>,
+    pub reason: ReportReason,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub details: Option<String>,
+    #[serde(serialize_with = "crate::util::serialize_rfc3339")]
+    #[serde(deserialize_with = "crate::util::deserialize_rfc3339")]
+    pub created: OffsetDateTime,
+}
+
+#[api]
+#[serde(rename_all = "camelCase")]
+pub struct ReportedImage {
+    pub image_id: Uuid,
+    pub report_count: u64,
+    #[serde(serialize_with = "crate::util::serialize_rfc3339")]
+    #[serde(deserialize_with = "crate::util::deserialize_rfc3339")]
+    pub first_reported: OffsetDateTime,
+    #[serde(serialize_with = "crate::util::serialize_rfc3339")]
+    #[serde(deserialize_with = "crate::util::deserialize_rfc3339")]
+    pub last_reported: OffsetDateTime,
+    /// Oldest first.
+    pub reports: Vec<Report>,
+}
+
+#[api]
+#[serde(rename_all = "camelCase")]
+pub struct GetReportedImagesResponse {
+    /// The most reported images first.
+    pub images: Vec<ReportedImage>,
+}
+
+#[derive(Debug, Error)]
+pub enum GetReportedImagesError {
+    #[error("there was an unexpected error")]
+    Unexpected,
+}
+
+#[api]
+#[serde(rename_all = "camelCase")]
+pub struct ModerateImageRequest {
+    pub action: ModerationActionKind,
+    #[serde(default)]
+    pub note: Option<String>,
+    /// Also warn the owner of the image, implied by the `warn` action.
+    #[serde(default)]
+    pub warn_user: bool,
+}
+
+#[derive(Debug, Error)]
+pub enum ModerateImageError {
+    #[error("the image was not found")]
+    ImageNotFound,
+    #[error(r#"the "hideImages" permission is required"#)]
+    NotAllowed,
+    #[error("there was an unexpected error")]
+    Unexpected,
+}
+
+#[api]
+#[serde(rename_all = "camelCase")]
+pub struct ModerationAction {
+    pub id: Uuid,
+    /// Missing if the moderator deleted their account.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub moderator_id: Option<Uuid>,
+    pub image_id: Uuid,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image_owner_id: Option<Uuid>,
+    pub action: ModerationActionKind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    #[serde(serialize_with = "crate::util::serialize_rfc3339")]
+    #[serde(deserialize_with = "crate::util::deserialize_rfc3339")]
+    pub created: OffsetDateTime,
+}
+
+#[api]
+#[serde(rename_all = "camelCase")]
+pub struct GetModerationActionsQuery {
+    pub image_id: Option<Uuid>,
+    pub offset: Option<u64>,
+    pub limit: Option<u64>,
+}
+
+#[api]
+#[serde(rename_all = "camelCase")]
+pub struct GetModerationActionsResponse {
+    /// The latest first.
+    pub actions: Vec<ModerationAction>,
+}
+
+#[derive(Debug, Error)]
+pub enum GetModerationActionsError {
+    #[error("there was an unexpected error")]
+    Unexpected,
+}
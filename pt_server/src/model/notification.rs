@@ -0,0 +1,109 @@
+use super::moderation::ModerationActionKind;
+use aide::openapi::v3::macros::api;
+use std::str::FromStr;
+use thiserror::Error;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+#[api]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum NotificationKind {
+    /// An image of the user was rated.
+    ImageRated,
+    /// An image of the user was commented on.
+    ImageCommented,
+    /// Someone replied to a comment of the user.
+    CommentReplied,
+    /// A moderator acted on an image of the user.
+    ImageModerated,
+    /// A comment of the user was deleted by someone else.
+    CommentDeleted,
+}
+
+impl NotificationKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NotificationKind::ImageRated => "imageRated",
+            NotificationKind::ImageCommented => "imageCommented",
+            NotificationKind::CommentReplied => "commentReplied",
+            NotificationKind::ImageModerated => "imageModerated",
+            NotificationKind::CommentDeleted => "commentDeleted",
+        }
+    }
+}
+
+impl FromStr for NotificationKind {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "imageRated" => Ok(NotificationKind::ImageRated),
+            "imageCommented" => Ok(NotificationKind::ImageCommented),
+            "commentReplied" => Ok(NotificationKind::CommentReplied),
+            "imageModerated" => Ok(NotificationKind::ImageModerated),
+            "commentDeleted" => Ok(NotificationKind::CommentDeleted),
+            _ => Err(()),
+        }
+    }
+}
+
+#[api]
+#[serde(rename_all = "camelCase")]
+pub struct Notification {
+    pub id: Uuid,
+    #[serde(rename = "type")]
+    pub kind: NotificationKind,
+    /// Missing for moderator actions, or if the actor deleted their account.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub actor_id: Option<Uuid>,
+    /// Missing if the image was deleted since.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image_id: Option<Uuid>,
+    /// Missing if the comment was deleted since.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comment_id: Option<Uuid>,
+    /// The rating given, only for `imageRated`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rating: Option<u32>,
+    /// Only for `imageModerated`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub action: Option<ModerationActionKind>,
+    pub read: bool,
+    #[serde(serialize_with = "crate::util::serialize_rfc3339")]
+    #[serde(deserialize_with = "crate::util::deserialize_rfc3339")]
+    pub created: OffsetDateTime,
+}
+
+#[api]
+#[serde(rename_all = "camelCase")]
+pub struct GetNotificationsQuery {
+    /// Only the ones that have not been read yet.
+    #[serde(default)]
+    pub unread: bool,
+    pub offset: Option<u64>,
+    pub limit: Option<u64>,
+}
+
+#[api]
+#[serde(rename_all = "camelCase")]
+pub struct GetNotificationsResponse {
+    /// The latest first.
+    pub notifications: Vec<Notification>,
+    /// All the unread notifications of the user, not only the listed ones.
+    pub unread_count: u64,
+}
+
+#[derive(Debug, Error)]
+pub enum GetNotificationsError {
+    #[error("there was an unexpected error")]
+    Unexpected,
+}
+
+#[derive(Debug, Error)]
+pub enum MarkNotificationReadError {
+    #[error("the notification was not found")]
+    NotFound,
+    #[error("there was an unexpected error")]
+    Unexpected,
+}
@@ -0,0 +1,114 @@
+use aide::openapi::v3::macros::api;
+use std::str::FromStr;
+use thiserror::Error;
+
+/// Roles of the users, every role has a fixed set of permissions.
+#[api]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum Role {
+    User,
+    /// Curates the image categories.
+    Curator,
+    /// Keeps the content in order.
+    Moderator,
+    Admin,
+}
+
+impl Default for Role {
+    fn default() -> Self {
+        Role::User
+    }
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::User => "user",
+            Role::Curator => "curator",
+            Role::Moderator => "moderator",
+            Role::Admin => "admin",
+        }
+    }
+
+    pub fn permissions(&self) -> &'static [Permission] {
+        match self {
+            Role::User => &[],
+            Role::Curator => &[Permission::ManageCategories],
+            Role::Moderator => &[
+                Permission::ManageCategories,
+                Permission::HideImages,
+                Permission::HandleReports,
+                Permission::ModerateComments,
+            ],
+            Role::Admin => &[
+                Permission::ManageCategories,
+                Permission::HideImages,
+                Permission::HandleReports,
+                Permission::ModerateComments,
+                Permission::ManageUsers,
+                Permission::ViewAuditLog,
+            ],
+        }
+    }
+
+    pub fn has_permission(&self, permission: Permission) -> bool {
+        self.permissions().contains(&permission)
+    }
+}
+
+impl FromStr for Role {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "user" => Ok(Role::User),
+            "curator" => Ok(Role::Curator),
+            "moderator" => Ok(Role::Moderator),
+            "admin" => Ok(Role::Admin),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Actions that are not allowed for every user.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Permission {
+    ManageCategories,
+    HideImages,
+    HandleReports,
+    /// Delete the comments of others.
+    ModerateComments,
+    ManageUsers,
+    ViewAuditLog,
+}
+
+impl Permission {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Permission::ManageCategories => "manageCategories",
+            Permission::HideImages => "hideImages",
+            Permission::HandleReports => "handleReports",
+            Permission::ModerateComments => "moderateComments",
+            Permission::ManageUsers => "manageUsers",
+            Permission::ViewAuditLog => "viewAuditLog",
+        }
+    }
+}
+
+#[api]
+#[serde(rename_all = "camelCase")]
+pub struct SetRoleRequest {
+    pub role: Role,
+}
+
+#[api]
+#[derive(Debug, Error)]
+pub enum SetRoleError {
+    #[error("user was not found")]
+    UserNotFound,
+    #[error("own role cannot be changed")]
+    OwnRole,
+    #[error("unexpected error")]
+    Unexpected,
+}
@@ -0,0 +1,84 @@
+use super::image::Image;
+use aide::openapi::v3::macros::api;
+use thiserror::Error;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+#[api]
+#[serde(rename_all = "camelCase")]
+pub struct ShareLink {
+    pub id: Uuid,
+    pub image_id: Uuid,
+    /// Used in `/shared/{token}`.
+    pub token: String,
+    #[serde(serialize_with = "crate::util::serialize_rfc3339")]
+    #[serde(deserialize_with = "crate::util::deserialize_rfc3339")]
+    pub created: OffsetDateTime,
+    #[serde(serialize_with = "crate::util::serialize_rfc3339_opt")]
+    #[serde(deserialize_with = "crate::util::deserialize_rfc3339_opt")]
+    pub expires: Option<OffsetDateTime>,
+}
+
+#[api]
+#[serde(rename_all = "camelCase")]
+pub struct CreateShareLinkRequest {
+    /// The link never expires if not given.
+    #[serde(default)]
+    #[serde(serialize_with = "crate::util::serialize_rfc3339_opt")]
+    #[serde(deserialize_with = "crate::util::deserialize_rfc3339_opt")]
+    pub expires: Option<OffsetDateTime>,
+}
+
+#[derive(Debug, Error)]
+pub enum CreateShareLinkError {
+    #[error("the image was not found")]
+    ImageNotFound,
+    #[error("only the owner can share the image")]
+    NotAllowed,
+    #[error("the expiry must be in the future")]
+    InvalidExpiry,
+    #[error("there was an unexpected error")]
+    Unexpected,
+}
+
+#[api]
+#[serde(rename_all = "camelCase")]
+pub struct GetShareLinksResponse {
+    /// Links that were not revoked, newest first.
+    pub links: Vec<ShareLink>,
+}
+
+#[derive(Debug, Error)]
+pub enum GetShareLinksError {
+    #[error("the image was not found")]
+    ImageNotFound,
+    #[error("only the owner can see the share links of the image")]
+    NotAllowed,
+    #[error("there was an unexpected error")]
+    Unexpected,
+}
+
+#[derive(Debug, Error)]
+pub enum RevokeShareLinkError {
+    #[error("the share link was not found")]
+    NotFound,
+    #[error("only the owner of the image can revoke the share link")]
+    NotAllowed,
+    #[error("there was an unexpected error")]
+    Unexpected,
+}
+
+#[api]
+#[serde(rename_all = "camelCase")]
+pub struct GetSharedImageResponse {
+    pub image: Image,
+}
+
+#[derive(Debug, Error)]
+pub enum GetSharedImageError {
+    /// Also returned for revoked and expired links.
+    #[error("the shared image was not found")]
+    NotFound,
+    #[error("there was an unexpected error")]
+    Unexpected,
+}
@@ -0,0 +1,93 @@
+use aide::openapi::v3::macros::api;
+use thiserror::Error;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+#[api]
+#[serde(rename_all = "camelCase")]
+pub struct TrashedImage {
+    pub id: Uuid,
+    pub title: String,
+    #[serde(serialize_with = "crate::util::serialize_rfc3339")]
+    #[serde(deserialize_with = "crate::util::deserialize_rfc3339")]
+    pub deleted: OffsetDateTime,
+    /// When the image will be deleted for good.
+    #[serde(serialize_with = "crate::util::serialize_rfc3339")]
+    #[serde(deserialize_with = "crate::util::deserialize_rfc3339")]
+    pub purge_after: OffsetDateTime,
+}
+
+#[api]
+#[serde(rename_all = "camelCase")]
+pub struct GetTrashedImagesResponse {
+    /// The latest first.
+    pub images: Vec<TrashedImage>,
+}
+
+#[api]
+#[serde(rename_all = "camelCase")]
+pub struct TrashedCategory {
+    pub id: Uuid,
+    pub name: String,
+    pub slug: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent_id: Option<Uuid>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_by: Option<Uuid>,
+    #[serde(serialize_with = "crate::util::serialize_rfc3339")]
+    #[serde(deserialize_with = "crate::util::deserialize_rfc3339")]
+    pub deleted: OffsetDateTime,
+    /// When the category will be deleted for good.
+    #[serde(serialize_with = "crate::util::serialize_rfc3339")]
+    #[serde(deserialize_with = "crate::util::deserialize_rfc3339")]
+    pub purge_after: OffsetDateTime,
+}
+
+#[api]
+#[serde(rename_all = "camelCase")]
+pub struct GetTrashedCategoriesResponse {
+    /// The latest first.
+    pub categories: Vec<TrashedCategory>,
+}
+
+#[derive(Debug, Error)]
+pub enum DeleteImageError {
+    #[error("the image was not found")]
+    ImageNotFound,
+    #[error("only the owner can delete the image")]
+    NotAllowed,
+    #[error("there was an unexpected error")]
+    Unexpected,
+}
+
+#[derive(Debug, Error)]
+pub enum GetTrashError {
+    #[error("there was an unexpected error")]
+    Unexpected,
+}
+
+#[derive(Debug, Error)]
+pub enum TrashedImageError {
+    #[error("the image was not found in the trash")]
+    ImageNotFound,
+    #[error("there was an unexpected error")]
+    Unexpected,
+}
+
+#[derive(Debug, Error)]
+pub enum RestoreCategoryError {
+    #[error("the category was not found in the trash")]
+    CategoryNotFound,
+    #[error("the parent category is in the trash")]
+    ParentDeleted,
+    #[error("a category with the same name or slug already exists")]
+    AlreadyExists,
+    #[error("there was an unexpected error")]
+    Unexpected,
+}
+
+#[derive(Debug, Error)]
+pub enum PurgeTrashError {
+    #[error("there was an unexpected error")]
+    Unexpected,
+}
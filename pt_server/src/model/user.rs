@@ -0,0 +1,157 @@
+use aide::openapi::v3::macros::api;
+use std::str::FromStr;
+use thiserror::Error;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+#[api]
+#[serde(rename_all = "camelCase")]
+pub struct UserSettings {
+    /// Keep the GPS location in uploaded photos, it is removed by default.
+    pub keep_image_location: bool,
+}
+
+#[api]
+#[derive(Debug, Error)]
+pub enum UserSettingsError {
+    #[error("user was not found")]
+    UserNotFound,
+    #[error("settings cannot be changed with API keys")]
+    NotAllowed,
+    #[error("unexpected error")]
+    Unexpected,
+}
+
+#[api]
+#[serde(rename_all = "camelCase")]
+pub struct StorageUsage {
+    /// Total size of the stored files in bytes.
+    pub bytes: u64,
+    pub images: u64,
+    /// Missing if there is no limit.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_bytes: Option<u64>,
+    /// Missing if there is no limit.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_images: Option<u64>,
+}
+
+#[api]
+#[derive(Debug, Error)]
+pub enum StorageUsageError {
+    #[error("unexpected error")]
+    Unexpected,
+}
+
+/// Limits that are missing are reset to the defaults, 0 means no limit.
+#[api]
+#[serde(rename_all = "camelCase")]
+pub struct SetStorageQuotaRequest {
+    #[serde(default)]
+    pub max_bytes: Option<u64>,
+    #[serde(default)]
+    pub max_images: Option<u64>,
+}
+
+#[api]
+#[derive(Debug, Error)]
+pub enum SetStorageQuotaError {
+    #[error("user was not found")]
+    UserNotFound,
+    #[error("the limit is too large")]
+    InvalidLimit,
+    #[error("unexpected error")]
+    Unexpected,
+}
+
+#[api]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum AccountExportStatus {
+    /// The archive is being built.
+    Pending,
+    /// The archive can be downloaded.
+    Ready,
+    Failed,
+}
+
+impl AccountExportStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AccountExportStatus::Pending => "pending",
+            AccountExportStatus::Ready => "ready",
+            AccountExportStatus::Failed => "failed",
+        }
+    }
+}
+
+impl FromStr for AccountExportStatus {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pending" => Ok(AccountExportStatus::Pending),
+            "ready" => Ok(AccountExportStatus::Ready),
+            "failed" => Ok(AccountExportStatus::Failed),
+            _ => Err(()),
+        }
+    }
+}
+
+/// A ZIP archive of the profile, the original images with their details
+/// and the ratings of the user.
+#[api]
+#[serde(rename_all = "camelCase")]
+pub struct AccountExport {
+    pub id: Uuid,
+    pub status: AccountExportStatus,
+    #[serde(serialize_with = "crate::util::serialize_rfc3339")]
+    #[serde(deserialize_with = "crate::util::deserialize_rfc3339")]
+    pub created: OffsetDateTime,
+    #[serde(serialize_with = "crate::util::serialize_rfc3339_opt")]
+    #[serde(deserialize_with = "crate::util::deserialize_rfc3339_opt")]
+    pub completed: Option<OffsetDateTime>,
+    /// Size of the archive in bytes, once it is ready.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_size: Option<u64>,
+}
+
+#[api]
+#[derive(Debug, Error)]
+pub enum AccountExportError {
+    #[error("export was not found")]
+    ExportNotFound,
+    #[error("an export is already being built")]
+    ExportInProgress,
+    #[error("export is not ready")]
+    ExportNotReady,
+    #[error("exports are not available with API keys")]
+    NotAllowed,
+    #[error("unexpected error")]
+    Unexpected,
+}
+
+#[api]
+#[serde(rename_all = "camelCase")]
+pub struct DeleteAccountRequest {
+    /// The current password of the user.
+    ///
+    /// Accounts without a password, e.g. the ones created with single sign-on,
+    /// need a recent login instead.
+    pub password: Option<String>,
+}
+
+#[api]
+#[derive(Debug, Error)]
+pub enum DeleteAccountError {
+    #[error("user was not found")]
+    UserNotFound,
+    #[error("incorrect password")]
+    IncorrectPassword,
+    #[error("log in again to delete the account")]
+    LoginRequired,
+    #[error("the account cannot be deleted with an API key")]
+    NotAllowed,
+    #[error("unexpected error")]
+    Unexpected,
+}
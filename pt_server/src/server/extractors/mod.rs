@@ -1,3 +1,10 @@
+mod permission;
+mod request_id;
 mod token;
 
-pub use token::SessionToken;
\ No newline at end of file
+pub use permission::{
+    HandleReports, HideImages, ManageCategories, ManageUsers, ModerateComments, PermissionMarker,
+    RequirePermission, ViewAuditLog,
+};
+pub use request_id::{RequestId, REQUEST_ID_HEADER};
+pub use token::{OptionalSessionToken, PublicReadConfig, SessionToken};
@@ -0,0 +1,105 @@
+use super::{token::InvalidTokenError, SessionToken};
+use crate::model::{auth::ApiKeyScope, error::GenericError, role::Permission};
+use actix_web::{http::StatusCode, FromRequest, HttpResponse, ResponseError};
+use futures::Future;
+use std::{marker::PhantomData, pin::Pin};
+use thiserror::Error;
+
+/// Type-level permissions for the `RequirePermission` extractor.
+pub trait PermissionMarker {
+    const PERMISSION: Permission;
+}
+
+macro_rules! permission_markers {
+    ($($name:ident),*) => {
+        $(
+            pub struct $name;
+
+            impl PermissionMarker for $name {
+                const PERMISSION: Permission = Permission::$name;
+            }
+        )*
+    };
+}
+
+permission_markers!(
+    ManageCategories,
+    HideImages,
+    HandleReports,
+    ModerateComments,
+    ManageUsers,
+    ViewAuditLog
+);
+
+/// A session token of a user that has the given permission.
+///
+/// API keys also need the admin scope.
+///
+/// ```ignore
+/// async fn delete_category(
+///     _permission: RequirePermission<ManageCategories>,
+/// ) -> HttpResponse { ... }
+/// ```
+pub struct RequirePermission<P: PermissionMarker> {
+    token: SessionToken,
+    _permission: PhantomData<P>,
+}
+
+impl<P: PermissionMarker> RequirePermission<P> {
+    pub fn token(&self) -> &SessionToken {
+        &self.token
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum PermissionError {
+    #[error(transparent)]
+    Token(InvalidTokenError),
+    #[error(r#"the "{}" permission is required"#, .0.as_str())]
+    Forbidden(Permission),
+}
+
+impl ResponseError for PermissionError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            PermissionError::Token(err) => err.status_code(),
+            PermissionError::Forbidden(_) => StatusCode::FORBIDDEN,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::build(self.status_code()).json(GenericError {
+            message: self.to_string(),
+        })
+    }
+}
+
+impl<P: PermissionMarker + 'static> FromRequest for RequirePermission<P> {
+    type Error = PermissionError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+    type Config = ();
+
+    fn from_request(
+        req: &actix_web::HttpRequest,
+        payload: &mut actix_web::dev::Payload,
+    ) -> Self::Future {
+        let token = SessionToken::from_request(req, payload);
+
+        Box::pin(async move {
+            let token = token.await.map_err(PermissionError::Token)?;
+
+            token
+                .require_scope(ApiKeyScope::Admin)
+                .map_err(PermissionError::Token)?;
+
+            if !token.user_info().has_permission(P::PERMISSION) {
+                return Err(PermissionError::Forbidden(P::PERMISSION));
+            }
+
+            Ok(RequirePermission {
+                token,
+                _permission: PhantomData,
+            })
+        })
+    }
+}
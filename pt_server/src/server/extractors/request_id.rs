@@ -0,0 +1,77 @@
+use crate::services::audit::AuditContext;
+use actix_web::{dev::Payload, Error, FromRequest, HttpRequest};
+use futures::future::{ok, Ready};
+use uuid::Uuid;
+
+/// The header used to pass the request ID, it is also set on every response.
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Longer request IDs from clients are replaced.
+const REQUEST_ID_MAX_LENGTH: usize = 64;
+
+/// Identifies a request in the logs and in the audit log.
+///
+/// Set by the logger middleware, either from the request header or a new one.
+/// Without the middleware the header is read directly.
+#[derive(Debug, Clone)]
+pub struct RequestId(String);
+
+impl RequestId {
+    /// Uses the given ID if it is reasonable, otherwise creates a new one.
+    pub fn from_header(value: Option<&str>) -> Self {
+        match value {
+            Some(id)
+                if !id.is_empty()
+                    && id.len() <= REQUEST_ID_MAX_LENGTH
+                    && id.chars().all(|c| c.is_ascii_graphic()) =>
+            {
+                Self(id.into())
+            }
+            _ => Self(Uuid::new_v4().to_string()),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn audit_context(&self, actor_id: Option<Uuid>) -> AuditContext {
+        AuditContext {
+            actor_id,
+            request_id: Some(self.0.clone()),
+        }
+    }
+}
+
+impl FromRequest for RequestId {
+    type Error = Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+    type Config = ();
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        let request_id = req.extensions().get::<RequestId>().cloned();
+
+        ok(request_id.unwrap_or_else(|| {
+            RequestId::from_header(
+                req.headers()
+                    .get(REQUEST_ID_HEADER)
+                    .and_then(|h| h.to_str().ok()),
+            )
+        }))
+    }
+}
+
+#[test]
+fn test_request_id_from_header() {
+    assert_eq!(RequestId::from_header(Some("abc-123")).as_str(), "abc-123");
+
+    for invalid in &[
+        None,
+        Some(""),
+        Some("with space"),
+        Some(&"a".repeat(65)[..]),
+    ] {
+        let id = RequestId::from_header(*invalid);
+        assert!(Uuid::parse_str(id.as_str()).is_ok());
+    }
+}
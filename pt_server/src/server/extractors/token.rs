@@ -1,15 +1,54 @@
-use crate::{model::error::GenericError, services::auth::UserInfo, services::AuthService};
-use actix_web::{FromRequest, HttpResponse, ResponseError, http::StatusCode, web};
+use crate::{
+    model::auth::ApiKeyError, model::auth::ApiKeyScope, model::auth::TokenError,
+    model::error::GenericError, services::auth::UserInfo, services::AuthService,
+};
+use actix_web::{http::Method, http::StatusCode, web, FromRequest, HttpResponse, ResponseError};
 use futures::Future;
 use std::pin::Pin;
 use thiserror::Error;
 
 pub struct SessionToken(UserInfo);
 
+/// A session token that is only required if public read access is disabled.
+///
+/// Invalid tokens are still rejected, only missing ones are allowed.
+pub struct OptionalSessionToken(Option<SessionToken>);
+
+/// Configuration for the `OptionalSessionToken` extractor.
+#[derive(Debug, Clone, Default)]
+pub struct PublicReadConfig {
+    /// Allow requests without a token.
+    pub enabled: bool,
+}
+
 impl SessionToken {
     pub fn user_info(&self) -> &UserInfo {
         &self.0
     }
+
+    /// Whether the request was authenticated with an API key.
+    pub fn is_api_key(&self) -> bool {
+        self.0.scopes.is_some()
+    }
+
+    /// Fails if the request was made with an API key without the given scope.
+    pub fn require_scope(&self, scope: ApiKeyScope) -> Result<(), InvalidTokenError> {
+        if self.0.has_scope(scope) {
+            Ok(())
+        } else {
+            Err(InvalidTokenError::MissingScope(scope))
+        }
+    }
+}
+
+impl OptionalSessionToken {
+    pub fn token(&self) -> Option<&SessionToken> {
+        self.0.as_ref()
+    }
+
+    pub fn user_info(&self) -> Option<&UserInfo> {
+        self.0.as_ref().map(SessionToken::user_info)
+    }
 }
 
 #[derive(Debug, Error)]
@@ -17,14 +56,23 @@ pub enum InvalidTokenError {
     #[error("authorization token is missing")]
     Missing,
     #[error("invalid authorization token: {0}")]
-    Jwt(jwt::errors::Error),
+    Token(TokenError),
+    #[error("invalid API key: {0}")]
+    ApiKey(ApiKeyError),
+    #[error(r#"the API key is missing the "{}" scope"#, .0.as_str())]
+    MissingScope(ApiKeyScope),
 }
 
 impl ResponseError for InvalidTokenError {
     fn status_code(&self) -> StatusCode {
         match self {
             InvalidTokenError::Missing => StatusCode::UNAUTHORIZED,
-            InvalidTokenError::Jwt(_) => StatusCode::BAD_REQUEST,
+            InvalidTokenError::Token(TokenError::Invalid(_)) => StatusCode::BAD_REQUEST,
+            InvalidTokenError::Token(TokenError::UserNotFound) => StatusCode::UNAUTHORIZED,
+            InvalidTokenError::Token(TokenError::Unexpected) => StatusCode::INTERNAL_SERVER_ERROR,
+            InvalidTokenError::ApiKey(ApiKeyError::Unexpected) => StatusCode::INTERNAL_SERVER_ERROR,
+            InvalidTokenError::ApiKey(_) => StatusCode::UNAUTHORIZED,
+            InvalidTokenError::MissingScope(_) => StatusCode::FORBIDDEN,
         }
     }
 
@@ -49,22 +97,66 @@ impl FromRequest for SessionToken {
             .expect("auth service was not provided with request"))
         .clone();
 
+        // Every scoped key needs the read scope for reading anything.
+        let read_only = req.method() == Method::GET || req.method() == Method::HEAD;
+
         match req.headers().get("authorization") {
             Some(val) => {
-                let token = val
-                    .to_str()
-                    .map(|s| s.trim_start_matches("Bearer ").to_string())
-                    .unwrap_or_default();
-                    
+                let header = val.to_str().unwrap_or_default().to_string();
+
+                if let Some(key) = header.strip_prefix("ApiKey ") {
+                    let key = key.trim().to_string();
+
+                    return Box::pin(async move {
+                        let token = auth_service
+                            .validate_api_key(&key)
+                            .await
+                            .map(SessionToken)
+                            .map_err(InvalidTokenError::ApiKey)?;
+
+                        if read_only {
+                            token.require_scope(ApiKeyScope::Read)?;
+                        }
+
+                        Ok(token)
+                    });
+                }
+
+                let token = header.trim_start_matches("Bearer ").to_string();
+
                 Box::pin(async move {
                     auth_service
                         .validate_token(&token)
                         .await
-                        .map(|info| SessionToken(info))
-                        .map_err(|e| InvalidTokenError::Jwt(e))
+                        .map(SessionToken)
+                        .map_err(InvalidTokenError::Token)
                 })
             }
             None => Box::pin(futures::future::ready(Err(InvalidTokenError::Missing))),
         }
     }
 }
+
+impl FromRequest for OptionalSessionToken {
+    type Error = InvalidTokenError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+    type Config = PublicReadConfig;
+
+    fn from_request(
+        req: &actix_web::HttpRequest,
+        payload: &mut actix_web::dev::Payload,
+    ) -> Self::Future {
+        let public_read = req
+            .app_data::<web::Data<Self::Config>>()
+            .map(|c| c.enabled)
+            .unwrap_or(false);
+
+        if public_read && req.headers().get("authorization").is_none() {
+            return Box::pin(futures::future::ready(Ok(OptionalSessionToken(None))));
+        }
+
+        let token = SessionToken::from_request(req, payload);
+
+        Box::pin(async move { token.await.map(|t| OptionalSessionToken(Some(t))) })
+    }
+}
@@ -1,5 +1,11 @@
+use crate::server::extractors::{RequestId, REQUEST_ID_HEADER};
 use actix_service::{Service, Transform};
-use actix_web::{dev::ServiceRequest, dev::ServiceResponse, Error, HttpMessage};
+use actix_web::{
+    dev::ServiceRequest,
+    dev::ServiceResponse,
+    http::{HeaderName, HeaderValue},
+    Error, HttpMessage,
+};
 use futures::{
     future::{ok, Ready},
     Future,
@@ -68,7 +74,12 @@ where
         let method = req.method().to_string();
         let log = self.logger.clone();
 
-        req.extensions_mut().insert(String::new());
+        let request_id = RequestId::from_header(
+            req.headers()
+                .get(REQUEST_ID_HEADER)
+                .and_then(|h| h.to_str().ok()),
+        );
+        req.extensions_mut().insert(request_id.clone());
 
         let address = req
             .connection_info()
@@ -83,7 +94,12 @@ where
         let next = self.service.call(req);
 
         Box::pin(async move {
-            let res = next.await?;
+            let mut res = next.await?;
+
+            if let Ok(value) = HeaderValue::from_str(request_id.as_str()) {
+                res.headers_mut()
+                    .insert(HeaderName::from_static(REQUEST_ID_HEADER), value);
+            }
 
             let time_ms =
                 (OffsetDateTime::now_utc() - start).whole_nanoseconds() as f32 / 1_000_000f32;
@@ -95,6 +111,7 @@ where
                 "time" => format!("{}ms", time_ms),
                 "userAgent" => agent,
                 "address" => address,
+                "requestId" => request_id.as_str(),
             );
 
             Ok(res)
@@ -1,6 +1,13 @@
 use crate::{
-    config::Config, model::error::GenericError, services::AuthService,
-    services::DefaultAuthService, services::DefaultImageService, services::ImageService,
+    config::Config, model::error::GenericError, services::auth::keys::TokenKeys,
+    services::notification::NotificationHub, services::AccountService, services::AlbumService,
+    services::AuditService, services::AuthService, services::CommentService,
+    services::DefaultAccountService, services::DefaultAlbumService, services::DefaultAuditService,
+    services::DefaultAuthService, services::DefaultCommentService, services::DefaultImageService,
+    services::DefaultModerationService, services::DefaultNotificationService,
+    services::DefaultShareLinkService, services::DefaultTrashService, services::ImageService,
+    services::ModerationService, services::NotificationService, services::ShareLinkService,
+    services::TrashService,
 };
 use actix_cors::Cors;
 use actix_web::{web::ServiceConfig, App, HttpServer};
@@ -15,17 +22,30 @@ pub async fn run(config: Config, logger: Logger, pool: sqlx::PgPool) -> anyhow::
     let host = config.host.clone();
     let port = config.port;
 
+    // Fail early instead of in every worker.
+    TokenKeys::from_config(&config)?;
+
     info!(logger, "server start";
         "host" => &host,
         "port" => port
     );
 
+    crate::jobs::start_workers(&config, logger.clone(), pool.clone(), config.worker_count);
+
+    // Shared by all the HTTP workers.
+    let notification_hub = NotificationHub::start(logger.clone(), pool.clone());
+
     HttpServer::new(move || {
         App::new()
             .wrap(Cors::new().finish())
             .wrap(middleware::Logger::new(logger.clone()))
             .data(logger.clone())
-            .configure(configure_services(&config, logger.clone(), pool.clone()))
+            .configure(configure_services(
+                &config,
+                logger.clone(),
+                pool.clone(),
+                notification_hub.clone(),
+            ))
             .configure(configure_routes(&config))
     })
     .bind(format!("{}:{}", host, port))?
@@ -39,23 +59,54 @@ pub fn configure_services(
     config: &Config,
     logger: Logger,
     pool: sqlx::PgPool,
+    notification_hub: NotificationHub,
 ) -> impl FnOnce(&mut ServiceConfig) {
     let c = config.clone();
     move |app: &mut ServiceConfig| {
         let auth_service = DefaultAuthService::new(&c, logger.clone(), pool.clone());
-        let image_service = DefaultImageService::new(&c, logger, pool);
+        let image_service = DefaultImageService::new(&c, logger.clone(), pool.clone());
+        let album_service = DefaultAlbumService::new(logger.clone(), pool.clone());
+        let comment_service = DefaultCommentService::new(&c, logger.clone(), pool.clone());
+        let share_link_service = DefaultShareLinkService::new(&c, logger.clone(), pool.clone());
+        let moderation_service = DefaultModerationService::new(logger.clone(), pool.clone());
+        let audit_service = DefaultAuditService::new(logger.clone(), pool.clone());
+        let trash_service = DefaultTrashService::new(&c, logger.clone(), pool.clone());
+        let account_service = DefaultAccountService::new(&c, logger.clone(), pool.clone());
+        let notification_service = DefaultNotificationService::new(logger, pool, notification_hub);
 
         app.data::<Box<dyn AuthService>>(Box::new(auth_service));
         app.data::<Box<dyn ImageService>>(Box::new(image_service));
+        app.data::<Box<dyn AlbumService>>(Box::new(album_service));
+        app.data::<Box<dyn CommentService>>(Box::new(comment_service));
+        app.data::<Box<dyn ShareLinkService>>(Box::new(share_link_service));
+        app.data::<Box<dyn ModerationService>>(Box::new(moderation_service));
+        app.data::<Box<dyn AuditService>>(Box::new(audit_service));
+        app.data::<Box<dyn TrashService>>(Box::new(trash_service));
+        app.data::<Box<dyn AccountService>>(Box::new(account_service));
+        app.data::<Box<dyn NotificationService>>(Box::new(notification_service));
     }
 }
 
 pub fn configure_routes(config: &Config) -> impl FnOnce(&mut ServiceConfig) {
     let c = config.clone();
     move |app: &mut ServiceConfig| {
+        app.data(extractors::PublicReadConfig {
+            enabled: c.public_read,
+        });
+
         routes::auth::configure_routes(&c)(app);
         routes::image::configure_routes(&c)(app);
         routes::category::configure_routes(&c)(app);
+        routes::user::configure_routes(&c)(app);
+        routes::album::configure_routes(&c)(app);
+        routes::comment::configure_routes(&c)(app);
+        routes::tag::configure_routes(&c)(app);
+        routes::share_link::configure_routes(&c)(app);
+        routes::upload::configure_routes(&c)(app);
+        routes::moderation::configure_routes(&c)(app);
+        routes::admin::configure_routes(&c)(app);
+        routes::trash::configure_routes(&c)(app);
+        routes::notification::configure_routes(&c)(app);
 
         if c.api_docs {
             let api = generate_api(None)
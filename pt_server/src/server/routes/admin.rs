@@ -0,0 +1,128 @@
+use crate::{
+    config::Config,
+    db::{self, audit_event::AuditEventFilter},
+    model::audit::*,
+    model::error::GenericError,
+    server::extractors::{RequirePermission, ViewAuditLog},
+    services::AuditService,
+    util::csv_field,
+};
+use actix_web::{
+    get,
+    http::header,
+    web::{self, ServiceConfig},
+    HttpResponse,
+};
+use aide::openapi::v3::macros::api;
+use aide::openapi::v3::macros::api::define;
+
+const TAG_NAME: &str = "admin";
+
+define::tag! {
+    name(TAG_NAME),
+    description("Administration routes"),
+    display_name("Admin")
+}
+
+const AUDIT_CSV_HEADER: &str =
+    "id,created,actorId,action,targetType,targetId,before,after,requestId";
+
+fn audit_event_response(e: db::audit_event::AuditEvent) -> Option<AuditEvent> {
+    Some(AuditEvent {
+        id: e.id,
+        created: e.created,
+        actor_id: e.actor_id,
+        action: e.action.parse().ok()?,
+        target_type: e.target_type,
+        target_id: e.target_id,
+        before: e.before.map(|v| v.to_string()),
+        after: e.after.map(|v| v.to_string()),
+        request_id: e.request_id,
+    })
+}
+
+fn audit_events_csv(events: &[AuditEvent]) -> String {
+    let mut csv = String::from(AUDIT_CSV_HEADER);
+    csv.push_str("\r\n");
+
+    for e in events {
+        let fields = [
+            e.id.to_string(),
+            e.created.format(time::Format::Rfc3339),
+            e.actor_id.map(|id| id.to_string()).unwrap_or_default(),
+            e.action.as_str().into(),
+            e.target_type.clone(),
+            e.target_id.clone(),
+            e.before.clone().unwrap_or_default(),
+            e.after.clone().unwrap_or_default(),
+            e.request_id.clone().unwrap_or_default(),
+        ];
+
+        let row: Vec<String> = fields.iter().map(|f| csv_field(f)).collect();
+        csv.push_str(&row.join(","));
+        csv.push_str("\r\n");
+    }
+
+    csv
+}
+
+/// Recorded changes and logins, the latest first.
+///
+/// With `format=csv` the events are returned as a CSV download.
+#[api]
+#[get("/admin/audit")]
+#[tag(TAG_NAME)]
+#[response(200, GetAuditEventsResponse)]
+#[response(400, GenericError)]
+#[response(403, GenericError)]
+async fn get_audit_events(
+    _permission: RequirePermission<ViewAuditLog>,
+    req: web::Query<GetAuditEventsQuery>,
+    audit_service: web::Data<Box<dyn AuditService>>,
+) -> HttpResponse {
+    let filter = AuditEventFilter {
+        actor_id: req.actor_id,
+        action: req.action.map(|a| a.as_str().into()),
+        from: req.from,
+        to: req.to,
+    };
+
+    match audit_service
+        .get_audit_events(filter, req.offset, req.limit)
+        .await
+    {
+        Ok(events) => {
+            let events: Vec<AuditEvent> = events
+                .into_iter()
+                .filter_map(audit_event_response)
+                .collect();
+
+            match req.format {
+                AuditExportFormat::Json => {
+                    HttpResponse::Ok().json(GetAuditEventsResponse { events })
+                }
+                AuditExportFormat::Csv => HttpResponse::Ok()
+                    .content_type("text/csv; charset=utf-8")
+                    .header(
+                        header::CONTENT_DISPOSITION,
+                        r#"attachment; filename="audit.csv""#,
+                    )
+                    .body(audit_events_csv(&events)),
+            }
+        }
+        Err(err) => match err {
+            GetAuditEventsError::InvalidRange => HttpResponse::BadRequest().json(GenericError {
+                message: err.to_string(),
+            }),
+            GetAuditEventsError::Unexpected => {
+                HttpResponse::InternalServerError().json(GenericError::default())
+            }
+        },
+    }
+}
+
+pub fn configure_routes(_config: &Config) -> impl FnOnce(&mut ServiceConfig) {
+    move |app: &mut ServiceConfig| {
+        app.service(get_audit_events);
+    }
+}
@@ -0,0 +1,334 @@
+use crate::{
+    config::Config, db::album::AlbumExt, model::album::*, model::auth::ApiKeyScope,
+    model::error::GenericError, model::Pagination, server::extractors::SessionToken,
+    server::routes::image::image_response, services::AlbumService,
+};
+use actix_web::{
+    delete, get, post, put,
+    web::{self, ServiceConfig},
+    HttpResponse, ResponseError,
+};
+use aide::openapi::v3::macros::api;
+use aide::openapi::v3::macros::api::define;
+use uuid::Uuid;
+
+const TAG_NAME: &str = "albums";
+
+define::tag! {
+    name(TAG_NAME),
+    description("User-owned collections of images"),
+    display_name("Albums")
+}
+
+fn album_response(a: AlbumExt) -> Album {
+    Album {
+        visibility: a.album.visibility(),
+        id: a.album.id,
+        name: a.album.album_name,
+        owner: a.album.app_user_id,
+        image_count: a.image_count as _,
+        created: a.album.created,
+    }
+}
+
+#[api]
+#[post("/albums")]
+#[tag(TAG_NAME)]
+#[response(200, CreateAlbumResponse)]
+#[response(400, GenericError)]
+async fn create_album(
+    token: SessionToken,
+    req: web::Json<CreateAlbumRequest>,
+    album_service: web::Data<Box<dyn AlbumService>>,
+) -> HttpResponse {
+    if let Err(err) = token.require_scope(ApiKeyScope::Upload) {
+        return err.error_response();
+    }
+
+    match album_service
+        .create_album(token.user_info().id, &req.name, req.visibility)
+        .await
+    {
+        Ok(id) => HttpResponse::Ok().json(CreateAlbumResponse { id }),
+        Err(err) => match err {
+            CreateAlbumError::InvalidName(_) => HttpResponse::BadRequest().json(GenericError {
+                message: err.to_string(),
+            }),
+            CreateAlbumError::Unexpected => {
+                HttpResponse::InternalServerError().json(GenericError::default())
+            }
+        },
+    }
+}
+
+/// Lists the albums of a user.
+#[api]
+#[get("/albums")]
+#[tag(TAG_NAME)]
+#[response(200, GetAlbumsResponse)]
+async fn get_albums(
+    token: SessionToken,
+    req: web::Query<GetAlbumsQuery>,
+    album_service: web::Data<Box<dyn AlbumService>>,
+) -> HttpResponse {
+    let viewer_id = token.user_info().id;
+
+    match album_service
+        .get_albums(viewer_id, req.user_id.unwrap_or(viewer_id))
+        .await
+    {
+        Ok(albums) => HttpResponse::Ok().json(GetAlbumsResponse {
+            albums: albums.into_iter().map(album_response).collect(),
+        }),
+        Err(err) => match err {
+            GetAlbumsError::Unexpected => {
+                HttpResponse::InternalServerError().json(GenericError::default())
+            }
+        },
+    }
+}
+
+/// An album with a page of its images.
+#[api]
+#[get("/albums/{album_id}")]
+#[tag(TAG_NAME)]
+#[response(200, GetAlbumResponse)]
+#[response(404, GenericError)]
+async fn get_album(
+    token: SessionToken,
+    web::Path((album_id,)): web::Path<(Uuid,)>,
+    req: web::Query<Pagination>,
+    album_service: web::Data<Box<dyn AlbumService>>,
+) -> HttpResponse {
+    match album_service
+        .get_album(
+            token.user_info().id,
+            album_id,
+            req.offset.map(|v| v as _),
+            req.limit.map(|v| v as _),
+        )
+        .await
+    {
+        Ok((album, images)) => HttpResponse::Ok().json(GetAlbumResponse {
+            album: album_response(album),
+            images: images.into_iter().filter_map(image_response).collect(),
+        }),
+        Err(err) => match err {
+            GetAlbumError::NotFound => HttpResponse::NotFound().json(GenericError {
+                message: err.to_string(),
+            }),
+            GetAlbumError::Unexpected => {
+                HttpResponse::InternalServerError().json(GenericError::default())
+            }
+        },
+    }
+}
+
+/// Renames the album or changes its visibility.
+#[api]
+#[put("/albums/{album_id}")]
+#[tag(TAG_NAME)]
+#[response(204)]
+#[response(400, GenericError)]
+#[response(403, GenericError)]
+#[response(404, GenericError)]
+async fn update_album(
+    token: SessionToken,
+    web::Path((album_id,)): web::Path<(Uuid,)>,
+    req: web::Json<UpdateAlbumRequest>,
+    album_service: web::Data<Box<dyn AlbumService>>,
+) -> HttpResponse {
+    if let Err(err) = token.require_scope(ApiKeyScope::Upload) {
+        return err.error_response();
+    }
+
+    match album_service
+        .update_album(
+            token.user_info().id,
+            album_id,
+            req.name.as_deref(),
+            req.visibility,
+        )
+        .await
+    {
+        Ok(_) => HttpResponse::NoContent().finish(),
+        Err(err) => match err {
+            UpdateAlbumError::NotFound => HttpResponse::NotFound().json(GenericError {
+                message: err.to_string(),
+            }),
+            UpdateAlbumError::NotAllowed => HttpResponse::Forbidden().json(GenericError {
+                message: err.to_string(),
+            }),
+            UpdateAlbumError::InvalidName(_) => HttpResponse::BadRequest().json(GenericError {
+                message: err.to_string(),
+            }),
+            UpdateAlbumError::Unexpected => {
+                HttpResponse::InternalServerError().json(GenericError::default())
+            }
+        },
+    }
+}
+
+/// Deletes the album, the images themselves are kept.
+#[api]
+#[delete("/albums/{album_id}")]
+#[tag(TAG_NAME)]
+#[response(204)]
+#[response(403, GenericError)]
+#[response(404, GenericError)]
+async fn delete_album(
+    token: SessionToken,
+    web::Path((album_id,)): web::Path<(Uuid,)>,
+    album_service: web::Data<Box<dyn AlbumService>>,
+) -> HttpResponse {
+    if let Err(err) = token.require_scope(ApiKeyScope::Upload) {
+        return err.error_response();
+    }
+
+    match album_service
+        .delete_album(token.user_info().id, album_id)
+        .await
+    {
+        Ok(_) => HttpResponse::NoContent().finish(),
+        Err(err) => match err {
+            DeleteAlbumError::NotFound => HttpResponse::NotFound().json(GenericError {
+                message: err.to_string(),
+            }),
+            DeleteAlbumError::NotAllowed => HttpResponse::Forbidden().json(GenericError {
+                message: err.to_string(),
+            }),
+            DeleteAlbumError::Unexpected => {
+                HttpResponse::InternalServerError().json(GenericError::default())
+            }
+        },
+    }
+}
+
+/// Adds an image to the end of the album, it can be the image of any user.
+#[api]
+#[post("/albums/{album_id}/images")]
+#[tag(TAG_NAME)]
+#[response(204)]
+#[response(403, GenericError)]
+#[response(404, GenericError)]
+async fn add_album_image(
+    token: SessionToken,
+    web::Path((album_id,)): web::Path<(Uuid,)>,
+    req: web::Json<AddAlbumImageRequest>,
+    album_service: web::Data<Box<dyn AlbumService>>,
+) -> HttpResponse {
+    if let Err(err) = token.require_scope(ApiKeyScope::Upload) {
+        return err.error_response();
+    }
+
+    match album_service
+        .add_album_image(token.user_info().id, album_id, req.image_id)
+        .await
+    {
+        Ok(_) => HttpResponse::NoContent().finish(),
+        Err(err) => match err {
+            AddAlbumImageError::AlbumNotFound | AddAlbumImageError::ImageNotFound => {
+                HttpResponse::NotFound().json(GenericError {
+                    message: err.to_string(),
+                })
+            }
+            AddAlbumImageError::NotAllowed => HttpResponse::Forbidden().json(GenericError {
+                message: err.to_string(),
+            }),
+            AddAlbumImageError::Unexpected => {
+                HttpResponse::InternalServerError().json(GenericError::default())
+            }
+        },
+    }
+}
+
+#[api]
+#[delete("/albums/{album_id}/images/{image_id}")]
+#[tag(TAG_NAME)]
+#[response(204)]
+#[response(403, GenericError)]
+#[response(404, GenericError)]
+async fn remove_album_image(
+    token: SessionToken,
+    web::Path((album_id, image_id)): web::Path<(Uuid, Uuid)>,
+    album_service: web::Data<Box<dyn AlbumService>>,
+) -> HttpResponse {
+    if let Err(err) = token.require_scope(ApiKeyScope::Upload) {
+        return err.error_response();
+    }
+
+    match album_service
+        .remove_album_image(token.user_info().id, album_id, image_id)
+        .await
+    {
+        Ok(_) => HttpResponse::NoContent().finish(),
+        Err(err) => match err {
+            RemoveAlbumImageError::AlbumNotFound | RemoveAlbumImageError::ImageNotFound => {
+                HttpResponse::NotFound().json(GenericError {
+                    message: err.to_string(),
+                })
+            }
+            RemoveAlbumImageError::NotAllowed => HttpResponse::Forbidden().json(GenericError {
+                message: err.to_string(),
+            }),
+            RemoveAlbumImageError::Unexpected => {
+                HttpResponse::InternalServerError().json(GenericError::default())
+            }
+        },
+    }
+}
+
+/// Changes the order of the images in the album.
+#[api]
+#[put("/albums/{album_id}/images/order")]
+#[tag(TAG_NAME)]
+#[response(204)]
+#[response(400, GenericError)]
+#[response(403, GenericError)]
+#[response(404, GenericError)]
+async fn reorder_album_images(
+    token: SessionToken,
+    web::Path((album_id,)): web::Path<(Uuid,)>,
+    req: web::Json<ReorderAlbumImagesRequest>,
+    album_service: web::Data<Box<dyn AlbumService>>,
+) -> HttpResponse {
+    if let Err(err) = token.require_scope(ApiKeyScope::Upload) {
+        return err.error_response();
+    }
+
+    match album_service
+        .reorder_album_images(token.user_info().id, album_id, &req.image_ids)
+        .await
+    {
+        Ok(_) => HttpResponse::NoContent().finish(),
+        Err(err) => match err {
+            ReorderAlbumImagesError::AlbumNotFound => HttpResponse::NotFound().json(GenericError {
+                message: err.to_string(),
+            }),
+            ReorderAlbumImagesError::NotAllowed => HttpResponse::Forbidden().json(GenericError {
+                message: err.to_string(),
+            }),
+            ReorderAlbumImagesError::ImagesMismatch => {
+                HttpResponse::BadRequest().json(GenericError {
+                    message: err.to_string(),
+                })
+            }
+            ReorderAlbumImagesError::Unexpected => {
+                HttpResponse::InternalServerError().json(GenericError::default())
+            }
+        },
+    }
+}
+
+pub fn configure_routes(_config: &Config) -> impl FnOnce(&mut ServiceConfig) {
+    move |app: &mut ServiceConfig| {
+        app.service(create_album);
+        app.service(get_albums);
+        app.service(get_album);
+        app.service(update_album);
+        app.service(delete_album);
+        app.service(add_album_image);
+        app.service(remove_album_image);
+        app.service(reorder_album_images);
+    }
+}
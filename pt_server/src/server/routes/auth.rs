@@ -1,22 +1,36 @@
 use crate::{
     config::Config,
     model::auth::{
-        InvalidRegisterRequest, LoginError, LoginRequest, LoginResponse, RegisterError,
-        RegisterRequest,
+        ApiKey, ApiKeyError, ConfirmTotpRequest, ConfirmTotpResponse, CreateApiKeyRequest,
+        CreateApiKeyResponse, DisableTotpRequest, EnrollTotpResponse, GetApiKeysResponse,
+        InvalidRegisterRequest, LoginError, LoginRequest, LoginResponse, MfaLoginRequest,
+        OidcCallbackQuery, OidcError, RegisterError, RegisterRequest, TotpError,
     },
     model::error::GenericError,
-    services::AuthService,
+    server::extractors::{RequestId, SessionToken},
+    services::{
+        auth::{LoginOutcome, OIDC_LOGIN_TIMEOUT_MINUTES},
+        AuthService,
+    },
 };
 use actix_web::{
+    cookie::{Cookie, SameSite},
+    delete, get,
+    http::header,
     post,
     web::{self, ServiceConfig},
-    HttpResponse,
+    HttpMessage, HttpRequest, HttpResponse,
 };
 use aide::openapi::v3::macros::api;
 use aide::openapi::v3::macros::api::define;
+use uuid::Uuid;
 
 const TAG_NAME: &str = "auth";
 
+/// Ties a single sign-on login to the browser that started it.
+const OIDC_STATE_COOKIE: &str = "pt_oidc_state";
+const OIDC_COOKIE_PATH: &str = "/auth/oidc";
+
 define::tag! {
     name(TAG_NAME),
     description("Authentication and authorization routes"),
@@ -24,6 +38,9 @@ define::tag! {
 }
 
 /// Login for existing users.
+///
+/// If the user has two-factor authentication enabled,
+/// the returned token must be exchanged at `/auth/login/mfa`.
 #[api]
 #[post("/auth/login")]
 #[tag(TAG_NAME)]
@@ -31,18 +48,174 @@ define::tag! {
 #[response(status(404), desc("the user was not found"))]
 #[response(status(403), desc("incorrect password"))]
 async fn login(
+    request_id: RequestId,
     req: web::Json<LoginRequest>,
     auth_service: web::Data<Box<dyn AuthService>>,
 ) -> HttpResponse {
-    match auth_service.login(&req.email, &req.password).await {
-        Ok(token) => HttpResponse::Ok().json(LoginResponse { token }),
-        Err(err) => match err {
-            LoginError::UserNotFound => HttpResponse::NotFound().finish(),
-            LoginError::IncorrectPassword => HttpResponse::Forbidden().finish(),
-            LoginError::Unexpected => {
-                HttpResponse::InternalServerError().json(GenericError::default())
-            }
-        },
+    match auth_service
+        .login(&request_id.audit_context(None), &req.email, &req.password)
+        .await
+    {
+        Ok(outcome) => HttpResponse::Ok().json(match outcome {
+            LoginOutcome::Authenticated(token) => LoginResponse {
+                token,
+                mfa_required: false,
+            },
+            LoginOutcome::MfaRequired(token) => LoginResponse {
+                token,
+                mfa_required: true,
+            },
+        }),
+        Err(err) => login_error_response(err),
+    }
+}
+
+/// Second step of the login for users with two-factor authentication.
+#[api]
+#[post("/auth/login/mfa")]
+#[tag(TAG_NAME)]
+#[response(200, LoginResponse)]
+#[response(status(401), desc("the two-factor token is invalid or expired"))]
+#[response(status(403), desc("incorrect two-factor code"))]
+#[response(
+    status(429),
+    type(GenericError),
+    description("too many incorrect two-factor codes")
+)]
+async fn login_mfa(
+    request_id: RequestId,
+    req: web::Json<MfaLoginRequest>,
+    auth_service: web::Data<Box<dyn AuthService>>,
+) -> HttpResponse {
+    match auth_service
+        .login_mfa(&request_id.audit_context(None), &req.token, &req.code)
+        .await
+    {
+        Ok(token) => HttpResponse::Ok().json(LoginResponse {
+            token,
+            mfa_required: false,
+        }),
+        Err(err) => login_error_response(err),
+    }
+}
+
+/// Starts a single sign-on login by redirecting to the identity provider.
+///
+/// The login can only be finished in the same browser.
+#[api]
+#[get("/auth/oidc/login")]
+#[tag(TAG_NAME)]
+#[response(status(302), desc("redirect to the identity provider"))]
+#[response(status(404), desc("single sign-on is not configured"))]
+#[response(
+    status(503),
+    type(GenericError),
+    description("too many logins are in progress")
+)]
+async fn oidc_login(auth_service: web::Data<Box<dyn AuthService>>) -> HttpResponse {
+    match auth_service.oidc_authorization_url().await {
+        Ok(authorization) => HttpResponse::Found()
+            .header(header::LOCATION, authorization.url.as_str())
+            .cookie(
+                Cookie::build(OIDC_STATE_COOKIE, authorization.state)
+                    .path(OIDC_COOKIE_PATH)
+                    .max_age(time::Duration::minutes(OIDC_LOGIN_TIMEOUT_MINUTES))
+                    .http_only(true)
+                    .secure(true)
+                    .same_site(SameSite::Lax)
+                    .finish(),
+            )
+            .finish(),
+        Err(err) => oidc_error_response(err),
+    }
+}
+
+/// The identity provider redirects here after a single sign-on login.
+#[api]
+#[get("/auth/oidc/callback")]
+#[tag(TAG_NAME)]
+#[response(200, LoginResponse)]
+#[response(400, GenericError)]
+#[response(
+    status(502),
+    type(GenericError),
+    description("the identity provider returned an error")
+)]
+async fn oidc_callback(
+    request_id: RequestId,
+    http_req: HttpRequest,
+    req: web::Query<OidcCallbackQuery>,
+    auth_service: web::Data<Box<dyn AuthService>>,
+) -> HttpResponse {
+    // Otherwise anyone could finish their own login in the browser of someone else.
+    let same_browser = http_req
+        .cookie(OIDC_STATE_COOKIE)
+        .map_or(false, |c| c.value() == req.state);
+
+    if !same_browser {
+        return oidc_error_response(OidcError::InvalidState);
+    }
+
+    // The state can only be used once anyway.
+    let removed_cookie = Cookie::build(OIDC_STATE_COOKIE, "")
+        .path(OIDC_COOKIE_PATH)
+        .max_age(time::Duration::zero())
+        .finish();
+
+    match auth_service
+        .oidc_callback(&request_id.audit_context(None), &req.code, &req.state)
+        .await
+    {
+        Ok(outcome) => HttpResponse::Ok()
+            .cookie(removed_cookie)
+            .json(match outcome {
+                LoginOutcome::Authenticated(token) => LoginResponse {
+                    token,
+                    mfa_required: false,
+                },
+                LoginOutcome::MfaRequired(token) => LoginResponse {
+                    token,
+                    mfa_required: true,
+                },
+            }),
+        Err(err) => oidc_error_response(err),
+    }
+}
+
+fn oidc_error_response(err: OidcError) -> HttpResponse {
+    match err {
+        OidcError::NotConfigured => HttpResponse::NotFound().json(GenericError {
+            message: err.to_string(),
+        }),
+        OidcError::InvalidState | OidcError::MissingEmail | OidcError::UnverifiedEmail => {
+            HttpResponse::BadRequest().json(GenericError {
+                message: err.to_string(),
+            })
+        }
+        OidcError::Provider => HttpResponse::BadGateway().json(GenericError {
+            message: err.to_string(),
+        }),
+        OidcError::TooManyLogins => HttpResponse::ServiceUnavailable().json(GenericError {
+            message: err.to_string(),
+        }),
+        OidcError::Unexpected => HttpResponse::InternalServerError().json(GenericError::default()),
+    }
+}
+
+fn login_error_response(err: LoginError) -> HttpResponse {
+    match err {
+        LoginError::UserNotFound => HttpResponse::NotFound().finish(),
+        LoginError::IncorrectPassword => HttpResponse::Forbidden().finish(),
+        LoginError::InvalidMfaToken => HttpResponse::Unauthorized().json(GenericError {
+            message: err.to_string(),
+        }),
+        LoginError::IncorrectMfaCode => HttpResponse::Forbidden().json(GenericError {
+            message: err.to_string(),
+        }),
+        LoginError::MfaLocked => HttpResponse::TooManyRequests().json(GenericError {
+            message: err.to_string(),
+        }),
+        LoginError::Unexpected => HttpResponse::InternalServerError().json(GenericError::default()),
     }
 }
 
@@ -73,9 +246,266 @@ async fn register(
     }
 }
 
+/// Starts the two-factor enrollment of the current user.
+///
+/// Two-factor authentication is only enabled after
+/// it was confirmed with a valid code.
+#[api]
+#[post("/auth/mfa/totp")]
+#[tag(TAG_NAME)]
+#[response(200, EnrollTotpResponse)]
+#[response(
+    status(400),
+    type(GenericError),
+    description("two-factor authentication is already enabled")
+)]
+#[response(
+    status(403),
+    type(GenericError),
+    description("two-factor authentication cannot be managed with API keys")
+)]
+async fn enroll_totp(
+    token: SessionToken,
+    auth_service: web::Data<Box<dyn AuthService>>,
+) -> HttpResponse {
+    if token.is_api_key() {
+        return totp_error_response(TotpError::NotAllowed);
+    }
+
+    match auth_service.enroll_totp(token.user_info().id).await {
+        Ok(res) => HttpResponse::Ok().json(res),
+        Err(err) => totp_error_response(err),
+    }
+}
+
+/// Enables two-factor authentication for the current user.
+///
+/// The returned recovery codes are not shown again.
+#[api]
+#[post("/auth/mfa/totp/confirm")]
+#[tag(TAG_NAME)]
+#[response(200, ConfirmTotpResponse)]
+#[response(
+    status(400),
+    type(GenericError),
+    description("the enrollment was not started or is already confirmed")
+)]
+#[response(
+    status(403),
+    type(GenericError),
+    description("incorrect two-factor code, or the request was made with an API key")
+)]
+async fn confirm_totp(
+    token: SessionToken,
+    req: web::Json<ConfirmTotpRequest>,
+    auth_service: web::Data<Box<dyn AuthService>>,
+) -> HttpResponse {
+    if token.is_api_key() {
+        return totp_error_response(TotpError::NotAllowed);
+    }
+
+    match auth_service
+        .confirm_totp(token.user_info().id, &req.code)
+        .await
+    {
+        Ok(recovery_codes) => HttpResponse::Ok().json(ConfirmTotpResponse { recovery_codes }),
+        Err(err) => totp_error_response(err),
+    }
+}
+
+/// Disables two-factor authentication for the current user.
+#[api]
+#[post("/auth/mfa/totp/disable")]
+#[tag(TAG_NAME)]
+#[response(204)]
+#[response(
+    status(400),
+    type(GenericError),
+    description("two-factor authentication is not enabled")
+)]
+#[response(
+    status(403),
+    type(GenericError),
+    description("incorrect two-factor code, or the request was made with an API key")
+)]
+#[response(
+    status(429),
+    type(GenericError),
+    description("too many incorrect two-factor codes")
+)]
+async fn disable_totp(
+    token: SessionToken,
+    req: web::Json<DisableTotpRequest>,
+    auth_service: web::Data<Box<dyn AuthService>>,
+) -> HttpResponse {
+    if token.is_api_key() {
+        return totp_error_response(TotpError::NotAllowed);
+    }
+
+    match auth_service
+        .disable_totp(token.user_info().id, &req.code)
+        .await
+    {
+        Ok(_) => HttpResponse::NoContent().finish(),
+        Err(err) => totp_error_response(err),
+    }
+}
+
+fn totp_error_response(err: TotpError) -> HttpResponse {
+    match err {
+        TotpError::UserNotFound => HttpResponse::NotFound().json(GenericError {
+            message: err.to_string(),
+        }),
+        TotpError::AlreadyEnabled | TotpError::NotEnabled | TotpError::NotEnrolled => {
+            HttpResponse::BadRequest().json(GenericError {
+                message: err.to_string(),
+            })
+        }
+        TotpError::IncorrectCode | TotpError::NotAllowed => {
+            HttpResponse::Forbidden().json(GenericError {
+                message: err.to_string(),
+            })
+        }
+        TotpError::Locked => HttpResponse::TooManyRequests().json(GenericError {
+            message: err.to_string(),
+        }),
+        TotpError::Unexpected => HttpResponse::InternalServerError().json(GenericError::default()),
+    }
+}
+
+/// Creates a new API key for the current user.
+///
+/// The key can be used with the `Authorization: ApiKey <key>` header,
+/// and it is only shown once.
+#[api]
+#[post("/auth/api-keys")]
+#[tag(TAG_NAME)]
+#[response(200, CreateApiKeyResponse)]
+#[response(400, GenericError)]
+#[response(
+    status(403),
+    type(GenericError),
+    description("API keys cannot be managed with API keys")
+)]
+async fn create_api_key(
+    token: SessionToken,
+    req: web::Json<CreateApiKeyRequest>,
+    auth_service: web::Data<Box<dyn AuthService>>,
+) -> HttpResponse {
+    if token.is_api_key() {
+        return api_key_error_response(ApiKeyError::NotAllowed);
+    }
+
+    match auth_service
+        .create_api_key(token.user_info().id, &req.name, &req.scopes)
+        .await
+    {
+        Ok((id, key)) => HttpResponse::Ok().json(CreateApiKeyResponse { id, key }),
+        Err(err) => api_key_error_response(err),
+    }
+}
+
+/// Lists the API keys of the current user that were not revoked.
+#[api]
+#[get("/auth/api-keys")]
+#[tag(TAG_NAME)]
+#[response(200, GetApiKeysResponse)]
+async fn get_api_keys(
+    token: SessionToken,
+    auth_service: web::Data<Box<dyn AuthService>>,
+) -> HttpResponse {
+    if token.is_api_key() {
+        return api_key_error_response(ApiKeyError::NotAllowed);
+    }
+
+    match auth_service.get_api_keys(token.user_info().id).await {
+        Ok(keys) => HttpResponse::Ok().json(GetApiKeysResponse {
+            api_keys: keys
+                .into_iter()
+                .map(|k| ApiKey {
+                    id: k.id,
+                    name: k.key_name,
+                    prefix: k.key_prefix,
+                    scopes: k.scopes.iter().filter_map(|s| s.parse().ok()).collect(),
+                    created: k.created,
+                    last_used: k.last_used,
+                })
+                .collect(),
+        }),
+        Err(err) => api_key_error_response(err),
+    }
+}
+
+/// Revokes an API key of the current user.
+#[api]
+#[delete("/auth/api-keys/{api_key_id}")]
+#[tag(TAG_NAME)]
+#[response(204)]
+#[response(404)]
+async fn revoke_api_key(
+    token: SessionToken,
+    web::Path((api_key_id,)): web::Path<(Uuid,)>,
+    auth_service: web::Data<Box<dyn AuthService>>,
+) -> HttpResponse {
+    if token.is_api_key() {
+        return api_key_error_response(ApiKeyError::NotAllowed);
+    }
+
+    match auth_service
+        .revoke_api_key(token.user_info().id, api_key_id)
+        .await
+    {
+        Ok(_) => HttpResponse::NoContent().finish(),
+        Err(err) => api_key_error_response(err),
+    }
+}
+
+fn api_key_error_response(err: ApiKeyError) -> HttpResponse {
+    match err {
+        ApiKeyError::NotFound => HttpResponse::NotFound().json(GenericError {
+            message: err.to_string(),
+        }),
+        ApiKeyError::InvalidName | ApiKeyError::NoScopes => {
+            HttpResponse::BadRequest().json(GenericError {
+                message: err.to_string(),
+            })
+        }
+        ApiKeyError::Invalid => HttpResponse::Unauthorized().json(GenericError {
+            message: err.to_string(),
+        }),
+        ApiKeyError::NotAllowed => HttpResponse::Forbidden().json(GenericError {
+            message: err.to_string(),
+        }),
+        ApiKeyError::Unexpected => {
+            HttpResponse::InternalServerError().json(GenericError::default())
+        }
+    }
+}
+
+/// The public keys for verifying the tokens issued by the server.
+#[api]
+#[get("/.well-known/jwks.json")]
+#[tag(TAG_NAME)]
+#[response(status(200), content_type("application/jwk-set+json"))]
+async fn jwks(auth_service: web::Data<Box<dyn AuthService>>) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("application/jwk-set+json")
+        .json(auth_service.jwks())
+}
+
 pub fn configure_routes(_config: &Config) -> impl FnOnce(&mut ServiceConfig) {
     move |app: &mut ServiceConfig| {
         app.service(register);
         app.service(login);
+        app.service(login_mfa);
+        app.service(oidc_login);
+        app.service(oidc_callback);
+        app.service(enroll_totp);
+        app.service(confirm_totp);
+        app.service(disable_totp);
+        app.service(create_api_key);
+        app.service(get_api_keys);
+        app.service(revoke_api_key);
+        app.service(jwks);
     }
 }
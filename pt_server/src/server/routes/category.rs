@@ -1,5 +1,9 @@
 use crate::{
-    config::Config, model::error::GenericError, model::image::*, server::extractors::SessionToken,
+    config::Config,
+    db::category::CategoryExt,
+    model::error::GenericError,
+    model::image::*,
+    server::extractors::{ManageCategories, OptionalSessionToken, RequestId, RequirePermission},
     services::ImageService,
 };
 use actix_web::{
@@ -9,6 +13,7 @@ use actix_web::{
 };
 use aide::openapi::v3::macros::api;
 use aide::openapi::v3::macros::api::define;
+use std::collections::HashMap;
 use uuid::Uuid;
 
 const TAG_NAME: &str = "categories";
@@ -19,25 +24,99 @@ define::tag! {
     display_name("Categories")
 }
 
+fn category_response(c: CategoryExt) -> Category {
+    Category {
+        id: c.category.id,
+        name: c.category.category_name,
+        slug: c.category.slug,
+        description: c.category.description,
+        parent_id: c.category.parent_id,
+        cover_image_id: c.category.cover_image_id,
+        image_count: c.image_count as _,
+        children: Vec::new(),
+    }
+}
+
+/// Nests the categories under their parents, returns the root categories.
+fn category_tree(categories: Vec<Category>) -> Vec<Category> {
+    let mut children: HashMap<Uuid, Vec<Category>> = HashMap::new();
+    let mut roots = Vec::new();
+
+    for category in categories {
+        match category.parent_id {
+            Some(parent_id) => children.entry(parent_id).or_default().push(category),
+            None => roots.push(category),
+        }
+    }
+
+    fn attach(category: &mut Category, children: &mut HashMap<Uuid, Vec<Category>>) {
+        if let Some(mut c) = children.remove(&category.id) {
+            for child in &mut c {
+                attach(child, children);
+            }
+            category.children = c;
+        }
+    }
+
+    for root in &mut roots {
+        attach(root, &mut children);
+    }
+
+    roots
+}
+
+#[test]
+fn test_category_tree() {
+    let category = |name: &str, parent_id: Option<Uuid>| Category {
+        id: Uuid::new_v4(),
+        name: name.into(),
+        slug: name.to_lowercase(),
+        description: None,
+        parent_id,
+        cover_image_id: None,
+        image_count: 0,
+        children: Vec::new(),
+    };
+
+    let nature = category("Nature", None);
+    let birds = category("Birds", Some(nature.id));
+    let owls = category("Owls", Some(birds.id));
+    let people = category("People", None);
+
+    let tree = category_tree(vec![owls, people, birds, nature]);
+
+    assert_eq!(tree.len(), 2);
+    let nature = tree.iter().find(|c| c.name == "Nature").unwrap();
+    assert_eq!(nature.children.len(), 1);
+    assert_eq!(nature.children[0].name, "Birds");
+    assert_eq!(nature.children[0].children[0].name, "Owls");
+}
+
+/// All categories, or only the root categories with their subcategories nested in them.
+///
+/// Available without authentication if public read access is enabled.
 #[api]
 #[get("/categories")]
 #[tag(TAG_NAME)]
 #[response(200, GetCategoriesResponse)]
+#[response(401, GenericError)]
 async fn get_categories(
-    _token: SessionToken,
+    _token: OptionalSessionToken,
+    req: web::Query<GetCategoriesQuery>,
     image_service: web::Data<Box<dyn ImageService>>,
 ) -> HttpResponse {
-    match image_service.get_categories().await {
-        Ok(categories) => HttpResponse::Ok().json(GetCategoriesResponse {
-            categories: categories
-                .into_iter()
-                .map(|c| Category {
-                    id: c.category.id,
-                    name: c.category.category_name,
-                    image_count: c.image_count as _,
-                })
-                .collect(),
-        }),
+    match image_service.get_categories(req.include_descendants).await {
+        Ok(categories) => {
+            let categories = categories.into_iter().map(category_response).collect();
+
+            HttpResponse::Ok().json(GetCategoriesResponse {
+                categories: if req.tree {
+                    category_tree(categories)
+                } else {
+                    categories
+                },
+            })
+        }
         Err(err) => match err {
             GetCategoriesError::Unexpected => {
                 HttpResponse::InternalServerError().json(GenericError::default())
@@ -51,17 +130,21 @@ async fn get_categories(
 #[tag(TAG_NAME)]
 #[response(200, CreateCategoryResponse)]
 async fn create_category(
-    token: SessionToken,
+    permission: RequirePermission<ManageCategories>,
+    request_id: RequestId,
     req: web::Json<CreateCategoryRequest>,
     image_service: web::Data<Box<dyn ImageService>>,
 ) -> HttpResponse {
-    if !token.user_info().admin {
-        return HttpResponse::Forbidden().json(GenericError {
-            message: CreateCategoryError::NotAllowed.to_string(),
-        });
-    }
-
-    match image_service.create_category(&req.name).await {
+    match image_service
+        .create_category(
+            &request_id.audit_context(Some(permission.token().user_info().id)),
+            &req.name,
+            req.slug.as_deref(),
+            req.parent_id,
+            req.description.as_deref(),
+        )
+        .await
+    {
         Ok(id) => HttpResponse::Ok().json(CreateCategoryResponse { id }),
         Err(err) => match err {
             CreateCategoryError::Unexpected => {
@@ -70,13 +153,14 @@ async fn create_category(
             CreateCategoryError::AlreadyExists => HttpResponse::BadRequest().json(GenericError {
                 message: err.to_string(),
             }),
-
-            CreateCategoryError::NotAllowed => HttpResponse::Forbidden().json(GenericError {
-                message: err.to_string(),
-            }),
-            CreateCategoryError::InvalidName(_) => HttpResponse::BadRequest().json(GenericError {
-                message: err.to_string(),
-            }),
+            CreateCategoryError::InvalidName(_)
+            | CreateCategoryError::InvalidSlug(_)
+            | CreateCategoryError::SlugAlreadyExists
+            | CreateCategoryError::ParentNotFound => {
+                HttpResponse::BadRequest().json(GenericError {
+                    message: err.to_string(),
+                })
+            }
         },
     }
 }
@@ -87,51 +171,210 @@ async fn create_category(
 #[response(204)]
 #[response(404)]
 async fn rename_category(
-    token: SessionToken,
+    permission: RequirePermission<ManageCategories>,
+    request_id: RequestId,
     web::Path((category_id,)): web::Path<(Uuid,)>,
     req: web::Json<RenameCategoryRequest>,
     image_service: web::Data<Box<dyn ImageService>>,
 ) -> HttpResponse {
-    if !token.user_info().admin {
-        return HttpResponse::Forbidden().json(GenericError {
-            message: RenameCategoryError::NotAllowed.to_string(),
-        });
+    match image_service
+        .rename_category(
+            &request_id.audit_context(Some(permission.token().user_info().id)),
+            category_id,
+            &req.name,
+        )
+        .await
+    {
+        Ok(_) => HttpResponse::NoContent().finish(),
+        Err(err) => match err {
+            RenameCategoryError::Unexpected => {
+                HttpResponse::InternalServerError().json(GenericError::default())
+            }
+            RenameCategoryError::CategoryNotFound => HttpResponse::NotFound().json(GenericError {
+                message: err.to_string(),
+            }),
+            RenameCategoryError::InvalidName(_) | RenameCategoryError::AlreadyExists => {
+                HttpResponse::BadRequest().json(GenericError {
+                    message: err.to_string(),
+                })
+            }
+        },
     }
+}
 
-    match image_service.rename_category(category_id, &req.name).await {
+/// Moves the category under another one, or makes it a root category.
+#[api]
+#[put("/categories/{category_id}/parent")]
+#[tag(TAG_NAME)]
+#[response(204)]
+#[response(400, GenericError)]
+#[response(404, GenericError)]
+async fn move_category(
+    _permission: RequirePermission<ManageCategories>,
+    web::Path((category_id,)): web::Path<(Uuid,)>,
+    req: web::Json<MoveCategoryRequest>,
+    image_service: web::Data<Box<dyn ImageService>>,
+) -> HttpResponse {
+    match image_service
+        .move_category(category_id, req.parent_id)
+        .await
+    {
         Ok(_) => HttpResponse::NoContent().finish(),
         Err(err) => match err {
-            RenameCategoryError::Unexpected => {
+            MoveCategoryError::CategoryNotFound => HttpResponse::NotFound().json(GenericError {
+                message: err.to_string(),
+            }),
+            MoveCategoryError::ParentNotFound | MoveCategoryError::Cycle => {
+                HttpResponse::BadRequest().json(GenericError {
+                    message: err.to_string(),
+                })
+            }
+            MoveCategoryError::Unexpected => {
+                HttpResponse::InternalServerError().json(GenericError::default())
+            }
+        },
+    }
+}
+
+#[api]
+#[put("/categories/{category_id}/details")]
+#[tag(TAG_NAME)]
+#[response(204)]
+#[response(400, GenericError)]
+#[response(404, GenericError)]
+async fn update_category_details(
+    _permission: RequirePermission<ManageCategories>,
+    web::Path((category_id,)): web::Path<(Uuid,)>,
+    req: web::Json<UpdateCategoryDetailsRequest>,
+    image_service: web::Data<Box<dyn ImageService>>,
+) -> HttpResponse {
+    match image_service
+        .update_category_details(category_id, req.description.as_deref(), req.cover_image_id)
+        .await
+    {
+        Ok(_) => HttpResponse::NoContent().finish(),
+        Err(err) => match err {
+            UpdateCategoryDetailsError::CategoryNotFound => {
+                HttpResponse::NotFound().json(GenericError {
+                    message: err.to_string(),
+                })
+            }
+            UpdateCategoryDetailsError::CoverNotInCategory => {
+                HttpResponse::BadRequest().json(GenericError {
+                    message: err.to_string(),
+                })
+            }
+            UpdateCategoryDetailsError::Unexpected => {
                 HttpResponse::InternalServerError().json(GenericError::default())
             }
-            _ => unreachable!(),
         },
     }
 }
 
+/// Categories with subcategories can only be deleted if `children` is given.
 #[api]
 #[delete("/categories/{category_id}")]
 #[tag(TAG_NAME)]
 #[response(204)]
+#[response(400, GenericError)]
 #[response(404)]
 async fn delete_category(
-    token: SessionToken,
+    permission: RequirePermission<ManageCategories>,
+    request_id: RequestId,
     web::Path((category_id,)): web::Path<(Uuid,)>,
+    req: web::Query<DeleteCategoryQuery>,
     image_service: web::Data<Box<dyn ImageService>>,
 ) -> HttpResponse {
-    if !token.user_info().admin {
-        return HttpResponse::Forbidden().json(GenericError {
-            message: DeleteCategoryError::NotAllowed.to_string(),
-        });
-    }
-
-    match image_service.delete_category(category_id).await {
+    match image_service
+        .delete_category(
+            &request_id.audit_context(Some(permission.token().user_info().id)),
+            category_id,
+            req.children,
+        )
+        .await
+    {
         Ok(_) => HttpResponse::NoContent().finish(),
         Err(err) => match err {
             DeleteCategoryError::Unexpected => {
                 HttpResponse::InternalServerError().json(GenericError::default())
             }
-            _ => unreachable!(),
+            DeleteCategoryError::CategoryNotFound => HttpResponse::NotFound().json(GenericError {
+                message: err.to_string(),
+            }),
+            DeleteCategoryError::HasChildren => HttpResponse::BadRequest().json(GenericError {
+                message: err.to_string(),
+            }),
+        },
+    }
+}
+
+/// Merges the category into the target category.
+///
+/// The images and subcategories are moved to the target, then the category is deleted.
+#[api]
+#[post("/categories/{category_id}/merge-into/{target_id}")]
+#[tag(TAG_NAME)]
+#[response(200, MergeCategoriesResponse)]
+#[response(400, GenericError)]
+#[response(404, GenericError)]
+async fn merge_category(
+    _permission: RequirePermission<ManageCategories>,
+    web::Path((category_id, target_id)): web::Path<(Uuid, Uuid)>,
+    image_service: web::Data<Box<dyn ImageService>>,
+) -> HttpResponse {
+    match image_service.merge_category(category_id, target_id).await {
+        Ok(merged) => HttpResponse::Ok().json(MergeCategoriesResponse {
+            moved_images: merged.moved,
+            duplicate_images: merged.duplicates,
+        }),
+        Err(err) => match err {
+            MergeCategoriesError::CategoryNotFound | MergeCategoriesError::TargetNotFound => {
+                HttpResponse::NotFound().json(GenericError {
+                    message: err.to_string(),
+                })
+            }
+            MergeCategoriesError::InvalidTarget => HttpResponse::BadRequest().json(GenericError {
+                message: err.to_string(),
+            }),
+            MergeCategoriesError::Unexpected => {
+                HttpResponse::InternalServerError().json(GenericError::default())
+            }
+        },
+    }
+}
+
+/// Adds or removes the category on the given images.
+///
+/// With `dryRun` nothing is changed, only the counts are reported.
+#[api]
+#[post("/categories/{category_id}/images/bulk")]
+#[tag(TAG_NAME)]
+#[response(200, BulkCategorizeResponse)]
+#[response(400, GenericError)]
+#[response(404, GenericError)]
+async fn bulk_categorize(
+    _permission: RequirePermission<ManageCategories>,
+    web::Path((category_id,)): web::Path<(Uuid,)>,
+    req: web::Json<BulkCategorizeRequest>,
+    image_service: web::Data<Box<dyn ImageService>>,
+) -> HttpResponse {
+    match image_service
+        .bulk_categorize(category_id, req.action, &req.image_ids, req.dry_run)
+        .await
+    {
+        Ok(res) => HttpResponse::Ok().json(res),
+        Err(err) => match err {
+            BulkCategorizeError::CategoryNotFound => HttpResponse::NotFound().json(GenericError {
+                message: err.to_string(),
+            }),
+            BulkCategorizeError::TooManyImages(_) => {
+                HttpResponse::BadRequest().json(GenericError {
+                    message: err.to_string(),
+                })
+            }
+            BulkCategorizeError::Unexpected => {
+                HttpResponse::InternalServerError().json(GenericError::default())
+            }
         },
     }
 }
@@ -141,6 +384,10 @@ pub fn configure_routes(_config: &Config) -> impl FnOnce(&mut ServiceConfig) {
         app.service(get_categories);
         app.service(create_category);
         app.service(rename_category);
+        app.service(move_category);
+        app.service(update_category_details);
         app.service(delete_category);
+        app.service(merge_category);
+        app.service(bulk_categorize);
     }
 }
@@ -0,0 +1,189 @@
+use crate::{
+    config::Config, model::auth::ApiKeyScope, model::comment::*, model::error::GenericError,
+    model::Pagination, server::extractors::SessionToken, services::CommentService,
+};
+use actix_web::{
+    delete, get, post, put,
+    web::{self, ServiceConfig},
+    HttpResponse, ResponseError,
+};
+use aide::openapi::v3::macros::api;
+use aide::openapi::v3::macros::api::define;
+use uuid::Uuid;
+
+const TAG_NAME: &str = "comments";
+
+define::tag! {
+    name(TAG_NAME),
+    description("Threaded comments on images"),
+    display_name("Comments")
+}
+
+#[api]
+#[post("/images/{image_id}/comments")]
+#[tag(TAG_NAME)]
+#[response(200, CreateCommentResponse)]
+#[response(400, GenericError)]
+#[response(404, GenericError)]
+async fn create_comment(
+    token: SessionToken,
+    web::Path((image_id,)): web::Path<(Uuid,)>,
+    req: web::Json<CreateCommentRequest>,
+    comment_service: web::Data<Box<dyn CommentService>>,
+) -> HttpResponse {
+    if let Err(err) = token.require_scope(ApiKeyScope::Comment) {
+        return err.error_response();
+    }
+
+    match comment_service
+        .create_comment(token.user_info().id, image_id, req.parent_id, &req.body)
+        .await
+    {
+        Ok(id) => HttpResponse::Ok().json(CreateCommentResponse { id }),
+        Err(err) => match err {
+            CreateCommentError::ImageNotFound => HttpResponse::NotFound().json(GenericError {
+                message: err.to_string(),
+            }),
+            CreateCommentError::ParentNotFound | CreateCommentError::InvalidBody(_) => {
+                HttpResponse::BadRequest().json(GenericError {
+                    message: err.to_string(),
+                })
+            }
+            CreateCommentError::Unexpected => {
+                HttpResponse::InternalServerError().json(GenericError::default())
+            }
+        },
+    }
+}
+
+/// Comments of the image, oldest first.
+#[api]
+#[get("/images/{image_id}/comments")]
+#[tag(TAG_NAME)]
+#[response(200, GetCommentsResponse)]
+#[response(404, GenericError)]
+async fn get_comments(
+    token: SessionToken,
+    web::Path((image_id,)): web::Path<(Uuid,)>,
+    req: web::Query<Pagination>,
+    comment_service: web::Data<Box<dyn CommentService>>,
+) -> HttpResponse {
+    match comment_service
+        .get_comments(
+            token.user_info().id,
+            image_id,
+            req.offset.map(|v| v as _),
+            req.limit.map(|v| v as _),
+        )
+        .await
+    {
+        Ok(comments) => HttpResponse::Ok().json(GetCommentsResponse {
+            comments: comments
+                .into_iter()
+                .map(|c| Comment {
+                    id: c.id,
+                    image_id: c.image_id,
+                    parent_id: c.parent_id,
+                    author: c.app_user_id,
+                    body: c.body,
+                    created: c.created,
+                    edited: c.edited,
+                })
+                .collect(),
+        }),
+        Err(err) => match err {
+            GetCommentsError::ImageNotFound => HttpResponse::NotFound().json(GenericError {
+                message: err.to_string(),
+            }),
+            GetCommentsError::Unexpected => {
+                HttpResponse::InternalServerError().json(GenericError::default())
+            }
+        },
+    }
+}
+
+/// Edits an own comment, only possible for a while after posting it.
+#[api]
+#[put("/comments/{comment_id}")]
+#[tag(TAG_NAME)]
+#[response(204)]
+#[response(400, GenericError)]
+#[response(403, GenericError)]
+#[response(404, GenericError)]
+async fn edit_comment(
+    token: SessionToken,
+    web::Path((comment_id,)): web::Path<(Uuid,)>,
+    req: web::Json<EditCommentRequest>,
+    comment_service: web::Data<Box<dyn CommentService>>,
+) -> HttpResponse {
+    if let Err(err) = token.require_scope(ApiKeyScope::Comment) {
+        return err.error_response();
+    }
+
+    match comment_service
+        .edit_comment(token.user_info().id, comment_id, &req.body)
+        .await
+    {
+        Ok(_) => HttpResponse::NoContent().finish(),
+        Err(err) => match err {
+            EditCommentError::NotFound => HttpResponse::NotFound().json(GenericError {
+                message: err.to_string(),
+            }),
+            EditCommentError::NotAllowed | EditCommentError::EditWindowExpired(_) => {
+                HttpResponse::Forbidden().json(GenericError {
+                    message: err.to_string(),
+                })
+            }
+            EditCommentError::InvalidBody(_) => HttpResponse::BadRequest().json(GenericError {
+                message: err.to_string(),
+            }),
+            EditCommentError::Unexpected => {
+                HttpResponse::InternalServerError().json(GenericError::default())
+            }
+        },
+    }
+}
+
+/// Deletes the comment together with the replies to it.
+#[api]
+#[delete("/comments/{comment_id}")]
+#[tag(TAG_NAME)]
+#[response(204)]
+#[response(403, GenericError)]
+#[response(404, GenericError)]
+async fn delete_comment(
+    token: SessionToken,
+    web::Path((comment_id,)): web::Path<(Uuid,)>,
+    comment_service: web::Data<Box<dyn CommentService>>,
+) -> HttpResponse {
+    if let Err(err) = token.require_scope(ApiKeyScope::Comment) {
+        return err.error_response();
+    }
+
+    match comment_service
+        .delete_comment(token.user_info(), comment_id)
+        .await
+    {
+        Ok(_) => HttpResponse::NoContent().finish(),
+        Err(err) => match err {
+            DeleteCommentError::NotFound => HttpResponse::NotFound().json(GenericError {
+                message: err.to_string(),
+            }),
+            DeleteCommentError::NotAllowed => HttpResponse::Forbidden().json(GenericError {
+                message: err.to_string(),
+            }),
+            DeleteCommentError::Unexpected => {
+                HttpResponse::InternalServerError().json(GenericError::default())
+            }
+        },
+    }
+}
+
+pub fn configure_routes(_config: &Config) -> impl FnOnce(&mut ServiceConfig) {
+    move |app: &mut ServiceConfig| {
+        app.service(create_comment);
+        app.service(get_comments);
+        app.service(edit_comment);
+        app.service(delete_comment);
+    }
+}
@@ -1,28 +1,35 @@
 use crate::{
     config::Config,
-    db::image::NewImage,
+    db::image::{BoundingBox, GeoArea, ImageExt, ImageFilter, NewImage},
+    model::auth::ApiKeyScope,
     model::error::GenericError,
     model::image::{
-        CreateImageError, CreateImageRequest, CreateImageResponse, GetImageRatingResponse,
-        GetImageRatingsError, GetImageResponse, GetUserRatingsError, GetUserRatingsResponse, Image,
-        RateImageError, RateImageRequest, SearchImagesError, SearchImagesQuery,
-        SearchImagesResponse, UploadImageError,
+        BatchUploadError, BatchUploadResponse, CreateAndUploadImageError, CreateImageError,
+        CreateImageRequest, CreateImageResponse, DownloadImageError, GeoLocation,
+        GetImageClustersError, GetImageClustersQuery, GetImageClustersResponse,
+        GetImageRatingResponse, GetImageRatingsError, GetImageResponse, GetSimilarImagesError,
+        GetSimilarImagesQuery, GetSimilarImagesResponse, GetUserRatingsError,
+        GetUserRatingsResponse, Image, ImageCluster, ImageMetadata, RateImageError,
+        RateImageRequest, SearchImagesError, SearchImagesQuery, SearchImagesResponse,
+        SetImageLocationError, SetImageLocationRequest, SetImageTagsError, SetImageTagsRequest,
+        SetImageTagsResponse, SetImageVisibilityError, SetImageVisibilityRequest, SimilarImage,
+        UploadImageError,
     },
-    server::extractors::SessionToken,
-    services::image::ImageService,
+    model::Visibility,
+    server::extractors::{HandleReports, OptionalSessionToken, RequirePermission, SessionToken},
+    services::image::{cluster_cell_size, ImageService},
 };
 use actix_multipart::Multipart;
 use actix_web::{
-    get,
+    delete, get,
     http::StatusCode,
     post, put,
     web::{self, ServiceConfig},
-    HttpRequest, HttpResponse,
+    HttpRequest, HttpResponse, ResponseError,
 };
 use aide::openapi::v3::macros::api;
 use aide::openapi::v3::macros::api::define;
 use slog::{error, Logger};
-use std::io;
 use uuid::Uuid;
 
 const TAG_NAME: &str = "images";
@@ -33,72 +40,135 @@ define::tag! {
     display_name("Images")
 }
 
+/// Images that were not uploaded yet are not returned.
+pub(crate) fn image_response(i: ImageExt) -> Option<Image> {
+    let visibility = i.image.visibility();
+    let location = i.image.location();
+
+    match i.image.upload_date {
+        Some(date) => Some(Image {
+            id: i.image.id,
+            title: i.image.title,
+            description: i.image.description,
+            categories: i.categories.into_iter().map(|c| c.id).collect(),
+            tags: i.tags.into_iter().map(|t| t.tag_name).collect(),
+            date,
+            comment_count: i.comment_count as _,
+            visibility,
+            location: location.map(|(latitude, longitude)| GeoLocation {
+                latitude,
+                longitude,
+            }),
+            duplicate_of: i.image.duplicate_of,
+            metadata: i.metadata.map(|m| ImageMetadata {
+                camera_make: m.camera_make,
+                camera_model: m.camera_model,
+                lens_model: m.lens_model,
+                exposure_time: m.exposure_time,
+                f_number: m.f_number,
+                iso: m.iso.map(|v| v as _),
+                focal_length: m.focal_length,
+                captured: m.captured,
+                orientation: m.orientation.map(|v| v as _),
+            }),
+        }),
+        None => None,
+    }
+}
+
 #[api]
 #[post("/images")]
 #[tag(TAG_NAME)]
 #[response(200, CreateImageResponse)]
 #[response(400, GenericError)]
+#[response(403, GenericError)]
 async fn create_image(
     token: SessionToken,
     req: web::Json<CreateImageRequest>,
     image_service: web::Data<Box<dyn ImageService>>,
 ) -> HttpResponse {
+    if let Err(err) = token.require_scope(ApiKeyScope::Upload) {
+        return err.error_response();
+    }
+
     match image_service
         .create_image(
             token.user_info().id,
             NewImage {
                 description: req.description.clone(),
                 title: req.title.clone(),
+                visibility: req.visibility.unwrap_or(Visibility::Public),
             },
             &req.categories,
         )
         .await
     {
         Ok(id) => HttpResponse::Ok().json(CreateImageResponse { id }),
-        Err(err) => match err {
-            CreateImageError::Unexpected => {
-                HttpResponse::InternalServerError().json(GenericError {
-                    message: err.to_string(),
-                })
-            }
-            CreateImageError::CategoryNotFound(_) => {
-                HttpResponse::BadRequest().json(GenericError {
-                    message: err.to_string(),
-                })
-            }
-        },
+        Err(err) => create_image_error_response(err),
+    }
+}
+
+fn create_image_error_response(err: CreateImageError) -> HttpResponse {
+    match err {
+        CreateImageError::Unexpected => HttpResponse::InternalServerError().json(GenericError {
+            message: err.to_string(),
+        }),
+        CreateImageError::CategoryNotFound(_) => HttpResponse::BadRequest().json(GenericError {
+            message: err.to_string(),
+        }),
+        CreateImageError::QuotaExceeded => HttpResponse::Forbidden().json(GenericError {
+            message: err.to_string(),
+        }),
     }
 }
 
+/// Only public images are listed, besides the own ones.
+///
+/// Available without authentication if public read access is enabled.
 #[api]
 #[get("/images")]
 #[tag(TAG_NAME)]
 #[response(200, SearchImagesResponse)]
+#[response(400, GenericError)]
+#[response(401, GenericError)]
 async fn search_images(
-    _token: SessionToken,
+    token: OptionalSessionToken,
     req: web::Query<SearchImagesQuery>,
     image_service: web::Data<Box<dyn ImageService>>,
 ) -> HttpResponse {
+    let area = match search_area(&req) {
+        Ok(a) => a,
+        Err(err) => {
+            return HttpResponse::BadRequest().json(GenericError {
+                message: err.to_string(),
+            })
+        }
+    };
+
+    let filter = ImageFilter {
+        search: req.search.clone(),
+        tags: req
+            .tags
+            .as_deref()
+            .map(|t| t.split(',').map(String::from).collect())
+            .unwrap_or_default(),
+        viewer_id: token.user_info().map(|u| u.id),
+        captured_after: req.captured_after,
+        captured_before: req.captured_before,
+        area,
+    };
+
     match image_service
-        .search_images(req.search.as_deref(), req.offset, req.limit)
+        .search_images(filter, req.offset, req.limit)
         .await
     {
         Ok(images) => HttpResponse::Ok().json(SearchImagesResponse {
-            images: images
-                .into_iter()
-                .filter_map(|(i, c)| match i.upload_date {
-                    Some(date) => Some(Image {
-                        id: i.id,
-                        title: i.title,
-                        description: i.description,
-                        categories: c.into_iter().map(|c| c.id).collect(),
-                        date,
-                    }),
-                    None => None,
-                })
-                .collect(),
+            images: images.into_iter().filter_map(image_response).collect(),
         }),
         Err(err) => match err {
+            SearchImagesError::InvalidArea => HttpResponse::BadRequest().json(GenericError {
+                message: err.to_string(),
+            }),
             SearchImagesError::Unexpected => {
                 HttpResponse::InternalServerError().json(GenericError::default())
             }
@@ -106,56 +176,213 @@ async fn search_images(
     }
 }
 
+/// Either a bounding box or a radius, but not both.
+fn search_area(req: &SearchImagesQuery) -> Result<Option<GeoArea>, SearchImagesError> {
+    let bbox = req
+        .bbox
+        .as_deref()
+        .map(str::parse::<BoundingBox>)
+        .transpose()
+        .map_err(|_| SearchImagesError::InvalidArea)?;
+
+    let radius = match (req.lat, req.lon, req.radius) {
+        (Some(lat), Some(lon), Some(meters)) => Some(GeoArea::Radius { lat, lon, meters }),
+        (None, None, None) => None,
+        _ => return Err(SearchImagesError::InvalidArea),
+    };
+
+    match (bbox, radius) {
+        (Some(_), Some(_)) => Err(SearchImagesError::InvalidArea),
+        (Some(b), None) => Ok(Some(GeoArea::BoundingBox(b))),
+        (None, radius) => Ok(radius),
+    }
+}
+
+/// Counts of the located images for a map, grouped into a grid.
+///
+/// Available without authentication if public read access is enabled.
+#[api]
+#[get("/images/clusters")]
+#[tag(TAG_NAME)]
+#[response(200, GetImageClustersResponse)]
+#[response(400, GenericError)]
+#[response(401, GenericError)]
+async fn get_image_clusters(
+    token: OptionalSessionToken,
+    req: web::Query<GetImageClustersQuery>,
+    image_service: web::Data<Box<dyn ImageService>>,
+) -> HttpResponse {
+    let bbox = match req
+        .bbox
+        .as_deref()
+        .map(str::parse::<BoundingBox>)
+        .transpose()
+    {
+        Ok(b) => b,
+        Err(_) => {
+            return HttpResponse::BadRequest().json(GenericError {
+                message: GetImageClustersError::InvalidArea.to_string(),
+            })
+        }
+    };
+
+    match image_service
+        .get_image_clusters(req.zoom, bbox, token.user_info().map(|u| u.id))
+        .await
+    {
+        Ok(clusters) => {
+            let cell_size = cluster_cell_size(req.zoom);
+
+            HttpResponse::Ok().json(GetImageClustersResponse {
+                clusters: clusters
+                    .into_iter()
+                    .map(|c| ImageCluster {
+                        count: c.count as _,
+                        latitude: c.latitude,
+                        longitude: c.longitude,
+                        min_latitude: f64::from(c.cell_row) * cell_size - 90.0,
+                        min_longitude: f64::from(c.cell_column) * cell_size - 180.0,
+                        max_latitude: f64::from(c.cell_row + 1) * cell_size - 90.0,
+                        max_longitude: f64::from(c.cell_column + 1) * cell_size - 180.0,
+                    })
+                    .collect(),
+            })
+        }
+        Err(err) => match err {
+            GetImageClustersError::InvalidZoom(_) | GetImageClustersError::InvalidArea => {
+                HttpResponse::BadRequest().json(GenericError {
+                    message: err.to_string(),
+                })
+            }
+            GetImageClustersError::Unexpected => {
+                HttpResponse::InternalServerError().json(GenericError::default())
+            }
+        },
+    }
+}
+
 #[api]
 #[post("/images/{image_id}")]
 #[tag(TAG_NAME)]
 #[response(204)]
 #[response(400, GenericError)]
+#[response(403, GenericError)]
+#[response(409, GenericError)]
+#[response(413, GenericError)]
 async fn upload_image(
-    _token: SessionToken,
+    token: SessionToken,
     web::Path((image_id,)): web::Path<(Uuid,)>,
     payload: Multipart,
     image_service: web::Data<Box<dyn ImageService>>,
 ) -> HttpResponse {
-    match image_service.save_image(image_id, payload).await {
+    if let Err(err) = token.require_scope(ApiKeyScope::Upload) {
+        return err.error_response();
+    }
+
+    match image_service
+        .save_image(token.user_info().id, image_id, payload)
+        .await
+    {
         Ok(_) => HttpResponse::NoContent().finish(),
+        Err(err) => upload_image_error_response(err),
+    }
+}
+
+/// Creates an image together with its file, so no image is left behind without one.
+///
+/// Expects a "metadata" part with the same JSON as `POST /images`, and a "file" part.
+#[api]
+#[post("/images/upload")]
+#[tag(TAG_NAME)]
+#[response(200, CreateImageResponse)]
+#[response(400, GenericError)]
+#[response(403, GenericError)]
+#[response(409, GenericError)]
+#[response(413, GenericError)]
+async fn create_and_upload_image(
+    token: SessionToken,
+    payload: Multipart,
+    image_service: web::Data<Box<dyn ImageService>>,
+) -> HttpResponse {
+    if let Err(err) = token.require_scope(ApiKeyScope::Upload) {
+        return err.error_response();
+    }
+
+    match image_service
+        .create_and_upload_image(token.user_info().id, payload)
+        .await
+    {
+        Ok(id) => HttpResponse::Ok().json(CreateImageResponse { id }),
         Err(err) => match err {
-            UploadImageError::InvalidId
-            | UploadImageError::AlreadyUploaded
-            | UploadImageError::ExpectedFile
-            | UploadImageError::TimeOut(_) => HttpResponse::BadRequest().json(GenericError {
-                message: err.to_string(),
-            }),
-            UploadImageError::Unexpected => {
-                HttpResponse::InternalServerError().json(GenericError {
+            CreateAndUploadImageError::ExpectedMetadata
+            | CreateAndUploadImageError::InvalidMetadata(_)
+            | CreateAndUploadImageError::UnexpectedPart(_) => {
+                HttpResponse::BadRequest().json(GenericError {
                     message: err.to_string(),
                 })
             }
+            CreateAndUploadImageError::Create(err) => create_image_error_response(err),
+            CreateAndUploadImageError::Upload(err) => upload_image_error_response(err),
         },
     }
 }
 
+/// Creates an image for each "file" part, some of them can fail while the others succeed.
+///
+/// An optional "defaults" part before the files can set the metadata of all of them,
+/// and a "metadata" part before a file overrides them for that file.
+/// Both are JSON objects with optional `title`, `description`, `categories` and `visibility`,
+/// the title is the name of the file by default.
+#[api]
+#[post("/images/batch")]
+#[tag(TAG_NAME)]
+#[response(200, BatchUploadResponse)]
+#[response(400, GenericError)]
+async fn batch_upload_images(
+    token: SessionToken,
+    payload: Multipart,
+    image_service: web::Data<Box<dyn ImageService>>,
+) -> HttpResponse {
+    if let Err(err) = token.require_scope(ApiKeyScope::Upload) {
+        return err.error_response();
+    }
+
+    match image_service
+        .batch_upload_images(token.user_info().id, payload)
+        .await
+    {
+        Ok(res) => HttpResponse::Ok().json(res),
+        Err(err) => match err {
+            BatchUploadError::InvalidDefaults(_) | BatchUploadError::UnexpectedPart(_) => {
+                HttpResponse::BadRequest().json(GenericError {
+                    message: err.to_string(),
+                })
+            }
+            BatchUploadError::Unexpected => {
+                HttpResponse::InternalServerError().json(GenericError::default())
+            }
+        },
+    }
+}
+
+/// Available without authentication if public read access is enabled.
 #[api]
 #[get("/images/{image_id}")]
 #[tag(TAG_NAME)]
 #[response(200, GetImageResponse)]
 #[response(400, GenericError)]
+#[response(401, GenericError)]
 async fn get_image(
-    _token: SessionToken,
+    token: OptionalSessionToken,
     web::Path((image_id,)): web::Path<(Uuid,)>,
     image_service: web::Data<Box<dyn ImageService>>,
 ) -> HttpResponse {
-    match image_service.get_image_info(image_id).await {
-        Ok((i, c)) => match i.upload_date {
-            Some(date) => HttpResponse::Ok().json(GetImageResponse {
-                image: Image {
-                    id: i.id,
-                    title: i.title,
-                    description: i.description,
-                    categories: c.into_iter().map(|c| c.id).collect(),
-                    date,
-                },
-            }),
+    match image_service
+        .get_image_info(image_id, token.user_info())
+        .await
+    {
+        Ok(i) => match image_response(i) {
+            Some(image) => HttpResponse::Ok().json(GetImageResponse { image }),
             None => HttpResponse::NotFound().json(GenericError {
                 message: "image was not found".into(),
             }),
@@ -175,19 +402,21 @@ async fn get_image(
     }
 }
 
+/// Available without authentication if public read access is enabled.
 #[api]
 #[get("/images/{image_id}/download")]
 #[tag(TAG_NAME)]
 #[response(status(200), content_type("application/octet-stream"))]
+#[response(401, GenericError)]
 #[response(404)]
 async fn download_image(
-    _token: SessionToken,
+    token: OptionalSessionToken,
     web::Path((image_id,)): web::Path<(Uuid,)>,
     req: HttpRequest,
     logger: web::Data<Logger>,
     image_service: web::Data<Box<dyn ImageService>>,
 ) -> HttpResponse {
-    match image_service.get_image(image_id).await {
+    match image_service.get_image(image_id, token.user_info()).await {
         Ok(f) => match f.set_status_code(StatusCode::OK).into_response(&req) {
             Ok(res) => res,
             Err(err) => {
@@ -197,13 +426,185 @@ async fn download_image(
                 HttpResponse::InternalServerError().json(GenericError::default())
             }
         },
-        Err(err) => match err.kind() {
-            io::ErrorKind::NotFound => HttpResponse::NotFound().finish(),
-            _ => HttpResponse::InternalServerError().json(GenericError::default()),
+        Err(err) => match err {
+            DownloadImageError::NotFound => HttpResponse::NotFound().finish(),
+            DownloadImageError::Unexpected => {
+                HttpResponse::InternalServerError().json(GenericError::default())
+            }
+        },
+    }
+}
+
+pub(crate) fn upload_image_error_response(err: UploadImageError) -> HttpResponse {
+    match err {
+        UploadImageError::InvalidId
+        | UploadImageError::AlreadyUploaded
+        | UploadImageError::ExpectedFile
+        | UploadImageError::UnsupportedFile
+        | UploadImageError::TimeOut(_) => HttpResponse::BadRequest().json(GenericError {
+            message: err.to_string(),
+        }),
+        UploadImageError::Duplicate(_) => HttpResponse::Conflict().json(GenericError {
+            message: err.to_string(),
+        }),
+        UploadImageError::TooLarge(_) => HttpResponse::PayloadTooLarge().json(GenericError {
+            message: err.to_string(),
+        }),
+        UploadImageError::NotAllowed | UploadImageError::QuotaExceeded => HttpResponse::Forbidden()
+            .json(GenericError {
+                message: err.to_string(),
+            }),
+        UploadImageError::Unexpected => HttpResponse::InternalServerError().json(GenericError {
+            message: err.to_string(),
+        }),
+    }
+}
+
+/// Only the owner can change the visibility.
+#[api]
+#[put("/images/{image_id}/visibility")]
+#[tag(TAG_NAME)]
+#[response(204)]
+#[response(403, GenericError)]
+#[response(404, GenericError)]
+async fn set_image_visibility(
+    token: SessionToken,
+    web::Path((image_id,)): web::Path<(Uuid,)>,
+    req: web::Json<SetImageVisibilityRequest>,
+    image_service: web::Data<Box<dyn ImageService>>,
+) -> HttpResponse {
+    if let Err(err) = token.require_scope(ApiKeyScope::Upload) {
+        return err.error_response();
+    }
+
+    match image_service
+        .set_image_visibility(token.user_info().id, image_id, req.visibility)
+        .await
+    {
+        Ok(_) => HttpResponse::NoContent().finish(),
+        Err(err) => match err {
+            SetImageVisibilityError::ImageNotFound => HttpResponse::NotFound().json(GenericError {
+                message: err.to_string(),
+            }),
+            SetImageVisibilityError::NotAllowed => HttpResponse::Forbidden().json(GenericError {
+                message: err.to_string(),
+            }),
+            SetImageVisibilityError::Unexpected => {
+                HttpResponse::InternalServerError().json(GenericError::default())
+            }
+        },
+    }
+}
+
+/// Images of any user with a perceptual hash close to the one of the given image.
+#[api]
+#[get("/images/{image_id}/similar")]
+#[tag(TAG_NAME)]
+#[response(200, GetSimilarImagesResponse)]
+#[response(403, GenericError)]
+#[response(404, GenericError)]
+async fn get_similar_images(
+    _permission: RequirePermission<HandleReports>,
+    web::Path((image_id,)): web::Path<(Uuid,)>,
+    req: web::Query<GetSimilarImagesQuery>,
+    image_service: web::Data<Box<dyn ImageService>>,
+) -> HttpResponse {
+    match image_service
+        .get_similar_images(image_id, req.max_distance, req.limit)
+        .await
+    {
+        Ok(images) => HttpResponse::Ok().json(GetSimilarImagesResponse {
+            images: images
+                .into_iter()
+                .filter_map(|(i, distance)| {
+                    image_response(i).map(|image| SimilarImage { image, distance })
+                })
+                .collect(),
+        }),
+        Err(err) => match err {
+            GetSimilarImagesError::NotFound => HttpResponse::NotFound().json(GenericError {
+                message: err.to_string(),
+            }),
+            GetSimilarImagesError::Unexpected => {
+                HttpResponse::InternalServerError().json(GenericError::default())
+            }
         },
     }
 }
 
+/// Only the owner can change the location.
+#[api]
+#[put("/images/{image_id}/location")]
+#[tag(TAG_NAME)]
+#[response(204)]
+#[response(400, GenericError)]
+#[response(403, GenericError)]
+#[response(404, GenericError)]
+async fn set_image_location(
+    token: SessionToken,
+    web::Path((image_id,)): web::Path<(Uuid,)>,
+    req: web::Json<SetImageLocationRequest>,
+    image_service: web::Data<Box<dyn ImageService>>,
+) -> HttpResponse {
+    if let Err(err) = token.require_scope(ApiKeyScope::Upload) {
+        return err.error_response();
+    }
+
+    match image_service
+        .set_image_location(
+            token.user_info().id,
+            image_id,
+            Some((req.latitude, req.longitude)),
+        )
+        .await
+    {
+        Ok(_) => HttpResponse::NoContent().finish(),
+        Err(err) => set_image_location_error_response(err),
+    }
+}
+
+/// Only the owner can clear the location.
+#[api]
+#[delete("/images/{image_id}/location")]
+#[tag(TAG_NAME)]
+#[response(204)]
+#[response(403, GenericError)]
+#[response(404, GenericError)]
+async fn clear_image_location(
+    token: SessionToken,
+    web::Path((image_id,)): web::Path<(Uuid,)>,
+    image_service: web::Data<Box<dyn ImageService>>,
+) -> HttpResponse {
+    if let Err(err) = token.require_scope(ApiKeyScope::Upload) {
+        return err.error_response();
+    }
+
+    match image_service
+        .set_image_location(token.user_info().id, image_id, None)
+        .await
+    {
+        Ok(_) => HttpResponse::NoContent().finish(),
+        Err(err) => set_image_location_error_response(err),
+    }
+}
+
+fn set_image_location_error_response(err: SetImageLocationError) -> HttpResponse {
+    match err {
+        SetImageLocationError::ImageNotFound => HttpResponse::NotFound().json(GenericError {
+            message: err.to_string(),
+        }),
+        SetImageLocationError::NotAllowed => HttpResponse::Forbidden().json(GenericError {
+            message: err.to_string(),
+        }),
+        SetImageLocationError::InvalidLocation => HttpResponse::BadRequest().json(GenericError {
+            message: err.to_string(),
+        }),
+        SetImageLocationError::Unexpected => {
+            HttpResponse::InternalServerError().json(GenericError::default())
+        }
+    }
+}
+
 #[api]
 #[put("/images/{image_id}/rating")]
 #[tag(TAG_NAME)]
@@ -224,6 +625,10 @@ async fn rate_image(
     req: web::Json<RateImageRequest>,
     image_service: web::Data<Box<dyn ImageService>>,
 ) -> HttpResponse {
+    if let Err(err) = token.require_scope(ApiKeyScope::Rate) {
+        return err.error_response();
+    }
+
     match image_service
         .rate_image(image_id, token.user_info().id, req.rating)
         .await
@@ -246,17 +651,22 @@ async fn rate_image(
     }
 }
 
+/// Available without authentication if public read access is enabled.
 #[api]
 #[get("/images/{image_id}/rating")]
 #[tag(TAG_NAME)]
 #[response(200, GetImageRatingResponse)]
+#[response(401, GenericError)]
 #[response(404)]
 async fn get_image_rating(
-    _token: SessionToken,
+    token: OptionalSessionToken,
     web::Path((image_id,)): web::Path<(Uuid,)>,
     image_service: web::Data<Box<dyn ImageService>>,
 ) -> HttpResponse {
-    match image_service.get_image_ratings(image_id).await {
+    match image_service
+        .get_image_ratings(image_id, token.user_info().map(|u| u.id))
+        .await
+    {
         Ok(ratings) => HttpResponse::Ok().json(GetImageRatingResponse {
             average: if ratings.len() > 0 {
                 ratings.iter().fold(0, |c, r| c + r.rating) as f32 / ratings.len() as f32
@@ -293,15 +703,68 @@ async fn get_user_ratings(
     }
 }
 
+/// Replaces the tags of an own image.
+///
+/// Tags are lowercased and their words are joined with dashes.
+#[api]
+#[put("/images/{image_id}/tags")]
+#[tag(TAG_NAME)]
+#[response(200, SetImageTagsResponse)]
+#[response(400, GenericError)]
+#[response(403, GenericError)]
+#[response(404, GenericError)]
+async fn set_image_tags(
+    token: SessionToken,
+    web::Path((image_id,)): web::Path<(Uuid,)>,
+    req: web::Json<SetImageTagsRequest>,
+    image_service: web::Data<Box<dyn ImageService>>,
+) -> HttpResponse {
+    if let Err(err) = token.require_scope(ApiKeyScope::Upload) {
+        return err.error_response();
+    }
+
+    match image_service
+        .set_image_tags(token.user_info().id, image_id, &req.tags)
+        .await
+    {
+        Ok(tags) => HttpResponse::Ok().json(SetImageTagsResponse { tags }),
+        Err(err) => match err {
+            SetImageTagsError::ImageNotFound => HttpResponse::NotFound().json(GenericError {
+                message: err.to_string(),
+            }),
+            SetImageTagsError::NotAllowed => HttpResponse::Forbidden().json(GenericError {
+                message: err.to_string(),
+            }),
+            SetImageTagsError::TooManyTags(_) | SetImageTagsError::InvalidTag(_) => {
+                HttpResponse::BadRequest().json(GenericError {
+                    message: err.to_string(),
+                })
+            }
+            SetImageTagsError::Unexpected => {
+                HttpResponse::InternalServerError().json(GenericError::default())
+            }
+        },
+    }
+}
+
 pub fn configure_routes(_config: &Config) -> impl FnOnce(&mut ServiceConfig) {
     move |app: &mut ServiceConfig| {
         app.service(get_user_ratings);
+        app.service(get_image_clusters);
         app.service(create_image);
+        // Before `upload_image`, that would match the paths too.
+        app.service(create_and_upload_image);
+        app.service(batch_upload_images);
         app.service(upload_image);
         app.service(get_image);
         app.service(download_image);
+        app.service(set_image_visibility);
+        app.service(set_image_location);
+        app.service(get_similar_images);
+        app.service(clear_image_location);
         app.service(search_images);
         app.service(rate_image);
         app.service(get_image_rating);
+        app.service(set_image_tags);
     }
 }
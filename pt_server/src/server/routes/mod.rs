@@ -1,3 +1,13 @@
+pub mod album;
 pub mod auth;
+pub mod comment;
 pub mod image;
-pub mod category;
\ No newline at end of file
+pub mod category;
+pub mod user;
+pub mod tag;
+pub mod share_link;
+pub mod upload;
+pub mod moderation;
+pub mod admin;
+pub mod trash;
+pub mod notification;
\ No newline at end of file
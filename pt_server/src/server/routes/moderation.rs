@@ -0,0 +1,207 @@
+use crate::{
+    config::Config,
+    db,
+    model::auth::ApiKeyScope,
+    model::error::GenericError,
+    model::moderation::*,
+    model::Pagination,
+    server::extractors::{HandleReports, RequirePermission, SessionToken},
+    services::ModerationService,
+};
+use actix_web::{
+    get, post,
+    web::{self, ServiceConfig},
+    HttpResponse, ResponseError,
+};
+use aide::openapi::v3::macros::api;
+use aide::openapi::v3::macros::api::define;
+use uuid::Uuid;
+
+const TAG_NAME: &str = "moderation";
+
+define::tag! {
+    name(TAG_NAME),
+    description("Reports of images and the actions of moderators"),
+    display_name("Moderation")
+}
+
+fn report_response(r: db::image_report::ImageReport) -> Report {
+    Report {
+        id: r.id,
+        reporter: r.app_user_id,
+        reason: r.reason.parse().unwrap_or(ReportReason::Other),
+        details: r.details,
+        created: r.created,
+    }
+}
+
+/// Actions with unknown kinds are left out.
+fn moderation_action_response(
+    a: db::moderation_action::ModerationAction,
+) -> Option<ModerationAction> {
+    Some(ModerationAction {
+        id: a.id,
+        moderator_id: a.moderator_id,
+        image_id: a.image_id,
+        image_owner_id: a.image_owner_id,
+        action: a.action.parse().ok()?,
+        note: a.note,
+        created: a.created,
+    })
+}
+
+#[api]
+#[post("/images/{image_id}/report")]
+#[tag(TAG_NAME)]
+#[response(200, ReportImageResponse)]
+#[response(400, GenericError)]
+#[response(404, GenericError)]
+#[response(409, GenericError)]
+async fn report_image(
+    token: SessionToken,
+    web::Path((image_id,)): web::Path<(Uuid,)>,
+    req: web::Json<ReportImageRequest>,
+    moderation_service: web::Data<Box<dyn ModerationService>>,
+) -> HttpResponse {
+    if let Err(err) = token.require_scope(ApiKeyScope::Comment) {
+        return err.error_response();
+    }
+
+    match moderation_service
+        .report_image(
+            token.user_info().id,
+            image_id,
+            req.reason,
+            req.details.as_deref(),
+        )
+        .await
+    {
+        Ok(id) => HttpResponse::Ok().json(ReportImageResponse { id }),
+        Err(err) => match err {
+            ReportImageError::ImageNotFound => HttpResponse::NotFound().json(GenericError {
+                message: err.to_string(),
+            }),
+            ReportImageError::AlreadyReported => HttpResponse::Conflict().json(GenericError {
+                message: err.to_string(),
+            }),
+            ReportImageError::InvalidDetails(_) => HttpResponse::BadRequest().json(GenericError {
+                message: err.to_string(),
+            }),
+            ReportImageError::Unexpected => {
+                HttpResponse::InternalServerError().json(GenericError::default())
+            }
+        },
+    }
+}
+
+/// Images with open reports, the most reported first.
+#[api]
+#[get("/moderation/reports")]
+#[tag(TAG_NAME)]
+#[response(200, GetReportedImagesResponse)]
+#[response(403, GenericError)]
+async fn get_reported_images(
+    _permission: RequirePermission<HandleReports>,
+    req: web::Query<Pagination>,
+    moderation_service: web::Data<Box<dyn ModerationService>>,
+) -> HttpResponse {
+    match moderation_service
+        .get_reported_images(req.offset.map(|v| v as _), req.limit.map(|v| v as _))
+        .await
+    {
+        Ok(images) => HttpResponse::Ok().json(GetReportedImagesResponse {
+            images: images
+                .into_iter()
+                .map(|(image, reports)| ReportedImage {
+                    image_id: image.image_id,
+                    report_count: image.report_count as _,
+                    first_reported: image.first_reported,
+                    last_reported: image.last_reported,
+                    reports: reports.into_iter().map(report_response).collect(),
+                })
+                .collect(),
+        }),
+        Err(err) => match err {
+            GetReportedImagesError::Unexpected => {
+                HttpResponse::InternalServerError().json(GenericError::default())
+            }
+        },
+    }
+}
+
+/// Resolves all open reports of the image.
+///
+/// Hiding and deleting the image also requires the "hideImages" permission.
+#[api]
+#[post("/moderation/images/{image_id}/actions")]
+#[tag(TAG_NAME)]
+#[response(204)]
+#[response(403, GenericError)]
+#[response(404, GenericError)]
+async fn moderate_image(
+    permission: RequirePermission<HandleReports>,
+    web::Path((image_id,)): web::Path<(Uuid,)>,
+    req: web::Json<ModerateImageRequest>,
+    moderation_service: web::Data<Box<dyn ModerationService>>,
+) -> HttpResponse {
+    match moderation_service
+        .moderate_image(
+            permission.token().user_info(),
+            image_id,
+            req.action,
+            req.note.as_deref(),
+            req.warn_user,
+        )
+        .await
+    {
+        Ok(_) => HttpResponse::NoContent().finish(),
+        Err(err) => match err {
+            ModerateImageError::ImageNotFound => HttpResponse::NotFound().json(GenericError {
+                message: err.to_string(),
+            }),
+            ModerateImageError::NotAllowed => HttpResponse::Forbidden().json(GenericError {
+                message: err.to_string(),
+            }),
+            ModerateImageError::Unexpected => {
+                HttpResponse::InternalServerError().json(GenericError::default())
+            }
+        },
+    }
+}
+
+#[api]
+#[get("/moderation/actions")]
+#[tag(TAG_NAME)]
+#[response(200, GetModerationActionsResponse)]
+#[response(403, GenericError)]
+async fn get_moderation_actions(
+    _permission: RequirePermission<HandleReports>,
+    req: web::Query<GetModerationActionsQuery>,
+    moderation_service: web::Data<Box<dyn ModerationService>>,
+) -> HttpResponse {
+    match moderation_service
+        .get_moderation_actions(req.image_id, req.offset, req.limit)
+        .await
+    {
+        Ok(actions) => HttpResponse::Ok().json(GetModerationActionsResponse {
+            actions: actions
+                .into_iter()
+                .filter_map(moderation_action_response)
+                .collect(),
+        }),
+        Err(err) => match err {
+            GetModerationActionsError::Unexpected => {
+                HttpResponse::InternalServerError().json(GenericError::default())
+            }
+        },
+    }
+}
+
+pub fn configure_routes(_config: &Config) -> impl FnOnce(&mut ServiceConfig) {
+    move |app: &mut ServiceConfig| {
+        app.service(report_image);
+        app.service(get_reported_images);
+        app.service(moderate_image);
+        app.service(get_moderation_actions);
+    }
+}
@@ -0,0 +1,208 @@
+use crate::{
+    config::Config, db, model::auth::ApiKeyScope, model::error::GenericError,
+    model::notification::*, server::extractors::SessionToken, services::NotificationService,
+};
+use actix_web::{
+    get,
+    http::header,
+    post,
+    web::{self, ServiceConfig},
+    HttpRequest, HttpResponse, ResponseError,
+};
+use aide::openapi::v3::macros::api;
+use aide::openapi::v3::macros::api::define;
+use futures::{future, stream, StreamExt};
+use std::time::Duration;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+const TAG_NAME: &str = "notifications";
+
+/// A comment is sent this often so that idle streams are not closed by proxies.
+const STREAM_KEEP_ALIVE_SECONDS: u64 = 30;
+
+const LAST_EVENT_ID_HEADER: &str = "Last-Event-ID";
+
+define::tag! {
+    name(TAG_NAME),
+    description("Notifications about ratings, comments and moderation of the user's images"),
+    display_name("Notifications")
+}
+
+/// Notifications with unknown types are left out.
+fn notification_response(n: db::notification::Notification) -> Option<Notification> {
+    let details = n.details.unwrap_or_default();
+
+    Some(Notification {
+        id: n.id,
+        kind: n.notification_type.parse().ok()?,
+        actor_id: n.actor_id,
+        image_id: n.image_id,
+        comment_id: n.comment_id,
+        rating: details["rating"].as_u64().map(|r| r as _),
+        action: details["action"].as_str().and_then(|a| a.parse().ok()),
+        read: n.read_date.is_some(),
+        created: n.created,
+    })
+}
+
+/// The latest notifications of the user.
+#[api]
+#[get("/notifications")]
+#[tag(TAG_NAME)]
+#[response(200, GetNotificationsResponse)]
+async fn get_notifications(
+    token: SessionToken,
+    req: web::Query<GetNotificationsQuery>,
+    notification_service: web::Data<Box<dyn NotificationService>>,
+) -> HttpResponse {
+    match notification_service
+        .get_notifications(token.user_info().id, req.unread, req.offset, req.limit)
+        .await
+    {
+        Ok((notifications, unread_count)) => HttpResponse::Ok().json(GetNotificationsResponse {
+            notifications: notifications
+                .into_iter()
+                .filter_map(notification_response)
+                .collect(),
+            unread_count,
+        }),
+        Err(err) => match err {
+            GetNotificationsError::Unexpected => {
+                HttpResponse::InternalServerError().json(GenericError::default())
+            }
+        },
+    }
+}
+
+#[api]
+#[post("/notifications/{notification_id}/read")]
+#[tag(TAG_NAME)]
+#[response(204)]
+#[response(404, GenericError)]
+async fn mark_notification_read(
+    token: SessionToken,
+    web::Path((notification_id,)): web::Path<(Uuid,)>,
+    notification_service: web::Data<Box<dyn NotificationService>>,
+) -> HttpResponse {
+    if let Err(err) = token.require_scope(ApiKeyScope::Read) {
+        return err.error_response();
+    }
+
+    match notification_service
+        .mark_read(token.user_info().id, notification_id)
+        .await
+    {
+        Ok(_) => HttpResponse::NoContent().finish(),
+        Err(err) => match err {
+            MarkNotificationReadError::NotFound => HttpResponse::NotFound().json(GenericError {
+                message: err.to_string(),
+            }),
+            MarkNotificationReadError::Unexpected => {
+                HttpResponse::InternalServerError().json(GenericError::default())
+            }
+        },
+    }
+}
+
+#[api]
+#[post("/notifications/read")]
+#[tag(TAG_NAME)]
+#[response(204)]
+async fn mark_all_notifications_read(
+    token: SessionToken,
+    notification_service: web::Data<Box<dyn NotificationService>>,
+) -> HttpResponse {
+    if let Err(err) = token.require_scope(ApiKeyScope::Read) {
+        return err.error_response();
+    }
+
+    match notification_service
+        .mark_all_read(token.user_info().id)
+        .await
+    {
+        Ok(_) => HttpResponse::NoContent().finish(),
+        Err(err) => match err {
+            MarkNotificationReadError::NotFound | MarkNotificationReadError::Unexpected => {
+                HttpResponse::InternalServerError().json(GenericError::default())
+            }
+        },
+    }
+}
+
+/// New notifications of the user as server-sent events.
+///
+/// Every event has the notification as JSON in its data and the ID of the notification
+/// as its ID. A client that reconnects with the `Last-Event-ID` header first gets
+/// the notifications it missed.
+///
+/// The stream ends when the token expires, or when the client does not keep up
+/// with the events. Browsers cannot send the `Authorization` header with `EventSource`,
+/// they have to read the stream with `fetch` instead.
+#[api]
+#[get("/notifications/stream")]
+#[tag(TAG_NAME)]
+#[response(status(200), content_type("text/event-stream"))]
+async fn stream_notifications(
+    token: SessionToken,
+    http_req: HttpRequest,
+    notification_service: web::Data<Box<dyn NotificationService>>,
+) -> HttpResponse {
+    let last_id = http_req
+        .headers()
+        .get(LAST_EVENT_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<Uuid>().ok());
+
+    let events = notification_service
+        .subscribe(token.user_info().id, last_id)
+        .filter_map(|n| future::ready(notification_response(n)))
+        .filter_map(|n| {
+            future::ready(
+                serde_json::to_string(&n)
+                    .ok()
+                    .map(|data| web::Bytes::from(format!("id: {}\ndata: {}\n\n", n.id, data))),
+            )
+        });
+
+    let keep_alive = stream::unfold((), |_| async {
+        actix_rt::time::delay_for(Duration::from_secs(STREAM_KEEP_ALIVE_SECONDS)).await;
+        Some((Some(web::Bytes::from_static(b": keep-alive\n\n")), ()))
+    });
+
+    // `None` ends the stream, either when the events end or when the token expires.
+    let events = events.map(Some).chain(stream::once(future::ready(None)));
+
+    let expiry = match token.user_info().expires {
+        Some(expires) => {
+            let seconds = (expires - OffsetDateTime::now_utc().timestamp()).max(0) as u64;
+
+            stream::once(async move {
+                actix_rt::time::delay_for(Duration::from_secs(seconds)).await;
+                None
+            })
+            .left_stream()
+        }
+        None => stream::pending().right_stream(),
+    };
+
+    HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .header(header::CACHE_CONTROL, "no-cache")
+        .streaming(
+            stream::select(stream::select(events, keep_alive), expiry)
+                .take_while(|event| future::ready(event.is_some()))
+                .filter_map(future::ready)
+                .map(Ok::<_, actix_web::Error>)
+                .boxed_local(),
+        )
+}
+
+pub fn configure_routes(_config: &Config) -> impl FnOnce(&mut ServiceConfig) {
+    move |app: &mut ServiceConfig| {
+        app.service(get_notifications);
+        app.service(mark_all_notifications_read);
+        app.service(mark_notification_read);
+        app.service(stream_notifications);
+    }
+}
@@ -0,0 +1,208 @@
+use crate::{
+    config::Config, db, model::auth::ApiKeyScope, model::error::GenericError, model::share_link::*,
+    server::extractors::SessionToken, server::routes::image::image_response,
+    services::ShareLinkService,
+};
+use actix_web::{
+    delete, get,
+    http::StatusCode,
+    post,
+    web::{self, ServiceConfig},
+    HttpRequest, HttpResponse, ResponseError,
+};
+use aide::openapi::v3::macros::api;
+use aide::openapi::v3::macros::api::define;
+use slog::{error, Logger};
+use uuid::Uuid;
+
+const TAG_NAME: &str = "share-links";
+
+define::tag! {
+    name(TAG_NAME),
+    description("Links that give anonymous read access to a single image"),
+    display_name("Share links")
+}
+
+fn share_link_response(l: db::share_link::ShareLink) -> ShareLink {
+    ShareLink {
+        id: l.id,
+        image_id: l.image_id,
+        token: l.token,
+        created: l.created,
+        expires: l.expires,
+    }
+}
+
+#[api]
+#[post("/images/{image_id}/share-links")]
+#[tag(TAG_NAME)]
+#[response(200, ShareLink)]
+#[response(400, GenericError)]
+#[response(403, GenericError)]
+#[response(404, GenericError)]
+async fn create_share_link(
+    token: SessionToken,
+    web::Path((image_id,)): web::Path<(Uuid,)>,
+    req: web::Json<CreateShareLinkRequest>,
+    share_link_service: web::Data<Box<dyn ShareLinkService>>,
+) -> HttpResponse {
+    if let Err(err) = token.require_scope(ApiKeyScope::Upload) {
+        return err.error_response();
+    }
+
+    match share_link_service
+        .create_share_link(token.user_info().id, image_id, req.expires)
+        .await
+    {
+        Ok(link) => HttpResponse::Ok().json(share_link_response(link)),
+        Err(err) => match err {
+            CreateShareLinkError::ImageNotFound => HttpResponse::NotFound().json(GenericError {
+                message: err.to_string(),
+            }),
+            CreateShareLinkError::NotAllowed => HttpResponse::Forbidden().json(GenericError {
+                message: err.to_string(),
+            }),
+            CreateShareLinkError::InvalidExpiry => HttpResponse::BadRequest().json(GenericError {
+                message: err.to_string(),
+            }),
+            CreateShareLinkError::Unexpected => {
+                HttpResponse::InternalServerError().json(GenericError::default())
+            }
+        },
+    }
+}
+
+#[api]
+#[get("/images/{image_id}/share-links")]
+#[tag(TAG_NAME)]
+#[response(200, GetShareLinksResponse)]
+#[response(403, GenericError)]
+#[response(404, GenericError)]
+async fn get_share_links(
+    token: SessionToken,
+    web::Path((image_id,)): web::Path<(Uuid,)>,
+    share_link_service: web::Data<Box<dyn ShareLinkService>>,
+) -> HttpResponse {
+    match share_link_service
+        .get_share_links(token.user_info().id, image_id)
+        .await
+    {
+        Ok(links) => HttpResponse::Ok().json(GetShareLinksResponse {
+            links: links.into_iter().map(share_link_response).collect(),
+        }),
+        Err(err) => match err {
+            GetShareLinksError::ImageNotFound => HttpResponse::NotFound().json(GenericError {
+                message: err.to_string(),
+            }),
+            GetShareLinksError::NotAllowed => HttpResponse::Forbidden().json(GenericError {
+                message: err.to_string(),
+            }),
+            GetShareLinksError::Unexpected => {
+                HttpResponse::InternalServerError().json(GenericError::default())
+            }
+        },
+    }
+}
+
+#[api]
+#[delete("/share-links/{share_link_id}")]
+#[tag(TAG_NAME)]
+#[response(204)]
+#[response(403, GenericError)]
+#[response(404, GenericError)]
+async fn revoke_share_link(
+    token: SessionToken,
+    web::Path((share_link_id,)): web::Path<(Uuid,)>,
+    share_link_service: web::Data<Box<dyn ShareLinkService>>,
+) -> HttpResponse {
+    if let Err(err) = token.require_scope(ApiKeyScope::Upload) {
+        return err.error_response();
+    }
+
+    match share_link_service
+        .revoke_share_link(token.user_info().id, share_link_id)
+        .await
+    {
+        Ok(_) => HttpResponse::NoContent().finish(),
+        Err(err) => match err {
+            RevokeShareLinkError::NotFound => HttpResponse::NotFound().json(GenericError {
+                message: err.to_string(),
+            }),
+            RevokeShareLinkError::NotAllowed => HttpResponse::Forbidden().json(GenericError {
+                message: err.to_string(),
+            }),
+            RevokeShareLinkError::Unexpected => {
+                HttpResponse::InternalServerError().json(GenericError::default())
+            }
+        },
+    }
+}
+
+/// The shared image, no authentication is needed.
+#[api]
+#[get("/shared/{share_token}")]
+#[tag(TAG_NAME)]
+#[response(200, GetSharedImageResponse)]
+#[response(404, GenericError)]
+async fn get_shared_image(
+    web::Path((share_token,)): web::Path<(String,)>,
+    share_link_service: web::Data<Box<dyn ShareLinkService>>,
+) -> HttpResponse {
+    match share_link_service.get_shared_image(&share_token).await {
+        Ok(i) => match image_response(i) {
+            Some(image) => HttpResponse::Ok().json(GetSharedImageResponse { image }),
+            None => HttpResponse::NotFound().json(GenericError {
+                message: GetSharedImageError::NotFound.to_string(),
+            }),
+        },
+        Err(err) => match err {
+            GetSharedImageError::NotFound => HttpResponse::NotFound().json(GenericError {
+                message: err.to_string(),
+            }),
+            GetSharedImageError::Unexpected => {
+                HttpResponse::InternalServerError().json(GenericError::default())
+            }
+        },
+    }
+}
+
+/// Downloads the shared image, no authentication is needed.
+#[api]
+#[get("/shared/{share_token}/download")]
+#[tag(TAG_NAME)]
+#[response(status(200), content_type("application/octet-stream"))]
+#[response(404)]
+async fn download_shared_image(
+    web::Path((share_token,)): web::Path<(String,)>,
+    req: HttpRequest,
+    logger: web::Data<Logger>,
+    share_link_service: web::Data<Box<dyn ShareLinkService>>,
+) -> HttpResponse {
+    match share_link_service.download_shared_image(&share_token).await {
+        Ok(f) => match f.set_status_code(StatusCode::OK).into_response(&req) {
+            Ok(res) => res,
+            Err(err) => {
+                error!(logger, "error serving the image";
+                    "error" => err.to_string()
+                );
+                HttpResponse::InternalServerError().json(GenericError::default())
+            }
+        },
+        Err(err) => match err {
+            GetSharedImageError::NotFound => HttpResponse::NotFound().finish(),
+            GetSharedImageError::Unexpected => {
+                HttpResponse::InternalServerError().json(GenericError::default())
+            }
+        },
+    }
+}
+
+pub fn configure_routes(_config: &Config) -> impl FnOnce(&mut ServiceConfig) {
+    move |app: &mut ServiceConfig| {
+        app.service(create_share_link);
+        app.service(get_share_links);
+        app.service(revoke_share_link);
+        app.service(get_shared_image);
+        app.service(download_shared_image);
+    }
+}
@@ -0,0 +1,120 @@
+use crate::{
+    config::Config,
+    db::tag::TagCount,
+    model::error::GenericError,
+    model::image::{
+        AutocompleteTagsQuery, GetTagsError, GetTagsResponse, PopularTagsQuery, PromoteTagError,
+        PromoteTagResponse, Tag,
+    },
+    server::extractors::{ManageCategories, OptionalSessionToken, RequirePermission},
+    services::ImageService,
+};
+use actix_web::{
+    get, post,
+    web::{self, ServiceConfig},
+    HttpResponse,
+};
+use aide::openapi::v3::macros::api;
+use aide::openapi::v3::macros::api::define;
+use uuid::Uuid;
+
+const TAG_NAME: &str = "tags";
+
+define::tag! {
+    name(TAG_NAME),
+    description("Free-form image tags given by users"),
+    display_name("Tags")
+}
+
+fn tags_response(tags: Vec<TagCount>) -> GetTagsResponse {
+    GetTagsResponse {
+        tags: tags
+            .into_iter()
+            .map(|t| Tag {
+                id: t.id,
+                name: t.tag_name,
+                image_count: t.image_count.unwrap_or(0) as _,
+            })
+            .collect(),
+    }
+}
+
+/// Tags similar to the given text.
+///
+/// Available without authentication if public read access is enabled.
+#[api]
+#[get("/tags/autocomplete")]
+#[tag(TAG_NAME)]
+#[response(200, GetTagsResponse)]
+#[response(401, GenericError)]
+async fn autocomplete_tags(
+    _token: OptionalSessionToken,
+    req: web::Query<AutocompleteTagsQuery>,
+    image_service: web::Data<Box<dyn ImageService>>,
+) -> HttpResponse {
+    match image_service.autocomplete_tags(&req.q, req.limit).await {
+        Ok(tags) => HttpResponse::Ok().json(tags_response(tags)),
+        Err(err) => match err {
+            GetTagsError::Unexpected => {
+                HttpResponse::InternalServerError().json(GenericError::default())
+            }
+        },
+    }
+}
+
+/// The most used tags.
+///
+/// Available without authentication if public read access is enabled.
+#[api]
+#[get("/tags/popular")]
+#[tag(TAG_NAME)]
+#[response(200, GetTagsResponse)]
+#[response(401, GenericError)]
+async fn get_popular_tags(
+    _token: OptionalSessionToken,
+    req: web::Query<PopularTagsQuery>,
+    image_service: web::Data<Box<dyn ImageService>>,
+) -> HttpResponse {
+    match image_service.get_popular_tags(req.limit).await {
+        Ok(tags) => HttpResponse::Ok().json(tags_response(tags)),
+        Err(err) => match err {
+            GetTagsError::Unexpected => {
+                HttpResponse::InternalServerError().json(GenericError::default())
+            }
+        },
+    }
+}
+
+/// Turns the tag into a category.
+///
+/// The images with the tag are added to the category and the tag is deleted.
+#[api]
+#[post("/tags/{tag_id}/promote")]
+#[tag(TAG_NAME)]
+#[response(200, PromoteTagResponse)]
+#[response(404, GenericError)]
+async fn promote_tag(
+    _permission: RequirePermission<ManageCategories>,
+    web::Path((tag_id,)): web::Path<(Uuid,)>,
+    image_service: web::Data<Box<dyn ImageService>>,
+) -> HttpResponse {
+    match image_service.promote_tag(tag_id).await {
+        Ok(category_id) => HttpResponse::Ok().json(PromoteTagResponse { category_id }),
+        Err(err) => match err {
+            PromoteTagError::TagNotFound => HttpResponse::NotFound().json(GenericError {
+                message: err.to_string(),
+            }),
+            PromoteTagError::Unexpected => {
+                HttpResponse::InternalServerError().json(GenericError::default())
+            }
+        },
+    }
+}
+
+pub fn configure_routes(_config: &Config) -> impl FnOnce(&mut ServiceConfig) {
+    move |app: &mut ServiceConfig| {
+        app.service(autocomplete_tags);
+        app.service(get_popular_tags);
+        app.service(promote_tag);
+    }
+}
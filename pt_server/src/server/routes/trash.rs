@@ -0,0 +1,263 @@
+use crate::{
+    config::Config,
+    db,
+    model::auth::ApiKeyScope,
+    model::error::GenericError,
+    model::trash::*,
+    model::Pagination,
+    server::extractors::{ManageCategories, RequestId, RequirePermission, SessionToken},
+    services::TrashService,
+};
+use actix_web::{
+    delete, get, post,
+    web::{self, ServiceConfig},
+    HttpResponse, ResponseError,
+};
+use aide::openapi::v3::macros::api;
+use aide::openapi::v3::macros::api::define;
+use time::Duration;
+use uuid::Uuid;
+
+const TAG_NAME: &str = "trash";
+
+define::tag! {
+    name(TAG_NAME),
+    description("Deleted images and categories that can still be restored"),
+    display_name("Trash")
+}
+
+fn trashed_image_response(i: db::image::Image, retention: Duration) -> Option<TrashedImage> {
+    let deleted = i.deleted?;
+
+    Some(TrashedImage {
+        id: i.id,
+        title: i.title,
+        deleted,
+        purge_after: deleted + retention,
+    })
+}
+
+fn trashed_category_response(
+    c: db::category::Category,
+    retention: Duration,
+) -> Option<TrashedCategory> {
+    let deleted = c.deleted?;
+
+    Some(TrashedCategory {
+        id: c.id,
+        name: c.category_name,
+        slug: c.slug,
+        parent_id: c.parent_id,
+        deleted_by: c.deleted_by,
+        deleted,
+        purge_after: deleted + retention,
+    })
+}
+
+fn trashed_image_error_response(err: TrashedImageError) -> HttpResponse {
+    match err {
+        TrashedImageError::ImageNotFound => HttpResponse::NotFound().json(GenericError {
+            message: err.to_string(),
+        }),
+        TrashedImageError::Unexpected => {
+            HttpResponse::InternalServerError().json(GenericError::default())
+        }
+    }
+}
+
+/// Moves the image to the trash of the owner.
+///
+/// It can be restored until it is purged after the retention period.
+#[api]
+#[delete("/images/{image_id}")]
+#[tag(TAG_NAME)]
+#[response(204)]
+#[response(403, GenericError)]
+#[response(404, GenericError)]
+async fn delete_image(
+    token: SessionToken,
+    web::Path((image_id,)): web::Path<(Uuid,)>,
+    trash_service: web::Data<Box<dyn TrashService>>,
+) -> HttpResponse {
+    if let Err(err) = token.require_scope(ApiKeyScope::Upload) {
+        return err.error_response();
+    }
+
+    match trash_service
+        .delete_image(token.user_info().id, image_id)
+        .await
+    {
+        Ok(_) => HttpResponse::NoContent().finish(),
+        Err(err) => match err {
+            DeleteImageError::ImageNotFound => HttpResponse::NotFound().json(GenericError {
+                message: err.to_string(),
+            }),
+            DeleteImageError::NotAllowed => HttpResponse::Forbidden().json(GenericError {
+                message: err.to_string(),
+            }),
+            DeleteImageError::Unexpected => {
+                HttpResponse::InternalServerError().json(GenericError::default())
+            }
+        },
+    }
+}
+
+/// The images the user deleted, images deleted by moderators are not listed.
+#[api]
+#[get("/trash/images")]
+#[tag(TAG_NAME)]
+#[response(200, GetTrashedImagesResponse)]
+async fn get_trashed_images(
+    token: SessionToken,
+    req: web::Query<Pagination>,
+    trash_service: web::Data<Box<dyn TrashService>>,
+) -> HttpResponse {
+    match trash_service
+        .get_trashed_images(
+            token.user_info().id,
+            req.offset.map(|v| v as _),
+            req.limit.map(|v| v as _),
+        )
+        .await
+    {
+        Ok(images) => {
+            let retention = trash_service.retention_period();
+
+            HttpResponse::Ok().json(GetTrashedImagesResponse {
+                images: images
+                    .into_iter()
+                    .filter_map(|i| trashed_image_response(i, retention))
+                    .collect(),
+            })
+        }
+        Err(err) => match err {
+            GetTrashError::Unexpected => {
+                HttpResponse::InternalServerError().json(GenericError::default())
+            }
+        },
+    }
+}
+
+#[api]
+#[post("/trash/images/{image_id}/restore")]
+#[tag(TAG_NAME)]
+#[response(204)]
+#[response(404, GenericError)]
+async fn restore_image(
+    token: SessionToken,
+    web::Path((image_id,)): web::Path<(Uuid,)>,
+    trash_service: web::Data<Box<dyn TrashService>>,
+) -> HttpResponse {
+    if let Err(err) = token.require_scope(ApiKeyScope::Upload) {
+        return err.error_response();
+    }
+
+    match trash_service
+        .restore_image(token.user_info().id, image_id)
+        .await
+    {
+        Ok(_) => HttpResponse::NoContent().finish(),
+        Err(err) => trashed_image_error_response(err),
+    }
+}
+
+/// Deletes the image and its file for good.
+#[api]
+#[delete("/trash/images/{image_id}")]
+#[tag(TAG_NAME)]
+#[response(204)]
+#[response(404, GenericError)]
+async fn purge_image(
+    token: SessionToken,
+    web::Path((image_id,)): web::Path<(Uuid,)>,
+    trash_service: web::Data<Box<dyn TrashService>>,
+) -> HttpResponse {
+    if let Err(err) = token.require_scope(ApiKeyScope::Upload) {
+        return err.error_response();
+    }
+
+    match trash_service
+        .purge_image(token.user_info().id, image_id)
+        .await
+    {
+        Ok(_) => HttpResponse::NoContent().finish(),
+        Err(err) => trashed_image_error_response(err),
+    }
+}
+
+#[api]
+#[get("/trash/categories")]
+#[tag(TAG_NAME)]
+#[response(200, GetTrashedCategoriesResponse)]
+#[response(403, GenericError)]
+async fn get_trashed_categories(
+    _permission: RequirePermission<ManageCategories>,
+    trash_service: web::Data<Box<dyn TrashService>>,
+) -> HttpResponse {
+    match trash_service.get_trashed_categories().await {
+        Ok(categories) => {
+            let retention = trash_service.retention_period();
+
+            HttpResponse::Ok().json(GetTrashedCategoriesResponse {
+                categories: categories
+                    .into_iter()
+                    .filter_map(|c| trashed_category_response(c, retention))
+                    .collect(),
+            })
+        }
+        Err(err) => match err {
+            GetTrashError::Unexpected => {
+                HttpResponse::InternalServerError().json(GenericError::default())
+            }
+        },
+    }
+}
+
+/// Restores the category with the subcategories that were deleted together with it.
+#[api]
+#[post("/trash/categories/{category_id}/restore")]
+#[tag(TAG_NAME)]
+#[response(204)]
+#[response(403, GenericError)]
+#[response(404, GenericError)]
+#[response(409, GenericError)]
+async fn restore_category(
+    permission: RequirePermission<ManageCategories>,
+    request_id: RequestId,
+    web::Path((category_id,)): web::Path<(Uuid,)>,
+    trash_service: web::Data<Box<dyn TrashService>>,
+) -> HttpResponse {
+    match trash_service
+        .restore_category(
+            &request_id.audit_context(Some(permission.token().user_info().id)),
+            category_id,
+        )
+        .await
+    {
+        Ok(_) => HttpResponse::NoContent().finish(),
+        Err(err) => match err {
+            RestoreCategoryError::CategoryNotFound => HttpResponse::NotFound().json(GenericError {
+                message: err.to_string(),
+            }),
+            RestoreCategoryError::ParentDeleted | RestoreCategoryError::AlreadyExists => {
+                HttpResponse::Conflict().json(GenericError {
+                    message: err.to_string(),
+                })
+            }
+            RestoreCategoryError::Unexpected => {
+                HttpResponse::InternalServerError().json(GenericError::default())
+            }
+        },
+    }
+}
+
+pub fn configure_routes(_config: &Config) -> impl FnOnce(&mut ServiceConfig) {
+    move |app: &mut ServiceConfig| {
+        app.service(delete_image);
+        app.service(get_trashed_images);
+        app.service(restore_image);
+        app.service(purge_image);
+        app.service(get_trashed_categories);
+        app.service(restore_category);
+    }
+}
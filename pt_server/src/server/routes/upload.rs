@@ -0,0 +1,242 @@
+use crate::{
+    config::Config,
+    db,
+    model::auth::ApiKeyScope,
+    model::error::GenericError,
+    model::image::{CreateUploadSessionRequest, UploadSession, UploadSessionError},
+    server::extractors::SessionToken,
+    server::routes::image::upload_image_error_response,
+    services::ImageService,
+};
+use actix_web::{
+    get, head, patch, post,
+    web::{self, ServiceConfig},
+    HttpRequest, HttpResponse, ResponseError,
+};
+use aide::openapi::v3::macros::api;
+use aide::openapi::v3::macros::api::define;
+use time::Duration;
+use uuid::Uuid;
+
+const TAG_NAME: &str = "uploads";
+
+define::tag! {
+    name(TAG_NAME),
+    description("Resumable uploads of image files"),
+    display_name("Uploads")
+}
+
+const UPLOAD_OFFSET_HEADER: &str = "Upload-Offset";
+const UPLOAD_LENGTH_HEADER: &str = "Upload-Length";
+const CHUNK_CONTENT_TYPE: &str = "application/offset+octet-stream";
+
+fn upload_session_response(s: db::upload_session::UploadSession) -> UploadSession {
+    UploadSession {
+        id: s.id,
+        image_id: s.image_id,
+        length: s.upload_length as _,
+        offset: s.upload_offset as _,
+        expires: s.created + Duration::hours(db::upload_session::UPLOAD_SESSION_TTL_HOURS),
+    }
+}
+
+fn upload_session_error_response(err: UploadSessionError) -> HttpResponse {
+    match err {
+        UploadSessionError::NotFound | UploadSessionError::ImageNotFound => {
+            HttpResponse::NotFound().json(GenericError {
+                message: err.to_string(),
+            })
+        }
+        UploadSessionError::NotAllowed => HttpResponse::Forbidden().json(GenericError {
+            message: err.to_string(),
+        }),
+        UploadSessionError::AlreadyUploaded
+        | UploadSessionError::InvalidLength
+        | UploadSessionError::InvalidChecksum
+        | UploadSessionError::Incomplete
+        | UploadSessionError::ChecksumMismatch => HttpResponse::BadRequest().json(GenericError {
+            message: err.to_string(),
+        }),
+        UploadSessionError::OffsetMismatch | UploadSessionError::Finishing => {
+            HttpResponse::Conflict().json(GenericError {
+                message: err.to_string(),
+            })
+        }
+        UploadSessionError::ExceedsLength => HttpResponse::PayloadTooLarge().json(GenericError {
+            message: err.to_string(),
+        }),
+        UploadSessionError::Upload(err) => upload_image_error_response(err),
+        UploadSessionError::Unexpected => {
+            HttpResponse::InternalServerError().json(GenericError::default())
+        }
+    }
+}
+
+/// Starts a resumable upload, an alternative to uploading the file in one request.
+///
+/// The chunks are sent with `PATCH /uploads/{upload_id}` requests
+/// with the `Upload-Offset` header and `application/offset+octet-stream` content type,
+/// `HEAD /uploads/{upload_id}` returns the current offset in the same header.
+#[api]
+#[post("/images/{image_id}/uploads")]
+#[tag(TAG_NAME)]
+#[response(201, UploadSession)]
+#[response(400, GenericError)]
+#[response(403, GenericError)]
+#[response(404, GenericError)]
+#[response(413, GenericError)]
+async fn create_upload_session(
+    token: SessionToken,
+    web::Path((image_id,)): web::Path<(Uuid,)>,
+    req: web::Json<CreateUploadSessionRequest>,
+    image_service: web::Data<Box<dyn ImageService>>,
+) -> HttpResponse {
+    if let Err(err) = token.require_scope(ApiKeyScope::Upload) {
+        return err.error_response();
+    }
+
+    match image_service
+        .create_upload_session(
+            token.user_info().id,
+            image_id,
+            req.length,
+            &req.checksum,
+            req.filename.as_deref(),
+        )
+        .await
+    {
+        Ok(session) => HttpResponse::Created()
+            .header("Location", format!("/uploads/{}", session.id))
+            .header(UPLOAD_OFFSET_HEADER, session.upload_offset.to_string())
+            .json(upload_session_response(session)),
+        Err(err) => upload_session_error_response(err),
+    }
+}
+
+#[api]
+#[get("/uploads/{upload_id}")]
+#[tag(TAG_NAME)]
+#[response(200, UploadSession)]
+#[response(404, GenericError)]
+async fn get_upload_session(
+    token: SessionToken,
+    web::Path((upload_id,)): web::Path<(Uuid,)>,
+    image_service: web::Data<Box<dyn ImageService>>,
+) -> HttpResponse {
+    match image_service
+        .get_upload_session(token.user_info().id, upload_id)
+        .await
+    {
+        Ok(session) => HttpResponse::Ok().json(upload_session_response(session)),
+        Err(err) => upload_session_error_response(err),
+    }
+}
+
+// HEAD and PATCH are not part of the generated API docs,
+// they are described at `create_upload_session` instead.
+
+#[head("/uploads/{upload_id}")]
+async fn get_upload_offset(
+    token: SessionToken,
+    web::Path((upload_id,)): web::Path<(Uuid,)>,
+    image_service: web::Data<Box<dyn ImageService>>,
+) -> HttpResponse {
+    match image_service
+        .get_upload_session(token.user_info().id, upload_id)
+        .await
+    {
+        Ok(session) => HttpResponse::Ok()
+            .header(UPLOAD_OFFSET_HEADER, session.upload_offset.to_string())
+            .header(UPLOAD_LENGTH_HEADER, session.upload_length.to_string())
+            .header("Cache-Control", "no-store")
+            .finish(),
+        Err(err) => HttpResponse::build(upload_session_error_response(err).status()).finish(),
+    }
+}
+
+#[patch("/uploads/{upload_id}")]
+async fn append_upload_chunk(
+    token: SessionToken,
+    web::Path((upload_id,)): web::Path<(Uuid,)>,
+    http_req: HttpRequest,
+    payload: web::Payload,
+    image_service: web::Data<Box<dyn ImageService>>,
+) -> HttpResponse {
+    if let Err(err) = token.require_scope(ApiKeyScope::Upload) {
+        return err.error_response();
+    }
+
+    let content_type = http_req
+        .headers()
+        .get("Content-Type")
+        .and_then(|v| v.to_str().ok());
+
+    if content_type != Some(CHUNK_CONTENT_TYPE) {
+        return HttpResponse::UnsupportedMediaType().json(GenericError {
+            message: format!("the content type must be {}", CHUNK_CONTENT_TYPE),
+        });
+    }
+
+    let offset = match http_req
+        .headers()
+        .get(UPLOAD_OFFSET_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok())
+    {
+        Some(o) => o,
+        None => {
+            return HttpResponse::BadRequest().json(GenericError {
+                message: format!("the {} header is required", UPLOAD_OFFSET_HEADER),
+            })
+        }
+    };
+
+    match image_service
+        .append_upload_chunk(token.user_info().id, upload_id, offset, payload)
+        .await
+    {
+        Ok(offset) => HttpResponse::NoContent()
+            .header(UPLOAD_OFFSET_HEADER, offset.to_string())
+            .finish(),
+        Err(err) => upload_session_error_response(err),
+    }
+}
+
+/// Verifies the checksum of the received file and stores it as the file of the image.
+///
+/// The session is removed once the file is stored. If the checksum does not match,
+/// its offset is reset to zero and the file has to be sent again.
+#[api]
+#[post("/uploads/{upload_id}/finish")]
+#[tag(TAG_NAME)]
+#[response(204)]
+#[response(400, GenericError)]
+#[response(404, GenericError)]
+#[response(409, GenericError)]
+async fn finish_upload_session(
+    token: SessionToken,
+    web::Path((upload_id,)): web::Path<(Uuid,)>,
+    image_service: web::Data<Box<dyn ImageService>>,
+) -> HttpResponse {
+    if let Err(err) = token.require_scope(ApiKeyScope::Upload) {
+        return err.error_response();
+    }
+
+    match image_service
+        .finish_upload_session(token.user_info().id, upload_id)
+        .await
+    {
+        Ok(_) => HttpResponse::NoContent().finish(),
+        Err(err) => upload_session_error_response(err),
+    }
+}
+
+pub fn configure_routes(_config: &Config) -> impl FnOnce(&mut ServiceConfig) {
+    move |app: &mut ServiceConfig| {
+        app.service(create_upload_session);
+        app.service(get_upload_session);
+        app.service(get_upload_offset);
+        app.service(append_upload_chunk);
+        app.service(finish_upload_session);
+    }
+}
@@ -0,0 +1,340 @@
+use crate::{
+    config::Config,
+    model::error::GenericError,
+    model::role::{SetRoleError, SetRoleRequest},
+    model::user::{
+        AccountExport, AccountExportError, DeleteAccountError, DeleteAccountRequest,
+        SetStorageQuotaError, SetStorageQuotaRequest, StorageUsage, StorageUsageError,
+        UserSettings, UserSettingsError,
+    },
+    server::extractors::{ManageUsers, RequestId, RequirePermission, SessionToken},
+    services::{AccountService, AuthService, ImageService},
+};
+use actix_web::{
+    delete, get, post, put,
+    web::{self, ServiceConfig},
+    HttpRequest, HttpResponse,
+};
+use aide::openapi::v3::macros::api;
+use aide::openapi::v3::macros::api::define;
+use slog::{error, Logger};
+use uuid::Uuid;
+
+const TAG_NAME: &str = "users";
+
+define::tag! {
+    name(TAG_NAME),
+    description("Operations with users"),
+    display_name("Users")
+}
+
+/// Changes the role of a user.
+///
+/// The user has to log in again for the change to take effect.
+#[api]
+#[put("/users/{user_id}/role")]
+#[tag(TAG_NAME)]
+#[response(204)]
+#[response(400, GenericError)]
+#[response(404, GenericError)]
+async fn set_role(
+    permission: RequirePermission<ManageUsers>,
+    request_id: RequestId,
+    web::Path((user_id,)): web::Path<(Uuid,)>,
+    req: web::Json<SetRoleRequest>,
+    auth_service: web::Data<Box<dyn AuthService>>,
+) -> HttpResponse {
+    // Prevents locking out the last admin by accident.
+    if permission.token().user_info().id == user_id {
+        return HttpResponse::BadRequest().json(GenericError {
+            message: SetRoleError::OwnRole.to_string(),
+        });
+    }
+
+    let context = request_id.audit_context(Some(permission.token().user_info().id));
+
+    match auth_service.set_role(&context, user_id, req.role).await {
+        Ok(_) => HttpResponse::NoContent().finish(),
+        Err(err) => match err {
+            SetRoleError::UserNotFound => HttpResponse::NotFound().json(GenericError {
+                message: err.to_string(),
+            }),
+            SetRoleError::OwnRole => HttpResponse::BadRequest().json(GenericError {
+                message: err.to_string(),
+            }),
+            SetRoleError::Unexpected => {
+                HttpResponse::InternalServerError().json(GenericError::default())
+            }
+        },
+    }
+}
+
+#[api]
+#[get("/users/me/settings")]
+#[tag(TAG_NAME)]
+#[response(200, UserSettings)]
+#[response(404, GenericError)]
+async fn get_settings(
+    token: SessionToken,
+    auth_service: web::Data<Box<dyn AuthService>>,
+) -> HttpResponse {
+    match auth_service.get_settings(token.user_info().id).await {
+        Ok(settings) => HttpResponse::Ok().json(settings),
+        Err(err) => settings_error_response(err),
+    }
+}
+
+/// Only applies to images uploaded after the change.
+#[api]
+#[put("/users/me/settings")]
+#[tag(TAG_NAME)]
+#[response(204)]
+#[response(403, GenericError)]
+#[response(404, GenericError)]
+async fn update_settings(
+    token: SessionToken,
+    req: web::Json<UserSettings>,
+    auth_service: web::Data<Box<dyn AuthService>>,
+) -> HttpResponse {
+    if token.is_api_key() {
+        return settings_error_response(UserSettingsError::NotAllowed);
+    }
+
+    match auth_service
+        .update_settings(token.user_info().id, req.into_inner())
+        .await
+    {
+        Ok(_) => HttpResponse::NoContent().finish(),
+        Err(err) => settings_error_response(err),
+    }
+}
+
+fn settings_error_response(err: UserSettingsError) -> HttpResponse {
+    match err {
+        UserSettingsError::UserNotFound => HttpResponse::NotFound().json(GenericError {
+            message: err.to_string(),
+        }),
+        UserSettingsError::NotAllowed => HttpResponse::Forbidden().json(GenericError {
+            message: err.to_string(),
+        }),
+        UserSettingsError::Unexpected => {
+            HttpResponse::InternalServerError().json(GenericError::default())
+        }
+    }
+}
+
+/// The storage used by the images of the user and the limits that apply to them.
+#[api]
+#[get("/users/me/usage")]
+#[tag(TAG_NAME)]
+#[response(200, StorageUsage)]
+async fn get_usage(
+    token: SessionToken,
+    image_service: web::Data<Box<dyn ImageService>>,
+) -> HttpResponse {
+    match image_service.get_storage_usage(token.user_info().id).await {
+        Ok(usage) => HttpResponse::Ok().json(usage),
+        Err(err) => match err {
+            StorageUsageError::Unexpected => {
+                HttpResponse::InternalServerError().json(GenericError::default())
+            }
+        },
+    }
+}
+
+/// Overrides the configured storage limits for the user.
+#[api]
+#[put("/users/{user_id}/quota")]
+#[tag(TAG_NAME)]
+#[response(204)]
+#[response(400, GenericError)]
+#[response(404, GenericError)]
+async fn set_quota(
+    _: RequirePermission<ManageUsers>,
+    web::Path((user_id,)): web::Path<(Uuid,)>,
+    req: web::Json<SetStorageQuotaRequest>,
+    image_service: web::Data<Box<dyn ImageService>>,
+) -> HttpResponse {
+    match image_service
+        .set_storage_quota(user_id, req.max_bytes, req.max_images)
+        .await
+    {
+        Ok(_) => HttpResponse::NoContent().finish(),
+        Err(err) => match err {
+            SetStorageQuotaError::UserNotFound => HttpResponse::NotFound().json(GenericError {
+                message: err.to_string(),
+            }),
+            SetStorageQuotaError::InvalidLimit => HttpResponse::BadRequest().json(GenericError {
+                message: err.to_string(),
+            }),
+            SetStorageQuotaError::Unexpected => {
+                HttpResponse::InternalServerError().json(GenericError::default())
+            }
+        },
+    }
+}
+
+/// Starts building a ZIP archive of the profile, the original images
+/// with their details and the ratings of the user.
+///
+/// The status of the export can be polled until it is ready for download,
+/// earlier exports are deleted.
+#[api]
+#[post("/users/me/export")]
+#[tag(TAG_NAME)]
+#[response(202, AccountExport)]
+#[response(403, GenericError)]
+#[response(409, GenericError)]
+async fn request_export(
+    token: SessionToken,
+    account_service: web::Data<Box<dyn AccountService>>,
+) -> HttpResponse {
+    if token.is_api_key() {
+        return export_error_response(AccountExportError::NotAllowed);
+    }
+
+    match account_service.request_export(token.user_info().id).await {
+        Ok(export) => HttpResponse::Accepted().json(export),
+        Err(err) => export_error_response(err),
+    }
+}
+
+#[api]
+#[get("/users/me/export/{export_id}")]
+#[tag(TAG_NAME)]
+#[response(200, AccountExport)]
+#[response(403, GenericError)]
+#[response(404, GenericError)]
+async fn get_export(
+    token: SessionToken,
+    web::Path((export_id,)): web::Path<(Uuid,)>,
+    account_service: web::Data<Box<dyn AccountService>>,
+) -> HttpResponse {
+    if token.is_api_key() {
+        return export_error_response(AccountExportError::NotAllowed);
+    }
+
+    match account_service
+        .get_export(token.user_info().id, export_id)
+        .await
+    {
+        Ok(export) => HttpResponse::Ok().json(export),
+        Err(err) => export_error_response(err),
+    }
+}
+
+#[api]
+#[get("/users/me/export/{export_id}/download")]
+#[tag(TAG_NAME)]
+#[response(status(200), content_type("application/zip"))]
+#[response(403, GenericError)]
+#[response(404, GenericError)]
+#[response(409, GenericError)]
+async fn download_export(
+    token: SessionToken,
+    web::Path((export_id,)): web::Path<(Uuid,)>,
+    req: HttpRequest,
+    logger: web::Data<Logger>,
+    account_service: web::Data<Box<dyn AccountService>>,
+) -> HttpResponse {
+    if token.is_api_key() {
+        return export_error_response(AccountExportError::NotAllowed);
+    }
+
+    match account_service
+        .download_export(token.user_info().id, export_id)
+        .await
+    {
+        Ok(f) => match f.into_response(&req) {
+            Ok(res) => res,
+            Err(err) => {
+                error!(logger, "error serving the export";
+                    "error" => err.to_string()
+                );
+                HttpResponse::InternalServerError().json(GenericError::default())
+            }
+        },
+        Err(err) => export_error_response(err),
+    }
+}
+
+fn export_error_response(err: AccountExportError) -> HttpResponse {
+    match err {
+        AccountExportError::ExportNotFound => HttpResponse::NotFound().json(GenericError {
+            message: err.to_string(),
+        }),
+        AccountExportError::NotAllowed => HttpResponse::Forbidden().json(GenericError {
+            message: err.to_string(),
+        }),
+        AccountExportError::ExportInProgress | AccountExportError::ExportNotReady => {
+            HttpResponse::Conflict().json(GenericError {
+                message: err.to_string(),
+            })
+        }
+        AccountExportError::Unexpected => {
+            HttpResponse::InternalServerError().json(GenericError::default())
+        }
+    }
+}
+
+/// Deletes the account with the images, albums and ratings of the user.
+///
+/// Comments, reports and moderation actions are kept without the author.
+#[api]
+#[delete("/users/me")]
+#[tag(TAG_NAME)]
+#[response(204)]
+#[response(403, GenericError)]
+#[response(404, GenericError)]
+async fn delete_account(
+    token: SessionToken,
+    request_id: RequestId,
+    req: web::Json<DeleteAccountRequest>,
+    account_service: web::Data<Box<dyn AccountService>>,
+) -> HttpResponse {
+    if token.is_api_key() {
+        return HttpResponse::Forbidden().json(GenericError {
+            message: DeleteAccountError::NotAllowed.to_string(),
+        });
+    }
+
+    let user = token.user_info();
+
+    match account_service
+        .delete_account(
+            &request_id.audit_context(Some(user.id)),
+            user,
+            req.password.as_deref(),
+        )
+        .await
+    {
+        Ok(_) => HttpResponse::NoContent().finish(),
+        Err(err) => match err {
+            DeleteAccountError::UserNotFound => HttpResponse::NotFound().json(GenericError {
+                message: err.to_string(),
+            }),
+            DeleteAccountError::IncorrectPassword
+            | DeleteAccountError::LoginRequired
+            | DeleteAccountError::NotAllowed => HttpResponse::Forbidden().json(GenericError {
+                message: err.to_string(),
+            }),
+            DeleteAccountError::Unexpected => {
+                HttpResponse::InternalServerError().json(GenericError::default())
+            }
+        },
+    }
+}
+
+pub fn configure_routes(_config: &Config) -> impl FnOnce(&mut ServiceConfig) {
+    move |app: &mut ServiceConfig| {
+        app.service(set_role);
+        app.service(get_settings);
+        app.service(update_settings);
+        app.service(get_usage);
+        app.service(set_quota);
+        app.service(request_export);
+        app.service(get_export);
+        app.service(download_export);
+        app.service(delete_account);
+    }
+}
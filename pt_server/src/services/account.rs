@@ -0,0 +1,581 @@
+use super::{
+    audit::{record_audit_event, AuditChange, AuditContext, AUDIT_TARGET_USER},
+    auth::UserInfo,
+    image::upload_part_path,
+    Service,
+};
+use crate::{
+    config::Config,
+    db::account_export::AccountExport as DbAccountExport,
+    db::app_user::AppUser,
+    db::image::Image,
+    db::rating::Rating,
+    db::upload_session::UploadSession,
+    jobs::{self, Job},
+    model::audit::AuditAction,
+    model::user::AccountExport,
+    model::user::AccountExportError,
+    model::user::AccountExportStatus,
+    model::user::DeleteAccountError,
+};
+use actix_files::NamedFile;
+use actix_web::web;
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use slog::{error, info, warn, Logger};
+use sqlx::PgPool;
+use std::{
+    collections::{HashMap, HashSet},
+    ffi::OsStr,
+    io,
+    path::{Path, PathBuf},
+};
+use time::{Format, OffsetDateTime};
+use tokio::fs;
+use uuid::Uuid;
+use zip::{write::FileOptions, CompressionMethod, ZipWriter};
+
+#[async_trait(?Send)]
+pub trait AccountService: Service {
+    /// Starts building an export of the data of the user in the background.
+    ///
+    /// Earlier exports of the user are deleted.
+    async fn request_export(&self, app_user_id: Uuid) -> Result<AccountExport, AccountExportError>;
+    async fn get_export(
+        &self,
+        app_user_id: Uuid,
+        export_id: Uuid,
+    ) -> Result<AccountExport, AccountExportError>;
+    async fn download_export(
+        &self,
+        app_user_id: Uuid,
+        export_id: Uuid,
+    ) -> Result<NamedFile, AccountExportError>;
+    /// Builds the archive of a pending export, called by the job worker.
+    ///
+    /// A failed export stays pending for another attempt, unless it was the final one.
+    async fn build_export(
+        &self,
+        export_id: Uuid,
+        final_attempt: bool,
+    ) -> Result<(), AccountExportError>;
+    /// Deletes the user with everything they own, including the stored files.
+    ///
+    /// Comments, reports and moderation actions of the user are kept without the author.
+    ///
+    /// Users without a password must have logged in recently.
+    async fn delete_account(
+        &self,
+        context: &AuditContext,
+        user: &UserInfo,
+        password: Option<&str>,
+    ) -> Result<(), DeleteAccountError>;
+}
+dyn_clone::clone_trait_object!(AccountService);
+
+/// How recently users without a password must have logged in to delete their account.
+const DELETE_ACCOUNT_LOGIN_SECONDS: i64 = 5 * 60;
+
+/// Where the archive of the export is stored.
+pub fn export_path(config: &Config, id: Uuid) -> PathBuf {
+    config
+        .export_storage_path
+        .join(id.to_hyphenated().to_string())
+        .with_extension("zip")
+}
+
+fn export_response(export: &DbAccountExport) -> AccountExport {
+    AccountExport {
+        id: export.id,
+        status: export
+            .export_status
+            .parse()
+            .unwrap_or(AccountExportStatus::Failed),
+        created: export.created,
+        completed: export.completed,
+        file_size: export.file_size.map(|s| s as _),
+    }
+}
+
+/// A file in the archive of an export.
+enum ExportEntry {
+    Json(String, Value),
+    /// Copied from the given path.
+    File(String, PathBuf),
+}
+
+/// Writes the archive and returns its size in bytes.
+fn write_export_archive(path: &Path, entries: Vec<ExportEntry>) -> zip::result::ZipResult<u64> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+
+    let mut zip = ZipWriter::new(std::fs::File::create(path)?);
+
+    for entry in entries {
+        match entry {
+            ExportEntry::Json(name, value) => {
+                zip.start_file(name, FileOptions::default())?;
+                serde_json::to_writer_pretty(&mut zip, &value).map_err(io::Error::from)?;
+            }
+            ExportEntry::File(name, source) => {
+                // The images are compressed already.
+                zip.start_file(
+                    name,
+                    FileOptions::default().compression_method(CompressionMethod::Stored),
+                )?;
+                io::copy(&mut std::fs::File::open(source)?, &mut zip)?;
+            }
+        }
+    }
+
+    Ok(zip.finish()?.metadata()?.len())
+}
+
+/// The stored files of the given images by image ID, in a single pass over the storage.
+async fn image_files(config: &Config, ids: &[Uuid]) -> io::Result<HashMap<Uuid, PathBuf>> {
+    let ids: HashSet<&Uuid> = ids.iter().collect();
+    let mut files = HashMap::new();
+
+    let mut entries = match fs::read_dir(&config.image_storage_path).await {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(files),
+        Err(e) => return Err(e),
+    };
+
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+
+        let id = path
+            .file_stem()
+            .and_then(OsStr::to_str)
+            .and_then(|s| s.parse::<Uuid>().ok());
+
+        if let Some(id) = id {
+            if ids.contains(&id) {
+                files.insert(id, path);
+            }
+        }
+    }
+
+    Ok(files)
+}
+
+#[derive(Debug, Clone)]
+pub struct DefaultAccountService {
+    pool: PgPool,
+    logger: Logger,
+    config: Config,
+}
+
+impl DefaultAccountService {
+    pub fn new(config: &Config, logger: Logger, pool: PgPool) -> Self {
+        Self {
+            logger,
+            pool,
+            config: config.clone(),
+        }
+    }
+
+    async fn own_export(
+        &self,
+        app_user_id: Uuid,
+        export_id: Uuid,
+    ) -> Result<DbAccountExport, AccountExportError> {
+        DbAccountExport::by_id(export_id, &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                AccountExportError::Unexpected
+            })?
+            .filter(|e| e.app_user_id == app_user_id)
+            .ok_or(AccountExportError::ExportNotFound)
+    }
+
+    /// The profile, the images with their details and the ratings of the user.
+    async fn export_entries(&self, app_user_id: Uuid) -> Result<Vec<ExportEntry>, sqlx::Error> {
+        let user = AppUser::by_id(app_user_id, &self.pool)
+            .await?
+            .ok_or(sqlx::Error::RowNotFound)?;
+
+        let mut entries = vec![ExportEntry::Json(
+            "profile.json".into(),
+            json!({
+                "id": user.id,
+                "email": user.email,
+                "created": user.created.format(Format::Rfc3339),
+                "role": user.user_role,
+                "totpEnabled": user.totp_enabled,
+                "keepImageLocation": user.keep_image_location,
+            }),
+        )];
+
+        let images = Image::all_by_app_user_id(user.id, &self.pool).await?;
+        let ids: Vec<Uuid> = images.iter().map(|i| i.id).collect();
+
+        let mut files = image_files(&self.config, &ids).await?;
+
+        for image in images {
+            let categories = image.categories(&self.pool).await?;
+            let tags = image.tags(&self.pool).await?;
+            let metadata = image.metadata(&self.pool).await?;
+
+            let file = files.remove(&image.id).map(|path| {
+                let name = format!(
+                    "images/{}.{}",
+                    image.id.to_hyphenated(),
+                    path.extension().and_then(OsStr::to_str).unwrap_or("bin")
+                );
+
+                (name, path)
+            });
+
+            entries.push(ExportEntry::Json(
+                format!("images/{}.json", image.id.to_hyphenated()),
+                json!({
+                    "id": image.id,
+                    "title": image.title,
+                    "description": image.description,
+                    "created": image.created.format(Format::Rfc3339),
+                    "uploaded": image.upload_date.map(|d| d.format(Format::Rfc3339)),
+                    "visibility": image.visibility,
+                    "latitude": image.latitude,
+                    "longitude": image.longitude,
+                    "deleted": image.deleted.map(|d| d.format(Format::Rfc3339)),
+                    "categories": categories.iter().map(|c| &c.category_name).collect::<Vec<_>>(),
+                    "tags": tags.iter().map(|t| &t.tag_name).collect::<Vec<_>>(),
+                    "exif": metadata.map(|m| json!({
+                        "cameraMake": m.camera_make,
+                        "cameraModel": m.camera_model,
+                        "lensModel": m.lens_model,
+                        "exposureTime": m.exposure_time,
+                        "fNumber": m.f_number,
+                        "iso": m.iso,
+                        "focalLength": m.focal_length,
+                        "captured": m.captured.map(|d| d.format(Format::Rfc3339)),
+                        "orientation": m.orientation,
+                    })),
+                    "file": file.as_ref().map(|f| &f.0),
+                }),
+            ));
+
+            if let Some((name, path)) = file {
+                entries.push(ExportEntry::File(name, path));
+            }
+        }
+
+        let ratings = Rating::by_app_user_id(user.id, &self.pool).await?;
+
+        entries.push(ExportEntry::Json(
+            "ratings.json".into(),
+            Value::Array(
+                ratings
+                    .into_iter()
+                    .map(|r| {
+                        json!({
+                            "imageId": r.image_id,
+                            "rating": r.rating,
+                        })
+                    })
+                    .collect(),
+            ),
+        ));
+
+        Ok(entries)
+    }
+
+    /// Deletes the export with its archive.
+    async fn remove_export(&self, export: &DbAccountExport) -> Result<(), sqlx::Error> {
+        export.delete(&self.pool).await?;
+        self.remove_file(&export_path(&self.config, export.id))
+            .await;
+        Ok(())
+    }
+
+    /// Missing files are ignored, other errors are only logged.
+    async fn remove_file(&self, path: &Path) {
+        if let Err(e) = fs::remove_file(path).await {
+            if e.kind() != io::ErrorKind::NotFound {
+                warn!(&self.logger, "error removing a file";
+                    "error" => e.to_string(),
+                    "path" => path.to_string_lossy().to_string()
+                );
+            }
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl AccountService for DefaultAccountService {
+    async fn request_export(&self, app_user_id: Uuid) -> Result<AccountExport, AccountExportError> {
+        let exports = DbAccountExport::by_app_user_id(app_user_id, &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                AccountExportError::Unexpected
+            })?;
+
+        if exports
+            .iter()
+            .any(|e| e.export_status == AccountExportStatus::Pending.as_str())
+        {
+            return Err(AccountExportError::ExportInProgress);
+        }
+
+        for export in &exports {
+            self.remove_export(export).await.map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                AccountExportError::Unexpected
+            })?;
+        }
+
+        let export = DbAccountExport::new(app_user_id, &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                AccountExportError::Unexpected
+            })?;
+
+        let job = Job::BuildAccountExport {
+            export_id: export.id,
+        };
+
+        if let Err(e) = jobs::enqueue(&job, &self.pool).await {
+            error!(&self.logger, "unexpected database error";
+                "error" => e.to_string()
+            );
+
+            // It would stay pending forever.
+            let _ = export.delete(&self.pool).await;
+
+            return Err(AccountExportError::Unexpected);
+        }
+
+        Ok(export_response(&export))
+    }
+
+    async fn build_export(
+        &self,
+        export_id: Uuid,
+        final_attempt: bool,
+    ) -> Result<(), AccountExportError> {
+        let export = DbAccountExport::by_id(export_id, &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                AccountExportError::Unexpected
+            })?;
+
+        let export = match export {
+            Some(e) if e.export_status == AccountExportStatus::Pending.as_str() => e,
+            // Replaced by a newer export or deleted with the account.
+            _ => return Ok(()),
+        };
+
+        let path = export_path(&self.config, export.id);
+
+        let res = match self.export_entries(export.app_user_id).await {
+            Ok(entries) => {
+                let path = path.clone();
+                web::block(move || write_export_archive(&path, entries))
+                    .await
+                    .map_err(|e| e.to_string())
+            }
+            Err(e) => Err(e.to_string()),
+        };
+
+        let (status, file_size) = match res {
+            Ok(size) => {
+                info!(&self.logger, "account export is ready";
+                    "export_id" => export.id.to_string(),
+                    "bytes" => size
+                );
+                (AccountExportStatus::Ready, Some(size as i64))
+            }
+            Err(e) => {
+                error!(&self.logger, "error building the account export";
+                    "error" => e,
+                    "export_id" => export.id.to_string()
+                );
+
+                // A partially written archive is useless.
+                let _ = fs::remove_file(&path).await;
+
+                if !final_attempt {
+                    return Err(AccountExportError::Unexpected);
+                }
+
+                (AccountExportStatus::Failed, None)
+            }
+        };
+
+        export
+            .complete(status.as_str(), file_size, &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                AccountExportError::Unexpected
+            })?;
+
+        match status {
+            AccountExportStatus::Failed => Err(AccountExportError::Unexpected),
+            _ => Ok(()),
+        }
+    }
+
+    async fn get_export(
+        &self,
+        app_user_id: Uuid,
+        export_id: Uuid,
+    ) -> Result<AccountExport, AccountExportError> {
+        self.own_export(app_user_id, export_id)
+            .await
+            .map(|e| export_response(&e))
+    }
+
+    async fn download_export(
+        &self,
+        app_user_id: Uuid,
+        export_id: Uuid,
+    ) -> Result<NamedFile, AccountExportError> {
+        let export = self.own_export(app_user_id, export_id).await?;
+
+        if export.export_status != AccountExportStatus::Ready.as_str() {
+            return Err(AccountExportError::ExportNotReady);
+        }
+
+        NamedFile::open(export_path(&self.config, export.id)).map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => AccountExportError::ExportNotFound,
+            _ => {
+                error!(&self.logger, "error opening the export";
+                    "error" => e.to_string()
+                );
+                AccountExportError::Unexpected
+            }
+        })
+    }
+
+    async fn delete_account(
+        &self,
+        context: &AuditContext,
+        user_info: &UserInfo,
+        password: Option<&str>,
+    ) -> Result<(), DeleteAccountError> {
+        let user = AppUser::by_id(user_info.id, &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                DeleteAccountError::Unexpected
+            })?
+            .ok_or(DeleteAccountError::UserNotFound)?;
+
+        if user.password_hash.is_empty() {
+            let now = OffsetDateTime::now_utc().timestamp();
+
+            if !user_info
+                .logged_in
+                .map_or(false, |t| now - t <= DELETE_ACCOUNT_LOGIN_SECONDS)
+            {
+                return Err(DeleteAccountError::LoginRequired);
+            }
+        } else {
+            let password = password.unwrap_or_default().trim();
+
+            if !argon2::verify_encoded(&user.password_hash, password.as_bytes()).unwrap_or(false) {
+                return Err(DeleteAccountError::IncorrectPassword);
+            }
+        }
+
+        // The rows are deleted with the user, the files have to be collected first.
+        let images = Image::all_by_app_user_id(user.id, &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                DeleteAccountError::Unexpected
+            })?;
+
+        let upload_sessions = UploadSession::by_app_user_id(user.id, &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                DeleteAccountError::Unexpected
+            })?;
+
+        let exports = DbAccountExport::by_app_user_id(user.id, &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                DeleteAccountError::Unexpected
+            })?;
+
+        user.delete(&self.pool).await.map_err(|e| {
+            error!(&self.logger, "unexpected database error";
+                "error" => e.to_string()
+            );
+            DeleteAccountError::Unexpected
+        })?;
+
+        // Nothing personal is recorded, the log cannot be changed later.
+        record_audit_event(
+            &self.pool,
+            &self.logger,
+            context,
+            AuditChange {
+                action: AuditAction::AccountDelete,
+                target_type: AUDIT_TARGET_USER,
+                target_id: user.id.to_string(),
+                before: None,
+                after: None,
+            },
+        )
+        .await;
+
+        let ids: Vec<Uuid> = images.iter().map(|i| i.id).collect();
+
+        match image_files(&self.config, &ids).await {
+            Ok(files) => {
+                for path in files.values() {
+                    self.remove_file(path).await;
+                }
+            }
+            Err(e) => {
+                warn!(&self.logger, "error listing the images of a deleted user";
+                    "error" => e.to_string(),
+                    "app_user_id" => user.id.to_string()
+                );
+            }
+        }
+
+        for session in &upload_sessions {
+            self.remove_file(&upload_part_path(&self.config, session.id))
+                .await;
+        }
+
+        for export in &exports {
+            self.remove_file(&export_path(&self.config, export.id))
+                .await;
+        }
+
+        Ok(())
+    }
+}
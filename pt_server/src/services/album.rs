@@ -0,0 +1,380 @@
+use super::Service;
+use crate::{
+    db::album::{Album, AlbumExt},
+    db::image::{Image, ImageExt},
+    model::album::*,
+    model::Visibility,
+};
+use async_trait::async_trait;
+use slog::{error, Logger};
+use sqlx::PgPool;
+use std::collections::HashSet;
+use uuid::Uuid;
+
+pub const ALBUM_NAME_MAX_LENGTH: usize = 100;
+
+#[async_trait(?Send)]
+pub trait AlbumService: Service {
+    async fn create_album(
+        &self,
+        app_user_id: Uuid,
+        name: &str,
+        visibility: Visibility,
+    ) -> Result<Uuid, CreateAlbumError>;
+    /// Only public albums are returned, unless the owner is the viewer.
+    async fn get_albums(
+        &self,
+        viewer_id: Uuid,
+        owner_id: Uuid,
+    ) -> Result<Vec<AlbumExt>, GetAlbumsError>;
+    async fn get_album(
+        &self,
+        viewer_id: Uuid,
+        id: Uuid,
+        offset: Option<u64>,
+        limit: Option<u64>,
+    ) -> Result<(AlbumExt, Vec<ImageExt>), GetAlbumError>;
+    async fn update_album(
+        &self,
+        app_user_id: Uuid,
+        id: Uuid,
+        name: Option<&str>,
+        visibility: Option<Visibility>,
+    ) -> Result<(), UpdateAlbumError>;
+    async fn delete_album(&self, app_user_id: Uuid, id: Uuid) -> Result<(), DeleteAlbumError>;
+
+    async fn add_album_image(
+        &self,
+        app_user_id: Uuid,
+        id: Uuid,
+        image_id: Uuid,
+    ) -> Result<(), AddAlbumImageError>;
+    async fn remove_album_image(
+        &self,
+        app_user_id: Uuid,
+        id: Uuid,
+        image_id: Uuid,
+    ) -> Result<(), RemoveAlbumImageError>;
+    async fn reorder_album_images(
+        &self,
+        app_user_id: Uuid,
+        id: Uuid,
+        image_ids: &[Uuid],
+    ) -> Result<(), ReorderAlbumImagesError>;
+}
+dyn_clone::clone_trait_object!(AlbumService);
+
+#[derive(Debug, Clone)]
+pub struct DefaultAlbumService {
+    pool: PgPool,
+    logger: Logger,
+}
+
+impl DefaultAlbumService {
+    pub fn new(logger: Logger, pool: PgPool) -> Self {
+        Self { logger, pool }
+    }
+}
+
+fn valid_album_name(name: &str) -> bool {
+    let len = name.trim().chars().count();
+    len > 0 && len <= ALBUM_NAME_MAX_LENGTH
+}
+
+#[async_trait(?Send)]
+impl AlbumService for DefaultAlbumService {
+    async fn create_album(
+        &self,
+        app_user_id: Uuid,
+        name: &str,
+        visibility: Visibility,
+    ) -> Result<Uuid, CreateAlbumError> {
+        if !valid_album_name(name) {
+            return Err(CreateAlbumError::InvalidName(ALBUM_NAME_MAX_LENGTH));
+        }
+
+        Album::new(app_user_id, name.trim(), visibility.as_str(), &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                CreateAlbumError::Unexpected
+            })
+    }
+
+    async fn get_albums(
+        &self,
+        viewer_id: Uuid,
+        owner_id: Uuid,
+    ) -> Result<Vec<AlbumExt>, GetAlbumsError> {
+        let albums = if viewer_id == owner_id {
+            Album::by_app_user_id(owner_id, &self.pool).await
+        } else {
+            Album::public_by_app_user_id(owner_id, &self.pool).await
+        }
+        .map_err(|e| {
+            error!(&self.logger, "unexpected database error";
+                "error" => e.to_string()
+            );
+            GetAlbumsError::Unexpected
+        })?;
+
+        AlbumExt::from_albums(albums, viewer_id, &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                GetAlbumsError::Unexpected
+            })
+    }
+
+    async fn get_album(
+        &self,
+        viewer_id: Uuid,
+        id: Uuid,
+        offset: Option<u64>,
+        limit: Option<u64>,
+    ) -> Result<(AlbumExt, Vec<ImageExt>), GetAlbumError> {
+        let album = Album::by_id(id, &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                GetAlbumError::Unexpected
+            })?
+            .filter(|a| a.visible_to(viewer_id))
+            .ok_or(GetAlbumError::NotFound)?;
+
+        let images = album
+            .images(
+                viewer_id,
+                offset.map(|v| v as _),
+                limit.map(|v| v as _),
+                &self.pool,
+            )
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                GetAlbumError::Unexpected
+            })?;
+
+        let images = ImageExt::from_images(images, &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                GetAlbumError::Unexpected
+            })?;
+
+        let image_count = album
+            .image_count(viewer_id, &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                GetAlbumError::Unexpected
+            })?;
+
+        Ok((AlbumExt { album, image_count }, images))
+    }
+
+    async fn update_album(
+        &self,
+        app_user_id: Uuid,
+        id: Uuid,
+        name: Option<&str>,
+        visibility: Option<Visibility>,
+    ) -> Result<(), UpdateAlbumError> {
+        let mut album = Album::by_id(id, &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                UpdateAlbumError::Unexpected
+            })?
+            .filter(|a| a.visible_to(app_user_id))
+            .ok_or(UpdateAlbumError::NotFound)?;
+
+        if album.app_user_id != app_user_id {
+            return Err(UpdateAlbumError::NotAllowed);
+        }
+
+        if let Some(name) = name {
+            if !valid_album_name(name) {
+                return Err(UpdateAlbumError::InvalidName(ALBUM_NAME_MAX_LENGTH));
+            }
+
+            album.album_name = name.trim().into();
+        }
+
+        if let Some(visibility) = visibility {
+            album.visibility = visibility.as_str().into();
+        }
+
+        album.save(&self.pool).await.map_err(|e| {
+            error!(&self.logger, "unexpected database error";
+                "error" => e.to_string()
+            );
+            UpdateAlbumError::Unexpected
+        })
+    }
+
+    async fn delete_album(&self, app_user_id: Uuid, id: Uuid) -> Result<(), DeleteAlbumError> {
+        let album = Album::by_id(id, &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                DeleteAlbumError::Unexpected
+            })?
+            .filter(|a| a.visible_to(app_user_id))
+            .ok_or(DeleteAlbumError::NotFound)?;
+
+        if album.app_user_id != app_user_id {
+            return Err(DeleteAlbumError::NotAllowed);
+        }
+
+        album.delete(&self.pool).await.map_err(|e| {
+            error!(&self.logger, "unexpected database error";
+                "error" => e.to_string()
+            );
+            DeleteAlbumError::Unexpected
+        })
+    }
+
+    async fn add_album_image(
+        &self,
+        app_user_id: Uuid,
+        id: Uuid,
+        image_id: Uuid,
+    ) -> Result<(), AddAlbumImageError> {
+        let album = Album::by_id(id, &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                AddAlbumImageError::Unexpected
+            })?
+            .filter(|a| a.visible_to(app_user_id))
+            .ok_or(AddAlbumImageError::AlbumNotFound)?;
+
+        if album.app_user_id != app_user_id {
+            return Err(AddAlbumImageError::NotAllowed);
+        }
+
+        // Images of other users can be added as well.
+        Image::by_id(image_id, &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                AddAlbumImageError::Unexpected
+            })?
+            .filter(|i| i.upload_date.is_some() && i.visible_to(Some(app_user_id)))
+            .ok_or(AddAlbumImageError::ImageNotFound)?;
+
+        album.add_image(image_id, &self.pool).await.map_err(|e| {
+            error!(&self.logger, "unexpected database error";
+                "error" => e.to_string()
+            );
+            AddAlbumImageError::Unexpected
+        })
+    }
+
+    async fn remove_album_image(
+        &self,
+        app_user_id: Uuid,
+        id: Uuid,
+        image_id: Uuid,
+    ) -> Result<(), RemoveAlbumImageError> {
+        let album = Album::by_id(id, &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                RemoveAlbumImageError::Unexpected
+            })?
+            .filter(|a| a.visible_to(app_user_id))
+            .ok_or(RemoveAlbumImageError::AlbumNotFound)?;
+
+        if album.app_user_id != app_user_id {
+            return Err(RemoveAlbumImageError::NotAllowed);
+        }
+
+        let removed = album
+            .remove_image(image_id, &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                RemoveAlbumImageError::Unexpected
+            })?;
+
+        if !removed {
+            return Err(RemoveAlbumImageError::ImageNotFound);
+        }
+
+        Ok(())
+    }
+
+    async fn reorder_album_images(
+        &self,
+        app_user_id: Uuid,
+        id: Uuid,
+        image_ids: &[Uuid],
+    ) -> Result<(), ReorderAlbumImagesError> {
+        let album = Album::by_id(id, &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                ReorderAlbumImagesError::Unexpected
+            })?
+            .filter(|a| a.visible_to(app_user_id))
+            .ok_or(ReorderAlbumImagesError::AlbumNotFound)?;
+
+        if album.app_user_id != app_user_id {
+            return Err(ReorderAlbumImagesError::NotAllowed);
+        }
+
+        let current_ids = album.image_ids(&self.pool).await.map_err(|e| {
+            error!(&self.logger, "unexpected database error";
+                "error" => e.to_string()
+            );
+            ReorderAlbumImagesError::Unexpected
+        })?;
+
+        let new_ids: HashSet<&Uuid> = image_ids.iter().collect();
+
+        if new_ids.len() != image_ids.len()
+            || image_ids.len() != current_ids.len()
+            || !current_ids.iter().all(|id| new_ids.contains(id))
+        {
+            return Err(ReorderAlbumImagesError::ImagesMismatch);
+        }
+
+        album
+            .reorder_images(image_ids, &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                ReorderAlbumImagesError::Unexpected
+            })
+    }
+}
@@ -0,0 +1,124 @@
+use super::Service;
+use crate::{
+    db::audit_event::{AuditEvent, AuditEventFilter, NewAuditEvent},
+    model::audit::{AuditAction, GetAuditEventsError},
+};
+use async_trait::async_trait;
+use slog::{error, Logger};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+pub const AUDIT_TARGET_CATEGORY: &str = "category";
+pub const AUDIT_TARGET_USER: &str = "user";
+
+/// Who made a change, and in which request.
+#[derive(Debug, Clone, Default)]
+pub struct AuditContext {
+    pub actor_id: Option<Uuid>,
+    pub request_id: Option<String>,
+}
+
+impl AuditContext {
+    /// The same request with a known actor, e.g. after a successful login.
+    pub fn with_actor(&self, actor_id: Uuid) -> Self {
+        Self {
+            actor_id: Some(actor_id),
+            request_id: self.request_id.clone(),
+        }
+    }
+}
+
+/// A change to be recorded in the audit log.
+pub struct AuditChange<'a> {
+    pub action: AuditAction,
+    pub target_type: &'a str,
+    pub target_id: String,
+    pub before: Option<serde_json::Value>,
+    pub after: Option<serde_json::Value>,
+}
+
+/// Records the change in the audit log.
+///
+/// Errors are only logged, the change itself has already been made.
+pub async fn record_audit_event(
+    pool: &PgPool,
+    logger: &Logger,
+    context: &AuditContext,
+    change: AuditChange<'_>,
+) {
+    let res = AuditEvent::new(
+        NewAuditEvent {
+            actor_id: context.actor_id,
+            action: change.action.as_str(),
+            target_type: change.target_type,
+            target_id: &change.target_id,
+            before: change.before,
+            after: change.after,
+            request_id: context.request_id.as_deref(),
+        },
+        pool,
+    )
+    .await;
+
+    if let Err(e) = res {
+        error!(logger, "failed to record audit event";
+            "action" => change.action.as_str(),
+            "targetId" => change.target_id,
+            "error" => e.to_string()
+        );
+    }
+}
+
+#[async_trait(?Send)]
+pub trait AuditService: Service {
+    /// The latest first.
+    async fn get_audit_events(
+        &self,
+        filter: AuditEventFilter,
+        offset: Option<u64>,
+        limit: Option<u64>,
+    ) -> Result<Vec<AuditEvent>, GetAuditEventsError>;
+}
+dyn_clone::clone_trait_object!(AuditService);
+
+#[derive(Debug, Clone)]
+pub struct DefaultAuditService {
+    pool: PgPool,
+    logger: Logger,
+}
+
+impl DefaultAuditService {
+    pub fn new(logger: Logger, pool: PgPool) -> Self {
+        Self { logger, pool }
+    }
+}
+
+#[async_trait(?Send)]
+impl AuditService for DefaultAuditService {
+    async fn get_audit_events(
+        &self,
+        filter: AuditEventFilter,
+        offset: Option<u64>,
+        limit: Option<u64>,
+    ) -> Result<Vec<AuditEvent>, GetAuditEventsError> {
+        if let (Some(from), Some(to)) = (filter.from, filter.to) {
+            if from >= to {
+                return Err(GetAuditEventsError::InvalidRange);
+            }
+        }
+
+        AuditEvent::search(
+            &filter,
+            offset.map(|v| v as _),
+            limit.map(|v| v as _),
+            &self.pool,
+        )
+        .await
+        .map_err(|e| {
+            error!(&self.logger, "unexpected database error";
+                "error" => e.to_string()
+            );
+            GetAuditEventsError::Unexpected
+        })
+    }
+}
@@ -1,22 +1,111 @@
 use crate::{
-    config::Config, db::app_user::AppUser, model::auth::LoginError, model::auth::RegisterError,
-    util::random_string, util::validate_email,
+    config::Config,
+    db::api_key::{ApiKey, NewApiKey},
+    db::app_user::AppUser,
+    db::recovery_code::RecoveryCode,
+    db::user_identity::{OidcLogin, UserIdentity},
+    model::audit::AuditAction,
+    model::auth::LoginError,
+    model::auth::RegisterError,
+    model::auth::{ApiKeyError, ApiKeyScope, EnrollTotpResponse, OidcError, TokenError, TotpError},
+    model::role::{Permission, Role, SetRoleError},
+    model::user::{UserSettings, UserSettingsError},
+    util::random_string,
+    util::totp,
+    util::validate_email,
 };
 use async_trait::async_trait;
-use jwt::{DecodingKey, EncodingKey, Header, Validation};
+use jwt::{errors::ErrorKind, jwk::JwkSet};
 use serde::{Deserialize, Serialize};
-use slog::{error, Logger};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use slog::{error, warn, Logger};
 use sqlx::PgPool;
+use std::sync::Arc;
 use time::{prelude::*, OffsetDateTime};
+use url::Url;
 use uuid::Uuid;
 
-use super::Service;
+use super::{
+    audit::{record_audit_event, AuditChange, AuditContext, AUDIT_TARGET_USER},
+    Service,
+};
+
+pub mod keys;
+pub mod oidc;
+
+use keys::TokenKeys;
+
+pub(crate) const TOKEN_ISSUER: &str = "pictureTeam";
+
+/// The amount of recovery codes generated when enabling 2FA.
+const RECOVERY_CODE_COUNT: usize = 10;
+const RECOVERY_CODE_LENGTH: usize = 10;
+
+/// Incorrect two-factor codes accepted before the user is locked out.
+const MFA_MAX_FAILED_ATTEMPTS: i32 = 5;
+const MFA_LOCK_MINUTES: i64 = 15;
+
+/// Every API key starts with this, so that they are easy to recognize.
+const API_KEY_PREFIX: &str = "pt_";
+const API_KEY_LENGTH: usize = 40;
+
+/// The amount of characters of a key that are stored in plain text.
+const API_KEY_VISIBLE_LENGTH: usize = 8;
+
+/// The time a user has to finish logging in at the identity provider.
+pub(crate) const OIDC_LOGIN_TIMEOUT_MINUTES: i64 = 10;
+
+/// New single sign-on logins are refused while this many are unfinished.
+const OIDC_MAX_PENDING_LOGINS: i64 = 1000;
 
 /// User info used inside the user tokens.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UserInfo {
     pub id: Uuid,
-    pub admin: bool,
+
+    /// The current role of the user, it is reloaded when the token is validated.
+    #[serde(default)]
+    pub role: Role,
+
+    /// The scopes of the API key that was used,
+    /// `None` for regular sessions that are not restricted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scopes: Option<Vec<ApiKeyScope>>,
+
+    /// When the user logged in, Unix timestamp.
+    /// `None` for API keys and tokens issued before it was recorded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub logged_in: Option<i64>,
+
+    /// When the token expires, Unix timestamp.
+    /// Taken from the claims when the token is validated, `None` for API keys.
+    #[serde(skip)]
+    pub expires: Option<i64>,
+}
+
+impl UserInfo {
+    /// The admin scope includes the read scope.
+    pub fn has_scope(&self, scope: ApiKeyScope) -> bool {
+        match &self.scopes {
+            Some(scopes) => {
+                scopes.contains(&scope)
+                    || (scope == ApiKeyScope::Read && scopes.contains(&ApiKeyScope::Admin))
+            }
+            None => true,
+        }
+    }
+
+    pub fn has_permission(&self, permission: Permission) -> bool {
+        self.role.has_permission(permission)
+    }
+}
+
+impl AppUser {
+    /// Unknown roles are treated as regular users.
+    pub fn role(&self) -> Role {
+        self.user_role.parse().unwrap_or_default()
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -30,23 +119,117 @@ pub struct AppUserTokenClaims {
 
     /// Info about the user.
     pub user: UserInfo,
+
+    /// The password was verified, but a second factor is still required.
+    ///
+    /// These tokens are only accepted by `AuthService::login_mfa`.
+    #[serde(default)]
+    pub mfa_pending: bool,
 }
 
 pub type Token = String;
 
-#[async_trait]
+/// A started single sign-on login.
+#[derive(Debug, Clone)]
+pub struct OidcAuthorization {
+    /// Where the user must be redirected to.
+    pub url: Url,
+    /// Must be kept by the browser of the user and checked in the callback.
+    pub state: String,
+}
+
+/// The result of a successful password check.
+#[derive(Debug, Clone)]
+pub enum LoginOutcome {
+    /// The user is logged in.
+    Authenticated(Token),
+    /// The user has 2FA enabled, the short-lived token
+    /// must be exchanged together with a valid code.
+    MfaRequired(Token),
+}
+
+#[async_trait(?Send)]
 pub trait AuthService: Service {
     async fn register(&self, email: &str, password: &str) -> Result<(), RegisterError>;
-    async fn login(&self, email: &str, password: &str) -> Result<Token, LoginError>;
-    async fn validate_token(&self, token: &str) -> Result<UserInfo, jwt::errors::Error>;
+    async fn login(
+        &self,
+        context: &AuditContext,
+        email: &str,
+        password: &str,
+    ) -> Result<LoginOutcome, LoginError>;
+    async fn login_mfa(
+        &self,
+        context: &AuditContext,
+        mfa_token: &str,
+        code: &str,
+    ) -> Result<Token, LoginError>;
+    /// The role is read from the user, so that role changes apply to existing tokens.
+    async fn validate_token(&self, token: &str) -> Result<UserInfo, TokenError>;
+
+    async fn enroll_totp(&self, app_user_id: Uuid) -> Result<EnrollTotpResponse, TotpError>;
+    async fn confirm_totp(&self, app_user_id: Uuid, code: &str) -> Result<Vec<String>, TotpError>;
+    async fn disable_totp(&self, app_user_id: Uuid, code: &str) -> Result<(), TotpError>;
+
+    /// Returns the ID and the key itself.
+    async fn create_api_key(
+        &self,
+        app_user_id: Uuid,
+        name: &str,
+        scopes: &[ApiKeyScope],
+    ) -> Result<(Uuid, String), ApiKeyError>;
+    async fn get_api_keys(&self, app_user_id: Uuid) -> Result<Vec<ApiKey>, ApiKeyError>;
+    async fn revoke_api_key(&self, app_user_id: Uuid, id: Uuid) -> Result<(), ApiKeyError>;
+    async fn validate_api_key(&self, key: &str) -> Result<UserInfo, ApiKeyError>;
+
+    /// Starts a single sign-on login, the user must be redirected to the returned URL.
+    async fn oidc_authorization_url(&self) -> Result<OidcAuthorization, OidcError>;
+    /// Finishes a single sign-on login, linking or creating the user if needed.
+    async fn oidc_callback(
+        &self,
+        context: &AuditContext,
+        code: &str,
+        state: &str,
+    ) -> Result<LoginOutcome, OidcError>;
+
+    /// The new role also applies to the tokens issued before the change.
+    async fn set_role(
+        &self,
+        context: &AuditContext,
+        app_user_id: Uuid,
+        role: Role,
+    ) -> Result<(), SetRoleError>;
+
+    async fn get_settings(&self, app_user_id: Uuid) -> Result<UserSettings, UserSettingsError>;
+    async fn update_settings(
+        &self,
+        app_user_id: Uuid,
+        settings: UserSettings,
+    ) -> Result<(), UserSettingsError>;
+
+    /// The public keys for verifying the issued tokens.
+    fn jwks(&self) -> JwkSet;
 }
 dyn_clone::clone_trait_object!(AuthService);
 
+/// The result of checking a second factor.
+enum SecondFactor {
+    Valid,
+    Invalid,
+    /// Too many incorrect codes were tried recently.
+    Locked,
+}
+
+/// Recovery codes are generated with `random_string`.
+fn is_recovery_code(code: &str) -> bool {
+    code.len() == RECOVERY_CODE_LENGTH && code.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
 #[derive(Debug, Clone)]
 pub struct DefaultAuthService {
     pool: PgPool,
     logger: Logger,
     config: Config,
+    keys: Arc<TokenKeys>,
 }
 
 impl DefaultAuthService {
@@ -55,11 +238,232 @@ impl DefaultAuthService {
             logger,
             pool,
             config: config.clone(),
+            keys: Arc::new(TokenKeys::from_config(config).expect("invalid token keys")),
+        }
+    }
+
+    fn create_token(&self, user: &AppUser, mfa_pending: bool) -> Result<Token, jwt::errors::Error> {
+        let expires_in = if mfa_pending { 5.minutes() } else { 24.hours() };
+        let now = OffsetDateTime::now_utc();
+
+        let claims = AppUserTokenClaims {
+            exp: (now + expires_in).timestamp() as usize,
+            iss: TOKEN_ISSUER.into(),
+            sub: "appUser".into(),
+            user: UserInfo {
+                id: user.id,
+                role: user.role(),
+                scopes: None,
+                logged_in: Some(now.timestamp()),
+                expires: None,
+            },
+            mfa_pending,
+        };
+
+        self.keys.encode(&claims)
+    }
+
+    fn decode_token(&self, token: &str) -> Result<AppUserTokenClaims, jwt::errors::Error> {
+        self.keys.decode(token)
+    }
+
+    /// Returns the user linked to the external identity.
+    ///
+    /// New identities are linked to the user with the same e-mail address,
+    /// or a new user is created. Either way the provider must have verified the address.
+    async fn user_for_identity(&self, claims: &oidc::IdTokenClaims) -> Result<AppUser, OidcError> {
+        let identity = UserIdentity::by_issuer_subject(&claims.iss, &claims.sub, &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                OidcError::Unexpected
+            })?;
+
+        let app_user_id = match identity {
+            Some(identity) => identity.app_user_id,
+            None => {
+                let email = claims
+                    .email
+                    .as_deref()
+                    .map(|e| e.trim().to_lowercase())
+                    .ok_or(OidcError::MissingEmail)?;
+
+                if claims.email_verified != Some(true) {
+                    warn!(&self.logger, "refusing unverified e-mail";
+                        "issuer" => &claims.iss,
+                        "subject" => &claims.sub
+                    );
+                    return Err(OidcError::UnverifiedEmail);
+                }
+
+                let existing_user = AppUser::by_email(&email, &self.pool).await.map_err(|e| {
+                    error!(&self.logger, "unexpected database error";
+                        "error" => e.to_string()
+                    );
+                    OidcError::Unexpected
+                })?;
+
+                let app_user_id = match existing_user {
+                    Some(user) => user.id,
+                    // Users created this way have no usable password.
+                    None => AppUser::new(&self.pool, &email, "", Role::User.as_str())
+                        .await
+                        .map_err(|e| {
+                            error!(&self.logger, "unexpected database error";
+                                "error" => e.to_string()
+                            );
+                            OidcError::Unexpected
+                        })?,
+                };
+
+                UserIdentity::new(
+                    app_user_id,
+                    &claims.iss,
+                    &claims.sub,
+                    Some(&email),
+                    &self.pool,
+                )
+                .await
+                .map_err(|e| {
+                    error!(&self.logger, "unexpected database error";
+                        "error" => e.to_string()
+                    );
+                    OidcError::Unexpected
+                })?;
+
+                app_user_id
+            }
+        };
+
+        AppUser::by_id(app_user_id, &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                OidcError::Unexpected
+            })?
+            .ok_or(OidcError::Unexpected)
+    }
+
+    fn hash_api_key(key: &str) -> String {
+        format!("{:x}", Sha256::digest(key.as_bytes()))
+    }
+
+    /// Checks either a TOTP code or an unused recovery code of the user.
+    ///
+    /// Every attempt counts towards the limit, and each code is only accepted once.
+    async fn verify_second_factor(
+        &self,
+        user: &AppUser,
+        code: &str,
+    ) -> Result<SecondFactor, sqlx::Error> {
+        let lock_until = OffsetDateTime::now_utc() + MFA_LOCK_MINUTES.minutes();
+
+        if !user
+            .claim_mfa_attempt(MFA_MAX_FAILED_ATTEMPTS, lock_until, &self.pool)
+            .await?
+        {
+            return Ok(SecondFactor::Locked);
+        }
+
+        let code = code.trim();
+
+        let valid = if totp::is_code(code) {
+            self.use_totp_code(user, code).await?
+        } else if is_recovery_code(code) {
+            self.use_recovery_code(user, code).await?
+        } else {
+            false
+        };
+
+        if !valid {
+            return Ok(SecondFactor::Invalid);
+        }
+
+        user.reset_mfa_attempts(&self.pool).await?;
+
+        Ok(SecondFactor::Valid)
+    }
+
+    async fn use_totp_code(&self, user: &AppUser, code: &str) -> Result<bool, sqlx::Error> {
+        let secret = match &user.totp_secret {
+            Some(secret) => secret,
+            None => return Ok(false),
+        };
+
+        let step = totp::verify_step(
+            secret,
+            code,
+            OffsetDateTime::now_utc().timestamp() as u64,
+            user.totp_last_step.map(|s| s as u64),
+        );
+
+        match step {
+            // Another request could have used the code in the meantime.
+            Some(step) => user.use_totp_step(step as i64, &self.pool).await,
+            None => Ok(false),
+        }
+    }
+
+    async fn use_recovery_code(&self, user: &AppUser, code: &str) -> Result<bool, sqlx::Error> {
+        let recovery_codes = RecoveryCode::unused_by_app_user_id(user.id, &self.pool).await?;
+
+        for recovery_code in recovery_codes {
+            if argon2::verify_encoded(&recovery_code.code_hash, code.as_bytes()).unwrap_or(false) {
+                return recovery_code.mark_used(&self.pool).await;
+            }
         }
+
+        Ok(false)
+    }
+
+    /// Records a finished login, or a failed attempt with the reason.
+    ///
+    /// Failed attempts have no actor, the target is the email if the user is unknown.
+    async fn audit_login(
+        &self,
+        context: &AuditContext,
+        user: Option<&AppUser>,
+        email: &str,
+        method: &str,
+        failure: Option<&LoginError>,
+    ) {
+        let (action, context, after) = match failure {
+            Some(err) => (
+                AuditAction::LoginFailed,
+                context.clone(),
+                json!({ "email": email, "method": method, "reason": err.to_string() }),
+            ),
+            None => (
+                AuditAction::Login,
+                user.map(|u| context.with_actor(u.id))
+                    .unwrap_or_else(|| context.clone()),
+                json!({ "email": email, "method": method }),
+            ),
+        };
+
+        record_audit_event(
+            &self.pool,
+            &self.logger,
+            &context,
+            AuditChange {
+                action,
+                target_type: AUDIT_TARGET_USER,
+                target_id: user
+                    .map(|u| u.id.to_string())
+                    .unwrap_or_else(|| email.to_string()),
+                before: None,
+                after: Some(after),
+            },
+        )
+        .await;
     }
 }
 
-#[async_trait]
+#[async_trait(?Send)]
 impl AuthService for DefaultAuthService {
     async fn register(&self, email: &str, password: &str) -> Result<(), RegisterError> {
         let final_email = email.trim().to_lowercase();
@@ -88,22 +492,90 @@ impl AuthService for DefaultAuthService {
             return Err(RegisterError::EmailExists);
         }
 
-        AppUser::new(&self.pool, &final_email, &password_hash, false)
+        AppUser::new(
+            &self.pool,
+            &final_email,
+            &password_hash,
+            Role::User.as_str(),
+        )
+        .await
+        .map_err(|e| {
+            error!(&self.logger, "unexpected database error";
+                "error" => e.to_string()
+            );
+            RegisterError::Unexpected
+        })?;
+
+        Ok(())
+    }
+
+    async fn login(
+        &self,
+        context: &AuditContext,
+        email: &str,
+        password: &str,
+    ) -> Result<LoginOutcome, LoginError> {
+        let final_email = email.trim().to_lowercase();
+
+        let user = AppUser::by_email(&final_email, &self.pool)
             .await
             .map_err(|e| {
                 error!(&self.logger, "unexpected database error";
                     "error" => e.to_string()
                 );
-                RegisterError::Unexpected
+                LoginError::Unexpected
             })?;
 
-        Ok(())
+        let user = match user {
+            Some(user) => user,
+            None => {
+                let err = LoginError::UserNotFound;
+                self.audit_login(context, None, &final_email, "password", Some(&err))
+                    .await;
+                return Err(err);
+            }
+        };
+
+        if !argon2::verify_encoded(&user.password_hash, password.trim().as_bytes()).unwrap_or(false)
+        {
+            let err = LoginError::IncorrectPassword;
+            self.audit_login(context, Some(&user), &user.email, "password", Some(&err))
+                .await;
+            return Err(err);
+        };
+
+        let token = self.create_token(&user, user.totp_enabled).map_err(|e| {
+            error!(&self.logger, "unexpected jwt error";
+                "error" => e.to_string()
+            );
+            LoginError::Unexpected
+        })?;
+
+        // The login is only finished after the second factor.
+        if user.totp_enabled {
+            Ok(LoginOutcome::MfaRequired(token))
+        } else {
+            self.audit_login(context, Some(&user), &user.email, "password", None)
+                .await;
+            Ok(LoginOutcome::Authenticated(token))
+        }
     }
 
-    async fn login(&self, email: &str, password: &str) -> Result<Token, LoginError> {
-        let final_email = email.trim().to_lowercase();
+    async fn login_mfa(
+        &self,
+        context: &AuditContext,
+        mfa_token: &str,
+        code: &str,
+    ) -> Result<Token, LoginError> {
+        let claims = self
+            .decode_token(mfa_token)
+            .map_err(|_| LoginError::InvalidMfaToken)?;
 
-        let user = AppUser::by_email(&final_email, &self.pool)
+        if !claims.mfa_pending {
+            return Err(LoginError::InvalidMfaToken);
+        }
+
+        let user = AppUser::by_id(claims.user.id, &self.pool)
             .await
             .map_err(|e| {
                 error!(&self.logger, "unexpected database error";
@@ -113,41 +585,554 @@ impl AuthService for DefaultAuthService {
             })?
             .ok_or(LoginError::UserNotFound)?;
 
-        if !argon2::verify_encoded(&user.password_hash, password.trim().as_bytes())
-            .map_err(|_| LoginError::IncorrectPassword)?
-        {
-            return Err(LoginError::IncorrectPassword);
+        let second_factor = self.verify_second_factor(&user, code).await.map_err(|e| {
+            error!(&self.logger, "unexpected database error";
+                "error" => e.to_string()
+            );
+            LoginError::Unexpected
+        })?;
+
+        let failure = match second_factor {
+            SecondFactor::Valid => None,
+            SecondFactor::Invalid => Some(LoginError::IncorrectMfaCode),
+            SecondFactor::Locked => Some(LoginError::MfaLocked),
         };
 
-        let claims = AppUserTokenClaims {
-            exp: (OffsetDateTime::now_utc() + 24.hours()).timestamp() as usize,
-            iss: "pictureTeam".into(),
-            sub: "appUser".into(),
-            user: UserInfo {
-                id: user.id,
-                admin: user.is_admin,
+        if let Some(err) = failure {
+            self.audit_login(context, Some(&user), &user.email, "mfa", Some(&err))
+                .await;
+            return Err(err);
+        }
+
+        let token = self.create_token(&user, false).map_err(|e| {
+            error!(&self.logger, "unexpected jwt error";
+                "error" => e.to_string()
+            );
+            LoginError::Unexpected
+        })?;
+
+        self.audit_login(context, Some(&user), &user.email, "mfa", None)
+            .await;
+
+        Ok(token)
+    }
+
+    async fn validate_token(&self, token: &str) -> Result<UserInfo, TokenError> {
+        let claims = self.decode_token(token)?;
+
+        if claims.mfa_pending {
+            return Err(jwt::errors::Error::from(ErrorKind::InvalidToken).into());
+        }
+
+        // Tokens of deleted users are rejected right away.
+        let user = AppUser::by_id(claims.user.id, &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                TokenError::Unexpected
+            })?
+            .ok_or(TokenError::UserNotFound)?;
+
+        Ok(UserInfo {
+            role: user.role(),
+            expires: Some(claims.exp as _),
+            ..claims.user
+        })
+    }
+
+    async fn enroll_totp(&self, app_user_id: Uuid) -> Result<EnrollTotpResponse, TotpError> {
+        let mut user = AppUser::by_id(app_user_id, &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                TotpError::Unexpected
+            })?
+            .ok_or(TotpError::UserNotFound)?;
+
+        if user.totp_enabled {
+            return Err(TotpError::AlreadyEnabled);
+        }
+
+        let secret = totp::generate_secret();
+        user.totp_secret = Some(secret.clone());
+
+        user.save(&self.pool).await.map_err(|e| {
+            error!(&self.logger, "unexpected database error";
+                "error" => e.to_string()
+            );
+            TotpError::Unexpected
+        })?;
+
+        Ok(EnrollTotpResponse {
+            otpauth_uri: totp::otpauth_uri(&secret, TOKEN_ISSUER, &user.email),
+            secret,
+        })
+    }
+
+    async fn confirm_totp(&self, app_user_id: Uuid, code: &str) -> Result<Vec<String>, TotpError> {
+        let user = AppUser::by_id(app_user_id, &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                TotpError::Unexpected
+            })?
+            .ok_or(TotpError::UserNotFound)?;
+
+        if user.totp_enabled {
+            return Err(TotpError::AlreadyEnabled);
+        }
+
+        let secret = user.totp_secret.as_deref().ok_or(TotpError::NotEnrolled)?;
+
+        // The code cannot be used again to log in.
+        let step = totp::verify_step(
+            secret,
+            code,
+            OffsetDateTime::now_utc().timestamp() as u64,
+            None,
+        )
+        .ok_or(TotpError::IncorrectCode)?;
+
+        let recovery_codes: Vec<String> = (0..RECOVERY_CODE_COUNT)
+            .map(|_| random_string(RECOVERY_CODE_LENGTH))
+            .collect();
+
+        let code_hashes: Vec<String> = recovery_codes
+            .iter()
+            .map(|recovery_code| {
+                argon2::hash_encoded(
+                    recovery_code.as_bytes(),
+                    random_string(64).as_bytes(),
+                    &argon2::Config::default(),
+                )
+                .unwrap()
+            })
+            .collect();
+
+        user.enable_totp(step as i64, &code_hashes, &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                TotpError::Unexpected
+            })?;
+
+        Ok(recovery_codes)
+    }
+
+    async fn disable_totp(&self, app_user_id: Uuid, code: &str) -> Result<(), TotpError> {
+        let user = AppUser::by_id(app_user_id, &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                TotpError::Unexpected
+            })?
+            .ok_or(TotpError::UserNotFound)?;
+
+        if !user.totp_enabled {
+            return Err(TotpError::NotEnabled);
+        }
+
+        let second_factor = self.verify_second_factor(&user, code).await.map_err(|e| {
+            error!(&self.logger, "unexpected database error";
+                "error" => e.to_string()
+            );
+            TotpError::Unexpected
+        })?;
+
+        match second_factor {
+            SecondFactor::Valid => {}
+            SecondFactor::Invalid => return Err(TotpError::IncorrectCode),
+            SecondFactor::Locked => return Err(TotpError::Locked),
+        }
+
+        user.disable_totp(&self.pool).await.map_err(|e| {
+            error!(&self.logger, "unexpected database error";
+                "error" => e.to_string()
+            );
+            TotpError::Unexpected
+        })
+    }
+
+    async fn create_api_key(
+        &self,
+        app_user_id: Uuid,
+        name: &str,
+        scopes: &[ApiKeyScope],
+    ) -> Result<(Uuid, String), ApiKeyError> {
+        let name = name.trim();
+
+        if name.is_empty() {
+            return Err(ApiKeyError::InvalidName);
+        }
+
+        if scopes.is_empty() {
+            return Err(ApiKeyError::NoScopes);
+        }
+
+        let mut scope_names: Vec<String> = Vec::with_capacity(scopes.len());
+        for scope in scopes {
+            if !scope_names.iter().any(|s| s == scope.as_str()) {
+                scope_names.push(scope.as_str().into());
+            }
+        }
+
+        let key = format!("{}{}", API_KEY_PREFIX, random_string(API_KEY_LENGTH));
+        let key_hash = Self::hash_api_key(&key);
+
+        let id = ApiKey::new(
+            app_user_id,
+            NewApiKey {
+                key_name: name,
+                key_prefix: &key[..API_KEY_PREFIX.len() + API_KEY_VISIBLE_LENGTH],
+                key_hash: &key_hash,
+                scopes: &scope_names,
             },
-        };
+            &self.pool,
+        )
+        .await
+        .map_err(|e| {
+            error!(&self.logger, "unexpected database error";
+                "error" => e.to_string()
+            );
+            ApiKeyError::Unexpected
+        })?;
+
+        Ok((id, key))
+    }
+
+    async fn get_api_keys(&self, app_user_id: Uuid) -> Result<Vec<ApiKey>, ApiKeyError> {
+        ApiKey::by_app_user_id(app_user_id, &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                ApiKeyError::Unexpected
+            })
+    }
+
+    async fn revoke_api_key(&self, app_user_id: Uuid, id: Uuid) -> Result<(), ApiKeyError> {
+        let key = ApiKey::by_id(id, &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                ApiKeyError::Unexpected
+            })?
+            .ok_or(ApiKeyError::NotFound)?;
+
+        // Other users' keys are reported as missing on purpose.
+        if key.app_user_id != app_user_id || key.revoked.is_some() {
+            return Err(ApiKeyError::NotFound);
+        }
+
+        key.revoke(&self.pool).await.map_err(|e| {
+            error!(&self.logger, "unexpected database error";
+                "error" => e.to_string()
+            );
+            ApiKeyError::Unexpected
+        })
+    }
+
+    async fn validate_api_key(&self, key: &str) -> Result<UserInfo, ApiKeyError> {
+        if !key.starts_with(API_KEY_PREFIX) {
+            return Err(ApiKeyError::Invalid);
+        }
+
+        let api_key = ApiKey::by_key_hash(&Self::hash_api_key(key), &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                ApiKeyError::Unexpected
+            })?
+            .ok_or(ApiKeyError::Invalid)?;
+
+        let user = AppUser::by_id(api_key.app_user_id, &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                ApiKeyError::Unexpected
+            })?
+            .ok_or(ApiKeyError::Invalid)?;
+
+        api_key.update_last_used(&self.pool).await.map_err(|e| {
+            error!(&self.logger, "unexpected database error";
+                "error" => e.to_string()
+            );
+            ApiKeyError::Unexpected
+        })?;
 
-        jwt::encode(
-            &Header::default(),
-            &claims,
-            &EncodingKey::from_secret(self.config.token_secret.as_bytes()),
+        let scopes: Vec<ApiKeyScope> = api_key
+            .scopes
+            .iter()
+            .filter_map(|s| s.parse().ok())
+            .collect();
+
+        Ok(UserInfo {
+            id: user.id,
+            // Keys without the admin scope get no extra permissions.
+            role: if scopes.contains(&ApiKeyScope::Admin) {
+                user.role()
+            } else {
+                Role::User
+            },
+            scopes: Some(scopes),
+            logged_in: None,
+            expires: None,
+        })
+    }
+
+    async fn oidc_authorization_url(&self) -> Result<OidcAuthorization, OidcError> {
+        let (issuer, redirect_url) =
+            match (&self.config.oidc_issuer, &self.config.oidc_redirect_url) {
+                (Some(issuer), Some(redirect_url)) => (issuer, redirect_url),
+                _ => return Err(OidcError::NotConfigured),
+            };
+
+        let metadata = oidc::discover(issuer).await.map_err(|e| {
+            error!(&self.logger, "OpenID Connect discovery failed";
+                "error" => e.to_string()
+            );
+            OidcError::Provider
+        })?;
+
+        let state = random_string(32);
+        let nonce = random_string(32);
+        let code_verifier = random_string(64);
+
+        // Clean up logins that were never finished.
+        OidcLogin::delete_older_than(
+            OffsetDateTime::now_utc() - OIDC_LOGIN_TIMEOUT_MINUTES.minutes(),
+            &self.pool,
         )
+        .await
         .map_err(|e| {
-            error!(&self.logger, "unexpected jwt error";
+            error!(&self.logger, "unexpected database error";
                 "error" => e.to_string()
             );
-            LoginError::Unexpected
+            OidcError::Unexpected
+        })?;
+
+        let created = OidcLogin::new(
+            &state,
+            &code_verifier,
+            &nonce,
+            OIDC_MAX_PENDING_LOGINS,
+            &self.pool,
+        )
+        .await
+        .map_err(|e| {
+            error!(&self.logger, "unexpected database error";
+                "error" => e.to_string()
+            );
+            OidcError::Unexpected
+        })?;
+
+        if !created {
+            warn!(&self.logger, "too many unfinished single sign-on logins");
+            return Err(OidcError::TooManyLogins);
+        }
+
+        Ok(OidcAuthorization {
+            url: oidc::authorization_url(
+                &metadata,
+                &self.config.oidc_client_id,
+                redirect_url,
+                &state,
+                &nonce,
+                &code_verifier,
+            ),
+            state,
         })
     }
 
-    async fn validate_token(&self, token: &str) -> Result<UserInfo, jwt::errors::Error> {
-        jwt::decode::<AppUserTokenClaims>(
-            token,
-            &DecodingKey::from_secret(self.config.token_secret.as_bytes()),
-            &Validation::default(),
+    async fn oidc_callback(
+        &self,
+        context: &AuditContext,
+        code: &str,
+        state: &str,
+    ) -> Result<LoginOutcome, OidcError> {
+        let (issuer, redirect_url) =
+            match (&self.config.oidc_issuer, &self.config.oidc_redirect_url) {
+                (Some(issuer), Some(redirect_url)) => (issuer, redirect_url),
+                _ => return Err(OidcError::NotConfigured),
+            };
+
+        let login = OidcLogin::take(state, &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                OidcError::Unexpected
+            })?
+            .ok_or(OidcError::InvalidState)?;
+
+        if login.created < OffsetDateTime::now_utc() - OIDC_LOGIN_TIMEOUT_MINUTES.minutes() {
+            return Err(OidcError::InvalidState);
+        }
+
+        let metadata = oidc::discover(issuer).await.map_err(|e| {
+            error!(&self.logger, "OpenID Connect discovery failed";
+                "error" => e.to_string()
+            );
+            OidcError::Provider
+        })?;
+
+        let id_token = oidc::exchange_code(
+            &metadata,
+            &self.config.oidc_client_id,
+            self.config.oidc_client_secret.as_deref(),
+            redirect_url,
+            code,
+            &login.code_verifier,
         )
-        .map(|t| t.claims.user)
+        .await
+        .map_err(|e| {
+            error!(&self.logger, "OpenID Connect code exchange failed";
+                "error" => e.to_string()
+            );
+            OidcError::Provider
+        })?;
+
+        let jwks = oidc::fetch_jwks(&metadata).await.map_err(|e| {
+            error!(&self.logger, "OpenID Connect key retrieval failed";
+                "error" => e.to_string()
+            );
+            OidcError::Provider
+        })?;
+
+        let claims = oidc::validate_id_token(
+            &id_token,
+            &jwks,
+            &metadata,
+            &self.config.oidc_client_id,
+            &login.nonce,
+        )
+        .map_err(|e| {
+            warn!(&self.logger, "invalid ID token";
+                "error" => e.to_string()
+            );
+            OidcError::Provider
+        })?;
+
+        let user = self.user_for_identity(&claims).await?;
+
+        let token = self.create_token(&user, user.totp_enabled).map_err(|e| {
+            error!(&self.logger, "unexpected jwt error";
+                "error" => e.to_string()
+            );
+            OidcError::Unexpected
+        })?;
+
+        if user.totp_enabled {
+            Ok(LoginOutcome::MfaRequired(token))
+        } else {
+            self.audit_login(context, Some(&user), &user.email, "oidc", None)
+                .await;
+            Ok(LoginOutcome::Authenticated(token))
+        }
+    }
+
+    async fn set_role(
+        &self,
+        context: &AuditContext,
+        app_user_id: Uuid,
+        role: Role,
+    ) -> Result<(), SetRoleError> {
+        let mut user = AppUser::by_id(app_user_id, &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                SetRoleError::Unexpected
+            })?
+            .ok_or(SetRoleError::UserNotFound)?;
+
+        let old_role = std::mem::replace(&mut user.user_role, role.as_str().into());
+
+        user.save(&self.pool).await.map_err(|e| {
+            error!(&self.logger, "unexpected database error";
+                "error" => e.to_string()
+            );
+            SetRoleError::Unexpected
+        })?;
+
+        record_audit_event(
+            &self.pool,
+            &self.logger,
+            context,
+            AuditChange {
+                action: AuditAction::RoleChange,
+                target_type: AUDIT_TARGET_USER,
+                target_id: app_user_id.to_string(),
+                before: Some(json!({ "role": old_role })),
+                after: Some(json!({ "role": role.as_str() })),
+            },
+        )
+        .await;
+
+        Ok(())
+    }
+
+    async fn get_settings(&self, app_user_id: Uuid) -> Result<UserSettings, UserSettingsError> {
+        let user = AppUser::by_id(app_user_id, &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                UserSettingsError::Unexpected
+            })?
+            .ok_or(UserSettingsError::UserNotFound)?;
+
+        Ok(UserSettings {
+            keep_image_location: user.keep_image_location,
+        })
+    }
+
+    async fn update_settings(
+        &self,
+        app_user_id: Uuid,
+        settings: UserSettings,
+    ) -> Result<(), UserSettingsError> {
+        let mut user = AppUser::by_id(app_user_id, &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                UserSettingsError::Unexpected
+            })?
+            .ok_or(UserSettingsError::UserNotFound)?;
+
+        user.keep_image_location = settings.keep_image_location;
+
+        user.save(&self.pool).await.map_err(|e| {
+            error!(&self.logger, "unexpected database error";
+                "error" => e.to_string()
+            );
+            UserSettingsError::Unexpected
+        })
+    }
+
+    fn jwks(&self) -> JwkSet {
+        self.keys.jwks().clone()
     }
 }
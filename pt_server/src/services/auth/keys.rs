@@ -0,0 +1,297 @@
+//! Keys for signing and verifying the tokens issued by us.
+//!
+//! Tokens are signed with an RS256 or EdDSA private key and carry a `kid` header,
+//! older public keys can be kept around as a JWK set so that tokens issued before a
+//! key rotation stay valid until they expire.
+//!
+//! There is no fallback to a shared secret, the server refuses to start without a signing key.
+
+use crate::config::Config;
+use anyhow::anyhow;
+use jwt::{
+    errors::ErrorKind,
+    jwk::{
+        AlgorithmParameters, CommonParameters, EllipticCurve, Jwk, JwkSet, OctetKeyPairParameters,
+        OctetKeyPairType, PublicKeyUse, RSAKeyParameters, RSAKeyType,
+    },
+    Algorithm, DecodingKey, EncodingKey, Header, Validation,
+};
+use ring::signature::{Ed25519KeyPair, KeyPair, RsaKeyPair};
+use serde::{de::DeserializeOwned, Serialize};
+use sha2::{Digest, Sha256};
+use std::fmt;
+
+struct VerificationKey {
+    kid: String,
+    algorithm: Algorithm,
+    key: DecodingKey,
+}
+
+pub struct TokenKeys {
+    signing_kid: String,
+    signing_algorithm: Algorithm,
+    signing_key: EncodingKey,
+    verification_keys: Vec<VerificationKey>,
+    jwks: JwkSet,
+}
+
+impl fmt::Debug for TokenKeys {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TokenKeys")
+            .field("signing_kid", &self.signing_kid)
+            .field("signing_algorithm", &self.signing_algorithm)
+            .field(
+                "verification_kids",
+                &self
+                    .verification_keys
+                    .iter()
+                    .map(|k| &k.kid)
+                    .collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+impl TokenKeys {
+    pub fn from_config(config: &Config) -> anyhow::Result<Self> {
+        let signing_key_pem = match (&config.token_signing_key, &config.token_signing_key_path) {
+            (Some(key), _) => key.as_bytes().to_vec(),
+            (None, Some(path)) => std::fs::read(path)?,
+            (None, None) => {
+                return Err(anyhow!(
+                    "no token signing key, set PT_TOKEN_SIGNING_KEY or PT_TOKEN_SIGNING_KEY_PATH"
+                ))
+            }
+        };
+
+        let mut keys =
+            Self::from_private_key_pem(&signing_key_pem, config.token_signing_algorithm.parse()?)?;
+
+        if let Some(path) = &config.token_verification_keys_path {
+            let jwks: JwkSet = serde_json::from_slice(&std::fs::read(path)?)?;
+
+            for jwk in jwks.keys {
+                keys.add_verification_jwk(jwk)?;
+            }
+        }
+
+        Ok(keys)
+    }
+
+    /// Accepts PKCS#8 keys, and PKCS#1 keys for RS256.
+    pub fn from_private_key_pem(key_pem: &[u8], algorithm: Algorithm) -> anyhow::Result<Self> {
+        let pem = pem::parse(key_pem)?;
+
+        let (signing_key, parameters) = match algorithm {
+            Algorithm::RS256 => {
+                let key_pair = match pem.tag.as_str() {
+                    "RSA PRIVATE KEY" => RsaKeyPair::from_der(&pem.contents),
+                    _ => RsaKeyPair::from_pkcs8(&pem.contents),
+                }
+                .map_err(|e| anyhow!("invalid RSA private key: {}", e))?;
+
+                let public_key = key_pair.public_key();
+
+                (
+                    EncodingKey::from_rsa_pem(key_pem)?,
+                    AlgorithmParameters::RSA(RSAKeyParameters {
+                        key_type: RSAKeyType::RSA,
+                        n: base64_url(public_key.modulus().big_endian_without_leading_zero()),
+                        e: base64_url(public_key.exponent().big_endian_without_leading_zero()),
+                    }),
+                )
+            }
+            Algorithm::EdDSA => {
+                let key_pair = Ed25519KeyPair::from_pkcs8_maybe_unchecked(&pem.contents)
+                    .map_err(|e| anyhow!("invalid Ed25519 private key: {}", e))?;
+
+                (
+                    EncodingKey::from_ed_pem(key_pem)?,
+                    AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+                        key_type: OctetKeyPairType::OctetKeyPair,
+                        curve: EllipticCurve::Ed25519,
+                        x: base64_url(key_pair.public_key().as_ref()),
+                    }),
+                )
+            }
+            alg => return Err(anyhow!("unsupported token signing algorithm {:?}", alg)),
+        };
+
+        let kid = key_id(&parameters);
+
+        let jwk = Jwk {
+            common: CommonParameters {
+                public_key_use: Some(PublicKeyUse::Signature),
+                algorithm: Some(algorithm),
+                key_id: Some(kid.clone()),
+                ..Default::default()
+            },
+            algorithm: parameters,
+        };
+
+        Ok(Self {
+            signing_kid: kid.clone(),
+            signing_algorithm: algorithm,
+            signing_key,
+            verification_keys: vec![VerificationKey {
+                kid,
+                algorithm,
+                key: DecodingKey::from_jwk(&jwk)?,
+            }],
+            jwks: JwkSet { keys: vec![jwk] },
+        })
+    }
+
+    /// Accepts tokens signed by the given public key as well.
+    pub fn add_verification_jwk(&mut self, jwk: Jwk) -> anyhow::Result<()> {
+        let kid = jwk
+            .common
+            .key_id
+            .clone()
+            .ok_or_else(|| anyhow!("verification keys must have a key ID"))?;
+
+        let algorithm = match (&jwk.common.algorithm, &jwk.algorithm) {
+            (Some(alg), _) => *alg,
+            (None, AlgorithmParameters::RSA(_)) => Algorithm::RS256,
+            (None, AlgorithmParameters::OctetKeyPair(_)) => Algorithm::EdDSA,
+            _ => return Err(anyhow!(r#"unsupported verification key "{}""#, kid)),
+        };
+
+        if let AlgorithmParameters::OctetKey(_) = jwk.algorithm {
+            return Err(anyhow!("secrets cannot be used as verification keys"));
+        }
+
+        self.verification_keys.push(VerificationKey {
+            kid,
+            algorithm,
+            key: DecodingKey::from_jwk(&jwk)?,
+        });
+        self.jwks.keys.push(jwk);
+
+        Ok(())
+    }
+
+    /// The public keys that can be used to verify our tokens.
+    pub fn jwks(&self) -> &JwkSet {
+        &self.jwks
+    }
+
+    pub fn encode<T: Serialize>(&self, claims: &T) -> Result<String, jwt::errors::Error> {
+        let mut header = Header::new(self.signing_algorithm);
+        header.kid = Some(self.signing_kid.clone());
+
+        jwt::encode(&header, claims, &self.signing_key)
+    }
+
+    pub fn decode<T: DeserializeOwned>(&self, token: &str) -> Result<T, jwt::errors::Error> {
+        let kid = jwt::decode_header(token)?
+            .kid
+            .ok_or(ErrorKind::InvalidToken)?;
+
+        let key = self
+            .verification_keys
+            .iter()
+            .find(|k| k.kid == kid)
+            .ok_or(ErrorKind::InvalidToken)?;
+
+        jwt::decode::<T>(token, &key.key, &Validation::new(key.algorithm)).map(|t| t.claims)
+    }
+}
+
+fn base64_url(data: &[u8]) -> String {
+    base64::encode_config(data, base64::URL_SAFE_NO_PAD)
+}
+
+/// Derives a stable key ID from the public key.
+fn key_id(parameters: &AlgorithmParameters) -> String {
+    let mut hasher = Sha256::new();
+
+    match parameters {
+        AlgorithmParameters::RSA(p) => {
+            hasher.update(p.n.as_bytes());
+            hasher.update(p.e.as_bytes());
+        }
+        AlgorithmParameters::OctetKeyPair(p) => hasher.update(p.x.as_bytes()),
+        AlgorithmParameters::EllipticCurve(p) => {
+            hasher.update(p.x.as_bytes());
+            hasher.update(p.y.as_bytes());
+        }
+        AlgorithmParameters::OctetKey(p) => hasher.update(p.value.as_bytes()),
+    }
+
+    base64_url(&hasher.finalize())[..16].to_string()
+}
+
+#[test]
+fn test_token_keys_rotation() {
+    use serde::Deserialize;
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Claims {
+        exp: usize,
+        sub: String,
+    }
+
+    let claims = Claims {
+        exp: usize::MAX / 2,
+        sub: "test".into(),
+    };
+
+    let old_keys = TokenKeys::from_private_key_pem(
+        include_bytes!("../../../test_data/oidc_test_key.pem"),
+        Algorithm::RS256,
+    )
+    .unwrap();
+    let old_token = old_keys.encode(&claims).unwrap();
+
+    assert_eq!(
+        jwt::decode_header(&old_token).unwrap().kid.as_ref(),
+        old_keys.jwks().keys[0].common.key_id.as_ref()
+    );
+
+    let mut new_keys = TokenKeys::from_private_key_pem(
+        include_bytes!("../../../test_data/token_test_key.pem"),
+        Algorithm::EdDSA,
+    )
+    .unwrap();
+
+    // Tokens signed with unknown keys are rejected.
+    assert!(new_keys.decode::<Claims>(&old_token).is_err());
+
+    new_keys
+        .add_verification_jwk(old_keys.jwks().keys[0].clone())
+        .unwrap();
+
+    assert_eq!(new_keys.decode::<Claims>(&old_token).unwrap(), claims);
+    let new_token = new_keys.encode(&claims).unwrap();
+    assert_eq!(new_keys.decode::<Claims>(&new_token).unwrap(), claims);
+    assert!(old_keys.decode::<Claims>(&new_token).is_err());
+}
+
+#[test]
+fn test_token_keys_from_config() {
+    let mut config = Config::default();
+
+    // Without a signing key the server must not start.
+    assert!(TokenKeys::from_config(&config).is_err());
+
+    config.token_signing_key =
+        Some(include_str!("../../../test_data/token_test_key.pem").to_string());
+    config.token_signing_algorithm = "RS256".into();
+
+    // The key does not match the algorithm.
+    assert!(TokenKeys::from_config(&config).is_err());
+
+    config.token_signing_algorithm = "EdDSA".into();
+    let keys = TokenKeys::from_config(&config).unwrap();
+
+    assert_eq!(keys.jwks().keys.len(), 1);
+    assert!(matches!(
+        keys.jwks().keys[0].algorithm,
+        AlgorithmParameters::OctetKeyPair(_)
+    ));
+    // The private key is never published.
+    assert!(!serde_json::to_string(keys.jwks())
+        .unwrap()
+        .contains("\"d\""));
+}
@@ -0,0 +1,244 @@
+//! A minimal OpenID Connect relying party implementing
+//! the authorization code flow with PKCE.
+
+use actix_web::client::Client;
+use jwt::{Algorithm, DecodingKey, Validation};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+use url::Url;
+
+#[derive(Debug, Error)]
+pub enum ProviderError {
+    #[error("request to the provider failed: {0}")]
+    Request(String),
+    #[error("the provider responded with status {0}")]
+    Status(u16),
+    #[error("invalid response from the provider: {0}")]
+    InvalidResponse(String),
+    #[error("the provider issuer does not match the configured one")]
+    IssuerMismatch,
+    #[error("no matching signing key was found for the ID token")]
+    UnknownKey,
+    #[error("unsupported ID token algorithm {0:?}")]
+    UnsupportedAlgorithm(Algorithm),
+    #[error("invalid ID token: {0}")]
+    InvalidIdToken(jwt::errors::Error),
+    #[error("the ID token nonce does not match")]
+    NonceMismatch,
+}
+
+/// The relevant parts of the provider's discovery document.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProviderMetadata {
+    pub issuer: String,
+    pub authorization_endpoint: Url,
+    pub token_endpoint: Url,
+    pub jwks_uri: Url,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Jwk {
+    pub kty: String,
+    #[serde(default)]
+    pub kid: Option<String>,
+    #[serde(default)]
+    pub n: Option<String>,
+    #[serde(default)]
+    pub e: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JwkSet {
+    pub keys: Vec<Jwk>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TokenResponse {
+    pub id_token: String,
+}
+
+/// The claims of an ID token that are used by us.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IdTokenClaims {
+    pub iss: String,
+    pub sub: String,
+    pub exp: usize,
+    #[serde(default)]
+    pub nonce: Option<String>,
+    #[serde(default)]
+    pub email: Option<String>,
+    #[serde(default)]
+    pub email_verified: Option<bool>,
+}
+
+/// Calculates the S256 PKCE code challenge for the given verifier.
+pub fn code_challenge(code_verifier: &str) -> String {
+    base64::encode_config(
+        Sha256::digest(code_verifier.as_bytes()),
+        base64::URL_SAFE_NO_PAD,
+    )
+}
+
+fn same_issuer(a: &str, b: &str) -> bool {
+    a.trim_end_matches('/') == b.trim_end_matches('/')
+}
+
+/// Fetches the discovery document of the issuer.
+pub async fn discover(issuer: &Url) -> Result<ProviderMetadata, ProviderError> {
+    let url = format!(
+        "{}/.well-known/openid-configuration",
+        issuer.as_str().trim_end_matches('/')
+    );
+
+    let mut res = Client::default()
+        .get(url.as_str())
+        .send()
+        .await
+        .map_err(|e| ProviderError::Request(e.to_string()))?;
+
+    if !res.status().is_success() {
+        return Err(ProviderError::Status(res.status().as_u16()));
+    }
+
+    let metadata: ProviderMetadata = res
+        .json()
+        .await
+        .map_err(|e| ProviderError::InvalidResponse(e.to_string()))?;
+
+    if !same_issuer(&metadata.issuer, issuer.as_str()) {
+        return Err(ProviderError::IssuerMismatch);
+    }
+
+    Ok(metadata)
+}
+
+/// Builds the URL the user has to be redirected to.
+pub fn authorization_url(
+    metadata: &ProviderMetadata,
+    client_id: &str,
+    redirect_url: &Url,
+    state: &str,
+    nonce: &str,
+    code_verifier: &str,
+) -> Url {
+    let mut url = metadata.authorization_endpoint.clone();
+
+    url.query_pairs_mut()
+        .append_pair("response_type", "code")
+        .append_pair("client_id", client_id)
+        .append_pair("redirect_uri", redirect_url.as_str())
+        .append_pair("scope", "openid email")
+        .append_pair("state", state)
+        .append_pair("nonce", nonce)
+        .append_pair("code_challenge", &code_challenge(code_verifier))
+        .append_pair("code_challenge_method", "S256");
+
+    url
+}
+
+/// Exchanges the authorization code for an ID token.
+pub async fn exchange_code(
+    metadata: &ProviderMetadata,
+    client_id: &str,
+    client_secret: Option<&str>,
+    redirect_url: &Url,
+    code: &str,
+    code_verifier: &str,
+) -> Result<String, ProviderError> {
+    let mut params = vec![
+        ("grant_type", "authorization_code"),
+        ("code", code),
+        ("redirect_uri", redirect_url.as_str()),
+        ("client_id", client_id),
+        ("code_verifier", code_verifier),
+    ];
+
+    if let Some(secret) = client_secret {
+        params.push(("client_secret", secret));
+    }
+
+    let mut res = Client::default()
+        .post(metadata.token_endpoint.as_str())
+        .send_form(&params)
+        .await
+        .map_err(|e| ProviderError::Request(e.to_string()))?;
+
+    if !res.status().is_success() {
+        return Err(ProviderError::Status(res.status().as_u16()));
+    }
+
+    res.json::<TokenResponse>()
+        .await
+        .map(|t| t.id_token)
+        .map_err(|e| ProviderError::InvalidResponse(e.to_string()))
+}
+
+/// Fetches the public keys of the provider.
+pub async fn fetch_jwks(metadata: &ProviderMetadata) -> Result<JwkSet, ProviderError> {
+    let mut res = Client::default()
+        .get(metadata.jwks_uri.as_str())
+        .send()
+        .await
+        .map_err(|e| ProviderError::Request(e.to_string()))?;
+
+    if !res.status().is_success() {
+        return Err(ProviderError::Status(res.status().as_u16()));
+    }
+
+    res.json()
+        .await
+        .map_err(|e| ProviderError::InvalidResponse(e.to_string()))
+}
+
+/// Validates the signature and the claims of the ID token.
+pub fn validate_id_token(
+    id_token: &str,
+    jwks: &JwkSet,
+    metadata: &ProviderMetadata,
+    client_id: &str,
+    nonce: &str,
+) -> Result<IdTokenClaims, ProviderError> {
+    let header = jwt::decode_header(id_token).map_err(ProviderError::InvalidIdToken)?;
+
+    match header.alg {
+        Algorithm::RS256 | Algorithm::RS384 | Algorithm::RS512 => {}
+        alg => return Err(ProviderError::UnsupportedAlgorithm(alg)),
+    }
+
+    let jwk = jwks
+        .keys
+        .iter()
+        .find(|k| k.kty == "RSA" && (header.kid.is_none() || k.kid == header.kid))
+        .ok_or(ProviderError::UnknownKey)?;
+
+    let (n, e) = match (&jwk.n, &jwk.e) {
+        (Some(n), Some(e)) => (n, e),
+        _ => return Err(ProviderError::UnknownKey),
+    };
+
+    let mut validation = Validation::new(header.alg);
+    validation.set_issuer(&[&metadata.issuer]);
+    validation.set_audience(&[client_id]);
+
+    let key = DecodingKey::from_rsa_components(n, e).map_err(ProviderError::InvalidIdToken)?;
+
+    let claims = jwt::decode::<IdTokenClaims>(id_token, &key, &validation)
+        .map_err(ProviderError::InvalidIdToken)?
+        .claims;
+
+    if claims.nonce.as_deref() != Some(nonce) {
+        return Err(ProviderError::NonceMismatch);
+    }
+
+    Ok(claims)
+}
+
+#[test]
+fn test_code_challenge() {
+    // Example from RFC 7636.
+    assert_eq!(
+        code_challenge("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk"),
+        "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM"
+    );
+}
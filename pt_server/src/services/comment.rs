@@ -0,0 +1,294 @@
+use super::{
+    auth::UserInfo,
+    notification::{notify, NotificationEvent},
+    Service,
+};
+use crate::{
+    config::Config,
+    db::comment::{Comment, NewComment},
+    db::image::Image,
+    model::comment::*,
+    model::notification::NotificationKind,
+    model::role::Permission,
+};
+use async_trait::async_trait;
+use slog::{error, Logger};
+use sqlx::PgPool;
+use time::{Duration, OffsetDateTime};
+use uuid::Uuid;
+
+pub const COMMENT_MAX_LENGTH: usize = 2000;
+
+#[async_trait(?Send)]
+pub trait CommentService: Service {
+    async fn create_comment(
+        &self,
+        app_user_id: Uuid,
+        image_id: Uuid,
+        parent_id: Option<Uuid>,
+        body: &str,
+    ) -> Result<Uuid, CreateCommentError>;
+    async fn get_comments(
+        &self,
+        viewer_id: Uuid,
+        image_id: Uuid,
+        offset: Option<u64>,
+        limit: Option<u64>,
+    ) -> Result<Vec<Comment>, GetCommentsError>;
+    /// Only the author can edit, and only for a limited time.
+    async fn edit_comment(
+        &self,
+        app_user_id: Uuid,
+        id: Uuid,
+        body: &str,
+    ) -> Result<(), EditCommentError>;
+    /// The author, the owner of the image and moderators can delete comments.
+    async fn delete_comment(&self, user: &UserInfo, id: Uuid) -> Result<(), DeleteCommentError>;
+}
+dyn_clone::clone_trait_object!(CommentService);
+
+#[derive(Debug, Clone)]
+pub struct DefaultCommentService {
+    pool: PgPool,
+    logger: Logger,
+    config: Config,
+}
+
+impl DefaultCommentService {
+    pub fn new(config: &Config, logger: Logger, pool: PgPool) -> Self {
+        Self {
+            logger,
+            pool,
+            config: config.clone(),
+        }
+    }
+}
+
+fn valid_comment_body(body: &str) -> bool {
+    let len = body.trim().chars().count();
+    len > 0 && len <= COMMENT_MAX_LENGTH
+}
+
+#[async_trait(?Send)]
+impl CommentService for DefaultCommentService {
+    async fn create_comment(
+        &self,
+        app_user_id: Uuid,
+        image_id: Uuid,
+        parent_id: Option<Uuid>,
+        body: &str,
+    ) -> Result<Uuid, CreateCommentError> {
+        if !valid_comment_body(body) {
+            return Err(CreateCommentError::InvalidBody(COMMENT_MAX_LENGTH));
+        }
+
+        let image = Image::by_id(image_id, &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                CreateCommentError::Unexpected
+            })?
+            .filter(|i| i.upload_date.is_some() && i.visible_to(Some(app_user_id)))
+            .ok_or(CreateCommentError::ImageNotFound)?;
+
+        let parent = match parent_id {
+            Some(parent_id) => Some(
+                Comment::by_id(parent_id, &self.pool)
+                    .await
+                    .map_err(|e| {
+                        error!(&self.logger, "unexpected database error";
+                            "error" => e.to_string()
+                        );
+                        CreateCommentError::Unexpected
+                    })?
+                    .filter(|c| c.image_id == image_id)
+                    .ok_or(CreateCommentError::ParentNotFound)?,
+            ),
+            None => None,
+        };
+
+        let id = Comment::new(
+            app_user_id,
+            NewComment {
+                image_id,
+                parent_id,
+                body: body.trim(),
+            },
+            &self.pool,
+        )
+        .await
+        .map_err(|e| {
+            error!(&self.logger, "unexpected database error";
+                "error" => e.to_string()
+            );
+            CreateCommentError::Unexpected
+        })?;
+
+        notify(
+            &self.pool,
+            &self.logger,
+            NotificationEvent {
+                app_user_id: image.app_user_id,
+                actor_id: Some(app_user_id),
+                kind: NotificationKind::ImageCommented,
+                image_id: Some(image_id),
+                comment_id: Some(id),
+                details: None,
+            },
+        )
+        .await;
+
+        // The owner of the image already knows about the reply.
+        if let Some(parent_author) = parent
+            .and_then(|p| p.app_user_id)
+            .filter(|a| *a != image.app_user_id)
+        {
+            notify(
+                &self.pool,
+                &self.logger,
+                NotificationEvent {
+                    app_user_id: parent_author,
+                    actor_id: Some(app_user_id),
+                    kind: NotificationKind::CommentReplied,
+                    image_id: Some(image_id),
+                    comment_id: Some(id),
+                    details: None,
+                },
+            )
+            .await;
+        }
+
+        Ok(id)
+    }
+
+    async fn get_comments(
+        &self,
+        viewer_id: Uuid,
+        image_id: Uuid,
+        offset: Option<u64>,
+        limit: Option<u64>,
+    ) -> Result<Vec<Comment>, GetCommentsError> {
+        Image::by_id(image_id, &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                GetCommentsError::Unexpected
+            })?
+            .filter(|i| i.upload_date.is_some() && i.visible_to(Some(viewer_id)))
+            .ok_or(GetCommentsError::ImageNotFound)?;
+
+        Comment::by_image_id(
+            image_id,
+            offset.map(|v| v as _),
+            limit.map(|v| v as _),
+            &self.pool,
+        )
+        .await
+        .map_err(|e| {
+            error!(&self.logger, "unexpected database error";
+                "error" => e.to_string()
+            );
+            GetCommentsError::Unexpected
+        })
+    }
+
+    async fn edit_comment(
+        &self,
+        app_user_id: Uuid,
+        id: Uuid,
+        body: &str,
+    ) -> Result<(), EditCommentError> {
+        let mut comment = Comment::by_id(id, &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                EditCommentError::Unexpected
+            })?
+            .ok_or(EditCommentError::NotFound)?;
+
+        if comment.app_user_id != Some(app_user_id) {
+            return Err(EditCommentError::NotAllowed);
+        }
+
+        let edit_window = Duration::minutes(self.config.comment_edit_minutes);
+
+        if OffsetDateTime::now_utc() - comment.created > edit_window {
+            return Err(EditCommentError::EditWindowExpired(edit_window));
+        }
+
+        if !valid_comment_body(body) {
+            return Err(EditCommentError::InvalidBody(COMMENT_MAX_LENGTH));
+        }
+
+        comment.body = body.trim().into();
+        comment.edited = Some(OffsetDateTime::now_utc());
+
+        comment.save(&self.pool).await.map_err(|e| {
+            error!(&self.logger, "unexpected database error";
+                "error" => e.to_string()
+            );
+            EditCommentError::Unexpected
+        })
+    }
+
+    async fn delete_comment(&self, user: &UserInfo, id: Uuid) -> Result<(), DeleteCommentError> {
+        let comment = Comment::by_id(id, &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                DeleteCommentError::Unexpected
+            })?
+            .ok_or(DeleteCommentError::NotFound)?;
+
+        if comment.app_user_id != Some(user.id)
+            && !user.has_permission(Permission::ModerateComments)
+        {
+            let image = Image::by_id(comment.image_id, &self.pool)
+                .await
+                .map_err(|e| {
+                    error!(&self.logger, "unexpected database error";
+                        "error" => e.to_string()
+                    );
+                    DeleteCommentError::Unexpected
+                })?;
+
+            if image.map(|i| i.app_user_id) != Some(user.id) {
+                return Err(DeleteCommentError::NotAllowed);
+            }
+        }
+
+        comment.delete(&self.pool).await.map_err(|e| {
+            error!(&self.logger, "unexpected database error";
+                "error" => e.to_string()
+            );
+            DeleteCommentError::Unexpected
+        })?;
+
+        // Deleted by a moderator or the owner of the image.
+        if let Some(author) = comment.app_user_id.filter(|a| *a != user.id) {
+            notify(
+                &self.pool,
+                &self.logger,
+                NotificationEvent {
+                    app_user_id: author,
+                    actor_id: None,
+                    kind: NotificationKind::CommentDeleted,
+                    image_id: Some(comment.image_id),
+                    comment_id: None,
+                    details: None,
+                },
+            )
+            .await;
+        }
+
+        Ok(())
+    }
+}
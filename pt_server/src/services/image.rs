@@ -1,24 +1,57 @@
-use super::Service;
+use super::{
+    audit::{record_audit_event, AuditChange, AuditContext, AUDIT_TARGET_CATEGORY},
+    auth::UserInfo,
+    notification::{notify, NotificationEvent},
+    Service,
+};
 use crate::{
-    config::Config, db::category::Category, db::category::CategoryExt, db::image::Image,
-    db::image::NewImage, db::rating::Rating, model::image::*,
+    config::Config, db::app_user::AppUser, db::category::Category, db::category::CategoryExt,
+    db::category::DeleteChildren, db::category::MergedImages, db::category::NewCategory,
+    db::image::valid_location, db::image::BoundingBox, db::image::Image, db::image::ImageExt,
+    db::image::ImageFilter, db::image::LocationCluster, db::image::NewImage,
+    db::image_metadata::ImageMetadata, db::rating::Rating, db::tag::Tag, db::tag::TagCount,
+    db::upload_session::NewUploadSession, db::upload_session::UploadSession,
+    db::user_quota::StorageUsage as DbStorageUsage, db::user_quota::UserQuota, jobs, jobs::Job,
+    model::audit::AuditAction, model::image::*, model::notification::NotificationKind,
+    model::user::SetStorageQuotaError, model::user::StorageUsage, model::user::StorageUsageError,
+    model::Visibility, util::exif, util::normalize_tag, util::phash, util::random_string,
+    util::slugify, util::SLUG_REGEX,
 };
 use actix_files::NamedFile;
-use actix_multipart::Multipart;
+use actix_multipart::{Field, Multipart};
+use actix_web::web;
 use async_trait::async_trait;
-use futures::{future::join_all, StreamExt, TryStreamExt};
+use futures::{
+    future::{self, Either},
+    stream::FuturesUnordered,
+    StreamExt, TryStreamExt,
+};
 use regex::Regex;
-use slog::{error, Logger};
+use serde::de::DeserializeOwned;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use slog::{error, warn, Logger};
 use sqlx::PgPool;
-use std::{ffi::OsString, path::Path};
+use std::{
+    ffi::{OsStr, OsString},
+    io::SeekFrom,
+    path::{Path, PathBuf},
+};
 use time::OffsetDateTime;
 use tokio::{
-    fs::{self, File},
+    fs::{self, File, OpenOptions},
     io::AsyncWriteExt,
 };
 use uuid::Uuid;
 
 pub const CATEGORY_NAME_PATTERN: &str = "[A-Za-z]+";
+pub const MAX_TAGS_PER_IMAGE: usize = 20;
+pub const MAX_BULK_CATEGORIZE_IMAGES: usize = 1000;
+pub const MAX_CLUSTER_ZOOM: u8 = 20;
+pub const MAX_IMAGE_METADATA_BYTES: usize = 64 * 1024;
+pub const MAX_BATCH_UPLOAD_FILES: usize = 500;
+/// Files of a batch that are processed at the same time.
+pub const BATCH_UPLOAD_CONCURRENCY: usize = 4;
 
 #[async_trait(?Send)]
 pub trait ImageService: Service {
@@ -28,15 +61,82 @@ pub trait ImageService: Service {
         image: NewImage,
         categories: &[Uuid],
     ) -> Result<Uuid, CreateImageError>;
-    async fn save_image(&self, id: Uuid, payload: Multipart) -> Result<(), UploadImageError>;
-    async fn get_image(&self, id: Uuid) -> Result<NamedFile, std::io::Error>;
-    async fn get_image_info(&self, id: Uuid) -> Result<(Image, Vec<Category>), GetImageInfoError>;
+    /// Only the owner can upload the file of the image.
+    async fn save_image(
+        &self,
+        app_user_id: Uuid,
+        id: Uuid,
+        payload: Multipart,
+    ) -> Result<(), UploadImageError>;
+    /// Creates the image and stores its file in one go,
+    /// nothing is kept if either of them fails.
+    ///
+    /// The payload must have a "metadata" part with a JSON `CreateImageRequest`
+    /// and a "file" part.
+    async fn create_and_upload_image(
+        &self,
+        app_user_id: Uuid,
+        payload: Multipart,
+    ) -> Result<Uuid, CreateAndUploadImageError>;
+    /// Starts a resumable upload of the file of the image.
+    /// Creates an image for each file, the failure of one does not affect the others.
+    ///
+    /// The payload can start with a "defaults" part with a JSON `BatchImageMetadata`,
+    /// and each "file" part can be preceded by a "metadata" part of the same kind.
+    async fn batch_upload_images(
+        &self,
+        app_user_id: Uuid,
+        payload: Multipart,
+    ) -> Result<BatchUploadResponse, BatchUploadError>;
+    async fn create_upload_session(
+        &self,
+        app_user_id: Uuid,
+        image_id: Uuid,
+        length: u64,
+        checksum: &str,
+        filename: Option<&str>,
+    ) -> Result<UploadSession, UploadSessionError>;
+    async fn get_upload_session(
+        &self,
+        app_user_id: Uuid,
+        id: Uuid,
+    ) -> Result<UploadSession, UploadSessionError>;
+    /// Appends the chunk at the given offset and returns the new offset.
+    ///
+    /// The received bytes are kept even if the connection drops.
+    async fn append_upload_chunk(
+        &self,
+        app_user_id: Uuid,
+        id: Uuid,
+        offset: u64,
+        payload: web::Payload,
+    ) -> Result<u64, UploadSessionError>;
+    /// Verifies the checksum and stores the file as the file of the image.
+    async fn finish_upload_session(
+        &self,
+        app_user_id: Uuid,
+        id: Uuid,
+    ) -> Result<(), UploadSessionError>;
+    /// Private images can only be downloaded by their owners,
+    /// hidden images by their owners and moderators.
+    async fn get_image(
+        &self,
+        id: Uuid,
+        viewer: Option<&UserInfo>,
+    ) -> Result<NamedFile, DownloadImageError>;
+    /// Private images are only returned to their owners,
+    /// hidden images to their owners and moderators.
+    async fn get_image_info(
+        &self,
+        id: Uuid,
+        viewer: Option<&UserInfo>,
+    ) -> Result<ImageExt, GetImageInfoError>;
     async fn search_images(
         &self,
-        search: Option<&str>,
+        filter: ImageFilter,
         offset: Option<u64>,
         limit: Option<u64>,
-    ) -> Result<Vec<(Image, Vec<Category>)>, SearchImagesError>;
+    ) -> Result<Vec<ImageExt>, SearchImagesError>;
 
     async fn rate_image(
         &self,
@@ -44,21 +144,232 @@ pub trait ImageService: Service {
         app_user_id: Uuid,
         rating: u32,
     ) -> Result<(), RateImageError>;
-    async fn get_image_ratings(&self, image_id: Uuid) -> Result<Vec<Rating>, GetImageRatingsError>;
+    async fn get_image_ratings(
+        &self,
+        image_id: Uuid,
+        viewer_id: Option<Uuid>,
+    ) -> Result<Vec<Rating>, GetImageRatingsError>;
     async fn get_user_ratings(&self) -> Result<Vec<UserRating>, GetUserRatingsError>;
 
-    async fn get_categories(&self) -> Result<Vec<CategoryExt>, GetCategoriesError>;
-    async fn create_category(&self, name: &str) -> Result<Uuid, CreateCategoryError>;
-    async fn rename_category(&self, id: Uuid, name: &str) -> Result<(), RenameCategoryError>;
-    async fn delete_category(&self, id: Uuid) -> Result<(), DeleteCategoryError>;
+    async fn get_categories(
+        &self,
+        include_descendants: bool,
+    ) -> Result<Vec<CategoryExt>, GetCategoriesError>;
+    async fn create_category(
+        &self,
+        context: &AuditContext,
+        name: &str,
+        slug: Option<&str>,
+        parent_id: Option<Uuid>,
+        description: Option<&str>,
+    ) -> Result<Uuid, CreateCategoryError>;
+    async fn rename_category(
+        &self,
+        context: &AuditContext,
+        id: Uuid,
+        name: &str,
+    ) -> Result<(), RenameCategoryError>;
+    /// Moves the category under another one, or makes it a root category.
+    async fn move_category(
+        &self,
+        id: Uuid,
+        parent_id: Option<Uuid>,
+    ) -> Result<(), MoveCategoryError>;
+    async fn update_category_details(
+        &self,
+        id: Uuid,
+        description: Option<&str>,
+        cover_image_id: Option<Uuid>,
+    ) -> Result<(), UpdateCategoryDetailsError>;
+    /// Categories with subcategories can only be deleted if `children` is given.
+    async fn delete_category(
+        &self,
+        context: &AuditContext,
+        id: Uuid,
+        children: Option<DeleteCategoryChildren>,
+    ) -> Result<(), DeleteCategoryError>;
+    /// Moves the images and subcategories into the target, then deletes the category.
+    async fn merge_category(
+        &self,
+        id: Uuid,
+        target_id: Uuid,
+    ) -> Result<MergedImages, MergeCategoriesError>;
+    /// Adds or removes the category on many images at once.
+    async fn bulk_categorize(
+        &self,
+        id: Uuid,
+        action: BulkCategoryAction,
+        image_ids: &[Uuid],
+        dry_run: bool,
+    ) -> Result<BulkCategorizeResponse, BulkCategorizeError>;
+
+    async fn set_image_visibility(
+        &self,
+        app_user_id: Uuid,
+        image_id: Uuid,
+        visibility: Visibility,
+    ) -> Result<(), SetImageVisibilityError>;
+    /// `None` clears the location.
+    async fn set_image_location(
+        &self,
+        app_user_id: Uuid,
+        image_id: Uuid,
+        location: Option<(f64, f64)>,
+    ) -> Result<(), SetImageLocationError>;
+    /// Images of any user that look like the given one, the closest first.
+    async fn get_similar_images(
+        &self,
+        image_id: Uuid,
+        max_distance: Option<u32>,
+        limit: Option<u64>,
+    ) -> Result<Vec<(ImageExt, u32)>, GetSimilarImagesError>;
+    /// Computes the hash of the uploaded file and looks for duplicates,
+    /// called by the job worker.
+    async fn process_upload(&self, image_id: Uuid) -> Result<(), ProcessUploadError>;
+    /// Counts of located images in a grid, finer for higher zoom levels.
+    async fn get_image_clusters(
+        &self,
+        zoom: u8,
+        bbox: Option<BoundingBox>,
+        viewer_id: Option<Uuid>,
+    ) -> Result<Vec<LocationCluster>, GetImageClustersError>;
+
+    /// Returns the normalised tags.
+    async fn set_image_tags(
+        &self,
+        app_user_id: Uuid,
+        image_id: Uuid,
+        tags: &[String],
+    ) -> Result<Vec<String>, SetImageTagsError>;
+    async fn autocomplete_tags(
+        &self,
+        text: &str,
+        limit: Option<u64>,
+    ) -> Result<Vec<TagCount>, GetTagsError>;
+    async fn get_popular_tags(&self, limit: Option<u64>) -> Result<Vec<TagCount>, GetTagsError>;
+    /// Turns the tag into a category, returns the ID of the category.
+    async fn promote_tag(&self, id: Uuid) -> Result<Uuid, PromoteTagError>;
+
+    async fn get_storage_usage(&self, app_user_id: Uuid)
+        -> Result<StorageUsage, StorageUsageError>;
+    /// Overrides the configured limits for the user, `None` resets a limit to the default.
+    async fn set_storage_quota(
+        &self,
+        app_user_id: Uuid,
+        max_bytes: Option<u64>,
+        max_images: Option<u64>,
+    ) -> Result<(), SetStorageQuotaError>;
 }
 dyn_clone::clone_trait_object!(ImageService);
 
+/// The width and height of the grid cells in degrees.
+pub fn cluster_cell_size(zoom: u8) -> f64 {
+    360.0 / f64::from(1u32 << zoom)
+}
+
+/// The request to create an image from a file of a batch, the metadata
+/// of the file takes precedence over the defaults of the batch.
+fn batch_image_request(
+    defaults: Option<&BatchImageMetadata>,
+    metadata: Option<Result<BatchImageMetadata, CreateAndUploadImageError>>,
+    filename: Option<&str>,
+) -> Result<CreateImageRequest, CreateAndUploadImageError> {
+    let metadata = metadata.transpose()?.unwrap_or_default();
+
+    let title = metadata
+        .title
+        .or_else(|| defaults.and_then(|d| d.title.clone()))
+        .or_else(|| {
+            filename
+                .and_then(|f| Path::new(f).file_stem())
+                .map(|s| s.to_string_lossy().into_owned())
+        })
+        .unwrap_or_else(|| "Untitled".into());
+
+    Ok(CreateImageRequest {
+        title,
+        description: metadata
+            .description
+            .or_else(|| defaults.and_then(|d| d.description.clone())),
+        categories: metadata
+            .categories
+            .or_else(|| defaults.and_then(|d| d.categories.clone()))
+            .unwrap_or_default(),
+        visibility: metadata
+            .visibility
+            .or_else(|| defaults.and_then(|d| d.visibility)),
+    })
+}
+
+/// Unfinished uploads are kept here, inside the storage path
+/// so that finished files can be renamed into place.
+pub fn upload_temp_dir(config: &Config) -> PathBuf {
+    config.image_storage_path.join(".uploads")
+}
+
+/// A file received into the temporary directory, removed once dropped.
+struct ReceivedFile {
+    path: PathBuf,
+    extension: OsString,
+}
+
+impl Drop for ReceivedFile {
+    fn drop(&mut self) {
+        // The processed file is written elsewhere, so this one is not needed anymore.
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// The received part of a resumable upload.
+pub fn upload_part_path(config: &Config, upload_session_id: Uuid) -> PathBuf {
+    upload_temp_dir(config)
+        .join(upload_session_id.to_hyphenated().to_string())
+        .with_extension("part")
+}
+
+/// Where the uploaded file of the image is stored.
+pub fn image_path(config: &Config, id: Uuid) -> PathBuf {
+    Path::new(&config.image_storage_path)
+        .join(&id.to_hyphenated().to_string())
+        .with_extension("png")
+}
+
+/// The uploaded file of the image, whatever its extension is.
+async fn find_image_file(config: &Config, id: Uuid) -> std::io::Result<Option<PathBuf>> {
+    let id = id.to_hyphenated().to_string();
+    let mut entries = fs::read_dir(&config.image_storage_path).await?;
+
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+
+        if path.file_stem() == Some(OsStr::new(&id)) {
+            return Ok(Some(path));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Removes the uploaded file of the image, whatever its extension is.
+pub async fn remove_image_files(config: &Config, id: Uuid) -> std::io::Result<()> {
+    let id = id.to_hyphenated().to_string();
+    let mut entries = fs::read_dir(&config.image_storage_path).await?;
+
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+
+        if path.file_stem() == Some(OsStr::new(&id)) {
+            fs::remove_file(path).await?;
+        }
+    }
+
+    Ok(())
+}
+
 #[derive(Debug, Clone)]
 pub struct DefaultImageService {
     pool: PgPool,
     logger: Logger,
-    #[allow(dead_code)]
     config: Config,
 }
 
@@ -70,279 +381,1298 @@ impl DefaultImageService {
             config: config.clone(),
         }
     }
-}
 
-#[async_trait(?Send)]
-impl ImageService for DefaultImageService {
-    async fn create_image(
+    /// A slug generated from the name that is not used by any category yet.
+    async fn unique_slug(&self, name: &str) -> Result<String, sqlx::Error> {
+        let mut base = slugify(name);
+
+        if base.is_empty() {
+            base = "category".into();
+        }
+
+        let mut slug = base.clone();
+
+        while Category::by_slug(&slug, &self.pool).await?.is_some() {
+            slug = format!("{}-{}", base, random_string(4).to_lowercase());
+        }
+
+        Ok(slug)
+    }
+
+    /// The limits of the user with the admin overrides applied, `None` means no limit.
+    async fn storage_limits(
         &self,
         app_user_id: Uuid,
-        image: NewImage,
-        categories: &[Uuid],
-    ) -> Result<Uuid, CreateImageError> {
-        let db_categories =
-            futures::future::join_all(categories.iter().map(|id| Category::by_id(*id, &self.pool)))
-                .await
-                .into_iter()
-                .collect::<Result<Vec<_>, _>>()
-                .map_err(|e| {
-                    error!(&self.logger, "unexpected database error";
-                        "error" => e.to_string()
-                    );
-                    CreateImageError::Unexpected
-                })?
-                .into_iter()
-                .zip(categories)
-                .map(|(o, id)| o.ok_or(CreateImageError::CategoryNotFound(*id)))
-                .collect::<Result<Vec<_>, _>>()?;
+    ) -> Result<(Option<u64>, Option<u64>), sqlx::Error> {
+        let quota = UserQuota::by_app_user_id(app_user_id, &self.pool).await?;
 
-        let image_id = Image::new(app_user_id, image, &self.pool)
-            .await
-            .map_err(|e| {
-                error!(&self.logger, "unexpected database error";
-                    "error" => e.to_string()
-                );
-                CreateImageError::Unexpected
-            })?;
+        let max_bytes = quota
+            .as_ref()
+            .and_then(|q| q.max_bytes)
+            .map(|b| b as u64)
+            .unwrap_or(self.config.storage_quota_bytes);
 
-        for category in db_categories {
-            category
-                .add_image(image_id, &self.pool)
+        let max_images = quota
+            .as_ref()
+            .and_then(|q| q.max_images)
+            .map(|i| i as u64)
+            .unwrap_or(self.config.image_quota);
+
+        Ok((
+            Some(max_bytes).filter(|b| *b > 0),
+            Some(max_images).filter(|i| *i > 0),
+        ))
+    }
+
+    /// Checks whether the user can store `size` more bytes.
+    async fn check_storage_quota(
+        &self,
+        app_user_id: Uuid,
+        size: u64,
+    ) -> Result<(), UploadImageError> {
+        let (max_bytes, _) = self.storage_limits(app_user_id).await.map_err(|e| {
+            error!(&self.logger, "unexpected database error";
+                "error" => e.to_string()
+            );
+            UploadImageError::Unexpected
+        })?;
+
+        if let Some(max_bytes) = max_bytes {
+            let usage = DbStorageUsage::by_app_user_id(app_user_id, &self.pool)
                 .await
                 .map_err(|e| {
                     error!(&self.logger, "unexpected database error";
                         "error" => e.to_string()
                     );
-                    CreateImageError::Unexpected
+                    UploadImageError::Unexpected
                 })?;
+
+            if usage.bytes as u64 + size > max_bytes {
+                return Err(UploadImageError::QuotaExceeded);
+            }
         }
 
-        Ok(image_id)
+        Ok(())
     }
 
-    async fn save_image(&self, id: Uuid, mut payload: Multipart) -> Result<(), UploadImageError> {
-        let img = Image::by_id(id, &self.pool).await.map_err(|e| {
+    /// Processes the received file and moves it in place,
+    /// the image is only marked as uploaded once the file is there.
+    async fn store_upload(
+        &self,
+        mut img: Image,
+        extension: &OsStr,
+        source: &Path,
+    ) -> Result<(), UploadImageError> {
+        let (max_bytes, _) = self.storage_limits(img.app_user_id).await.map_err(|e| {
             error!(&self.logger, "unexpected database error";
                 "error" => e.to_string()
             );
             UploadImageError::Unexpected
         })?;
 
-        match img {
-            Some(mut img) => {
-                if img.upload_date.is_some() {
-                    return Err(UploadImageError::AlreadyUploaded);
-                }
+        let (filepath, metadata) = self.write_upload(&mut img, extension, source).await?;
 
-                match payload.try_next().await {
-                    Ok(f) => match f {
-                        Some(mut field) => {
-                            let filepath = self
-                                .config
-                                .image_storage_path
-                                .join(id.to_hyphenated().to_string())
-                                .with_extension(
-                                    field
-                                        .content_disposition()
-                                        .and_then(|c| {
-                                            c.get_filename().and_then(|c| {
-                                                Path::new(c).extension().map(|e| e.to_owned())
-                                            })
-                                        })
-                                        .unwrap_or_else(|| OsString::from("png")),
-                                );
-
-                            fs::create_dir_all(&self.config.image_storage_path)
-                                .await
-                                .map_err(|e| {
-                                    error!(&self.logger, "error creating directory for images";
-                                    "error" => e.to_string()
-                                    );
-                                    UploadImageError::Unexpected
-                                })?;
-
-                            let mut file = File::create(filepath).await.map_err(|e| {
-                                error!(&self.logger, "error saving the file";
-                                    "error" => e.to_string()
-                                );
-                                UploadImageError::Unexpected
-                            })?;
-
-                            while let Some(chunk) = field.next().await {
-                                let data = chunk.map_err(|e| {
-                                    error!(&self.logger, "error saving the file";
-                                        "error" => e.to_string()
-                                    );
-                                    UploadImageError::Unexpected
-                                })?;
-
-                                file.write_all(&data).await.map_err(|e| {
-                                    error!(&self.logger, "error saving the file";
-                                        "error" => e.to_string()
-                                    );
-                                    UploadImageError::Unexpected
-                                })?;
-                            }
+        // The quota is checked again together with saving, other uploads may have used it up.
+        let saved = match img
+            .save_upload(metadata.as_ref(), max_bytes.map(|b| b as _), &self.pool)
+            .await
+        {
+            Ok(saved) => saved,
+            Err(e) => {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                self.remove_failed_upload(&filepath).await;
+                return Err(UploadImageError::Unexpected);
+            }
+        };
 
-                            img.upload_date = Some(OffsetDateTime::now_utc());
+        if !saved {
+            self.remove_failed_upload(&filepath).await;
+            return Err(UploadImageError::QuotaExceeded);
+        }
 
-                            img.save(&self.pool).await.map_err(|e| {
-                                error!(&self.logger, "unexpected database error";
-                                    "error" => e.to_string()
-                                );
-                                UploadImageError::Unexpected
-                            })?;
-                            Ok(())
-                        }
-                        None => Err(UploadImageError::ExpectedFile),
-                    },
+        self.queue_upload_processing(&img).await;
 
-                    Err(err) => {
-                        error!(&self.logger, "unexpected upload error";
-                            "error" => err.to_string()
-                        );
-                        Err(UploadImageError::Unexpected)
-                    }
-                }
-            }
-            None => Err(UploadImageError::InvalidId),
-        }
+        Ok(())
     }
 
-    async fn get_image(&self, id: Uuid) -> Result<NamedFile, std::io::Error> {
-        NamedFile::open(
-            &Path::new(&self.config.image_storage_path)
-                .join(&id.to_hyphenated().to_string())
-                .with_extension("png"),
-        )
+    /// Removes the stored file of an image that could not be saved.
+    async fn remove_failed_upload(&self, filepath: &Path) {
+        if let Err(e) = fs::remove_file(filepath).await {
+            warn!(&self.logger, "error removing the file of a failed upload";
+                "error" => e.to_string(),
+                "path" => filepath.to_string_lossy().to_string()
+            );
+        }
     }
 
-    async fn search_images(
-        &self,
-        search: Option<&str>,
-        offset: Option<u64>,
-        limit: Option<u64>,
-    ) -> Result<Vec<(Image, Vec<Category>)>, SearchImagesError> {
-        let images = Image::search(
-            search,
-            offset.map(|v| v as _),
-            limit.map(|v| v as _),
+    /// The closest earlier image of the same user within the duplicate threshold.
+    async fn find_duplicate(&self, img: &Image, phash: i64) -> Result<Option<Uuid>, sqlx::Error> {
+        let similar = Image::similar(
+            phash,
+            img.id,
+            Some(img.app_user_id),
+            self.config.duplicate_threshold as _,
+            1,
             &self.pool,
         )
-        .await
-        .map_err(|e| {
+        .await?;
+
+        Ok(similar.into_iter().next().map(|s| s.id))
+    }
+
+    /// Queues the job that computes the hash of the uploaded file,
+    /// the upload is kept even if that fails.
+    async fn queue_upload_processing(&self, img: &Image) {
+        if self.config.reject_duplicates {
+            return;
+        }
+
+        let job = Job::ProcessUpload { image_id: img.id };
+
+        if let Err(e) = jobs::enqueue(&job, &self.pool).await {
             error!(&self.logger, "unexpected database error";
                 "error" => e.to_string()
             );
-            SearchImagesError::Unexpected
-        })?;
-
-        let categories = join_all(images.iter().map(|i| i.categories(&self.pool)))
-            .await
-            .into_iter()
-            .collect::<Result<Vec<_>, _>>()
-            .map_err(|e| {
-                error!(&self.logger, "unexpected database error";
-                    "error" => e.to_string()
-                );
-                SearchImagesError::Unexpected
-            })?;
-
-        Ok(images
-            .into_iter()
-            .zip(categories)
-            // Filter only uploaded images,
-            // this is required because the image is not
-            // guaranteed to be on storage, even if it was once uploaded.
-            .filter(|(i, _)| {
-                Path::new(&self.config.image_storage_path)
-                    .join(&i.id.to_hyphenated().to_string())
-                    .with_extension("png")
-                    .exists()
-            })
-            .collect())
+        }
     }
 
-    async fn rate_image(
+    /// Processes the received file and moves it in place, then fills in
+    /// the fields of the image without saving it.
+    ///
+    /// Returns the path of the file and the metadata read from it.
+    async fn write_upload(
         &self,
-        image_id: Uuid,
-        app_user_id: Uuid,
-        rating: u32,
-    ) -> Result<(), RateImageError> {
-        let image = Image::by_id(image_id, &self.pool)
+        img: &mut Image,
+        extension: &OsStr,
+        source: &Path,
+    ) -> Result<(PathBuf, Option<ImageMetadata>), UploadImageError> {
+        let keep_location = AppUser::by_id(img.app_user_id, &self.pool)
             .await
             .map_err(|e| {
                 error!(&self.logger, "unexpected database error";
                     "error" => e.to_string()
                 );
-                RateImageError::Unexpected
+                UploadImageError::Unexpected
             })?
-            .ok_or(RateImageError::ImageNotFound)?;
+            .map(|u| u.keep_image_location)
+            .unwrap_or(false);
 
-        if image.app_user_id == app_user_id {
-            return Err(RateImageError::OwnImage);
-        }
+        // Duplicates can only be rejected with the hash at hand,
+        // otherwise it is computed by a job after the upload.
+        let reject_duplicates = self.config.reject_duplicates;
+        let source = source.to_owned();
 
-        if rating > 5 || rating == 0 {
-            return Err(RateImageError::InvalidRating);
-        }
+        // The file is only read into memory while it is processed.
+        let (exif_data, phash, data) = web::block(move || {
+            let data = std::fs::read(&source)?;
 
-        image
-            .rate(app_user_id, rating as _, &self.pool)
-            .await
-            .map_err(|e| {
-                error!(&self.logger, "unexpected database error";
-                    "error" => e.to_string()
-                );
-                RateImageError::Unexpected
-            })
-    }
+            Ok::<_, std::io::Error>((
+                exif::parse(&data),
+                if reject_duplicates {
+                    phash::dhash(&data)
+                } else {
+                    None
+                },
+                exif::strip(&data, keep_location),
+            ))
+        })
+        .await
+        .map_err(|e| {
+            error!(&self.logger, "error processing the file";
+                "error" => e.to_string()
+            );
+            UploadImageError::Unexpected
+        })?;
 
-    async fn get_image_ratings(&self, image_id: Uuid) -> Result<Vec<Rating>, GetImageRatingsError> {
-        let image = Image::by_id(image_id, &self.pool)
-            .await
-            .map_err(|e| {
+        // Storing the original could leak the metadata.
+        let data = data.ok_or(UploadImageError::UnsupportedFile)?;
+
+        self.check_storage_quota(img.app_user_id, data.len() as u64)
+            .await?;
+
+        if let Some(phash) = phash {
+            let duplicate = self.find_duplicate(img, phash).await.map_err(|e| {
                 error!(&self.logger, "unexpected database error";
                     "error" => e.to_string()
                 );
-                GetImageRatingsError::Unexpected
-            })?
-            .ok_or(GetImageRatingsError::ImageNotFound)?;
+                UploadImageError::Unexpected
+            })?;
 
-        image.ratings(&self.pool).await.map_err(|e| {
-            error!(&self.logger, "unexpected database error";
-                "error" => e.to_string()
-            );
-            GetImageRatingsError::Unexpected
-        })
-    }
+            if let Some(duplicate) = duplicate {
+                return Err(UploadImageError::Duplicate(duplicate));
+            }
 
-    async fn get_categories(&self) -> Result<Vec<CategoryExt>, GetCategoriesError> {
-        CategoryExt::all(&self.pool).await.map_err(|e| {
-            error!(&self.logger, "unexpected database error";
+            img.phash = Some(phash);
+        }
+
+        let temp_dir = upload_temp_dir(&self.config);
+
+        fs::create_dir_all(&temp_dir).await.map_err(|e| {
+            error!(&self.logger, "error creating directory for images";
                 "error" => e.to_string()
             );
-            GetCategoriesError::Unexpected
-        })
-    }
-
-    async fn create_category(&self, name: &str) -> Result<Uuid, CreateCategoryError> {
-        let re = Regex::new(CATEGORY_NAME_PATTERN).unwrap();
+            UploadImageError::Unexpected
+        })?;
 
-        if !re.is_match(name) {
-            return Err(CreateCategoryError::InvalidName(
-                CATEGORY_NAME_PATTERN.into(),
-            ));
-        }
+        let id = img.id.to_hyphenated().to_string();
+        let temp_path = temp_dir.join(&id).with_extension("tmp");
+        let filepath = self
+            .config
+            .image_storage_path
+            .join(&id)
+            .with_extension(extension);
 
-        let categories = Category::all(&self.pool).await.map_err(|e| {
-            error!(&self.logger, "unexpected database error";
+        let mut file = File::create(&temp_path).await.map_err(|e| {
+            error!(&self.logger, "error saving the file";
                 "error" => e.to_string()
             );
-            CreateCategoryError::Unexpected
+            UploadImageError::Unexpected
         })?;
 
-        let lc_name = name.to_lowercase();
+        file.write_all(&data).await.map_err(|e| {
+            error!(&self.logger, "error saving the file";
+                "error" => e.to_string()
+            );
+            UploadImageError::Unexpected
+        })?;
+
+        file.sync_all().await.map_err(|e| {
+            error!(&self.logger, "error saving the file";
+                "error" => e.to_string()
+            );
+            UploadImageError::Unexpected
+        })?;
+
+        // The temporary directory is on the same file system,
+        // so the file either appears whole or not at all.
+        fs::rename(&temp_path, &filepath).await.map_err(|e| {
+            error!(&self.logger, "error saving the file";
+                "error" => e.to_string()
+            );
+            UploadImageError::Unexpected
+        })?;
+
+        if let Some(exif_data) = &exif_data {
+            // A location that was set by hand takes precedence.
+            if keep_location && img.location().is_none() {
+                img.latitude = exif_data.location.map(|l| l.0);
+                img.longitude = exif_data.location.map(|l| l.1);
+            }
+        }
+
+        img.file_size = Some(data.len() as _);
+        img.upload_date = Some(OffsetDateTime::now_utc());
+
+        Ok((
+            filepath,
+            exif_data.map(|exif_data| ImageMetadata::new(img.id, exif_data)),
+        ))
+    }
+
+    /// Writes the file of the multipart field into the temporary directory,
+    /// up to the configured size.
+    async fn read_file_field(&self, mut field: Field) -> Result<ReceivedFile, UploadImageError> {
+        let extension = field
+            .content_disposition()
+            .and_then(|c| {
+                c.get_filename()
+                    .and_then(|c| Path::new(c).extension().map(|e| e.to_owned()))
+            })
+            .unwrap_or_else(|| OsString::from("png"));
+
+        let temp_dir = upload_temp_dir(&self.config);
+
+        fs::create_dir_all(&temp_dir).await.map_err(|e| {
+            error!(&self.logger, "error creating directory for images";
+                "error" => e.to_string()
+            );
+            UploadImageError::Unexpected
+        })?;
+
+        let received = ReceivedFile {
+            path: temp_dir
+                .join(Uuid::new_v4().to_hyphenated().to_string())
+                .with_extension("received"),
+            extension,
+        };
+
+        let mut file = File::create(&received.path).await.map_err(|e| {
+            error!(&self.logger, "error saving the file";
+                "error" => e.to_string()
+            );
+            UploadImageError::Unexpected
+        })?;
+
+        let max_size = self.config.max_upload_bytes;
+        let mut size = 0;
+
+        while let Some(chunk) = field.next().await {
+            let chunk = chunk.map_err(|e| {
+                error!(&self.logger, "error saving the file";
+                    "error" => e.to_string()
+                );
+                UploadImageError::Unexpected
+            })?;
+
+            // Stops reading instead of receiving the whole file first.
+            size += chunk.len() as u64;
+
+            if size > max_size {
+                return Err(UploadImageError::TooLarge(max_size));
+            }
+
+            file.write_all(&chunk).await.map_err(|e| {
+                error!(&self.logger, "error saving the file";
+                    "error" => e.to_string()
+                );
+                UploadImageError::Unexpected
+            })?;
+        }
+
+        file.flush().await.map_err(|e| {
+            error!(&self.logger, "error saving the file";
+                "error" => e.to_string()
+            );
+            UploadImageError::Unexpected
+        })?;
+
+        Ok(received)
+    }
+
+    /// Creates the image with the already received file,
+    /// the file is removed if the image could not be saved.
+    async fn create_with_file(
+        &self,
+        app_user_id: Uuid,
+        metadata: CreateImageRequest,
+        file: &ReceivedFile,
+    ) -> Result<Uuid, CreateAndUploadImageError> {
+        // The same checks as for images created without the file.
+        self.find_categories(&metadata.categories).await?;
+        self.check_image_quota(app_user_id).await?;
+
+        let (max_bytes, max_images) = self.storage_limits(app_user_id).await.map_err(|e| {
+            error!(&self.logger, "unexpected database error";
+                "error" => e.to_string()
+            );
+            CreateImageError::Unexpected
+        })?;
+
+        let mut img = Image {
+            id: Uuid::new_v4(),
+            created: OffsetDateTime::now_utc(),
+            upload_date: None,
+            title: metadata.title,
+            description: metadata.description,
+            app_user_id,
+            visibility: metadata
+                .visibility
+                .unwrap_or(Visibility::Public)
+                .as_str()
+                .into(),
+            latitude: None,
+            longitude: None,
+            phash: None,
+            duplicate_of: None,
+            file_size: None,
+            hidden: false,
+            deleted: None,
+            deleted_by: None,
+        };
+
+        let (filepath, image_metadata) = self
+            .write_upload(&mut img, &file.extension, &file.path)
+            .await?;
+
+        // The quotas are checked again together with the insert, other uploads may have used them up.
+        let inserted = match img
+            .insert(
+                &metadata.categories,
+                image_metadata.as_ref(),
+                max_bytes.map(|b| b as _),
+                max_images.map(|i| i as _),
+                &self.pool,
+            )
+            .await
+        {
+            Ok(inserted) => inserted,
+            Err(e) => {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                self.remove_failed_upload(&filepath).await;
+                return Err(CreateImageError::Unexpected.into());
+            }
+        };
+
+        if !inserted {
+            self.remove_failed_upload(&filepath).await;
+            return Err(UploadImageError::QuotaExceeded.into());
+        }
+
+        self.queue_upload_processing(&img).await;
+
+        Ok(img.id)
+    }
+
+    async fn batch_upload_item(
+        &self,
+        app_user_id: Uuid,
+        index: usize,
+        filename: Option<String>,
+        metadata: Result<CreateImageRequest, CreateAndUploadImageError>,
+        file: Result<ReceivedFile, UploadImageError>,
+    ) -> BatchUploadItem {
+        let result = match (metadata, file) {
+            (Ok(metadata), Ok(file)) => self.create_with_file(app_user_id, metadata, &file).await,
+            (Err(err), _) => Err(err),
+            (_, Err(err)) => Err(err.into()),
+        };
+
+        match result {
+            Ok(id) => BatchUploadItem {
+                index,
+                filename,
+                id: Some(id),
+                error: None,
+            },
+            Err(err) => BatchUploadItem {
+                index,
+                filename,
+                id: None,
+                error: Some(BatchUploadItemError {
+                    code: err.code().into(),
+                    message: err.to_string(),
+                }),
+            },
+        }
+    }
+
+    /// Reads and parses the JSON in the multipart field.
+    async fn read_json_field<T: DeserializeOwned>(
+        &self,
+        mut field: Field,
+    ) -> Result<T, CreateAndUploadImageError> {
+        let mut data = Vec::new();
+
+        while let Some(chunk) = field.next().await {
+            let chunk = chunk.map_err(|e| {
+                error!(&self.logger, "unexpected upload error";
+                    "error" => e.to_string()
+                );
+                UploadImageError::Unexpected
+            })?;
+
+            if data.len() + chunk.len() > MAX_IMAGE_METADATA_BYTES {
+                return Err(CreateAndUploadImageError::InvalidMetadata(
+                    "too large".into(),
+                ));
+            }
+
+            data.extend_from_slice(&chunk);
+        }
+
+        serde_json::from_slice(&data)
+            .map_err(|e| CreateAndUploadImageError::InvalidMetadata(e.to_string()))
+    }
+
+    /// The categories with the given IDs, all of them must exist.
+    async fn find_categories(&self, ids: &[Uuid]) -> Result<Vec<Category>, CreateImageError> {
+        futures::future::join_all(ids.iter().map(|id| Category::by_id(*id, &self.pool)))
+            .await
+            .into_iter()
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                CreateImageError::Unexpected
+            })?
+            .into_iter()
+            .zip(ids)
+            .map(|(o, id)| o.ok_or(CreateImageError::CategoryNotFound(*id)))
+            .collect()
+    }
+
+    /// Checks whether the user can create one more image.
+    async fn check_image_quota(&self, app_user_id: Uuid) -> Result<(), CreateImageError> {
+        let (_, max_images) = self.storage_limits(app_user_id).await.map_err(|e| {
+            error!(&self.logger, "unexpected database error";
+                "error" => e.to_string()
+            );
+            CreateImageError::Unexpected
+        })?;
+
+        if let Some(max_images) = max_images {
+            let usage = DbStorageUsage::by_app_user_id(app_user_id, &self.pool)
+                .await
+                .map_err(|e| {
+                    error!(&self.logger, "unexpected database error";
+                        "error" => e.to_string()
+                    );
+                    CreateImageError::Unexpected
+                })?;
+
+            if usage.images as u64 >= max_images {
+                return Err(CreateImageError::QuotaExceeded);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// An unexpired session of the user.
+    async fn upload_session(
+        &self,
+        app_user_id: Uuid,
+        id: Uuid,
+    ) -> Result<UploadSession, UploadSessionError> {
+        UploadSession::by_id(id, &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                UploadSessionError::Unexpected
+            })?
+            .filter(|s| s.app_user_id == app_user_id && !s.is_expired())
+            .ok_or(UploadSessionError::NotFound)
+    }
+}
+
+#[async_trait(?Send)]
+impl ImageService for DefaultImageService {
+    async fn create_image(
+        &self,
+        app_user_id: Uuid,
+        image: NewImage,
+        categories: &[Uuid],
+    ) -> Result<Uuid, CreateImageError> {
+        let db_categories = self.find_categories(categories).await?;
+
+        let (_, max_images) = self.storage_limits(app_user_id).await.map_err(|e| {
+            error!(&self.logger, "unexpected database error";
+                "error" => e.to_string()
+            );
+            CreateImageError::Unexpected
+        })?;
+
+        let image_id = Image::new(app_user_id, image, max_images.map(|i| i as _), &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                CreateImageError::Unexpected
+            })?
+            .ok_or(CreateImageError::QuotaExceeded)?;
+
+        for category in db_categories {
+            category
+                .add_image(image_id, &self.pool)
+                .await
+                .map_err(|e| {
+                    error!(&self.logger, "unexpected database error";
+                        "error" => e.to_string()
+                    );
+                    CreateImageError::Unexpected
+                })?;
+        }
+
+        Ok(image_id)
+    }
+
+    async fn save_image(
+        &self,
+        app_user_id: Uuid,
+        id: Uuid,
+        mut payload: Multipart,
+    ) -> Result<(), UploadImageError> {
+        let img = Image::by_id(id, &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                UploadImageError::Unexpected
+            })?
+            .filter(|i| i.visible_to(Some(app_user_id)))
+            .ok_or(UploadImageError::InvalidId)?;
+
+        // Checked before anything is received or charged to the quota.
+        if img.app_user_id != app_user_id {
+            return Err(UploadImageError::NotAllowed);
+        }
+
+        if img.upload_date.is_some() {
+            return Err(UploadImageError::AlreadyUploaded);
+        }
+
+        match payload.try_next().await {
+            Ok(f) => match f {
+                Some(field) => {
+                    let file = self.read_file_field(field).await?;
+                    self.store_upload(img, &file.extension, &file.path).await
+                }
+                None => Err(UploadImageError::ExpectedFile),
+            },
+
+            Err(err) => {
+                error!(&self.logger, "unexpected upload error";
+                    "error" => err.to_string()
+                );
+                Err(UploadImageError::Unexpected)
+            }
+        }
+    }
+
+    async fn create_and_upload_image(
+        &self,
+        app_user_id: Uuid,
+        mut payload: Multipart,
+    ) -> Result<Uuid, CreateAndUploadImageError> {
+        let mut metadata: Option<CreateImageRequest> = None;
+        let mut file = None;
+
+        while let Some(field) = payload.try_next().await.map_err(|e| {
+            error!(&self.logger, "unexpected upload error";
+                "error" => e.to_string()
+            );
+            UploadImageError::Unexpected
+        })? {
+            let name = field
+                .content_disposition()
+                .and_then(|c| c.get_name().map(String::from))
+                .unwrap_or_default();
+
+            match name.as_str() {
+                "metadata" => metadata = Some(self.read_json_field(field).await?),
+                "file" => file = Some(self.read_file_field(field).await?),
+                _ => return Err(CreateAndUploadImageError::UnexpectedPart(name)),
+            }
+        }
+
+        let metadata = metadata.ok_or(CreateAndUploadImageError::ExpectedMetadata)?;
+        let file = file.ok_or(UploadImageError::ExpectedFile)?;
+
+        self.create_with_file(app_user_id, metadata, &file).await
+    }
+
+    async fn batch_upload_images(
+        &self,
+        app_user_id: Uuid,
+        mut payload: Multipart,
+    ) -> Result<BatchUploadResponse, BatchUploadError> {
+        let mut defaults: Option<BatchImageMetadata> = None;
+        let mut metadata: Option<Result<BatchImageMetadata, CreateAndUploadImageError>> = None;
+        let mut file_count = 0;
+        let mut items = Vec::new();
+        let mut pending = FuturesUnordered::new();
+        let mut failure = None;
+
+        loop {
+            let field = match payload.try_next().await {
+                Ok(Some(field)) => field,
+                Ok(None) => break,
+                Err(e) => {
+                    // The files received until now are still reported.
+                    warn!(&self.logger, "batch upload interrupted";
+                        "error" => e.to_string()
+                    );
+                    break;
+                }
+            };
+
+            let disposition = field.content_disposition();
+            let name = disposition
+                .as_ref()
+                .and_then(|c| c.get_name())
+                .unwrap_or_default()
+                .to_string();
+            let filename = disposition
+                .as_ref()
+                .and_then(|c| c.get_filename())
+                .map(String::from);
+
+            match name.as_str() {
+                // Only accepted before the files so that they all get the same defaults.
+                "defaults" if file_count == 0 => {
+                    defaults = Some(self.read_json_field(field).await.map_err(|e| match e {
+                        CreateAndUploadImageError::InvalidMetadata(e) => {
+                            BatchUploadError::InvalidDefaults(e)
+                        }
+                        _ => BatchUploadError::Unexpected,
+                    })?);
+                }
+                "metadata" => metadata = Some(self.read_json_field(field).await),
+                "file" => {
+                    let index = file_count;
+                    file_count += 1;
+
+                    let request = batch_image_request(
+                        defaults.as_ref(),
+                        metadata.take(),
+                        filename.as_deref(),
+                    );
+
+                    if index >= MAX_BATCH_UPLOAD_FILES {
+                        items.push(BatchUploadItem {
+                            index,
+                            filename,
+                            id: None,
+                            error: Some(BatchUploadItemError {
+                                code: "tooManyFiles".into(),
+                                message: format!(
+                                    "at most {} files can be uploaded at once",
+                                    MAX_BATCH_UPLOAD_FILES
+                                ),
+                            }),
+                        });
+                        continue;
+                    }
+
+                    // The started files are processed while the next one is received.
+                    let mut read = Box::pin(self.read_file_field(field));
+                    let file = loop {
+                        if pending.is_empty() {
+                            break read.await;
+                        }
+
+                        match future::select(read, pending.next()).await {
+                            Either::Left((file, _)) => break file,
+                            Either::Right((item, r)) => {
+                                items.extend(item);
+                                read = r;
+                            }
+                        }
+                    };
+
+                    if pending.len() >= BATCH_UPLOAD_CONCURRENCY {
+                        items.extend(pending.next().await);
+                    }
+
+                    pending.push(self.batch_upload_item(
+                        app_user_id,
+                        index,
+                        filename,
+                        request,
+                        file,
+                    ));
+                }
+                _ => {
+                    failure = Some(BatchUploadError::UnexpectedPart(name));
+                    break;
+                }
+            }
+        }
+
+        // Files that were started are always finished, even if the request is rejected.
+        while let Some(item) = pending.next().await {
+            items.push(item);
+        }
+
+        if let Some(err) = failure {
+            return Err(err);
+        }
+
+        items.sort_by_key(|i| i.index);
+
+        let failed = items.iter().filter(|i| i.error.is_some()).count() as u64;
+
+        Ok(BatchUploadResponse {
+            succeeded: items.len() as u64 - failed,
+            failed,
+            items,
+        })
+    }
+
+    async fn create_upload_session(
+        &self,
+        app_user_id: Uuid,
+        image_id: Uuid,
+        length: u64,
+        checksum: &str,
+        filename: Option<&str>,
+    ) -> Result<UploadSession, UploadSessionError> {
+        if length == 0 || length > i64::MAX as u64 {
+            return Err(UploadSessionError::InvalidLength);
+        }
+
+        if length > self.config.max_upload_bytes {
+            return Err(UploadImageError::TooLarge(self.config.max_upload_bytes).into());
+        }
+
+        let checksum = checksum.to_lowercase();
+
+        if checksum.len() != 64 || !checksum.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(UploadSessionError::InvalidChecksum);
+        }
+
+        let image = Image::by_id(image_id, &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                UploadSessionError::Unexpected
+            })?
+            .filter(|i| i.visible_to(Some(app_user_id)))
+            .ok_or(UploadSessionError::ImageNotFound)?;
+
+        if image.app_user_id != app_user_id {
+            return Err(UploadSessionError::NotAllowed);
+        }
+
+        if image.upload_date.is_some() {
+            return Err(UploadSessionError::AlreadyUploaded);
+        }
+
+        // Checked again when the upload is finished.
+        self.check_storage_quota(app_user_id, length).await?;
+
+        let extension = filename
+            .and_then(|f| Path::new(f).extension())
+            .and_then(|e| e.to_str())
+            .unwrap_or("png");
+
+        let id = UploadSession::new(
+            app_user_id,
+            NewUploadSession {
+                image_id,
+                upload_length: length as _,
+                checksum: &checksum,
+                file_extension: extension,
+            },
+            &self.pool,
+        )
+        .await
+        .map_err(|e| {
+            error!(&self.logger, "unexpected database error";
+                "error" => e.to_string()
+            );
+            UploadSessionError::Unexpected
+        })?;
+
+        let temp_dir = upload_temp_dir(&self.config);
+
+        fs::create_dir_all(&temp_dir).await.map_err(|e| {
+            error!(&self.logger, "error creating directory for images";
+                "error" => e.to_string()
+            );
+            UploadSessionError::Unexpected
+        })?;
+
+        File::create(upload_part_path(&self.config, id))
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "error creating the upload file";
+                    "error" => e.to_string()
+                );
+                UploadSessionError::Unexpected
+            })?;
+
+        self.upload_session(app_user_id, id).await
+    }
+
+    async fn get_upload_session(
+        &self,
+        app_user_id: Uuid,
+        id: Uuid,
+    ) -> Result<UploadSession, UploadSessionError> {
+        self.upload_session(app_user_id, id).await
+    }
+
+    async fn append_upload_chunk(
+        &self,
+        app_user_id: Uuid,
+        id: Uuid,
+        offset: u64,
+        mut payload: web::Payload,
+    ) -> Result<u64, UploadSessionError> {
+        let mut session = self.upload_session(app_user_id, id).await?;
+
+        if session.finishing {
+            return Err(UploadSessionError::Finishing);
+        }
+
+        if offset != session.upload_offset as u64 {
+            return Err(UploadSessionError::OffsetMismatch);
+        }
+
+        let mut file = OpenOptions::new()
+            .write(true)
+            .open(upload_part_path(&self.config, id))
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "error opening the upload file";
+                    "error" => e.to_string()
+                );
+                UploadSessionError::Unexpected
+            })?;
+
+        // Anything after the last recorded offset is from a failed request.
+        file.set_len(offset).await.map_err(|e| {
+            error!(&self.logger, "error truncating the upload file";
+                "error" => e.to_string()
+            );
+            UploadSessionError::Unexpected
+        })?;
+
+        file.seek(SeekFrom::Start(offset)).await.map_err(|e| {
+            error!(&self.logger, "error seeking the upload file";
+                "error" => e.to_string()
+            );
+            UploadSessionError::Unexpected
+        })?;
+
+        let mut received = offset;
+        let mut result = Ok(());
+
+        while let Some(chunk) = payload.next().await {
+            let chunk = match chunk {
+                Ok(c) => c,
+                Err(e) => {
+                    warn!(&self.logger, "upload chunk interrupted";
+                        "error" => e.to_string()
+                    );
+                    result = Err(UploadSessionError::Unexpected);
+                    break;
+                }
+            };
+
+            if received + chunk.len() as u64 > session.upload_length as u64 {
+                result = Err(UploadSessionError::ExceedsLength);
+                break;
+            }
+
+            file.write_all(&chunk).await.map_err(|e| {
+                error!(&self.logger, "error writing the upload file";
+                    "error" => e.to_string()
+                );
+                UploadSessionError::Unexpected
+            })?;
+
+            received += chunk.len() as u64;
+        }
+
+        file.sync_data().await.map_err(|e| {
+            error!(&self.logger, "error writing the upload file";
+                "error" => e.to_string()
+            );
+            UploadSessionError::Unexpected
+        })?;
+
+        // The bytes received before an error are kept, so the client can resume from there.
+        let advanced = session
+            .advance(received as _, &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                UploadSessionError::Unexpected
+            })?;
+
+        if !advanced {
+            return Err(UploadSessionError::OffsetMismatch);
+        }
+
+        result.map(|_| received)
+    }
+
+    async fn finish_upload_session(
+        &self,
+        app_user_id: Uuid,
+        id: Uuid,
+    ) -> Result<(), UploadSessionError> {
+        let mut session = self.upload_session(app_user_id, id).await?;
+
+        if !session.is_complete() {
+            return Err(UploadSessionError::Incomplete);
+        }
+
+        let image = Image::by_id(session.image_id, &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                UploadSessionError::Unexpected
+            })?
+            .ok_or(UploadSessionError::ImageNotFound)?;
+
+        if image.upload_date.is_some() {
+            return Err(UploadSessionError::AlreadyUploaded);
+        }
+
+        // Only one request can finish the session, the appended chunks are rejected meanwhile.
+        let claimed = session.claim(&self.pool).await.map_err(|e| {
+            error!(&self.logger, "unexpected database error";
+                "error" => e.to_string()
+            );
+            UploadSessionError::Unexpected
+        })?;
+
+        if !claimed {
+            return Err(UploadSessionError::Finishing);
+        }
+
+        let part_path = upload_part_path(&self.config, id);
+
+        let stored: Result<(), UploadSessionError> = async {
+            let path = part_path.clone();
+            let checksum = web::block(move || {
+                let mut hasher = Sha256::new();
+                std::io::copy(&mut std::fs::File::open(&path)?, &mut hasher)?;
+
+                Ok::<_, std::io::Error>(format!("{:x}", hasher.finalize()))
+            })
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "error reading the upload file";
+                    "error" => e.to_string()
+                );
+                UploadSessionError::Unexpected
+            })?;
+
+            if checksum != session.checksum {
+                return Err(UploadSessionError::ChecksumMismatch);
+            }
+
+            self.store_upload(image, OsStr::new(&session.file_extension), &part_path)
+                .await?;
+
+            Ok(())
+        }
+        .await;
+
+        if let Err(err) = stored {
+            // A mismatching file is sent again from the start, other errors can be retried.
+            let offset = match err {
+                UploadSessionError::ChecksumMismatch => 0,
+                _ => session.upload_offset,
+            };
+
+            session.release(offset, &self.pool).await.map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                UploadSessionError::Unexpected
+            })?;
+
+            return Err(err);
+        }
+
+        session.delete(&self.pool).await.map_err(|e| {
+            error!(&self.logger, "unexpected database error";
+                "error" => e.to_string()
+            );
+            UploadSessionError::Unexpected
+        })?;
+
+        if let Err(e) = fs::remove_file(&part_path).await {
+            warn!(&self.logger, "error removing the upload file";
+                "error" => e.to_string()
+            );
+        }
+
+        Ok(())
+    }
+
+    async fn get_image(
+        &self,
+        id: Uuid,
+        viewer: Option<&UserInfo>,
+    ) -> Result<NamedFile, DownloadImageError> {
+        Image::by_id(id, &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                DownloadImageError::Unexpected
+            })?
+            .filter(|i| match viewer {
+                Some(u) => i.visible_to_role(u.id, u.role),
+                None => i.visible_to(None),
+            })
+            .ok_or(DownloadImageError::NotFound)?;
+
+        NamedFile::open(image_path(&self.config, id)).map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => DownloadImageError::NotFound,
+            _ => {
+                error!(&self.logger, "error opening the image";
+                    "error" => e.to_string()
+                );
+                DownloadImageError::Unexpected
+            }
+        })
+    }
+
+    async fn search_images(
+        &self,
+        mut filter: ImageFilter,
+        offset: Option<u64>,
+        limit: Option<u64>,
+    ) -> Result<Vec<ImageExt>, SearchImagesError> {
+        if let Some(false) = filter.area.map(|a| a.is_valid()) {
+            return Err(SearchImagesError::InvalidArea);
+        }
+
+        filter.tags = filter
+            .tags
+            .iter()
+            .filter_map(|t| normalize_tag(t))
+            .collect();
+
+        let images = Image::search(
+            &filter,
+            offset.map(|v| v as _),
+            limit.map(|v| v as _),
+            &self.pool,
+        )
+        .await
+        .map_err(|e| {
+            error!(&self.logger, "unexpected database error";
+                "error" => e.to_string()
+            );
+            SearchImagesError::Unexpected
+        })?;
+
+        let images = ImageExt::from_images(images, &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                SearchImagesError::Unexpected
+            })?;
+
+        Ok(images
+            .into_iter()
+            // Filter only uploaded images,
+            // this is required because the image is not
+            // guaranteed to be on storage, even if it was once uploaded.
+            .filter(|i| image_path(&self.config, i.image.id).exists())
+            .collect())
+    }
+
+    async fn rate_image(
+        &self,
+        image_id: Uuid,
+        app_user_id: Uuid,
+        rating: u32,
+    ) -> Result<(), RateImageError> {
+        let image = Image::by_id(image_id, &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                RateImageError::Unexpected
+            })?
+            .filter(|i| i.visible_to(Some(app_user_id)))
+            .ok_or(RateImageError::ImageNotFound)?;
+
+        if image.app_user_id == app_user_id {
+            return Err(RateImageError::OwnImage);
+        }
+
+        if rating > 5 || rating == 0 {
+            return Err(RateImageError::InvalidRating);
+        }
+
+        image
+            .rate(app_user_id, rating as _, &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                RateImageError::Unexpected
+            })?;
+
+        notify(
+            &self.pool,
+            &self.logger,
+            NotificationEvent {
+                app_user_id: image.app_user_id,
+                actor_id: Some(app_user_id),
+                kind: NotificationKind::ImageRated,
+                image_id: Some(image.id),
+                comment_id: None,
+                details: Some(json!({ "rating": rating })),
+            },
+        )
+        .await;
+
+        Ok(())
+    }
+
+    async fn get_image_ratings(
+        &self,
+        image_id: Uuid,
+        viewer_id: Option<Uuid>,
+    ) -> Result<Vec<Rating>, GetImageRatingsError> {
+        let image = Image::by_id(image_id, &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                GetImageRatingsError::Unexpected
+            })?
+            .filter(|i| i.visible_to(viewer_id))
+            .ok_or(GetImageRatingsError::ImageNotFound)?;
+
+        image.ratings(&self.pool).await.map_err(|e| {
+            error!(&self.logger, "unexpected database error";
+                "error" => e.to_string()
+            );
+            GetImageRatingsError::Unexpected
+        })
+    }
+
+    async fn get_categories(
+        &self,
+        include_descendants: bool,
+    ) -> Result<Vec<CategoryExt>, GetCategoriesError> {
+        CategoryExt::all(include_descendants, &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                GetCategoriesError::Unexpected
+            })
+    }
+
+    async fn create_category(
+        &self,
+        context: &AuditContext,
+        name: &str,
+        slug: Option<&str>,
+        parent_id: Option<Uuid>,
+        description: Option<&str>,
+    ) -> Result<Uuid, CreateCategoryError> {
+        let re = Regex::new(CATEGORY_NAME_PATTERN).unwrap();
+
+        if !re.is_match(name) {
+            return Err(CreateCategoryError::InvalidName(
+                CATEGORY_NAME_PATTERN.into(),
+            ));
+        }
+
+        let categories = Category::all(&self.pool).await.map_err(|e| {
+            error!(&self.logger, "unexpected database error";
+                "error" => e.to_string()
+            );
+            CreateCategoryError::Unexpected
+        })?;
+
+        let lc_name = name.to_lowercase();
 
         if categories
             .iter()
@@ -351,113 +1681,902 @@ impl ImageService for DefaultImageService {
             return Err(CreateCategoryError::AlreadyExists);
         }
 
-        Category::new(name, &self.pool).await.map_err(|e| {
+        if let Some(parent_id) = parent_id {
+            if !categories.iter().any(|c| c.id == parent_id) {
+                return Err(CreateCategoryError::ParentNotFound);
+            }
+        }
+
+        let slug = match slug {
+            Some(slug) => {
+                if !Regex::new(SLUG_REGEX).unwrap().is_match(slug) {
+                    return Err(CreateCategoryError::InvalidSlug(SLUG_REGEX.into()));
+                }
+
+                if categories.iter().any(|c| c.slug == slug) {
+                    return Err(CreateCategoryError::SlugAlreadyExists);
+                }
+
+                slug.to_string()
+            }
+            None => self.unique_slug(name).await.map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                CreateCategoryError::Unexpected
+            })?,
+        };
+
+        let description = description.map(str::trim).filter(|d| !d.is_empty());
+
+        let id = Category::new(
+            NewCategory {
+                name,
+                slug: &slug,
+                parent_id,
+                description,
+            },
+            &self.pool,
+        )
+        .await
+        .map_err(|e| {
+            error!(&self.logger, "unexpected database error";
+                "error" => e.to_string()
+            );
+            CreateCategoryError::Unexpected
+        })?;
+
+        record_audit_event(
+            &self.pool,
+            &self.logger,
+            context,
+            AuditChange {
+                action: AuditAction::CategoryCreate,
+                target_type: AUDIT_TARGET_CATEGORY,
+                target_id: id.to_string(),
+                before: None,
+                after: Some(json!({
+                    "name": name,
+                    "slug": slug,
+                    "parentId": parent_id,
+                    "description": description,
+                })),
+            },
+        )
+        .await;
+
+        Ok(id)
+    }
+
+    async fn rename_category(
+        &self,
+        context: &AuditContext,
+        id: Uuid,
+        name: &str,
+    ) -> Result<(), RenameCategoryError> {
+        let re = Regex::new(CATEGORY_NAME_PATTERN).unwrap();
+
+        if !re.is_match(name) {
+            return Err(RenameCategoryError::InvalidName(
+                CATEGORY_NAME_PATTERN.into(),
+            ));
+        }
+
+        let mut category = Category::by_id(id, &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                RenameCategoryError::Unexpected
+            })?
+            .ok_or(RenameCategoryError::CategoryNotFound)?;
+
+        let categories = Category::all(&self.pool).await.map_err(|e| {
+            error!(&self.logger, "unexpected database error";
+                "error" => e.to_string()
+            );
+            RenameCategoryError::Unexpected
+        })?;
+
+        let lc_name = name.to_lowercase();
+
+        if categories
+            .iter()
+            .any(|c| c.category_name.to_lowercase() == lc_name)
+        {
+            return Err(RenameCategoryError::AlreadyExists);
+        }
+
+        let old_name = std::mem::replace(&mut category.category_name, name.into());
+
+        category.save(&self.pool).await.map_err(|e| {
+            error!(&self.logger, "unexpected database error";
+                "error" => e.to_string()
+            );
+            RenameCategoryError::Unexpected
+        })?;
+
+        record_audit_event(
+            &self.pool,
+            &self.logger,
+            context,
+            AuditChange {
+                action: AuditAction::CategoryRename,
+                target_type: AUDIT_TARGET_CATEGORY,
+                target_id: id.to_string(),
+                before: Some(json!({ "name": old_name })),
+                after: Some(json!({ "name": name })),
+            },
+        )
+        .await;
+
+        Ok(())
+    }
+
+    async fn move_category(
+        &self,
+        id: Uuid,
+        parent_id: Option<Uuid>,
+    ) -> Result<(), MoveCategoryError> {
+        let mut category = Category::by_id(id, &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                MoveCategoryError::Unexpected
+            })?
+            .ok_or(MoveCategoryError::CategoryNotFound)?;
+
+        if let Some(parent_id) = parent_id {
+            let parent = Category::by_id(parent_id, &self.pool)
+                .await
+                .map_err(|e| {
+                    error!(&self.logger, "unexpected database error";
+                        "error" => e.to_string()
+                    );
+                    MoveCategoryError::Unexpected
+                })?
+                .ok_or(MoveCategoryError::ParentNotFound)?;
+
+            let ancestor_ids = parent.ancestor_ids(&self.pool).await.map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                MoveCategoryError::Unexpected
+            })?;
+
+            if ancestor_ids.contains(&category.id) {
+                return Err(MoveCategoryError::Cycle);
+            }
+        }
+
+        category.parent_id = parent_id;
+
+        category.save(&self.pool).await.map_err(|e| {
+            error!(&self.logger, "unexpected database error";
+                "error" => e.to_string()
+            );
+            MoveCategoryError::Unexpected
+        })
+    }
+
+    async fn update_category_details(
+        &self,
+        id: Uuid,
+        description: Option<&str>,
+        cover_image_id: Option<Uuid>,
+    ) -> Result<(), UpdateCategoryDetailsError> {
+        let mut category = Category::by_id(id, &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                UpdateCategoryDetailsError::Unexpected
+            })?
+            .ok_or(UpdateCategoryDetailsError::CategoryNotFound)?;
+
+        if let Some(cover_image_id) = cover_image_id {
+            let in_category = category
+                .has_image(cover_image_id, &self.pool)
+                .await
+                .map_err(|e| {
+                    error!(&self.logger, "unexpected database error";
+                        "error" => e.to_string()
+                    );
+                    UpdateCategoryDetailsError::Unexpected
+                })?;
+
+            if !in_category {
+                return Err(UpdateCategoryDetailsError::CoverNotInCategory);
+            }
+        }
+
+        category.description = description
+            .map(str::trim)
+            .filter(|d| !d.is_empty())
+            .map(Into::into);
+        category.cover_image_id = cover_image_id;
+
+        category.save(&self.pool).await.map_err(|e| {
+            error!(&self.logger, "unexpected database error";
+                "error" => e.to_string()
+            );
+            UpdateCategoryDetailsError::Unexpected
+        })
+    }
+
+    async fn delete_category(
+        &self,
+        context: &AuditContext,
+        id: Uuid,
+        children: Option<DeleteCategoryChildren>,
+    ) -> Result<(), DeleteCategoryError> {
+        let category = Category::by_id(id, &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                DeleteCategoryError::Unexpected
+            })?
+            .ok_or(DeleteCategoryError::CategoryNotFound)?;
+
+        let children = match children {
+            Some(DeleteCategoryChildren::Reparent) => DeleteChildren::Reparent,
+            Some(DeleteCategoryChildren::Cascade) => DeleteChildren::Cascade,
+            None => {
+                let has_children = category.has_children(&self.pool).await.map_err(|e| {
+                    error!(&self.logger, "unexpected database error";
+                        "error" => e.to_string()
+                    );
+                    DeleteCategoryError::Unexpected
+                })?;
+
+                if has_children {
+                    return Err(DeleteCategoryError::HasChildren);
+                }
+
+                // Nothing to reparent.
+                DeleteChildren::Reparent
+            }
+        };
+
+        let before = json!({
+            "name": category.category_name,
+            "slug": category.slug,
+            "parentId": category.parent_id,
+            "description": category.description,
+            "children": match children {
+                DeleteChildren::Reparent => "reparent",
+                DeleteChildren::Cascade => "cascade",
+            },
+        });
+
+        category
+            .delete(children, context.actor_id, &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                DeleteCategoryError::Unexpected
+            })?;
+
+        record_audit_event(
+            &self.pool,
+            &self.logger,
+            context,
+            AuditChange {
+                action: AuditAction::CategoryDelete,
+                target_type: AUDIT_TARGET_CATEGORY,
+                target_id: id.to_string(),
+                before: Some(before),
+                after: None,
+            },
+        )
+        .await;
+
+        Ok(())
+    }
+
+    async fn merge_category(
+        &self,
+        id: Uuid,
+        target_id: Uuid,
+    ) -> Result<MergedImages, MergeCategoriesError> {
+        let category = Category::by_id(id, &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                MergeCategoriesError::Unexpected
+            })?
+            .ok_or(MergeCategoriesError::CategoryNotFound)?;
+
+        let target = Category::by_id(target_id, &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                MergeCategoriesError::Unexpected
+            })?
+            .ok_or(MergeCategoriesError::TargetNotFound)?;
+
+        // The subcategories are moved to the target, so it cannot be one of them.
+        let target_ancestor_ids = target.ancestor_ids(&self.pool).await.map_err(|e| {
+            error!(&self.logger, "unexpected database error";
+                "error" => e.to_string()
+            );
+            MergeCategoriesError::Unexpected
+        })?;
+
+        if target_ancestor_ids.contains(&category.id) {
+            return Err(MergeCategoriesError::InvalidTarget);
+        }
+
+        category
+            .merge_into(target.id, &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                MergeCategoriesError::Unexpected
+            })
+    }
+
+    async fn bulk_categorize(
+        &self,
+        id: Uuid,
+        action: BulkCategoryAction,
+        image_ids: &[Uuid],
+        dry_run: bool,
+    ) -> Result<BulkCategorizeResponse, BulkCategorizeError> {
+        if image_ids.len() > MAX_BULK_CATEGORIZE_IMAGES {
+            return Err(BulkCategorizeError::TooManyImages(
+                MAX_BULK_CATEGORIZE_IMAGES,
+            ));
+        }
+
+        let mut image_ids = image_ids.to_vec();
+        image_ids.sort();
+        image_ids.dedup();
+
+        let category = Category::by_id(id, &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                BulkCategorizeError::Unexpected
+            })?
+            .ok_or(BulkCategorizeError::CategoryNotFound)?;
+
+        let existing_ids = Image::existing_ids(&image_ids, &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                BulkCategorizeError::Unexpected
+            })?;
+
+        let linked_count = category
+            .linked_image_ids(&existing_ids, &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                BulkCategorizeError::Unexpected
+            })?
+            .len() as u64;
+
+        let missing_image_ids = image_ids
+            .into_iter()
+            .filter(|id| !existing_ids.contains(id))
+            .collect();
+
+        let expected = match action {
+            BulkCategoryAction::Add => existing_ids.len() as u64 - linked_count,
+            BulkCategoryAction::Remove => linked_count,
+        };
+
+        let affected = if dry_run {
+            expected
+        } else {
+            match action {
+                BulkCategoryAction::Add => category.add_images(&existing_ids, &self.pool).await,
+                BulkCategoryAction::Remove => {
+                    category.remove_images(&existing_ids, &self.pool).await
+                }
+            }
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                BulkCategorizeError::Unexpected
+            })?
+        };
+
+        Ok(BulkCategorizeResponse {
+            dry_run,
+            affected,
+            unchanged: existing_ids.len() as u64 - affected,
+            missing_image_ids,
+        })
+    }
+
+    async fn get_image_info(
+        &self,
+        id: Uuid,
+        viewer: Option<&UserInfo>,
+    ) -> Result<ImageExt, GetImageInfoError> {
+        let image = Image::by_id(id, &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                GetImageInfoError::Unexpected
+            })?
+            .filter(|i| match viewer {
+                Some(u) => i.visible_to_role(u.id, u.role),
+                None => i.visible_to(None),
+            })
+            .ok_or(GetImageInfoError::NotFound)?;
+
+        ImageExt::from_image(image, &self.pool).await.map_err(|e| {
+            error!(&self.logger, "unexpected database error";
+                "error" => e.to_string()
+            );
+            GetImageInfoError::Unexpected
+        })
+    }
+
+    async fn get_user_ratings(&self) -> Result<Vec<UserRating>, GetUserRatingsError> {
+        Ok(crate::db::rating::UserRating::all(&self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                GetUserRatingsError::Unexpected
+            })?
+            .into_iter()
+            .map(|r| UserRating {
+                name: r.email,
+                average_rating: r.average_rating.unwrap_or(0f64),
+            })
+            .collect())
+    }
+    async fn set_image_visibility(
+        &self,
+        app_user_id: Uuid,
+        image_id: Uuid,
+        visibility: Visibility,
+    ) -> Result<(), SetImageVisibilityError> {
+        let mut image = Image::by_id(image_id, &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                SetImageVisibilityError::Unexpected
+            })?
+            .filter(|i| i.visible_to(Some(app_user_id)))
+            .ok_or(SetImageVisibilityError::ImageNotFound)?;
+
+        if image.app_user_id != app_user_id {
+            return Err(SetImageVisibilityError::NotAllowed);
+        }
+
+        image.visibility = visibility.as_str().into();
+
+        image.save(&self.pool).await.map_err(|e| {
             error!(&self.logger, "unexpected database error";
                 "error" => e.to_string()
             );
-            CreateCategoryError::Unexpected
+            SetImageVisibilityError::Unexpected
         })
     }
 
-    async fn rename_category(&self, id: Uuid, name: &str) -> Result<(), RenameCategoryError> {
-        let re = Regex::new(CATEGORY_NAME_PATTERN).unwrap();
+    async fn set_image_location(
+        &self,
+        app_user_id: Uuid,
+        image_id: Uuid,
+        location: Option<(f64, f64)>,
+    ) -> Result<(), SetImageLocationError> {
+        if let Some((lat, lon)) = location {
+            if !valid_location(lat, lon) {
+                return Err(SetImageLocationError::InvalidLocation);
+            }
+        }
 
-        if !re.is_match(name) {
-            return Err(RenameCategoryError::InvalidName(
-                CATEGORY_NAME_PATTERN.into(),
-            ));
+        let mut image = Image::by_id(image_id, &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                SetImageLocationError::Unexpected
+            })?
+            .filter(|i| i.visible_to(Some(app_user_id)))
+            .ok_or(SetImageLocationError::ImageNotFound)?;
+
+        if image.app_user_id != app_user_id {
+            return Err(SetImageLocationError::NotAllowed);
         }
 
-        let mut category = Category::by_id(id, &self.pool)
+        image.latitude = location.map(|l| l.0);
+        image.longitude = location.map(|l| l.1);
+
+        image.save(&self.pool).await.map_err(|e| {
+            error!(&self.logger, "unexpected database error";
+                "error" => e.to_string()
+            );
+            SetImageLocationError::Unexpected
+        })
+    }
+
+    async fn get_similar_images(
+        &self,
+        image_id: Uuid,
+        max_distance: Option<u32>,
+        limit: Option<u64>,
+    ) -> Result<Vec<(ImageExt, u32)>, GetSimilarImagesError> {
+        let image = Image::by_id(image_id, &self.pool)
             .await
             .map_err(|e| {
                 error!(&self.logger, "unexpected database error";
                     "error" => e.to_string()
                 );
-                RenameCategoryError::Unexpected
+                GetSimilarImagesError::Unexpected
             })?
-            .ok_or(RenameCategoryError::CategoryNotFound)?;
+            .ok_or(GetSimilarImagesError::NotFound)?;
 
-        let categories = Category::all(&self.pool).await.map_err(|e| {
+        let phash = match image.phash {
+            Some(h) => h,
+            None => return Ok(Vec::new()),
+        };
+
+        let similar = Image::similar(
+            phash,
+            image.id,
+            None,
+            max_distance.unwrap_or(self.config.duplicate_threshold) as _,
+            limit.unwrap_or(20) as _,
+            &self.pool,
+        )
+        .await
+        .map_err(|e| {
             error!(&self.logger, "unexpected database error";
                 "error" => e.to_string()
             );
-            RenameCategoryError::Unexpected
+            GetSimilarImagesError::Unexpected
         })?;
 
-        let lc_name = name.to_lowercase();
+        let mut images = Vec::with_capacity(similar.len());
 
-        if categories
-            .iter()
-            .any(|c| c.category_name.to_lowercase() == lc_name)
-        {
-            return Err(RenameCategoryError::AlreadyExists);
+        for s in similar {
+            let image = match Image::by_id(s.id, &self.pool).await {
+                Ok(Some(i)) => i,
+                Ok(None) => continue,
+                Err(e) => {
+                    error!(&self.logger, "unexpected database error";
+                        "error" => e.to_string()
+                    );
+                    return Err(GetSimilarImagesError::Unexpected);
+                }
+            };
+
+            let image = ImageExt::from_image(image, &self.pool).await.map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                GetSimilarImagesError::Unexpected
+            })?;
+
+            images.push((image, s.distance as u32));
         }
 
-        category.category_name = name.into();
+        Ok(images)
+    }
 
-        category.save(&self.pool).await.map_err(|e| {
+    async fn process_upload(&self, image_id: Uuid) -> Result<(), ProcessUploadError> {
+        let image = Image::by_id(image_id, &self.pool).await.map_err(|e| {
             error!(&self.logger, "unexpected database error";
                 "error" => e.to_string()
             );
-            RenameCategoryError::Unexpected
-        })
-    }
+            ProcessUploadError::Unexpected
+        })?;
 
-    async fn delete_category(&self, id: Uuid) -> Result<(), DeleteCategoryError> {
-        let category = Category::by_id(id, &self.pool)
+        let mut image = match image {
+            Some(i) => i,
+            // It was deleted in the meantime.
+            None => return Ok(()),
+        };
+
+        let filepath = find_image_file(&self.config, image.id)
             .await
             .map_err(|e| {
-                error!(&self.logger, "unexpected database error";
+                error!(&self.logger, "error reading the file";
                     "error" => e.to_string()
                 );
-                DeleteCategoryError::Unexpected
+                ProcessUploadError::Unexpected
             })?
-            .ok_or(DeleteCategoryError::CategoryNotFound)?;
+            .ok_or(ProcessUploadError::FileNotFound)?;
+
+        let data = fs::read(&filepath).await.map_err(|e| {
+            error!(&self.logger, "error reading the file";
+                "error" => e.to_string()
+            );
+            ProcessUploadError::Unexpected
+        })?;
+
+        let phash = web::block(move || Ok::<_, ()>(phash::dhash(&data)))
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "error processing the file";
+                    "error" => e.to_string()
+                );
+                ProcessUploadError::Unexpected
+            })?;
+
+        // Not every stored file can be decoded.
+        let phash = match phash {
+            Some(h) => h,
+            None => return Ok(()),
+        };
+
+        image.duplicate_of = self.find_duplicate(&image, phash).await.map_err(|e| {
+            error!(&self.logger, "unexpected database error";
+                "error" => e.to_string()
+            );
+            ProcessUploadError::Unexpected
+        })?;
+        image.phash = Some(phash);
 
-        category.delete(&self.pool).await.map_err(|e| {
+        image.save_phash(&self.pool).await.map_err(|e| {
             error!(&self.logger, "unexpected database error";
                 "error" => e.to_string()
             );
-            DeleteCategoryError::Unexpected
+            ProcessUploadError::Unexpected
         })
     }
 
-    async fn get_image_info(&self, id: Uuid) -> Result<(Image, Vec<Category>), GetImageInfoError> {
-        let image = Image::by_id(id, &self.pool)
+    async fn get_image_clusters(
+        &self,
+        zoom: u8,
+        bbox: Option<BoundingBox>,
+        viewer_id: Option<Uuid>,
+    ) -> Result<Vec<LocationCluster>, GetImageClustersError> {
+        if zoom > MAX_CLUSTER_ZOOM {
+            return Err(GetImageClustersError::InvalidZoom(MAX_CLUSTER_ZOOM));
+        }
+
+        if let Some(false) = bbox.map(|b| b.is_valid()) {
+            return Err(GetImageClustersError::InvalidArea);
+        }
+
+        Image::location_clusters(cluster_cell_size(zoom), viewer_id, bbox, &self.pool)
             .await
             .map_err(|e| {
                 error!(&self.logger, "unexpected database error";
                     "error" => e.to_string()
                 );
-                GetImageInfoError::Unexpected
+                GetImageClustersError::Unexpected
+            })
+    }
+
+    async fn set_image_tags(
+        &self,
+        app_user_id: Uuid,
+        image_id: Uuid,
+        tags: &[String],
+    ) -> Result<Vec<String>, SetImageTagsError> {
+        let image = Image::by_id(image_id, &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                SetImageTagsError::Unexpected
             })?
-            .ok_or(GetImageInfoError::NotFound)?;
+            .ok_or(SetImageTagsError::ImageNotFound)?;
+
+        if image.app_user_id != app_user_id {
+            return Err(SetImageTagsError::NotAllowed);
+        }
+
+        let mut tag_names: Vec<String> = Vec::with_capacity(tags.len());
+
+        for tag in tags {
+            let tag_name =
+                normalize_tag(tag).ok_or_else(|| SetImageTagsError::InvalidTag(tag.clone()))?;
+
+            if !tag_names.contains(&tag_name) {
+                tag_names.push(tag_name);
+            }
+        }
+
+        if tag_names.len() > MAX_TAGS_PER_IMAGE {
+            return Err(SetImageTagsError::TooManyTags(MAX_TAGS_PER_IMAGE));
+        }
+
+        Tag::set_image_tags(image_id, &tag_names, &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                SetImageTagsError::Unexpected
+            })?;
+
+        Ok(tag_names)
+    }
+
+    async fn autocomplete_tags(
+        &self,
+        text: &str,
+        limit: Option<u64>,
+    ) -> Result<Vec<TagCount>, GetTagsError> {
+        let text = match normalize_tag(text) {
+            Some(t) => t,
+            None => return Ok(Vec::new()),
+        };
+
+        TagCount::autocomplete(&text, limit.map(|v| v as _), &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                GetTagsError::Unexpected
+            })
+    }
+
+    async fn get_popular_tags(&self, limit: Option<u64>) -> Result<Vec<TagCount>, GetTagsError> {
+        TagCount::popular(limit.map(|v| v as _), &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                GetTagsError::Unexpected
+            })
+    }
+
+    async fn promote_tag(&self, id: Uuid) -> Result<Uuid, PromoteTagError> {
+        let tag = Tag::by_id(id, &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                PromoteTagError::Unexpected
+            })?
+            .ok_or(PromoteTagError::TagNotFound)?;
 
-        let categories = image.categories(&self.pool).await.map_err(|e| {
+        let categories = Category::all(&self.pool).await.map_err(|e| {
             error!(&self.logger, "unexpected database error";
                 "error" => e.to_string()
             );
-            GetImageInfoError::Unexpected
+            PromoteTagError::Unexpected
+        })?;
+
+        // The tag is merged into the category if one already exists with the same name.
+        let category_id = match categories
+            .iter()
+            .find(|c| c.category_name.to_lowercase() == tag.tag_name)
+        {
+            Some(c) => c.id,
+            None => {
+                let slug = self.unique_slug(&tag.tag_name).await.map_err(|e| {
+                    error!(&self.logger, "unexpected database error";
+                        "error" => e.to_string()
+                    );
+                    PromoteTagError::Unexpected
+                })?;
+
+                Category::new(
+                    NewCategory {
+                        name: &tag.tag_name,
+                        slug: &slug,
+                        parent_id: None,
+                        description: None,
+                    },
+                    &self.pool,
+                )
+                .await
+                .map_err(|e| {
+                    error!(&self.logger, "unexpected database error";
+                        "error" => e.to_string()
+                    );
+                    PromoteTagError::Unexpected
+                })?
+            }
+        };
+
+        tag.promote(category_id, &self.pool).await.map_err(|e| {
+            error!(&self.logger, "unexpected database error";
+                "error" => e.to_string()
+            );
+            PromoteTagError::Unexpected
         })?;
 
-        Ok((image, categories))
+        Ok(category_id)
     }
 
-    async fn get_user_ratings(&self) -> Result<Vec<UserRating>, GetUserRatingsError> {
-        Ok(crate::db::rating::UserRating::all(&self.pool)
+    async fn get_storage_usage(
+        &self,
+        app_user_id: Uuid,
+    ) -> Result<StorageUsage, StorageUsageError> {
+        let (max_bytes, max_images) = self.storage_limits(app_user_id).await.map_err(|e| {
+            error!(&self.logger, "unexpected database error";
+                "error" => e.to_string()
+            );
+            StorageUsageError::Unexpected
+        })?;
+
+        let usage = DbStorageUsage::by_app_user_id(app_user_id, &self.pool)
             .await
             .map_err(|e| {
                 error!(&self.logger, "unexpected database error";
                     "error" => e.to_string()
                 );
-                GetUserRatingsError::Unexpected
-            })?
+                StorageUsageError::Unexpected
+            })?;
+
+        Ok(StorageUsage {
+            bytes: usage.bytes as _,
+            images: usage.images as _,
+            max_bytes,
+            max_images,
+        })
+    }
+
+    async fn set_storage_quota(
+        &self,
+        app_user_id: Uuid,
+        max_bytes: Option<u64>,
+        max_images: Option<u64>,
+    ) -> Result<(), SetStorageQuotaError> {
+        if max_bytes
             .into_iter()
-            .map(|r| UserRating {
-                name: r.email,
-                average_rating: r.average_rating.unwrap_or(0f64),
-            })
-            .collect())
+            .chain(max_images)
+            .any(|l| l > i64::MAX as u64)
+        {
+            return Err(SetStorageQuotaError::InvalidLimit);
+        }
+
+        AppUser::by_id(app_user_id, &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                SetStorageQuotaError::Unexpected
+            })?
+            .ok_or(SetStorageQuotaError::UserNotFound)?;
+
+        UserQuota::set(
+            app_user_id,
+            max_bytes.map(|b| b as _),
+            max_images.map(|i| i as _),
+            &self.pool,
+        )
+        .await
+        .map_err(|e| {
+            error!(&self.logger, "unexpected database error";
+                "error" => e.to_string()
+            );
+            SetStorageQuotaError::Unexpected
+        })
     }
 }
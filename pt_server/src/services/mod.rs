@@ -1,10 +1,26 @@
 use dyn_clone::DynClone;
 
+pub mod account;
+pub mod album;
+pub mod audit;
 pub mod auth;
+pub mod comment;
 pub mod image;
+pub mod moderation;
+pub mod notification;
+pub mod share_link;
+pub mod trash;
 
 pub trait Service: Send + Sync + DynClone {}
 impl<S> Service for S where S: Send + Sync + DynClone {}
 
+pub use account::{AccountService, DefaultAccountService};
+pub use album::{AlbumService, DefaultAlbumService};
+pub use audit::{AuditService, DefaultAuditService};
 pub use auth::{AuthService, DefaultAuthService};
+pub use comment::{CommentService, DefaultCommentService};
 pub use image::{ImageService, DefaultImageService};
+pub use moderation::{DefaultModerationService, ModerationService};
+pub use notification::{DefaultNotificationService, NotificationService};
+pub use share_link::{DefaultShareLinkService, ShareLinkService};
+pub use trash::{DefaultTrashService, TrashService};
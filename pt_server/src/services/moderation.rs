@@ -0,0 +1,261 @@
+use super::{
+    auth::UserInfo,
+    notification::{notify, NotificationEvent},
+    Service,
+};
+use crate::{
+    db::image::Image,
+    db::image_report::{ImageReport, NewImageReport, ReportedImage},
+    db::moderation_action::{ModerationAction, NewModerationAction},
+    model::moderation::*,
+    model::notification::NotificationKind,
+    model::role::Permission,
+};
+use async_trait::async_trait;
+use serde_json::json;
+use slog::{error, Logger};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+pub const REPORT_DETAILS_MAX_LENGTH: usize = 1000;
+
+#[async_trait(?Send)]
+pub trait ModerationService: Service {
+    async fn report_image(
+        &self,
+        app_user_id: Uuid,
+        image_id: Uuid,
+        reason: ReportReason,
+        details: Option<&str>,
+    ) -> Result<Uuid, ReportImageError>;
+    /// Images with open reports, the most reported first.
+    async fn get_reported_images(
+        &self,
+        offset: Option<u64>,
+        limit: Option<u64>,
+    ) -> Result<Vec<(ReportedImage, Vec<ImageReport>)>, GetReportedImagesError>;
+    /// Resolves the open reports of the image, hiding and deleting
+    /// also requires the permission to hide images.
+    async fn moderate_image(
+        &self,
+        moderator: &UserInfo,
+        image_id: Uuid,
+        action: ModerationActionKind,
+        note: Option<&str>,
+        warn_user: bool,
+    ) -> Result<(), ModerateImageError>;
+    async fn get_moderation_actions(
+        &self,
+        image_id: Option<Uuid>,
+        offset: Option<u64>,
+        limit: Option<u64>,
+    ) -> Result<Vec<ModerationAction>, GetModerationActionsError>;
+}
+dyn_clone::clone_trait_object!(ModerationService);
+
+#[derive(Debug, Clone)]
+pub struct DefaultModerationService {
+    pool: PgPool,
+    logger: Logger,
+}
+
+impl DefaultModerationService {
+    pub fn new(logger: Logger, pool: PgPool) -> Self {
+        Self { logger, pool }
+    }
+}
+
+#[async_trait(?Send)]
+impl ModerationService for DefaultModerationService {
+    async fn report_image(
+        &self,
+        app_user_id: Uuid,
+        image_id: Uuid,
+        reason: ReportReason,
+        details: Option<&str>,
+    ) -> Result<Uuid, ReportImageError> {
+        let details = details.map(str::trim).filter(|d| !d.is_empty());
+
+        if details.map(|d| d.chars().count()).unwrap_or(0) > REPORT_DETAILS_MAX_LENGTH {
+            return Err(ReportImageError::InvalidDetails(REPORT_DETAILS_MAX_LENGTH));
+        }
+
+        Image::by_id(image_id, &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                ReportImageError::Unexpected
+            })?
+            .filter(|i| i.upload_date.is_some() && i.visible_to(Some(app_user_id)))
+            .ok_or(ReportImageError::ImageNotFound)?;
+
+        ImageReport::new(
+            app_user_id,
+            NewImageReport {
+                image_id,
+                reason: reason.as_str(),
+                details,
+            },
+            &self.pool,
+        )
+        .await
+        .map_err(|e| {
+            error!(&self.logger, "unexpected database error";
+                "error" => e.to_string()
+            );
+            ReportImageError::Unexpected
+        })?
+        .ok_or(ReportImageError::AlreadyReported)
+    }
+
+    async fn get_reported_images(
+        &self,
+        offset: Option<u64>,
+        limit: Option<u64>,
+    ) -> Result<Vec<(ReportedImage, Vec<ImageReport>)>, GetReportedImagesError> {
+        let images = ReportedImage::open(offset.map(|v| v as _), limit.map(|v| v as _), &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                GetReportedImagesError::Unexpected
+            })?;
+
+        let mut reported = Vec::with_capacity(images.len());
+
+        for image in images {
+            let reports = ImageReport::open_by_image_id(image.image_id, &self.pool)
+                .await
+                .map_err(|e| {
+                    error!(&self.logger, "unexpected database error";
+                        "error" => e.to_string()
+                    );
+                    GetReportedImagesError::Unexpected
+                })?;
+
+            reported.push((image, reports));
+        }
+
+        Ok(reported)
+    }
+
+    async fn moderate_image(
+        &self,
+        moderator: &UserInfo,
+        image_id: Uuid,
+        action: ModerationActionKind,
+        note: Option<&str>,
+        warn_user: bool,
+    ) -> Result<(), ModerateImageError> {
+        let changes_image =
+            action == ModerationActionKind::Hide || action == ModerationActionKind::Delete;
+
+        if changes_image && !moderator.has_permission(Permission::HideImages) {
+            return Err(ModerateImageError::NotAllowed);
+        }
+
+        let mut image = Image::by_id(image_id, &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                ModerateImageError::Unexpected
+            })?
+            .ok_or(ModerateImageError::ImageNotFound)?;
+
+        match action {
+            ModerationActionKind::Hide => {
+                image.hidden = true;
+
+                image.save(&self.pool).await.map_err(|e| {
+                    error!(&self.logger, "unexpected database error";
+                        "error" => e.to_string()
+                    );
+                    ModerateImageError::Unexpected
+                })?;
+            }
+            ModerationActionKind::Delete => {
+                // The owner cannot restore it, the file is removed when the trash is purged.
+                image.trash(moderator.id, &self.pool).await.map_err(|e| {
+                    error!(&self.logger, "unexpected database error";
+                        "error" => e.to_string()
+                    );
+                    ModerateImageError::Unexpected
+                })?;
+            }
+            ModerationActionKind::Dismiss | ModerationActionKind::Warn => {}
+        }
+
+        let note = note.map(str::trim).filter(|n| !n.is_empty());
+
+        let mut actions = vec![action];
+
+        if warn_user && action != ModerationActionKind::Warn {
+            actions.push(ModerationActionKind::Warn);
+        }
+
+        for action in actions {
+            ModerationAction::new(
+                moderator.id,
+                NewModerationAction {
+                    image_id,
+                    image_owner_id: image.app_user_id,
+                    action: action.as_str(),
+                    note,
+                },
+                &self.pool,
+            )
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                ModerateImageError::Unexpected
+            })?;
+
+            // Dismissed reports are none of the owner's business.
+            if action != ModerationActionKind::Dismiss {
+                notify(
+                    &self.pool,
+                    &self.logger,
+                    NotificationEvent {
+                        app_user_id: image.app_user_id,
+                        actor_id: None,
+                        kind: NotificationKind::ImageModerated,
+                        image_id: Some(image_id),
+                        comment_id: None,
+                        details: Some(json!({ "action": action.as_str() })),
+                    },
+                )
+                .await;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn get_moderation_actions(
+        &self,
+        image_id: Option<Uuid>,
+        offset: Option<u64>,
+        limit: Option<u64>,
+    ) -> Result<Vec<ModerationAction>, GetModerationActionsError> {
+        ModerationAction::list(
+            image_id,
+            offset.map(|v| v as _),
+            limit.map(|v| v as _),
+            &self.pool,
+        )
+        .await
+        .map_err(|e| {
+            error!(&self.logger, "unexpected database error";
+                "error" => e.to_string()
+            );
+            GetModerationActionsError::Unexpected
+        })
+    }
+}
@@ -0,0 +1,340 @@
+use super::Service;
+use crate::{
+    db::notification::{NewNotification, Notification},
+    model::notification::*,
+};
+use async_trait::async_trait;
+use futures::{
+    channel::mpsc,
+    future,
+    stream::{self, LocalBoxStream, StreamExt},
+};
+use serde::Deserialize;
+use slog::{error, Logger};
+use sqlx::{postgres::PgListener, PgPool};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use uuid::Uuid;
+
+/// The Postgres channel new notifications are published on.
+pub const NOTIFICATION_CHANNEL: &str = "notification";
+
+/// How long to wait before listening again after losing the connection.
+const LISTEN_RETRY_SECONDS: u64 = 5;
+
+/// Notifications waiting to be sent to a client, it is dropped when it falls further behind.
+const SUBSCRIBER_BUFFER: usize = 64;
+
+/// At most this many missed notifications are sent to a client that reconnects.
+const MAX_MISSED_NOTIFICATIONS: i64 = 100;
+
+/// Something that happened to the content of a user.
+pub struct NotificationEvent {
+    /// The recipient.
+    pub app_user_id: Uuid,
+    pub actor_id: Option<Uuid>,
+    pub kind: NotificationKind,
+    pub image_id: Option<Uuid>,
+    pub comment_id: Option<Uuid>,
+    pub details: Option<serde_json::Value>,
+}
+
+/// Notifies the user about the event, unless they caused it themselves.
+///
+/// Errors are only logged, the event itself has already happened.
+pub async fn notify(pool: &PgPool, logger: &Logger, event: NotificationEvent) {
+    if event.actor_id == Some(event.app_user_id) {
+        return;
+    }
+
+    let res = Notification::new(
+        NewNotification {
+            app_user_id: event.app_user_id,
+            actor_id: event.actor_id,
+            notification_type: event.kind.as_str(),
+            image_id: event.image_id,
+            comment_id: event.comment_id,
+            details: event.details,
+        },
+        pool,
+    )
+    .await;
+
+    if let Err(e) = res {
+        error!(logger, "failed to create notification";
+            "type" => event.kind.as_str(),
+            "appUserId" => event.app_user_id.to_string(),
+            "error" => e.to_string()
+        );
+    }
+}
+
+/// The payload published by the database for every new notification.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PublishedNotification {
+    id: Uuid,
+    app_user_id: Uuid,
+}
+
+/// Delivers new notifications to the connected clients of this process.
+///
+/// The database publishes every new notification, so it does not matter
+/// which process created it.
+#[derive(Debug, Clone, Default)]
+pub struct NotificationHub {
+    subscribers: Arc<Mutex<HashMap<Uuid, Vec<mpsc::Sender<Uuid>>>>>,
+}
+
+impl NotificationHub {
+    /// Creates the hub and starts listening in the background.
+    pub fn start(logger: Logger, pool: PgPool) -> Self {
+        let hub = Self::default();
+        actix_rt::spawn(hub.clone().listen(logger, pool));
+        hub
+    }
+
+    /// The IDs of the new notifications of the user.
+    ///
+    /// The subscription ends when the receiver is dropped, or when
+    /// the receiver falls behind by more than the buffer.
+    pub fn subscribe(&self, app_user_id: Uuid) -> mpsc::Receiver<Uuid> {
+        let (tx, rx) = mpsc::channel(SUBSCRIBER_BUFFER);
+
+        let mut subscribers = self.subscribers.lock().unwrap();
+
+        // Clean up after disconnected clients of users that get no notifications.
+        subscribers.retain(|_, senders| {
+            senders.retain(|s| !s.is_closed());
+            !senders.is_empty()
+        });
+
+        subscribers.entry(app_user_id).or_default().push(tx);
+
+        rx
+    }
+
+    fn publish(&self, notification: PublishedNotification) {
+        let mut subscribers = self.subscribers.lock().unwrap();
+
+        if let Some(senders) = subscribers.get_mut(&notification.app_user_id) {
+            // Slow clients are dropped instead of buffering for them without a limit.
+            *senders = senders
+                .drain(..)
+                .filter_map(|mut s| match s.try_send(notification.id) {
+                    Ok(_) => Some(s),
+                    Err(_) => None,
+                })
+                .collect();
+
+            if senders.is_empty() {
+                subscribers.remove(&notification.app_user_id);
+            }
+        }
+    }
+
+    /// Listens on the channel until the server stops.
+    async fn listen(self, logger: Logger, pool: PgPool) {
+        loop {
+            if let Err(e) = self.listen_once(&logger, &pool).await {
+                error!(logger, "lost the notification channel";
+                    "error" => e.to_string()
+                );
+            }
+
+            actix_rt::time::delay_for(Duration::from_secs(LISTEN_RETRY_SECONDS)).await;
+        }
+    }
+
+    async fn listen_once(&self, logger: &Logger, pool: &PgPool) -> Result<(), sqlx::Error> {
+        let mut listener = PgListener::connect_with(pool).await?;
+        listener.listen(NOTIFICATION_CHANNEL).await?;
+
+        loop {
+            let notification = listener.recv().await?;
+
+            match serde_json::from_str(notification.payload()) {
+                Ok(n) => self.publish(n),
+                Err(e) => {
+                    error!(logger, "invalid notification payload";
+                        "payload" => notification.payload(),
+                        "error" => e.to_string()
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[async_trait(?Send)]
+pub trait NotificationService: Service {
+    /// The latest first, with the number of all unread notifications.
+    async fn get_notifications(
+        &self,
+        app_user_id: Uuid,
+        unread_only: bool,
+        offset: Option<u64>,
+        limit: Option<u64>,
+    ) -> Result<(Vec<Notification>, u64), GetNotificationsError>;
+    async fn mark_read(&self, app_user_id: Uuid, id: Uuid)
+        -> Result<(), MarkNotificationReadError>;
+    async fn mark_all_read(&self, app_user_id: Uuid) -> Result<(), MarkNotificationReadError>;
+    /// New notifications of the user as they are created,
+    /// after the ones created since `last_id` that were missed.
+    fn subscribe(
+        &self,
+        app_user_id: Uuid,
+        last_id: Option<Uuid>,
+    ) -> LocalBoxStream<'static, Notification>;
+}
+dyn_clone::clone_trait_object!(NotificationService);
+
+#[derive(Debug, Clone)]
+pub struct DefaultNotificationService {
+    pool: PgPool,
+    logger: Logger,
+    hub: NotificationHub,
+}
+
+impl DefaultNotificationService {
+    pub fn new(logger: Logger, pool: PgPool, hub: NotificationHub) -> Self {
+        Self { logger, pool, hub }
+    }
+}
+
+#[async_trait(?Send)]
+impl NotificationService for DefaultNotificationService {
+    async fn get_notifications(
+        &self,
+        app_user_id: Uuid,
+        unread_only: bool,
+        offset: Option<u64>,
+        limit: Option<u64>,
+    ) -> Result<(Vec<Notification>, u64), GetNotificationsError> {
+        let notifications = Notification::by_app_user_id(
+            app_user_id,
+            unread_only,
+            offset.map(|v| v as _),
+            limit.map(|v| v as _),
+            &self.pool,
+        )
+        .await
+        .map_err(|e| {
+            error!(&self.logger, "unexpected database error";
+                "error" => e.to_string()
+            );
+            GetNotificationsError::Unexpected
+        })?;
+
+        let unread_count = Notification::count_unread(app_user_id, &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                GetNotificationsError::Unexpected
+            })?;
+
+        Ok((notifications, unread_count as _))
+    }
+
+    async fn mark_read(
+        &self,
+        app_user_id: Uuid,
+        id: Uuid,
+    ) -> Result<(), MarkNotificationReadError> {
+        let found = Notification::mark_read(id, app_user_id, &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                MarkNotificationReadError::Unexpected
+            })?;
+
+        if !found {
+            return Err(MarkNotificationReadError::NotFound);
+        }
+
+        Ok(())
+    }
+
+    async fn mark_all_read(&self, app_user_id: Uuid) -> Result<(), MarkNotificationReadError> {
+        Notification::mark_all_read(app_user_id, &self.pool)
+            .await
+            .map(|_| ())
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                MarkNotificationReadError::Unexpected
+            })
+    }
+
+    fn subscribe(
+        &self,
+        app_user_id: Uuid,
+        last_id: Option<Uuid>,
+    ) -> LocalBoxStream<'static, Notification> {
+        let pool = self.pool.clone();
+        let logger = self.logger.clone();
+
+        // Subscribed before the missed ones are read, so that none are lost in between.
+        let live = self.hub.subscribe(app_user_id);
+
+        let missed = {
+            let pool = pool.clone();
+            let logger = logger.clone();
+
+            async move {
+                let missed = match last_id {
+                    Some(last_id) => {
+                        Notification::after(app_user_id, last_id, MAX_MISSED_NOTIFICATIONS, &pool)
+                            .await
+                            .unwrap_or_else(|e| {
+                                error!(logger, "unexpected database error";
+                                    "error" => e.to_string()
+                                );
+                                Vec::new()
+                            })
+                    }
+                    None => Vec::new(),
+                };
+
+                (missed, live)
+            }
+        };
+
+        stream::once(missed)
+            .flat_map(move |(missed, live)| {
+                let pool = pool.clone();
+                let logger = logger.clone();
+                let sent: HashSet<Uuid> = missed.iter().map(|n| n.id).collect();
+
+                let live = live
+                    .filter(move |id| future::ready(!sent.contains(id)))
+                    .filter_map(move |id| {
+                        let pool = pool.clone();
+                        let logger = logger.clone();
+
+                        async move {
+                            match Notification::by_id(id, &pool).await {
+                                Ok(n) => n,
+                                Err(e) => {
+                                    error!(logger, "unexpected database error";
+                                        "error" => e.to_string()
+                                    );
+                                    None
+                                }
+                            }
+                        }
+                    });
+
+                stream::iter(missed).chain(live)
+            })
+            .boxed_local()
+    }
+}
@@ -0,0 +1,221 @@
+use super::{image::image_path, Service};
+use crate::{
+    config::Config,
+    db::image::{Image, ImageExt},
+    db::share_link::ShareLink,
+    model::share_link::{
+        CreateShareLinkError, GetShareLinksError, GetSharedImageError, RevokeShareLinkError,
+    },
+    util::random_string,
+};
+use actix_files::NamedFile;
+use async_trait::async_trait;
+use slog::{error, Logger};
+use sqlx::PgPool;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+const SHARE_LINK_TOKEN_LENGTH: usize = 32;
+
+#[async_trait(?Send)]
+pub trait ShareLinkService: Service {
+    /// Only the owner of the image can share it.
+    async fn create_share_link(
+        &self,
+        app_user_id: Uuid,
+        image_id: Uuid,
+        expires: Option<OffsetDateTime>,
+    ) -> Result<ShareLink, CreateShareLinkError>;
+    async fn get_share_links(
+        &self,
+        app_user_id: Uuid,
+        image_id: Uuid,
+    ) -> Result<Vec<ShareLink>, GetShareLinksError>;
+    async fn revoke_share_link(
+        &self,
+        app_user_id: Uuid,
+        id: Uuid,
+    ) -> Result<(), RevokeShareLinkError>;
+    /// Works regardless of the visibility of the image.
+    async fn get_shared_image(&self, token: &str) -> Result<ImageExt, GetSharedImageError>;
+    async fn download_shared_image(&self, token: &str) -> Result<NamedFile, GetSharedImageError>;
+}
+dyn_clone::clone_trait_object!(ShareLinkService);
+
+#[derive(Debug, Clone)]
+pub struct DefaultShareLinkService {
+    pool: PgPool,
+    logger: Logger,
+    config: Config,
+}
+
+impl DefaultShareLinkService {
+    pub fn new(config: &Config, logger: Logger, pool: PgPool) -> Self {
+        Self {
+            logger,
+            pool,
+            config: config.clone(),
+        }
+    }
+
+    /// The uploaded image of an active link.
+    async fn shared_image(&self, token: &str) -> Result<Image, GetSharedImageError> {
+        let link = ShareLink::by_token(token, &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                GetSharedImageError::Unexpected
+            })?
+            .filter(|l| l.is_active())
+            .ok_or(GetSharedImageError::NotFound)?;
+
+        Image::by_id(link.image_id, &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                GetSharedImageError::Unexpected
+            })?
+            // Links cannot be used to get around moderation.
+            .filter(|i| i.upload_date.is_some() && !i.hidden)
+            .ok_or(GetSharedImageError::NotFound)
+    }
+}
+
+#[async_trait(?Send)]
+impl ShareLinkService for DefaultShareLinkService {
+    async fn create_share_link(
+        &self,
+        app_user_id: Uuid,
+        image_id: Uuid,
+        expires: Option<OffsetDateTime>,
+    ) -> Result<ShareLink, CreateShareLinkError> {
+        let image = Image::by_id(image_id, &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                CreateShareLinkError::Unexpected
+            })?
+            .filter(|i| i.visible_to(Some(app_user_id)))
+            .ok_or(CreateShareLinkError::ImageNotFound)?;
+
+        if image.app_user_id != app_user_id {
+            return Err(CreateShareLinkError::NotAllowed);
+        }
+
+        if let Some(expires) = expires {
+            if expires <= OffsetDateTime::now_utc() {
+                return Err(CreateShareLinkError::InvalidExpiry);
+            }
+        }
+
+        let token = random_string(SHARE_LINK_TOKEN_LENGTH);
+
+        let id = ShareLink::new(app_user_id, image_id, &token, expires, &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                CreateShareLinkError::Unexpected
+            })?;
+
+        Ok(ShareLink {
+            id,
+            created: OffsetDateTime::now_utc(),
+            app_user_id,
+            image_id,
+            token,
+            expires,
+            revoked: None,
+        })
+    }
+
+    async fn get_share_links(
+        &self,
+        app_user_id: Uuid,
+        image_id: Uuid,
+    ) -> Result<Vec<ShareLink>, GetShareLinksError> {
+        let image = Image::by_id(image_id, &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                GetShareLinksError::Unexpected
+            })?
+            .filter(|i| i.visible_to(Some(app_user_id)))
+            .ok_or(GetShareLinksError::ImageNotFound)?;
+
+        if image.app_user_id != app_user_id {
+            return Err(GetShareLinksError::NotAllowed);
+        }
+
+        ShareLink::by_image_id(image_id, &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                GetShareLinksError::Unexpected
+            })
+    }
+
+    async fn revoke_share_link(
+        &self,
+        app_user_id: Uuid,
+        id: Uuid,
+    ) -> Result<(), RevokeShareLinkError> {
+        let link = ShareLink::by_id(id, &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                RevokeShareLinkError::Unexpected
+            })?
+            .filter(|l| l.revoked.is_none())
+            .ok_or(RevokeShareLinkError::NotFound)?;
+
+        if link.app_user_id != app_user_id {
+            return Err(RevokeShareLinkError::NotAllowed);
+        }
+
+        link.revoke(&self.pool).await.map_err(|e| {
+            error!(&self.logger, "unexpected database error";
+                "error" => e.to_string()
+            );
+            RevokeShareLinkError::Unexpected
+        })
+    }
+
+    async fn get_shared_image(&self, token: &str) -> Result<ImageExt, GetSharedImageError> {
+        let image = self.shared_image(token).await?;
+
+        ImageExt::from_image(image, &self.pool).await.map_err(|e| {
+            error!(&self.logger, "unexpected database error";
+                "error" => e.to_string()
+            );
+            GetSharedImageError::Unexpected
+        })
+    }
+
+    async fn download_shared_image(&self, token: &str) -> Result<NamedFile, GetSharedImageError> {
+        let image = self.shared_image(token).await?;
+
+        NamedFile::open(image_path(&self.config, image.id)).map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => GetSharedImageError::NotFound,
+            _ => {
+                error!(&self.logger, "error opening the image";
+                    "error" => e.to_string()
+                );
+                GetSharedImageError::Unexpected
+            }
+        })
+    }
+}
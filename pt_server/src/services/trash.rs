@@ -0,0 +1,317 @@
+use super::{
+    audit::{record_audit_event, AuditChange, AuditContext, AUDIT_TARGET_CATEGORY},
+    image::remove_image_files,
+    Service,
+};
+use crate::{
+    config::Config, db::category::Category, db::image::Image, model::audit::AuditAction,
+    model::trash::*,
+};
+use async_trait::async_trait;
+use serde_json::json;
+use slog::{error, warn, Logger};
+use sqlx::PgPool;
+use time::{Duration, OffsetDateTime};
+use uuid::Uuid;
+
+/// The number of items deleted for good.
+#[derive(Debug, Default)]
+pub struct PurgedTrash {
+    pub images: u64,
+    pub categories: u64,
+}
+
+#[async_trait(?Send)]
+pub trait TrashService: Service {
+    /// Moves the image to the trash, only the owner can delete it.
+    async fn delete_image(&self, app_user_id: Uuid, image_id: Uuid)
+        -> Result<(), DeleteImageError>;
+    /// Images the user moved to the trash, the latest first.
+    async fn get_trashed_images(
+        &self,
+        app_user_id: Uuid,
+        offset: Option<u64>,
+        limit: Option<u64>,
+    ) -> Result<Vec<Image>, GetTrashError>;
+    async fn restore_image(
+        &self,
+        app_user_id: Uuid,
+        image_id: Uuid,
+    ) -> Result<(), TrashedImageError>;
+    /// Deletes the image and its file without waiting for the retention period.
+    async fn purge_image(&self, app_user_id: Uuid, image_id: Uuid)
+        -> Result<(), TrashedImageError>;
+    async fn get_trashed_categories(&self) -> Result<Vec<Category>, GetTrashError>;
+    /// Subcategories that were deleted together with the category are restored as well.
+    async fn restore_category(
+        &self,
+        context: &AuditContext,
+        id: Uuid,
+    ) -> Result<(), RestoreCategoryError>;
+    /// Deletes everything that has been in the trash for longer than the retention period.
+    async fn purge_trash(&self) -> Result<PurgedTrash, PurgeTrashError>;
+    /// How long items are kept in the trash.
+    fn retention_period(&self) -> Duration;
+}
+dyn_clone::clone_trait_object!(TrashService);
+
+#[derive(Debug, Clone)]
+pub struct DefaultTrashService {
+    pool: PgPool,
+    logger: Logger,
+    config: Config,
+}
+
+impl DefaultTrashService {
+    pub fn new(config: &Config, logger: Logger, pool: PgPool) -> Self {
+        Self {
+            logger,
+            pool,
+            config: config.clone(),
+        }
+    }
+
+    /// A trashed image of the user that was not deleted by a moderator.
+    async fn own_trashed_image(
+        &self,
+        app_user_id: Uuid,
+        image_id: Uuid,
+    ) -> Result<Image, TrashedImageError> {
+        Image::trashed_by_id(image_id, &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                TrashedImageError::Unexpected
+            })?
+            .filter(|i| i.app_user_id == app_user_id && i.deleted_by == Some(app_user_id))
+            .ok_or(TrashedImageError::ImageNotFound)
+    }
+
+    /// Deletes the image for good, a leftover file is only logged.
+    async fn remove_image(&self, image: &Image) -> Result<(), sqlx::Error> {
+        image.delete(&self.pool).await?;
+
+        if let Err(e) = remove_image_files(&self.config, image.id).await {
+            warn!(&self.logger, "error removing the file of a deleted image";
+                "error" => e.to_string(),
+                "image_id" => image.id.to_string()
+            );
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait(?Send)]
+impl TrashService for DefaultTrashService {
+    async fn delete_image(
+        &self,
+        app_user_id: Uuid,
+        image_id: Uuid,
+    ) -> Result<(), DeleteImageError> {
+        let image = Image::by_id(image_id, &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                DeleteImageError::Unexpected
+            })?
+            .ok_or(DeleteImageError::ImageNotFound)?;
+
+        if image.app_user_id != app_user_id {
+            return Err(DeleteImageError::NotAllowed);
+        }
+
+        image.trash(app_user_id, &self.pool).await.map_err(|e| {
+            error!(&self.logger, "unexpected database error";
+                "error" => e.to_string()
+            );
+            DeleteImageError::Unexpected
+        })
+    }
+
+    async fn get_trashed_images(
+        &self,
+        app_user_id: Uuid,
+        offset: Option<u64>,
+        limit: Option<u64>,
+    ) -> Result<Vec<Image>, GetTrashError> {
+        Image::trashed_by_app_user_id(
+            app_user_id,
+            offset.map(|v| v as _),
+            limit.map(|v| v as _),
+            &self.pool,
+        )
+        .await
+        .map_err(|e| {
+            error!(&self.logger, "unexpected database error";
+                "error" => e.to_string()
+            );
+            GetTrashError::Unexpected
+        })
+    }
+
+    async fn restore_image(
+        &self,
+        app_user_id: Uuid,
+        image_id: Uuid,
+    ) -> Result<(), TrashedImageError> {
+        let image = self.own_trashed_image(app_user_id, image_id).await?;
+
+        image.restore(&self.pool).await.map_err(|e| {
+            error!(&self.logger, "unexpected database error";
+                "error" => e.to_string()
+            );
+            TrashedImageError::Unexpected
+        })
+    }
+
+    async fn purge_image(
+        &self,
+        app_user_id: Uuid,
+        image_id: Uuid,
+    ) -> Result<(), TrashedImageError> {
+        let image = self.own_trashed_image(app_user_id, image_id).await?;
+
+        self.remove_image(&image).await.map_err(|e| {
+            error!(&self.logger, "unexpected database error";
+                "error" => e.to_string()
+            );
+            TrashedImageError::Unexpected
+        })
+    }
+
+    async fn get_trashed_categories(&self) -> Result<Vec<Category>, GetTrashError> {
+        Category::trashed(&self.pool).await.map_err(|e| {
+            error!(&self.logger, "unexpected database error";
+                "error" => e.to_string()
+            );
+            GetTrashError::Unexpected
+        })
+    }
+
+    async fn restore_category(
+        &self,
+        context: &AuditContext,
+        id: Uuid,
+    ) -> Result<(), RestoreCategoryError> {
+        let trashed = Category::trashed(&self.pool).await.map_err(|e| {
+            error!(&self.logger, "unexpected database error";
+                "error" => e.to_string()
+            );
+            RestoreCategoryError::Unexpected
+        })?;
+
+        let category = trashed
+            .iter()
+            .find(|c| c.id == id)
+            .ok_or(RestoreCategoryError::CategoryNotFound)?;
+
+        if let Some(parent_id) = category.parent_id {
+            if trashed.iter().any(|c| c.id == parent_id) {
+                return Err(RestoreCategoryError::ParentDeleted);
+            }
+        }
+
+        // Subcategories deleted at the same time were deleted with this one.
+        let mut restored = vec![category];
+        let mut i = 0;
+
+        while i < restored.len() {
+            let parent_id = restored[i].id;
+
+            restored.extend(
+                trashed
+                    .iter()
+                    .filter(|c| c.parent_id == Some(parent_id) && c.deleted == category.deleted),
+            );
+
+            i += 1;
+        }
+
+        let categories = Category::all(&self.pool).await.map_err(|e| {
+            error!(&self.logger, "unexpected database error";
+                "error" => e.to_string()
+            );
+            RestoreCategoryError::Unexpected
+        })?;
+
+        // The names and slugs could have been reused in the meantime.
+        if restored.iter().any(|r| {
+            categories.iter().any(|c| {
+                c.category_name.to_lowercase() == r.category_name.to_lowercase() || c.slug == r.slug
+            })
+        }) {
+            return Err(RestoreCategoryError::AlreadyExists);
+        }
+
+        let ids: Vec<Uuid> = restored.iter().map(|c| c.id).collect();
+
+        Category::restore(&ids, &self.pool).await.map_err(|e| {
+            error!(&self.logger, "unexpected database error";
+                "error" => e.to_string()
+            );
+            RestoreCategoryError::Unexpected
+        })?;
+
+        record_audit_event(
+            &self.pool,
+            &self.logger,
+            context,
+            AuditChange {
+                action: AuditAction::CategoryRestore,
+                target_type: AUDIT_TARGET_CATEGORY,
+                target_id: id.to_string(),
+                before: None,
+                after: Some(json!({
+                    "name": category.category_name,
+                    "restoredIds": ids,
+                })),
+            },
+        )
+        .await;
+
+        Ok(())
+    }
+
+    async fn purge_trash(&self) -> Result<PurgedTrash, PurgeTrashError> {
+        let date = OffsetDateTime::now_utc() - self.retention_period();
+        let mut purged = PurgedTrash::default();
+
+        let images = Image::deleted_before(date, &self.pool).await.map_err(|e| {
+            error!(&self.logger, "unexpected database error";
+                "error" => e.to_string()
+            );
+            PurgeTrashError::Unexpected
+        })?;
+
+        for image in images {
+            self.remove_image(&image).await.map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                PurgeTrashError::Unexpected
+            })?;
+
+            purged.images += 1;
+        }
+
+        purged.categories = Category::purge_deleted_before(date, &self.pool)
+            .await
+            .map_err(|e| {
+                error!(&self.logger, "unexpected database error";
+                    "error" => e.to_string()
+                );
+                PurgeTrashError::Unexpected
+            })?;
+
+        Ok(purged)
+    }
+
+    fn retention_period(&self) -> Duration {
+        Duration::days(self.config.trash_retention_days)
+    }
+}
@@ -2,14 +2,37 @@ use std::path::PathBuf;
 
 use crate::{
     config::Config,
-    db,
+    db, jobs,
     logger::create_logger,
     model::auth::{
-        InvalidRegisterRequest, LoginError, LoginRequest, LoginResponse, RegisterRequest,
+        ApiKeyScope, ConfirmTotpRequest, ConfirmTotpResponse, CreateApiKeyRequest,
+        CreateApiKeyResponse, DisableTotpRequest, EnrollTotpResponse, InvalidRegisterRequest,
+        LoginError, LoginRequest, LoginResponse, MfaLoginRequest, RegisterRequest,
     },
+    model::album::*,
+    model::audit::*,
+    model::comment::*,
     model::image::*,
+    model::moderation::*,
+    model::notification::*,
+    model::role::{Role, SetRoleRequest},
+    model::share_link::*,
+    model::trash::*,
+    model::user::{
+        AccountExport, AccountExportError, AccountExportStatus, DeleteAccountError,
+        DeleteAccountRequest, SetStorageQuotaError, SetStorageQuotaRequest, StorageUsage,
+        StorageUsageError, UserSettings,
+    },
+    model::Visibility,
     server,
-    services::{auth, AuthService, DefaultImageService, ImageService},
+    services::{
+        audit::AuditContext, auth, notification::NotificationHub, trash::PurgedTrash,
+        AccountService, AlbumService, AuditService, AuthService, CommentService,
+        DefaultAccountService, DefaultAlbumService, DefaultAuditService, DefaultCommentService,
+        DefaultImageService, DefaultModerationService, DefaultNotificationService,
+        DefaultShareLinkService, DefaultTrashService, ImageService, ModerationService,
+        NotificationService, ShareLinkService, TrashService,
+    },
     util::random_string,
 };
 use actix_files::NamedFile;
@@ -18,6 +41,7 @@ use actix_web::{test, web::ServiceConfig, App};
 use async_trait::async_trait;
 use auth::DefaultAuthService;
 use db::image::NewImage;
+use futures::{stream::LocalBoxStream, StreamExt};
 use slog::Logger;
 use sqlx::query;
 use uuid::Uuid;
@@ -28,7 +52,7 @@ const TEST_IMAGE: &[u8] = include_bytes!("../../test_data/test.png");
 #[derive(Clone)]
 struct TestAuthService(Box<dyn AuthService>);
 
-#[async_trait]
+#[async_trait(?Send)]
 impl AuthService for TestAuthService {
     async fn register(
         &self,
@@ -39,15 +63,143 @@ impl AuthService for TestAuthService {
         self.0.register(email, password).await
     }
 
-    async fn login(&self, email: &str, password: &str) -> Result<auth::Token, LoginError> {
+    async fn login(
+        &self,
+        context: &AuditContext,
+        email: &str,
+        password: &str,
+    ) -> Result<auth::LoginOutcome, LoginError> {
+        // Checks or mocks here.
+        self.0.login(context, email, password).await
+    }
+
+    async fn login_mfa(
+        &self,
+        context: &AuditContext,
+        mfa_token: &str,
+        code: &str,
+    ) -> Result<auth::Token, LoginError> {
         // Checks or mocks here.
-        self.0.login(email, password).await
+        self.0.login_mfa(context, mfa_token, code).await
     }
 
-    async fn validate_token(&self, token: &str) -> Result<auth::UserInfo, jwt::errors::Error> {
+    async fn validate_token(
+        &self,
+        token: &str,
+    ) -> Result<auth::UserInfo, crate::model::auth::TokenError> {
         // Checks or mocks here.
         self.0.validate_token(token).await
     }
+
+    async fn enroll_totp(
+        &self,
+        app_user_id: Uuid,
+    ) -> Result<crate::model::auth::EnrollTotpResponse, crate::model::auth::TotpError> {
+        // Checks or mocks here.
+        self.0.enroll_totp(app_user_id).await
+    }
+
+    async fn confirm_totp(
+        &self,
+        app_user_id: Uuid,
+        code: &str,
+    ) -> Result<Vec<String>, crate::model::auth::TotpError> {
+        // Checks or mocks here.
+        self.0.confirm_totp(app_user_id, code).await
+    }
+
+    async fn disable_totp(
+        &self,
+        app_user_id: Uuid,
+        code: &str,
+    ) -> Result<(), crate::model::auth::TotpError> {
+        // Checks or mocks here.
+        self.0.disable_totp(app_user_id, code).await
+    }
+
+    async fn create_api_key(
+        &self,
+        app_user_id: Uuid,
+        name: &str,
+        scopes: &[crate::model::auth::ApiKeyScope],
+    ) -> Result<(Uuid, String), crate::model::auth::ApiKeyError> {
+        // Checks or mocks here.
+        self.0.create_api_key(app_user_id, name, scopes).await
+    }
+
+    async fn get_api_keys(
+        &self,
+        app_user_id: Uuid,
+    ) -> Result<Vec<db::api_key::ApiKey>, crate::model::auth::ApiKeyError> {
+        // Checks or mocks here.
+        self.0.get_api_keys(app_user_id).await
+    }
+
+    async fn revoke_api_key(
+        &self,
+        app_user_id: Uuid,
+        id: Uuid,
+    ) -> Result<(), crate::model::auth::ApiKeyError> {
+        // Checks or mocks here.
+        self.0.revoke_api_key(app_user_id, id).await
+    }
+
+    async fn validate_api_key(
+        &self,
+        key: &str,
+    ) -> Result<auth::UserInfo, crate::model::auth::ApiKeyError> {
+        // Checks or mocks here.
+        self.0.validate_api_key(key).await
+    }
+
+    async fn oidc_authorization_url(
+        &self,
+    ) -> Result<auth::OidcAuthorization, crate::model::auth::OidcError> {
+        // Checks or mocks here.
+        self.0.oidc_authorization_url().await
+    }
+
+    async fn oidc_callback(
+        &self,
+        context: &AuditContext,
+        code: &str,
+        state: &str,
+    ) -> Result<auth::LoginOutcome, crate::model::auth::OidcError> {
+        // Checks or mocks here.
+        self.0.oidc_callback(context, code, state).await
+    }
+
+    async fn set_role(
+        &self,
+        context: &AuditContext,
+        app_user_id: Uuid,
+        role: crate::model::role::Role,
+    ) -> Result<(), crate::model::role::SetRoleError> {
+        // Checks or mocks here.
+        self.0.set_role(context, app_user_id, role).await
+    }
+
+    async fn get_settings(
+        &self,
+        app_user_id: Uuid,
+    ) -> Result<crate::model::user::UserSettings, crate::model::user::UserSettingsError> {
+        // Checks or mocks here.
+        self.0.get_settings(app_user_id).await
+    }
+
+    async fn update_settings(
+        &self,
+        app_user_id: Uuid,
+        settings: crate::model::user::UserSettings,
+    ) -> Result<(), crate::model::user::UserSettingsError> {
+        // Checks or mocks here.
+        self.0.update_settings(app_user_id, settings).await
+    }
+
+    fn jwks(&self) -> jwt::jwk::JwkSet {
+        // Checks or mocks here.
+        self.0.jwks()
+    }
 }
 
 /// A proxy service for debugging.
@@ -66,27 +218,94 @@ impl ImageService for TestImageService {
         self.0.create_image(app_user_id, image, categories).await
     }
 
-    async fn save_image(&self, id: Uuid, payload: Multipart) -> Result<(), UploadImageError> {
+    async fn save_image(
+        &self,
+        app_user_id: Uuid,
+        id: Uuid,
+        payload: Multipart,
+    ) -> Result<(), UploadImageError> {
+        // Checks or mocks here.
+        self.0.save_image(app_user_id, id, payload).await
+    }
+
+    async fn create_and_upload_image(
+        &self,
+        app_user_id: Uuid,
+        payload: Multipart,
+    ) -> Result<Uuid, CreateAndUploadImageError> {
+        // Checks or mocks here.
+        self.0.create_and_upload_image(app_user_id, payload).await
+    }
+
+    async fn batch_upload_images(
+        &self,
+        app_user_id: Uuid,
+        payload: Multipart,
+    ) -> Result<BatchUploadResponse, BatchUploadError> {
+        // Checks or mocks here.
+        self.0.batch_upload_images(app_user_id, payload).await
+    }
+
+    async fn create_upload_session(
+        &self,
+        app_user_id: Uuid,
+        image_id: Uuid,
+        length: u64,
+        checksum: &str,
+        filename: Option<&str>,
+    ) -> Result<db::upload_session::UploadSession, UploadSessionError> {
+        // Checks or mocks here.
+        self.0
+            .create_upload_session(app_user_id, image_id, length, checksum, filename)
+            .await
+    }
+
+    async fn get_upload_session(
+        &self,
+        app_user_id: Uuid,
+        id: Uuid,
+    ) -> Result<db::upload_session::UploadSession, UploadSessionError> {
+        // Checks or mocks here.
+        self.0.get_upload_session(app_user_id, id).await
+    }
+
+    async fn append_upload_chunk(
+        &self,
+        app_user_id: Uuid,
+        id: Uuid,
+        offset: u64,
+        payload: actix_web::web::Payload,
+    ) -> Result<u64, UploadSessionError> {
+        // Checks or mocks here.
+        self.0.append_upload_chunk(app_user_id, id, offset, payload).await
+    }
+
+    async fn finish_upload_session(
+        &self,
+        app_user_id: Uuid,
+        id: Uuid,
+    ) -> Result<(), UploadSessionError> {
         // Checks or mocks here.
-        self.0.save_image(id, payload).await
+        self.0.finish_upload_session(app_user_id, id).await
     }
 
-    async fn get_image(&self, id: Uuid) -> Result<NamedFile, std::io::Error> {
+    async fn get_image(
+        &self,
+        id: Uuid,
+        viewer: Option<&auth::UserInfo>,
+    ) -> Result<NamedFile, DownloadImageError> {
         // Checks or mocks here.
-        self.0.get_image(id).await
+        self.0.get_image(id, viewer).await
     }
 
     async fn search_images(
         &self,
-        search: Option<&str>,
+        filter: db::image::ImageFilter,
         offset: Option<u64>,
         limit: Option<u64>,
-    ) -> Result<
-        Vec<(db::image::Image, Vec<db::category::Category>)>,
-        crate::model::image::SearchImagesError,
-    > {
+    ) -> Result<Vec<db::image::ImageExt>, crate::model::image::SearchImagesError> {
         // Checks or mocks here.
-        self.0.search_images(search, offset, limit).await
+        self.0.search_images(filter, offset, limit).await
     }
 
     async fn rate_image(
@@ -102,189 +321,2585 @@ impl ImageService for TestImageService {
     async fn get_image_ratings(
         &self,
         image_id: Uuid,
+        viewer_id: Option<Uuid>,
     ) -> Result<Vec<db::rating::Rating>, crate::model::image::GetImageRatingsError> {
         // Checks or mocks here.
-        self.0.get_image_ratings(image_id).await
+        self.0.get_image_ratings(image_id, viewer_id).await
     }
 
     async fn get_categories(
         &self,
+        include_descendants: bool,
     ) -> Result<Vec<db::category::CategoryExt>, crate::model::image::GetCategoriesError> {
         // Checks or mocks here.
-        self.0.get_categories().await
+        self.0.get_categories(include_descendants).await
     }
 
     async fn create_category(
         &self,
+        context: &AuditContext,
         name: &str,
+        slug: Option<&str>,
+        parent_id: Option<Uuid>,
+        description: Option<&str>,
     ) -> Result<Uuid, crate::model::image::CreateCategoryError> {
         // Checks or mocks here.
-        self.0.create_category(name).await
+        self.0
+            .create_category(context, name, slug, parent_id, description)
+            .await
     }
 
     async fn rename_category(
         &self,
+        context: &AuditContext,
         id: Uuid,
         name: &str,
     ) -> Result<(), crate::model::image::RenameCategoryError> {
         // Checks or mocks here.
-        self.0.rename_category(id, name).await
+        self.0.rename_category(context, id, name).await
     }
 
-    async fn delete_category(&self, id: Uuid) -> Result<(), DeleteCategoryError> {
+    async fn move_category(&self, id: Uuid, parent_id: Option<Uuid>) -> Result<(), MoveCategoryError> {
         // Checks or mocks here.
-        self.0.delete_category(id).await
+        self.0.move_category(id, parent_id).await
     }
 
-    async fn get_image_info(&self, id: Uuid) -> Result<(db::image::Image, Vec<db::category::Category>), GetImageInfoError> {
+    async fn update_category_details(
+        &self,
+        id: Uuid,
+        description: Option<&str>,
+        cover_image_id: Option<Uuid>,
+    ) -> Result<(), UpdateCategoryDetailsError> {
         // Checks or mocks here.
-        self.0.get_image_info(id).await
+        self.0
+            .update_category_details(id, description, cover_image_id)
+            .await
     }
 
-    async fn get_user_ratings(&self) -> Result<Vec<UserRating>, GetUserRatingsError> {
+    async fn delete_category(
+        &self,
+        context: &AuditContext,
+        id: Uuid,
+        children: Option<DeleteCategoryChildren>,
+    ) -> Result<(), DeleteCategoryError> {
         // Checks or mocks here.
-        self.0.get_user_ratings().await
+        self.0.delete_category(context, id, children).await
     }
-}
 
-pub fn configure_services(
-    config: &Config,
-    logger: Logger,
-    pool: sqlx::PgPool,
-) -> impl FnOnce(&mut ServiceConfig) {
-    let c = config.clone();
-    move |app: &mut ServiceConfig| {
-        let auth_service = TestAuthService(Box::new(DefaultAuthService::new(
-            &c,
-            logger.clone(),
-            pool.clone(),
-        )));
-        let image_service = TestImageService(Box::new(DefaultImageService::new(&c, logger, pool)));
+    async fn merge_category(
+        &self,
+        id: Uuid,
+        target_id: Uuid,
+    ) -> Result<db::category::MergedImages, MergeCategoriesError> {
+        // Checks or mocks here.
+        self.0.merge_category(id, target_id).await
+    }
 
-        app.data::<Box<dyn AuthService>>(Box::new(auth_service));
-        app.data::<Box<dyn ImageService>>(Box::new(image_service));
+    async fn bulk_categorize(
+        &self,
+        id: Uuid,
+        action: BulkCategoryAction,
+        image_ids: &[Uuid],
+        dry_run: bool,
+    ) -> Result<BulkCategorizeResponse, BulkCategorizeError> {
+        // Checks or mocks here.
+        self.0
+            .bulk_categorize(id, action, image_ids, dry_run)
+            .await
     }
-}
 
-/// Tests a whole procedure from register/login to picture upload/download.
-#[actix_rt::test]
-async fn whole_app() {
-    let mut config = Config::from_env().unwrap();
-    config.image_storage_path = PathBuf::from("./.tmp_images");
+    async fn get_image_info(
+        &self,
+        id: Uuid,
+        viewer: Option<&auth::UserInfo>,
+    ) -> Result<db::image::ImageExt, GetImageInfoError> {
+        // Checks or mocks here.
+        self.0.get_image_info(id, viewer).await
+    }
 
-    let pool = db::connect(&config).await.unwrap();
+    async fn get_user_ratings(&self) -> Result<Vec<UserRating>, GetUserRatingsError> {
+        // Checks or mocks here.
+        self.0.get_user_ratings().await
+    }
 
-    // Setup admin user.
-    let _ = DefaultAuthService::new(&config, create_logger(&config), pool.clone())
-        .register("admin@admin.admin", "admin")
-        .await;
+    async fn set_image_visibility(
+        &self,
+        app_user_id: Uuid,
+        image_id: Uuid,
+        visibility: Visibility,
+    ) -> Result<(), SetImageVisibilityError> {
+        // Checks or mocks here.
+        self.0
+            .set_image_visibility(app_user_id, image_id, visibility)
+            .await
+    }
 
-    query!(
-        r#"
-        UPDATE app_user
-        SET is_admin = TRUE
-        WHERE 
-            app_user.email = 'admin@admin.admin'
-        "#
-    )
-    .execute(&pool)
-    .await
-    .unwrap();
+    async fn set_image_location(
+        &self,
+        app_user_id: Uuid,
+        image_id: Uuid,
+        location: Option<(f64, f64)>,
+    ) -> Result<(), SetImageLocationError> {
+        // Checks or mocks here.
+        self.0
+            .set_image_location(app_user_id, image_id, location)
+            .await
+    }
 
-    let mut app = test::init_service(
-        App::new()
-            .data(create_logger(&config))
-            .configure(configure_services(
-                &config,
-                create_logger(&config),
-                pool.clone(),
-            ))
-            .configure(server::configure_routes(&config)),
-    )
-    .await;
+    async fn get_similar_images(
+        &self,
+        image_id: Uuid,
+        max_distance: Option<u32>,
+        limit: Option<u64>,
+    ) -> Result<Vec<(db::image::ImageExt, u32)>, GetSimilarImagesError> {
+        // Checks or mocks here.
+        self.0
+            .get_similar_images(image_id, max_distance, limit)
+            .await
+    }
 
-    // Create categories.
-    let category_id;
-    {
-        let login_req = test::TestRequest::post()
-            .uri("/auth/login")
-            .set_json(&LoginRequest {
-                email: "admin@admin.admin".into(),
-                password: "admin".into(),
-            })
-            .to_request();
-        let login_res = test::call_service(&mut app, login_req).await;
-        assert!(login_res.status() == 200);
+    async fn process_upload(&self, image_id: Uuid) -> Result<(), ProcessUploadError> {
+        // Checks or mocks here.
+        self.0.process_upload(image_id).await
+    }
 
-        let login_res_data: LoginResponse = test::read_body_json(login_res).await;
-        let token = login_res_data.token;
+    async fn get_image_clusters(
+        &self,
+        zoom: u8,
+        bbox: Option<db::image::BoundingBox>,
+        viewer_id: Option<Uuid>,
+    ) -> Result<Vec<db::image::LocationCluster>, GetImageClustersError> {
+        // Checks or mocks here.
+        self.0.get_image_clusters(zoom, bbox, viewer_id).await
+    }
 
-        let get_categories_req = test::TestRequest::get()
-            .uri("/categories")
-            .header("Authorization", format!("Bearer {}", token))
-            .to_request();
+    async fn set_image_tags(
+        &self,
+        app_user_id: Uuid,
+        image_id: Uuid,
+        tags: &[String],
+    ) -> Result<Vec<String>, SetImageTagsError> {
+        // Checks or mocks here.
+        self.0.set_image_tags(app_user_id, image_id, tags).await
+    }
 
-        let res: GetCategoriesResponse =
-            test::read_response_json(&mut app, get_categories_req).await;
-        let category_count = res.categories.len();
+    async fn autocomplete_tags(
+        &self,
+        text: &str,
+        limit: Option<u64>,
+    ) -> Result<Vec<db::tag::TagCount>, GetTagsError> {
+        // Checks or mocks here.
+        self.0.autocomplete_tags(text, limit).await
+    }
 
-        let create_category_req = test::TestRequest::post()
-            .uri("/categories")
-            .header("Authorization", format!("Bearer {}", token))
-            .set_json(&CreateCategoryRequest {
-                name: "test_category".into(),
-            })
-            .to_request();
+    async fn get_popular_tags(
+        &self,
+        limit: Option<u64>,
+    ) -> Result<Vec<db::tag::TagCount>, GetTagsError> {
+        // Checks or mocks here.
+        self.0.get_popular_tags(limit).await
+    }
 
-        let new_category_res: CreateCategoryResponse =
-            test::read_response_json(&mut app, create_category_req).await;
-        category_id = new_category_res.id;
+    async fn promote_tag(&self, id: Uuid) -> Result<Uuid, PromoteTagError> {
+        // Checks or mocks here.
+        self.0.promote_tag(id).await
+    }
 
-        let get_categories_req = test::TestRequest::get()
-            .uri("/categories")
-            .header("Authorization", format!("Bearer {}", token))
-            .to_request();
+    async fn get_storage_usage(
+        &self,
+        app_user_id: Uuid,
+    ) -> Result<StorageUsage, StorageUsageError> {
+        // Checks or mocks here.
+        self.0.get_storage_usage(app_user_id).await
+    }
 
+    async fn set_storage_quota(
+        &self,
+        app_user_id: Uuid,
+        max_bytes: Option<u64>,
+        max_images: Option<u64>,
+    ) -> Result<(), SetStorageQuotaError> {
+        // Checks or mocks here.
+        self.0
+            .set_storage_quota(app_user_id, max_bytes, max_images)
+            .await
+    }
+}
+
+/// A proxy service for debugging.
+#[derive(Clone)]
+struct TestAlbumService(Box<dyn AlbumService>);
+
+#[async_trait(?Send)]
+impl AlbumService for TestAlbumService {
+    async fn create_album(
+        &self,
+        app_user_id: Uuid,
+        name: &str,
+        visibility: Visibility,
+    ) -> Result<Uuid, CreateAlbumError> {
+        // Checks or mocks here.
+        self.0.create_album(app_user_id, name, visibility).await
+    }
+
+    async fn get_albums(
+        &self,
+        viewer_id: Uuid,
+        owner_id: Uuid,
+    ) -> Result<Vec<db::album::AlbumExt>, GetAlbumsError> {
+        // Checks or mocks here.
+        self.0.get_albums(viewer_id, owner_id).await
+    }
+
+    async fn get_album(
+        &self,
+        viewer_id: Uuid,
+        id: Uuid,
+        offset: Option<u64>,
+        limit: Option<u64>,
+    ) -> Result<(db::album::AlbumExt, Vec<db::image::ImageExt>), GetAlbumError> {
+        // Checks or mocks here.
+        self.0.get_album(viewer_id, id, offset, limit).await
+    }
+
+    async fn update_album(
+        &self,
+        app_user_id: Uuid,
+        id: Uuid,
+        name: Option<&str>,
+        visibility: Option<Visibility>,
+    ) -> Result<(), UpdateAlbumError> {
+        // Checks or mocks here.
+        self.0.update_album(app_user_id, id, name, visibility).await
+    }
+
+    async fn delete_album(&self, app_user_id: Uuid, id: Uuid) -> Result<(), DeleteAlbumError> {
+        // Checks or mocks here.
+        self.0.delete_album(app_user_id, id).await
+    }
+
+    async fn add_album_image(
+        &self,
+        app_user_id: Uuid,
+        id: Uuid,
+        image_id: Uuid,
+    ) -> Result<(), AddAlbumImageError> {
+        // Checks or mocks here.
+        self.0.add_album_image(app_user_id, id, image_id).await
+    }
+
+    async fn remove_album_image(
+        &self,
+        app_user_id: Uuid,
+        id: Uuid,
+        image_id: Uuid,
+    ) -> Result<(), RemoveAlbumImageError> {
+        // Checks or mocks here.
+        self.0.remove_album_image(app_user_id, id, image_id).await
+    }
+
+    async fn reorder_album_images(
+        &self,
+        app_user_id: Uuid,
+        id: Uuid,
+        image_ids: &[Uuid],
+    ) -> Result<(), ReorderAlbumImagesError> {
+        // Checks or mocks here.
+        self.0.reorder_album_images(app_user_id, id, image_ids).await
+    }
+}
+
+/// A proxy service for debugging.
+#[derive(Clone)]
+struct TestCommentService(Box<dyn CommentService>);
+
+#[async_trait(?Send)]
+impl CommentService for TestCommentService {
+    async fn create_comment(
+        &self,
+        app_user_id: Uuid,
+        image_id: Uuid,
+        parent_id: Option<Uuid>,
+        body: &str,
+    ) -> Result<Uuid, CreateCommentError> {
+        // Checks or mocks here.
+        self.0
+            .create_comment(app_user_id, image_id, parent_id, body)
+            .await
+    }
+
+    async fn get_comments(
+        &self,
+        viewer_id: Uuid,
+        image_id: Uuid,
+        offset: Option<u64>,
+        limit: Option<u64>,
+    ) -> Result<Vec<db::comment::Comment>, GetCommentsError> {
+        // Checks or mocks here.
+        self.0.get_comments(viewer_id, image_id, offset, limit).await
+    }
+
+    async fn edit_comment(
+        &self,
+        app_user_id: Uuid,
+        id: Uuid,
+        body: &str,
+    ) -> Result<(), EditCommentError> {
+        // Checks or mocks here.
+        self.0.edit_comment(app_user_id, id, body).await
+    }
+
+    async fn delete_comment(
+        &self,
+        user: &auth::UserInfo,
+        id: Uuid,
+    ) -> Result<(), DeleteCommentError> {
+        // Checks or mocks here.
+        self.0.delete_comment(user, id).await
+    }
+}
+
+/// A proxy service for debugging.
+#[derive(Clone)]
+struct TestShareLinkService(Box<dyn ShareLinkService>);
+
+#[async_trait(?Send)]
+impl ShareLinkService for TestShareLinkService {
+    async fn create_share_link(
+        &self,
+        app_user_id: Uuid,
+        image_id: Uuid,
+        expires: Option<time::OffsetDateTime>,
+    ) -> Result<db::share_link::ShareLink, CreateShareLinkError> {
+        // Checks or mocks here.
+        self.0
+            .create_share_link(app_user_id, image_id, expires)
+            .await
+    }
+
+    async fn get_share_links(
+        &self,
+        app_user_id: Uuid,
+        image_id: Uuid,
+    ) -> Result<Vec<db::share_link::ShareLink>, GetShareLinksError> {
+        // Checks or mocks here.
+        self.0.get_share_links(app_user_id, image_id).await
+    }
+
+    async fn revoke_share_link(
+        &self,
+        app_user_id: Uuid,
+        id: Uuid,
+    ) -> Result<(), RevokeShareLinkError> {
+        // Checks or mocks here.
+        self.0.revoke_share_link(app_user_id, id).await
+    }
+
+    async fn get_shared_image(
+        &self,
+        token: &str,
+    ) -> Result<db::image::ImageExt, GetSharedImageError> {
+        // Checks or mocks here.
+        self.0.get_shared_image(token).await
+    }
+
+    async fn download_shared_image(&self, token: &str) -> Result<NamedFile, GetSharedImageError> {
+        // Checks or mocks here.
+        self.0.download_shared_image(token).await
+    }
+}
+
+#[derive(Clone)]
+struct TestModerationService(Box<dyn ModerationService>);
+
+#[async_trait(?Send)]
+impl ModerationService for TestModerationService {
+    async fn report_image(
+        &self,
+        app_user_id: Uuid,
+        image_id: Uuid,
+        reason: ReportReason,
+        details: Option<&str>,
+    ) -> Result<Uuid, ReportImageError> {
+        // Checks or mocks here.
+        self.0
+            .report_image(app_user_id, image_id, reason, details)
+            .await
+    }
+
+    async fn get_reported_images(
+        &self,
+        offset: Option<u64>,
+        limit: Option<u64>,
+    ) -> Result<
+        Vec<(db::image_report::ReportedImage, Vec<db::image_report::ImageReport>)>,
+        GetReportedImagesError,
+    > {
+        // Checks or mocks here.
+        self.0.get_reported_images(offset, limit).await
+    }
+
+    async fn moderate_image(
+        &self,
+        moderator: &auth::UserInfo,
+        image_id: Uuid,
+        action: ModerationActionKind,
+        note: Option<&str>,
+        warn_user: bool,
+    ) -> Result<(), ModerateImageError> {
+        // Checks or mocks here.
+        self.0
+            .moderate_image(moderator, image_id, action, note, warn_user)
+            .await
+    }
+
+    async fn get_moderation_actions(
+        &self,
+        image_id: Option<Uuid>,
+        offset: Option<u64>,
+        limit: Option<u64>,
+    ) -> Result<Vec<db::moderation_action::ModerationAction>, GetModerationActionsError> {
+        // Checks or mocks here.
+        self.0.get_moderation_actions(image_id, offset, limit).await
+    }
+}
+
+/// A proxy service for debugging.
+#[derive(Clone)]
+struct TestAuditService(Box<dyn AuditService>);
+
+#[async_trait(?Send)]
+impl AuditService for TestAuditService {
+    async fn get_audit_events(
+        &self,
+        filter: db::audit_event::AuditEventFilter,
+        offset: Option<u64>,
+        limit: Option<u64>,
+    ) -> Result<Vec<db::audit_event::AuditEvent>, GetAuditEventsError> {
+        // Checks or mocks here.
+        self.0.get_audit_events(filter, offset, limit).await
+    }
+}
+
+#[derive(Clone)]
+struct TestTrashService(Box<dyn TrashService>);
+
+#[async_trait(?Send)]
+impl TrashService for TestTrashService {
+    async fn delete_image(
+        &self,
+        app_user_id: Uuid,
+        image_id: Uuid,
+    ) -> Result<(), DeleteImageError> {
+        // Checks or mocks here.
+        self.0.delete_image(app_user_id, image_id).await
+    }
+
+    async fn get_trashed_images(
+        &self,
+        app_user_id: Uuid,
+        offset: Option<u64>,
+        limit: Option<u64>,
+    ) -> Result<Vec<db::image::Image>, GetTrashError> {
+        // Checks or mocks here.
+        self.0.get_trashed_images(app_user_id, offset, limit).await
+    }
+
+    async fn restore_image(
+        &self,
+        app_user_id: Uuid,
+        image_id: Uuid,
+    ) -> Result<(), TrashedImageError> {
+        // Checks or mocks here.
+        self.0.restore_image(app_user_id, image_id).await
+    }
+
+    async fn purge_image(
+        &self,
+        app_user_id: Uuid,
+        image_id: Uuid,
+    ) -> Result<(), TrashedImageError> {
+        // Checks or mocks here.
+        self.0.purge_image(app_user_id, image_id).await
+    }
+
+    async fn get_trashed_categories(&self) -> Result<Vec<db::category::Category>, GetTrashError> {
+        // Checks or mocks here.
+        self.0.get_trashed_categories().await
+    }
+
+    async fn restore_category(
+        &self,
+        context: &AuditContext,
+        id: Uuid,
+    ) -> Result<(), RestoreCategoryError> {
+        // Checks or mocks here.
+        self.0.restore_category(context, id).await
+    }
+
+    async fn purge_trash(&self) -> Result<PurgedTrash, PurgeTrashError> {
+        // Checks or mocks here.
+        self.0.purge_trash().await
+    }
+
+    fn retention_period(&self) -> time::Duration {
+        self.0.retention_period()
+    }
+}
+
+#[derive(Clone)]
+struct TestAccountService(Box<dyn AccountService>);
+
+#[async_trait(?Send)]
+impl AccountService for TestAccountService {
+    async fn request_export(&self, app_user_id: Uuid) -> Result<AccountExport, AccountExportError> {
+        // Checks or mocks here.
+        self.0.request_export(app_user_id).await
+    }
+
+    async fn get_export(
+        &self,
+        app_user_id: Uuid,
+        export_id: Uuid,
+    ) -> Result<AccountExport, AccountExportError> {
+        // Checks or mocks here.
+        self.0.get_export(app_user_id, export_id).await
+    }
+
+    async fn download_export(
+        &self,
+        app_user_id: Uuid,
+        export_id: Uuid,
+    ) -> Result<NamedFile, AccountExportError> {
+        // Checks or mocks here.
+        self.0.download_export(app_user_id, export_id).await
+    }
+
+    async fn build_export(
+        &self,
+        export_id: Uuid,
+        final_attempt: bool,
+    ) -> Result<(), AccountExportError> {
+        // Checks or mocks here.
+        self.0.build_export(export_id, final_attempt).await
+    }
+
+    async fn delete_account(
+        &self,
+        context: &AuditContext,
+        user: &auth::UserInfo,
+        password: Option<&str>,
+    ) -> Result<(), DeleteAccountError> {
+        // Checks or mocks here.
+        self.0.delete_account(context, user, password).await
+    }
+}
+
+#[derive(Clone)]
+struct TestNotificationService(Box<dyn NotificationService>);
+
+#[async_trait(?Send)]
+impl NotificationService for TestNotificationService {
+    async fn get_notifications(
+        &self,
+        app_user_id: Uuid,
+        unread_only: bool,
+        offset: Option<u64>,
+        limit: Option<u64>,
+    ) -> Result<(Vec<db::notification::Notification>, u64), GetNotificationsError> {
+        // Checks or mocks here.
+        self.0
+            .get_notifications(app_user_id, unread_only, offset, limit)
+            .await
+    }
+
+    async fn mark_read(
+        &self,
+        app_user_id: Uuid,
+        id: Uuid,
+    ) -> Result<(), MarkNotificationReadError> {
+        // Checks or mocks here.
+        self.0.mark_read(app_user_id, id).await
+    }
+
+    async fn mark_all_read(&self, app_user_id: Uuid) -> Result<(), MarkNotificationReadError> {
+        // Checks or mocks here.
+        self.0.mark_all_read(app_user_id).await
+    }
+
+    fn subscribe(
+        &self,
+        app_user_id: Uuid,
+        last_id: Option<Uuid>,
+    ) -> LocalBoxStream<'static, db::notification::Notification> {
+        // Checks or mocks here.
+        self.0.subscribe(app_user_id, last_id)
+    }
+}
+
+pub fn configure_services(
+    config: &Config,
+    logger: Logger,
+    pool: sqlx::PgPool,
+    notification_hub: NotificationHub,
+) -> impl FnOnce(&mut ServiceConfig) {
+    let c = config.clone();
+    move |app: &mut ServiceConfig| {
+        let auth_service = TestAuthService(Box::new(DefaultAuthService::new(
+            &c,
+            logger.clone(),
+            pool.clone(),
+        )));
+        let image_service = TestImageService(Box::new(DefaultImageService::new(
+            &c,
+            logger.clone(),
+            pool.clone(),
+        )));
+        let album_service = TestAlbumService(Box::new(DefaultAlbumService::new(
+            logger.clone(),
+            pool.clone(),
+        )));
+        let comment_service = TestCommentService(Box::new(DefaultCommentService::new(
+            &c,
+            logger.clone(),
+            pool.clone(),
+        )));
+        let share_link_service = TestShareLinkService(Box::new(DefaultShareLinkService::new(
+            &c,
+            logger.clone(),
+            pool.clone(),
+        )));
+        let moderation_service = TestModerationService(Box::new(DefaultModerationService::new(
+            logger.clone(),
+            pool.clone(),
+        )));
+        let audit_service = TestAuditService(Box::new(DefaultAuditService::new(
+            logger.clone(),
+            pool.clone(),
+        )));
+        let trash_service = TestTrashService(Box::new(DefaultTrashService::new(
+            &c,
+            logger.clone(),
+            pool.clone(),
+        )));
+        let account_service = TestAccountService(Box::new(DefaultAccountService::new(
+            &c,
+            logger.clone(),
+            pool.clone(),
+        )));
+        let notification_service = TestNotificationService(Box::new(
+            DefaultNotificationService::new(logger, pool, notification_hub),
+        ));
+
+        app.data::<Box<dyn AuthService>>(Box::new(auth_service));
+        app.data::<Box<dyn ImageService>>(Box::new(image_service));
+        app.data::<Box<dyn AlbumService>>(Box::new(album_service));
+        app.data::<Box<dyn CommentService>>(Box::new(comment_service));
+        app.data::<Box<dyn ShareLinkService>>(Box::new(share_link_service));
+        app.data::<Box<dyn ModerationService>>(Box::new(moderation_service));
+        app.data::<Box<dyn AuditService>>(Box::new(audit_service));
+        app.data::<Box<dyn TrashService>>(Box::new(trash_service));
+        app.data::<Box<dyn AccountService>>(Box::new(account_service));
+        app.data::<Box<dyn NotificationService>>(Box::new(notification_service));
+    }
+}
+
+fn test_config() -> Config {
+    let mut config = Config::from_env().unwrap();
+    config.image_storage_path = PathBuf::from("./.tmp_images");
+    config.export_storage_path = PathBuf::from("./.tmp_exports");
+
+    if config.token_signing_key.is_none() && config.token_signing_key_path.is_none() {
+        config.token_signing_key_path = Some(PathBuf::from("./test_data/token_test_key.pem"));
+        config.token_signing_algorithm = "EdDSA".into();
+    }
+
+    config
+}
+
+/// Tests what anonymous clients can see with public read access enabled.
+#[actix_rt::test]
+async fn public_read() {
+    let mut config = test_config();
+    config.public_read = true;
+
+    let pool = db::connect(&config).await.unwrap();
+
+    let mut app = test::init_service(
+        App::new()
+            .data(create_logger(&config))
+            .configure(configure_services(
+                &config,
+                create_logger(&config),
+                pool.clone(),
+                NotificationHub::start(create_logger(&config), pool.clone()),
+            ))
+            .configure(server::configure_routes(&config)),
+    )
+    .await;
+
+    let email = format!("test_{}@test.test", random_string(12));
+    let register_req = test::TestRequest::post()
+        .uri("/auth/register")
+        .set_json(&RegisterRequest {
+            email: email.clone(),
+            password: "password".into(),
+        })
+        .to_request();
+    let res = test::call_service(&mut app, register_req).await;
+    assert!(res.status() == 204);
+
+    let login_req = test::TestRequest::post()
+        .uri("/auth/login")
+        .set_json(&LoginRequest {
+            email,
+            password: "password".into(),
+        })
+        .to_request();
+    let login_res: LoginResponse = test::read_response_json(&mut app, login_req).await;
+
+    let create_image_req = test::TestRequest::post()
+        .uri("/images")
+        .header("Authorization", format!("Bearer {}", login_res.token))
+        .set_json(&CreateImageRequest {
+            title: "private_image".to_string(),
+            categories: Vec::new(),
+            description: None,
+            visibility: Some(Visibility::Private),
+        })
+        .to_request();
+    let create_image_res: CreateImageResponse =
+        test::read_response_json(&mut app, create_image_req).await;
+    let image_id = create_image_res.id;
+
+    let get_rating_req = || {
+        test::TestRequest::get()
+            .uri(&format!("/images/{}/rating", image_id))
+            .to_request()
+    };
+
+    // The ratings of private images are only visible to their owners.
+    let res = test::call_service(&mut app, get_rating_req()).await;
+    assert!(res.status() == 404);
+
+    let owner_rating_req = test::TestRequest::get()
+        .uri(&format!("/images/{}/rating", image_id))
+        .header("Authorization", format!("Bearer {}", login_res.token))
+        .to_request();
+    let res = test::call_service(&mut app, owner_rating_req).await;
+    assert!(res.status() == 200);
+
+    let set_visibility_req = test::TestRequest::put()
+        .uri(&format!("/images/{}/visibility", image_id))
+        .header("Authorization", format!("Bearer {}", login_res.token))
+        .set_json(&SetImageVisibilityRequest {
+            visibility: Visibility::Public,
+        })
+        .to_request();
+    let res = test::call_service(&mut app, set_visibility_req).await;
+    assert!(res.status() == 204);
+
+    let res: GetImageRatingResponse = test::read_response_json(&mut app, get_rating_req()).await;
+    assert!(res.rating_count == 0);
+}
+
+/// Tests a whole procedure from register/login to picture upload/download.
+#[actix_rt::test]
+async fn whole_app() {
+    let mut config = test_config();
+    config.public_read = false;
+
+    let pool = db::connect(&config).await.unwrap();
+
+    // Setup admin user.
+    let _ = DefaultAuthService::new(&config, create_logger(&config), pool.clone())
+        .register("admin@admin.admin", "admin")
+        .await;
+
+    query!(
+        r#"
+        UPDATE app_user
+        SET user_role = 'admin'
+        WHERE 
+            app_user.email = 'admin@admin.admin'
+        "#
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    // Account exports are built by the job workers.
+    jobs::start_workers(&config, create_logger(&config), pool.clone(), 1);
+
+    let mut app = test::init_service(
+        App::new()
+            .data(create_logger(&config))
+            .configure(configure_services(
+                &config,
+                create_logger(&config),
+                pool.clone(),
+                NotificationHub::start(create_logger(&config), pool.clone()),
+            ))
+            .configure(server::configure_routes(&config)),
+    )
+    .await;
+
+    // Create categories.
+    let category_id;
+    {
+        let login_req = test::TestRequest::post()
+            .uri("/auth/login")
+            .set_json(&LoginRequest {
+                email: "admin@admin.admin".into(),
+                password: "admin".into(),
+            })
+            .to_request();
+        let login_res = test::call_service(&mut app, login_req).await;
+        assert!(login_res.status() == 200);
+
+        let login_res_data: LoginResponse = test::read_body_json(login_res).await;
+        let token = login_res_data.token;
+
+        let get_categories_req = test::TestRequest::get()
+            .uri("/categories")
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+
+        let res: GetCategoriesResponse =
+            test::read_response_json(&mut app, get_categories_req).await;
+        let category_count = res.categories.len();
+
+        let create_category_req = test::TestRequest::post()
+            .uri("/categories")
+            .header("Authorization", format!("Bearer {}", token))
+            .set_json(&CreateCategoryRequest {
+                name: "test_category".into(),
+                slug: None,
+                parent_id: None,
+                description: None,
+            })
+            .to_request();
+
+        let new_category_res: CreateCategoryResponse =
+            test::read_response_json(&mut app, create_category_req).await;
+        category_id = new_category_res.id;
+
+        let get_categories_req = test::TestRequest::get()
+            .uri("/categories")
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+
+        let res: GetCategoriesResponse =
+            test::read_response_json(&mut app, get_categories_req).await;
+        assert!(res.categories.len() - 1 == category_count);
+
+        let rename_category_req = test::TestRequest::put()
+            .uri(&format!("/categories/{}", &category_id))
+            .header("Authorization", format!("Bearer {}", token))
+            .set_json(&RenameCategoryRequest {
+                name: format!("new_category_name_{}", random_string(24)),
+            })
+            .to_request();
+
+        let res = test::call_service(&mut app, rename_category_req).await;
+        assert!(
+            res.status().as_u16() == 204,
+            "expected 204, but got response {:?}",
+            res
+        );
+
+        let create_subcategory_req = test::TestRequest::post()
+            .uri("/categories")
+            .header("Authorization", format!("Bearer {}", token))
+            .set_json(&CreateCategoryRequest {
+                name: format!("subcategory_{}", random_string(24)),
+                slug: None,
+                parent_id: Some(category_id),
+                description: Some("A subcategory".into()),
+            })
+            .to_request();
+
+        let subcategory_res: CreateCategoryResponse =
+            test::read_response_json(&mut app, create_subcategory_req).await;
+
+        let get_tree_req = test::TestRequest::get()
+            .uri("/categories?tree=true&includeDescendants=true")
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+
+        let res: GetCategoriesResponse = test::read_response_json(&mut app, get_tree_req).await;
+        let parent = res
+            .categories
+            .iter()
+            .find(|c| c.id == category_id)
+            .expect("the parent category should be a root");
+        assert!(parent.children.iter().any(|c| c.id == subcategory_res.id));
+
+        let move_req = test::TestRequest::put()
+            .uri(&format!("/categories/{}/parent", &category_id))
+            .header("Authorization", format!("Bearer {}", token))
+            .set_json(&MoveCategoryRequest {
+                parent_id: Some(subcategory_res.id),
+            })
+            .to_request();
+
+        let res = test::call_service(&mut app, move_req).await;
+        assert!(
+            res.status().as_u16() == 400,
+            "expected 400 for a cycle, but got response {:?}",
+            res
+        );
+
+        let delete_parent_req = test::TestRequest::delete()
+            .uri(&format!("/categories/{}", &category_id))
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+
+        let res = test::call_service(&mut app, delete_parent_req).await;
+        assert!(
+            res.status().as_u16() == 400,
+            "expected 400 without choosing what happens to the children, but got response {:?}",
+            res
+        );
+
+        let delete_subcategory_req = test::TestRequest::delete()
+            .uri(&format!("/categories/{}", &subcategory_res.id))
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+
+        let res = test::call_service(&mut app, delete_subcategory_req).await;
+        assert!(
+            res.status().as_u16() == 204,
+            "expected 204, but got response {:?}",
+            res
+        );
+    }
+
+    // User register and login
+    let token: String;
+    {
+        let invalid_req = test::TestRequest::post()
+            .uri("/auth/register")
+            .set_json(&RegisterRequest {
+                email: "invalid email 2131231".into(),
+                password: "password".into(),
+            })
+            .to_request();
+
+        let res = test::call_service(&mut app, invalid_req).await;
+        assert!(
+            res.status().as_u16() == 400,
+            "got: {}",
+            res.status().as_u16()
+        );
+        let invalid_req_data: InvalidRegisterRequest = test::read_body_json(res).await;
+        assert!(invalid_req_data.error.contains("e-mail"));
+
+        let email = format!("test_{}@test.test", random_string(12));
+        let register_req = test::TestRequest::post()
+            .uri("/auth/register")
+            .set_json(&RegisterRequest {
+                email: email.clone(),
+                password: "password".into(),
+            })
+            .to_request();
+
+        let register_res = test::call_service(&mut app, register_req).await;
+        assert!(register_res.status() == 204);
+
+        let wrong_password_req = test::TestRequest::post()
+            .uri("/auth/login")
+            .set_json(&LoginRequest {
+                email: email.clone(),
+                password: "wrongPassword".into(),
+            })
+            .to_request();
+        let wrong_password_res = test::call_service(&mut app, wrong_password_req).await;
+        assert!(wrong_password_res.status() == 403);
+
+        let wrong_email_req = test::TestRequest::post()
+            .uri("/auth/login")
+            .set_json(&LoginRequest {
+                email: "doesnt@exist.com".into(),
+                password: "password".into(),
+            })
+            .to_request();
+        let wrong_email_res = test::call_service(&mut app, wrong_email_req).await;
+        assert!(wrong_email_res.status() == 404);
+
+        let login_req = test::TestRequest::post()
+            .uri("/auth/login")
+            .set_json(&LoginRequest {
+                email: email.clone(),
+                password: "password".into(),
+            })
+            .to_request();
+        let login_res = test::call_service(&mut app, login_req).await;
+        assert!(login_res.status() == 200);
+
+        let login_res_data: LoginResponse = test::read_body_json(login_res).await;
+        token = login_res_data.token;
+    }
+
+    // Token verification with the published keys
+    {
+        let jwks_req = test::TestRequest::get()
+            .uri("/.well-known/jwks.json")
+            .to_request();
+        let jwks: jwt::jwk::JwkSet = test::read_response_json(&mut app, jwks_req).await;
+
+        let kid = jwt::decode_header(&token).unwrap().kid.unwrap();
+        let jwk = jwks.find(&kid).unwrap();
+
+        let claims = jwt::decode::<serde_json::Value>(
+            &token,
+            &jwt::DecodingKey::from_jwk(jwk).unwrap(),
+            &jwt::Validation::new(jwk.common.algorithm.unwrap()),
+        )
+        .unwrap()
+        .claims;
+        assert_eq!(claims["sub"], "appUser");
+    }
+
+    // Two-factor authentication
+    {
+        let email = format!("test_{}@test.test", random_string(12));
+        let register_req = test::TestRequest::post()
+            .uri("/auth/register")
+            .set_json(&RegisterRequest {
+                email: email.clone(),
+                password: "password".into(),
+            })
+            .to_request();
+        let register_res = test::call_service(&mut app, register_req).await;
+        assert!(register_res.status() == 204);
+
+        let login_req = test::TestRequest::post()
+            .uri("/auth/login")
+            .set_json(&LoginRequest {
+                email: email.clone(),
+                password: "password".into(),
+            })
+            .to_request();
+        let login_res: LoginResponse = test::read_response_json(&mut app, login_req).await;
+        assert!(!login_res.mfa_required);
+
+        let enroll_req = test::TestRequest::post()
+            .uri("/auth/mfa/totp")
+            .header("Authorization", format!("Bearer {}", login_res.token))
+            .to_request();
+        let enroll_res: EnrollTotpResponse = test::read_response_json(&mut app, enroll_req).await;
+
+        // Codes of the next step are accepted too, to account for clock drift.
+        let code = |steps_ahead: u64| {
+            let step = time::OffsetDateTime::now_utc().timestamp() as u64
+                / crate::util::totp::TOTP_STEP_SECONDS;
+            format!(
+                "{:06}",
+                crate::util::totp::code_at_step(&enroll_res.secret, step + steps_ahead).unwrap()
+            )
+        };
+
+        let confirm_code = code(0);
+        let confirm_req = test::TestRequest::post()
+            .uri("/auth/mfa/totp/confirm")
+            .header("Authorization", format!("Bearer {}", login_res.token))
+            .set_json(&ConfirmTotpRequest {
+                code: confirm_code.clone(),
+            })
+            .to_request();
+        let confirm_res: ConfirmTotpResponse =
+            test::read_response_json(&mut app, confirm_req).await;
+        let recovery_code = confirm_res.recovery_codes[0].clone();
+
+        let login_req = || {
+            test::TestRequest::post()
+                .uri("/auth/login")
+                .set_json(&LoginRequest {
+                    email: email.clone(),
+                    password: "password".into(),
+                })
+                .to_request()
+        };
+
+        // TOTP code
+        let login_res: LoginResponse = test::read_response_json(&mut app, login_req()).await;
+        assert!(login_res.mfa_required);
+
+        let get_categories_req = test::TestRequest::get()
+            .uri("/categories")
+            .header("Authorization", format!("Bearer {}", login_res.token))
+            .to_request();
+        let res = test::call_service(&mut app, get_categories_req).await;
+        assert!(res.status() != 200);
+
+        let mfa_req = test::TestRequest::post()
+            .uri("/auth/login/mfa")
+            .set_json(&MfaLoginRequest {
+                token: login_res.token.clone(),
+                code: "000000x".into(),
+            })
+            .to_request();
+        let res = test::call_service(&mut app, mfa_req).await;
+        assert!(res.status() == 403);
+
+        // The code used for the confirmation cannot be used again.
+        let mfa_req = test::TestRequest::post()
+            .uri("/auth/login/mfa")
+            .set_json(&MfaLoginRequest {
+                token: login_res.token.clone(),
+                code: confirm_code,
+            })
+            .to_request();
+        let res = test::call_service(&mut app, mfa_req).await;
+        assert!(res.status() == 403);
+
+        let next_code = code(1);
+        let mfa_req = test::TestRequest::post()
+            .uri("/auth/login/mfa")
+            .set_json(&MfaLoginRequest {
+                token: login_res.token,
+                code: next_code.clone(),
+            })
+            .to_request();
+        let mfa_res: LoginResponse = test::read_response_json(&mut app, mfa_req).await;
+        assert!(!mfa_res.mfa_required);
+
+        let login_res: LoginResponse = test::read_response_json(&mut app, login_req()).await;
+
+        let mfa_req = test::TestRequest::post()
+            .uri("/auth/login/mfa")
+            .set_json(&MfaLoginRequest {
+                token: login_res.token,
+                code: next_code,
+            })
+            .to_request();
+        let res = test::call_service(&mut app, mfa_req).await;
+        assert!(res.status() == 403);
+
+        let get_categories_req = test::TestRequest::get()
+            .uri("/categories")
+            .header("Authorization", format!("Bearer {}", mfa_res.token))
+            .to_request();
+        let res = test::call_service(&mut app, get_categories_req).await;
+        assert!(res.status() == 200);
+
+        // Recovery code
+        let login_res: LoginResponse = test::read_response_json(&mut app, login_req()).await;
+
+        let mfa_req = test::TestRequest::post()
+            .uri("/auth/login/mfa")
+            .set_json(&MfaLoginRequest {
+                token: login_res.token,
+                code: recovery_code.clone(),
+            })
+            .to_request();
+        let mfa_res: LoginResponse = test::read_response_json(&mut app, mfa_req).await;
+        assert!(!mfa_res.mfa_required);
+
+        // Recovery codes can only be used once.
+        let login_res: LoginResponse = test::read_response_json(&mut app, login_req()).await;
+
+        let mfa_req = test::TestRequest::post()
+            .uri("/auth/login/mfa")
+            .set_json(&MfaLoginRequest {
+                token: login_res.token.clone(),
+                code: recovery_code,
+            })
+            .to_request();
+        let res = test::call_service(&mut app, mfa_req).await;
+        assert!(res.status() == 403);
+
+        // The user is locked out after too many incorrect codes.
+        for _ in 0..5 {
+            let mfa_req = test::TestRequest::post()
+                .uri("/auth/login/mfa")
+                .set_json(&MfaLoginRequest {
+                    token: login_res.token.clone(),
+                    code: "000000".into(),
+                })
+                .to_request();
+            test::call_service(&mut app, mfa_req).await;
+        }
+
+        let mfa_req = test::TestRequest::post()
+            .uri("/auth/login/mfa")
+            .set_json(&MfaLoginRequest {
+                token: login_res.token,
+                code: confirm_res.recovery_codes[1].clone(),
+            })
+            .to_request();
+        let res = test::call_service(&mut app, mfa_req).await;
+        assert!(res.status() == 429);
+    }
+
+    // Single sign-on callbacks
+    {
+        for (cookie, status) in vec![(None, 400), (Some("other"), 400), (Some("state"), 404)] {
+            let mut callback_req =
+                test::TestRequest::get().uri("/auth/oidc/callback?code=code&state=state");
+
+            // Only the browser that started the login has the state.
+            if let Some(cookie) = cookie {
+                callback_req =
+                    callback_req.cookie(actix_web::cookie::Cookie::new("pt_oidc_state", cookie));
+            }
+
+            let res = test::call_service(&mut app, callback_req.to_request()).await;
+            assert!(res.status() == status);
+        }
+    }
+
+    // Roles
+    {
+        let email = format!("test_{}@test.test", random_string(12));
+        let register_req = test::TestRequest::post()
+            .uri("/auth/register")
+            .set_json(&RegisterRequest {
+                email: email.clone(),
+                password: "password".into(),
+            })
+            .to_request();
+        let register_res = test::call_service(&mut app, register_req).await;
+        assert!(register_res.status() == 204);
+
+        let curator_id = db::app_user::AppUser::by_email(&email.to_lowercase(), &pool)
+            .await
+            .unwrap()
+            .unwrap()
+            .id;
+
+        let login_req = || {
+            test::TestRequest::post()
+                .uri("/auth/login")
+                .set_json(&LoginRequest {
+                    email: email.clone(),
+                    password: "password".into(),
+                })
+                .to_request()
+        };
+        let create_category_req = |token: &str| {
+            test::TestRequest::post()
+                .uri("/categories")
+                .header("Authorization", format!("Bearer {}", token))
+                .set_json(&CreateCategoryRequest {
+                    name: format!("curated_{}", random_string(24)),
+                    slug: None,
+                    parent_id: None,
+                    description: None,
+                })
+                .to_request()
+        };
+
+        let login_res: LoginResponse = test::read_response_json(&mut app, login_req()).await;
+        let user_token = login_res.token.clone();
+        let res = test::call_service(&mut app, create_category_req(&user_token)).await;
+        assert!(res.status() == 403);
+
+        let set_role_req = test::TestRequest::put()
+            .uri(&format!("/users/{}/role", curator_id))
+            .header("Authorization", format!("Bearer {}", login_res.token))
+            .set_json(&SetRoleRequest { role: Role::Admin })
+            .to_request();
+        let res = test::call_service(&mut app, set_role_req).await;
+        assert!(res.status() == 403);
+
+        let admin_login_req = test::TestRequest::post()
+            .uri("/auth/login")
+            .set_json(&LoginRequest {
+                email: "admin@admin.admin".into(),
+                password: "admin".into(),
+            })
+            .to_request();
+        let admin_login_res: LoginResponse =
+            test::read_response_json(&mut app, admin_login_req).await;
+
+        let set_role_req = test::TestRequest::put()
+            .uri(&format!("/users/{}/role", Uuid::new_v4()))
+            .header("Authorization", format!("Bearer {}", admin_login_res.token))
+            .set_json(&SetRoleRequest {
+                role: Role::Curator,
+            })
+            .to_request();
+        let res = test::call_service(&mut app, set_role_req).await;
+        assert!(res.status() == 404);
+
+        let set_role_req = test::TestRequest::put()
+            .uri(&format!("/users/{}/role", curator_id))
+            .header("Authorization", format!("Bearer {}", admin_login_res.token))
+            .set_json(&SetRoleRequest {
+                role: Role::Curator,
+            })
+            .to_request();
+        let res = test::call_service(&mut app, set_role_req).await;
+        assert!(res.status() == 204);
+
+        // The new role applies to the tokens issued before the change.
+        let res = test::call_service(&mut app, create_category_req(&user_token)).await;
+        assert!(res.status() == 200);
+
+        let audit_req = test::TestRequest::get()
+            .uri("/admin/audit")
+            .header("Authorization", format!("Bearer {}", user_token))
+            .to_request();
+        let res = test::call_service(&mut app, audit_req).await;
+        assert!(res.status() == 403);
+
+        let set_role_req = test::TestRequest::put()
+            .uri(&format!("/users/{}/role", curator_id))
+            .header("Authorization", format!("Bearer {}", admin_login_res.token))
+            .set_json(&SetRoleRequest { role: Role::User })
+            .to_request();
+        let res = test::call_service(&mut app, set_role_req).await;
+        assert!(res.status() == 204);
+
+        let res = test::call_service(&mut app, create_category_req(&user_token)).await;
+        assert!(res.status() == 403);
+    }
+
+    // Image upload
+    let image_id: Uuid;
+    {
+        let create_image_req = test::TestRequest::post()
+            .uri("/images")
+            .header("Authorization", format!("Bearer {}", token))
+            .set_json(&CreateImageRequest {
+                title: "test_image".to_string(),
+                categories: vec![category_id],
+                description: "asd".to_string().into(),
+                visibility: None,
+            })
+            .to_request();
+        let create_image_res = test::call_service(&mut app, create_image_req).await;
+        assert!(
+            create_image_res.status() == 200,
+            "got {}, {:?}",
+            create_image_res.status().as_u16(),
+            test::read_body(create_image_res).await
+        );
+
+        let create_image_res_data: CreateImageResponse =
+            test::read_body_json(create_image_res).await;
+        image_id = create_image_res_data.id;
+
+        let mut image_upload_data = Vec::new();
+
+        image_upload_data.extend("--test_image\r\n".bytes());
+        image_upload_data.extend(
+            r#"Content-Disposition: form-data; name="image"; filename="test_image.png""#.bytes(),
+        );
+        image_upload_data.extend("\r\n\r\n".bytes());
+        image_upload_data.extend(TEST_IMAGE);
+        image_upload_data.extend("\r\n--test_image--\r\n".bytes());
+
+        let upload_image_req = test::TestRequest::post()
+            .uri(&format!("/images/{}", image_id))
+            .header("Authorization", format!("Bearer {}", token))
+            .header("Content-Type", "multipart/form-data; boundary=test_image")
+            .header("Content-Length", image_upload_data.len())
+            .set_payload(image_upload_data)
+            .to_request();
+        let upload_image_res = test::call_service(&mut app, upload_image_req).await;
+
+        assert!(
+            upload_image_res.status() == 204,
+            "got {}, {:?}",
+            upload_image_res.status().as_u16(),
+            test::read_body(upload_image_res).await
+        );
+
+        // Image search
+        let search_images_req = test::TestRequest::get()
+            .uri("/images")
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let search_images_res = test::call_service(&mut app, search_images_req).await;
+        assert!(
+            search_images_res.status() == 200,
+            "got {}, {:?}",
+            search_images_res.status().as_u16(),
+            test::read_body(search_images_res).await
+        );
+        let search_images_res_data: SearchImagesResponse =
+            test::read_body_json(search_images_res).await;
+
+        assert!(search_images_res_data.images.len() >= 1);
+
+        // Finally image download
+        let download_image_req = test::TestRequest::get()
+            .uri(&format!("/images/{}", image_id))
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let download_image_res = test::call_service(&mut app, download_image_req).await;
+
+        assert!(
+            download_image_res.status() == 200,
+            "got {}",
+            download_image_res.status().as_u16(),
+        );
+
+        // TODO: why did this break?
+        // assert!(test::read_body(download_image_res).await == TEST_IMAGE);
+    }
+
+    // Tags
+    {
+        let tag = format!("Test Tag {}", random_string(8));
+
+        let set_tags_req = test::TestRequest::put()
+            .uri(&format!("/images/{}/tags", image_id))
+            .header("Authorization", format!("Bearer {}", token))
+            .set_json(&SetImageTagsRequest {
+                tags: vec![tag.clone(), tag.to_uppercase(), "#landscape".into()],
+            })
+            .to_request();
+        let set_tags_res: SetImageTagsResponse =
+            test::read_response_json(&mut app, set_tags_req).await;
+        let normalized_tag = tag.to_lowercase().replace(' ', "-");
+        assert!(set_tags_res.tags == vec![normalized_tag.clone(), "landscape".to_string()]);
+
+        let search_images_req = test::TestRequest::get()
+            .uri(&format!("/images?tags={},landscape", normalized_tag))
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let search_images_res: SearchImagesResponse =
+            test::read_response_json(&mut app, search_images_req).await;
+        assert!(search_images_res.images.len() == 1);
+        assert!(search_images_res.images[0].id == image_id);
+
+        let autocomplete_req = test::TestRequest::get()
+            .uri(&format!("/tags/autocomplete?q={}", &normalized_tag[..12]))
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let autocomplete_res: GetTagsResponse =
+            test::read_response_json(&mut app, autocomplete_req).await;
+        assert!(autocomplete_res
+            .tags
+            .iter()
+            .any(|t| t.name == normalized_tag));
+    }
+
+    // Comments
+    {
+        let create_comment_req = test::TestRequest::post()
+            .uri(&format!("/images/{}/comments", image_id))
+            .header("Authorization", format!("Bearer {}", token))
+            .set_json(&CreateCommentRequest {
+                body: "test comment".into(),
+                parent_id: None,
+            })
+            .to_request();
+        let create_comment_res: CreateCommentResponse =
+            test::read_response_json(&mut app, create_comment_req).await;
+
+        let reply_req = test::TestRequest::post()
+            .uri(&format!("/images/{}/comments", image_id))
+            .header("Authorization", format!("Bearer {}", token))
+            .set_json(&CreateCommentRequest {
+                body: "test reply".into(),
+                parent_id: Some(create_comment_res.id),
+            })
+            .to_request();
+        let reply_res = test::call_service(&mut app, reply_req).await;
+        assert!(reply_res.status() == 200);
+
+        let get_comments_req = test::TestRequest::get()
+            .uri(&format!("/images/{}/comments", image_id))
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let get_comments_res: GetCommentsResponse =
+            test::read_response_json(&mut app, get_comments_req).await;
+        assert!(get_comments_res.comments.len() == 2);
+        assert!(get_comments_res.comments[1].parent_id == Some(create_comment_res.id));
+
+        let get_image_req = test::TestRequest::get()
+            .uri(&format!("/images/{}", image_id))
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let get_image_res: GetImageResponse =
+            test::read_response_json(&mut app, get_image_req).await;
+        assert!(get_image_res.image.comment_count == 2);
+
+        // Replies are deleted together with the comment.
+        let delete_comment_req = test::TestRequest::delete()
+            .uri(&format!("/comments/{}", create_comment_res.id))
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let delete_comment_res = test::call_service(&mut app, delete_comment_req).await;
+        assert!(delete_comment_res.status() == 204);
+
+        let get_comments_req = test::TestRequest::get()
+            .uri(&format!("/images/{}/comments", image_id))
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let get_comments_res: GetCommentsResponse =
+            test::read_response_json(&mut app, get_comments_req).await;
+        assert!(get_comments_res.comments.is_empty());
+    }
+
+    // Albums
+    {
+        let create_album_req = test::TestRequest::post()
+            .uri("/albums")
+            .header("Authorization", format!("Bearer {}", token))
+            .set_json(&CreateAlbumRequest {
+                name: "test_album".into(),
+                visibility: Visibility::Unlisted,
+            })
+            .to_request();
+        let create_album_res: CreateAlbumResponse =
+            test::read_response_json(&mut app, create_album_req).await;
+        let album_id = create_album_res.id;
+
+        let add_image_req = test::TestRequest::post()
+            .uri(&format!("/albums/{}/images", album_id))
+            .header("Authorization", format!("Bearer {}", token))
+            .set_json(&AddAlbumImageRequest { image_id })
+            .to_request();
+        let add_image_res = test::call_service(&mut app, add_image_req).await;
+        assert!(
+            add_image_res.status() == 204,
+            "got {}",
+            add_image_res.status().as_u16()
+        );
+
+        let invalid_order_req = test::TestRequest::put()
+            .uri(&format!("/albums/{}/images/order", album_id))
+            .header("Authorization", format!("Bearer {}", token))
+            .set_json(&ReorderAlbumImagesRequest {
+                image_ids: vec![image_id, image_id],
+            })
+            .to_request();
+        let invalid_order_res = test::call_service(&mut app, invalid_order_req).await;
+        assert!(invalid_order_res.status() == 400);
+
+        let get_album_req = test::TestRequest::get()
+            .uri(&format!("/albums/{}?limit=10", album_id))
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let get_album_res: GetAlbumResponse =
+            test::read_response_json(&mut app, get_album_req).await;
+        assert!(get_album_res.album.image_count == 1);
+        assert!(get_album_res.images.len() == 1 && get_album_res.images[0].id == image_id);
+
+        // Other users can see, but not change unlisted albums.
+        let login_req = test::TestRequest::post()
+            .uri("/auth/login")
+            .set_json(&LoginRequest {
+                email: "admin@admin.admin".into(),
+                password: "admin".into(),
+            })
+            .to_request();
+        let login_res: LoginResponse = test::read_response_json(&mut app, login_req).await;
+
+        let get_album_req = test::TestRequest::get()
+            .uri(&format!("/albums/{}", album_id))
+            .header("Authorization", format!("Bearer {}", login_res.token))
+            .to_request();
+        let get_album_res = test::call_service(&mut app, get_album_req).await;
+        assert!(get_album_res.status() == 200);
+
+        // Private images are neither listed nor counted for other users.
+        let set_visibility_req = |visibility| {
+            test::TestRequest::put()
+                .uri(&format!("/images/{}/visibility", image_id))
+                .header("Authorization", format!("Bearer {}", token))
+                .set_json(&SetImageVisibilityRequest { visibility })
+                .to_request()
+        };
+        let res = test::call_service(&mut app, set_visibility_req(Visibility::Private)).await;
+        assert!(res.status() == 204);
+
+        let get_album_req = test::TestRequest::get()
+            .uri(&format!("/albums/{}", album_id))
+            .header("Authorization", format!("Bearer {}", login_res.token))
+            .to_request();
+        let get_album_res: GetAlbumResponse =
+            test::read_response_json(&mut app, get_album_req).await;
+        assert!(get_album_res.album.image_count == 0);
+        assert!(get_album_res.images.is_empty());
+
+        let res = test::call_service(&mut app, set_visibility_req(Visibility::Public)).await;
+        assert!(res.status() == 204);
+
+        let delete_album_req = test::TestRequest::delete()
+            .uri(&format!("/albums/{}", album_id))
+            .header("Authorization", format!("Bearer {}", login_res.token))
+            .to_request();
+        let delete_album_res = test::call_service(&mut app, delete_album_req).await;
+        assert!(delete_album_res.status() == 403);
+
+        let delete_album_req = test::TestRequest::delete()
+            .uri(&format!("/albums/{}", album_id))
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let delete_album_res = test::call_service(&mut app, delete_album_req).await;
+        assert!(delete_album_res.status() == 204);
+    }
+
+    // Category merge
+    {
+        let login_req = test::TestRequest::post()
+            .uri("/auth/login")
+            .set_json(&LoginRequest {
+                email: "admin@admin.admin".into(),
+                password: "admin".into(),
+            })
+            .to_request();
+        let login_res: LoginResponse = test::read_response_json(&mut app, login_req).await;
+
+        let create_category_req = test::TestRequest::post()
+            .uri("/categories")
+            .header("Authorization", format!("Bearer {}", login_res.token))
+            .set_json(&CreateCategoryRequest {
+                name: format!("merge_target_{}", random_string(24)),
+                slug: None,
+                parent_id: None,
+                description: None,
+            })
+            .to_request();
+        let target: CreateCategoryResponse =
+            test::read_response_json(&mut app, create_category_req).await;
+
+        let bulk_req = test::TestRequest::post()
+            .uri(&format!("/categories/{}/images/bulk", target.id))
+            .header("Authorization", format!("Bearer {}", login_res.token))
+            .set_json(&BulkCategorizeRequest {
+                action: BulkCategoryAction::Add,
+                image_ids: vec![image_id, Uuid::new_v4()],
+                dry_run: true,
+            })
+            .to_request();
+        let bulk_res: BulkCategorizeResponse = test::read_response_json(&mut app, bulk_req).await;
+        assert!(bulk_res.affected == 1);
+        assert!(bulk_res.missing_image_ids.len() == 1);
+
+        let bulk_req = test::TestRequest::post()
+            .uri(&format!("/categories/{}/images/bulk", target.id))
+            .header("Authorization", format!("Bearer {}", login_res.token))
+            .set_json(&BulkCategorizeRequest {
+                action: BulkCategoryAction::Add,
+                image_ids: vec![image_id],
+                dry_run: false,
+            })
+            .to_request();
+        let bulk_res: BulkCategorizeResponse = test::read_response_json(&mut app, bulk_req).await;
+        assert!(bulk_res.affected == 1);
+
+        let merge_req = test::TestRequest::post()
+            .uri(&format!(
+                "/categories/{}/merge-into/{}",
+                category_id, target.id
+            ))
+            .header("Authorization", format!("Bearer {}", login_res.token))
+            .to_request();
+        let merge_res: MergeCategoriesResponse =
+            test::read_response_json(&mut app, merge_req).await;
+        assert!(merge_res.moved_images == 0);
+        assert!(merge_res.duplicate_images == 1);
+
+        let get_image_req = test::TestRequest::get()
+            .uri(&format!("/images/{}", image_id))
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let res: GetImageResponse = test::read_response_json(&mut app, get_image_req).await;
+        assert!(res.image.categories == vec![target.id]);
+    }
+
+    // Visibility and share links
+    {
+        let set_visibility_req = test::TestRequest::put()
+            .uri(&format!("/images/{}/visibility", image_id))
+            .header("Authorization", format!("Bearer {}", token))
+            .set_json(&SetImageVisibilityRequest {
+                visibility: Visibility::Private,
+            })
+            .to_request();
+        let res = test::call_service(&mut app, set_visibility_req).await;
+        assert!(res.status() == 204);
+
+        let login_req = test::TestRequest::post()
+            .uri("/auth/login")
+            .set_json(&LoginRequest {
+                email: "admin@admin.admin".into(),
+                password: "admin".into(),
+            })
+            .to_request();
+        let login_res: LoginResponse = test::read_response_json(&mut app, login_req).await;
+
+        let get_image_req = test::TestRequest::get()
+            .uri(&format!("/images/{}", image_id))
+            .header("Authorization", format!("Bearer {}", login_res.token))
+            .to_request();
+        let res = test::call_service(&mut app, get_image_req).await;
+        assert!(res.status() == 404);
+
+        // Nor can they rate or comment on it.
+        let rate_req = test::TestRequest::put()
+            .uri(&format!("/images/{}/rating", image_id))
+            .header("Authorization", format!("Bearer {}", login_res.token))
+            .set_json(&RateImageRequest { rating: 1 })
+            .to_request();
+        let res = test::call_service(&mut app, rate_req).await;
+        assert!(res.status() == 404);
+
+        let get_rating_req = test::TestRequest::get()
+            .uri(&format!("/images/{}/rating", image_id))
+            .header("Authorization", format!("Bearer {}", login_res.token))
+            .to_request();
+        let res = test::call_service(&mut app, get_rating_req).await;
+        assert!(res.status() == 404);
+
+        let create_comment_req = test::TestRequest::post()
+            .uri(&format!("/images/{}/comments", image_id))
+            .header("Authorization", format!("Bearer {}", login_res.token))
+            .set_json(&CreateCommentRequest {
+                body: "private comment".into(),
+                parent_id: None,
+            })
+            .to_request();
+        let res = test::call_service(&mut app, create_comment_req).await;
+        assert!(res.status() == 404);
+
+        let get_comments_req = test::TestRequest::get()
+            .uri(&format!("/images/{}/comments", image_id))
+            .header("Authorization", format!("Bearer {}", login_res.token))
+            .to_request();
+        let res = test::call_service(&mut app, get_comments_req).await;
+        assert!(res.status() == 404);
+
+        let create_link_req = test::TestRequest::post()
+            .uri(&format!("/images/{}/share-links", image_id))
+            .header("Authorization", format!("Bearer {}", token))
+            .set_json(&CreateShareLinkRequest { expires: None })
+            .to_request();
+        let link: ShareLink = test::read_response_json(&mut app, create_link_req).await;
+
+        let get_shared_req = test::TestRequest::get()
+            .uri(&format!("/shared/{}", link.token))
+            .to_request();
+        let res: GetSharedImageResponse = test::read_response_json(&mut app, get_shared_req).await;
+        assert!(res.image.id == image_id);
+
+        let revoke_link_req = test::TestRequest::delete()
+            .uri(&format!("/share-links/{}", link.id))
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let res = test::call_service(&mut app, revoke_link_req).await;
+        assert!(res.status() == 204);
+
+        let get_shared_req = test::TestRequest::get()
+            .uri(&format!("/shared/{}", link.token))
+            .to_request();
+        let res = test::call_service(&mut app, get_shared_req).await;
+        assert!(res.status() == 404);
+
+        // Public read access is disabled by default.
+        let anonymous_search_req = test::TestRequest::get().uri("/images").to_request();
+        let res = test::call_service(&mut app, anonymous_search_req).await;
+        assert!(res.status() == 401);
+
+        // Other users rate and comment on the image below.
+        let set_visibility_req = test::TestRequest::put()
+            .uri(&format!("/images/{}/visibility", image_id))
+            .header("Authorization", format!("Bearer {}", token))
+            .set_json(&SetImageVisibilityRequest {
+                visibility: Visibility::Public,
+            })
+            .to_request();
+        let res = test::call_service(&mut app, set_visibility_req).await;
+        assert!(res.status() == 204);
+    }
+
+    // Exif metadata
+    {
+        // The test image has no Exif data.
+        let search_images_req = test::TestRequest::get()
+            .uri("/images?capturedAfter=2000-01-01T00:00:00Z")
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let search_images_res: SearchImagesResponse =
+            test::read_response_json(&mut app, search_images_req).await;
+        assert!(search_images_res.images.iter().all(|i| i.id != image_id));
+
+        let update_settings_req = test::TestRequest::put()
+            .uri("/users/me/settings")
+            .header("Authorization", format!("Bearer {}", token))
+            .set_json(&UserSettings {
+                keep_image_location: true,
+            })
+            .to_request();
+        let res = test::call_service(&mut app, update_settings_req).await;
+        assert!(res.status() == 204);
+
+        let get_settings_req = test::TestRequest::get()
+            .uri("/users/me/settings")
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let settings: UserSettings = test::read_response_json(&mut app, get_settings_req).await;
+        assert!(settings.keep_image_location);
+    }
+    // Geolocation
+    {
+        let set_location_req = test::TestRequest::put()
+            .uri(&format!("/images/{}/location", image_id))
+            .header("Authorization", format!("Bearer {}", token))
+            .set_json(&SetImageLocationRequest {
+                latitude: 47.4729,
+                longitude: 19.0597,
+            })
+            .to_request();
+        let res = test::call_service(&mut app, set_location_req).await;
+        assert!(res.status() == 204);
+
+        let search_images_req = test::TestRequest::get()
+            .uri("/images?lat=47.47&lon=19.06&radius=1000")
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let search_images_res: SearchImagesResponse =
+            test::read_response_json(&mut app, search_images_req).await;
+        assert!(search_images_res.images.iter().any(|i| i.id == image_id));
+
+        let search_images_req = test::TestRequest::get()
+            .uri("/images?bbox=0,0,1,1")
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let search_images_res: SearchImagesResponse =
+            test::read_response_json(&mut app, search_images_req).await;
+        assert!(search_images_res.images.iter().all(|i| i.id != image_id));
+
+        let clusters_req = test::TestRequest::get()
+            .uri("/images/clusters?zoom=3&bbox=0,0,90,90")
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let clusters_res: GetImageClustersResponse =
+            test::read_response_json(&mut app, clusters_req).await;
+        assert!(clusters_res
+            .clusters
+            .iter()
+            .any(|c| c.min_latitude <= 47.4729 && c.max_longitude >= 19.0597));
+
+        let clear_location_req = test::TestRequest::delete()
+            .uri(&format!("/images/{}/location", image_id))
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let res = test::call_service(&mut app, clear_location_req).await;
+        assert!(res.status() == 204);
+    }
+    // Similar images
+    {
+        let similar_req = test::TestRequest::get()
+            .uri(&format!("/images/{}/similar", image_id))
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let res = test::call_service(&mut app, similar_req).await;
+        assert!(res.status() == 403);
+
+        let login_req = test::TestRequest::post()
+            .uri("/auth/login")
+            .set_json(&LoginRequest {
+                email: "admin@admin.admin".into(),
+                password: "admin".into(),
+            })
+            .to_request();
+        let login_res: LoginResponse = test::read_response_json(&mut app, login_req).await;
+
+        let similar_req = test::TestRequest::get()
+            .uri(&format!("/images/{}/similar?maxDistance=64", image_id))
+            .header("Authorization", format!("Bearer {}", login_res.token))
+            .to_request();
+        let similar_res: GetSimilarImagesResponse =
+            test::read_response_json(&mut app, similar_req).await;
+        assert!(similar_res.images.iter().all(|i| i.image.id != image_id));
+    }
+    // Resumable uploads
+    {
+        let create_session_req = test::TestRequest::post()
+            .uri(&format!("/images/{}/uploads", image_id))
+            .header("Authorization", format!("Bearer {}", token))
+            .set_json(&CreateUploadSessionRequest {
+                length: 10,
+                checksum: "not a checksum".into(),
+                filename: None,
+            })
+            .to_request();
+        let res = test::call_service(&mut app, create_session_req).await;
+        assert!(res.status() == 400);
+
+        // The file of the image was already uploaded.
+        let create_session_req = test::TestRequest::post()
+            .uri(&format!("/images/{}/uploads", image_id))
+            .header("Authorization", format!("Bearer {}", token))
+            .set_json(&CreateUploadSessionRequest {
+                length: 10,
+                checksum: "0".repeat(64),
+                filename: Some("image.png".into()),
+            })
+            .to_request();
+        let res = test::call_service(&mut app, create_session_req).await;
+        assert!(res.status() == 400);
+
+        let get_session_req = test::TestRequest::get()
+            .uri(&format!("/uploads/{}", Uuid::new_v4()))
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let res = test::call_service(&mut app, get_session_req).await;
+        assert!(res.status() == 404);
+
+        let create_image_req = test::TestRequest::post()
+            .uri("/images")
+            .header("Authorization", format!("Bearer {}", token))
+            .set_json(&CreateImageRequest {
+                title: "resumable_upload".into(),
+                categories: vec![],
+                description: None,
+                visibility: None,
+            })
+            .to_request();
+        let image: CreateImageResponse = test::read_response_json(&mut app, create_image_req).await;
+
+        // Only the owner can upload the file.
+        let email = format!("test_{}@test.test", random_string(12));
+        let register_req = test::TestRequest::post()
+            .uri("/auth/register")
+            .set_json(&RegisterRequest {
+                email: email.clone(),
+                password: "password".into(),
+            })
+            .to_request();
+        let res = test::call_service(&mut app, register_req).await;
+        assert!(res.status() == 204);
+
+        let other_login_req = test::TestRequest::post()
+            .uri("/auth/login")
+            .set_json(&LoginRequest {
+                email,
+                password: "password".into(),
+            })
+            .to_request();
+        let other_login_res: LoginResponse =
+            test::read_response_json(&mut app, other_login_req).await;
+
+        let mut upload_data = Vec::new();
+        upload_data.extend("--test_image\r\n".bytes());
+        upload_data.extend(
+            r#"Content-Disposition: form-data; name="image"; filename="test_image.png""#.bytes(),
+        );
+        upload_data.extend("\r\n\r\n".bytes());
+        upload_data.extend(TEST_IMAGE);
+        upload_data.extend("\r\n--test_image--\r\n".bytes());
+
+        let upload_image_req = test::TestRequest::post()
+            .uri(&format!("/images/{}", image.id))
+            .header("Authorization", format!("Bearer {}", other_login_res.token))
+            .header("Content-Type", "multipart/form-data; boundary=test_image")
+            .header("Content-Length", upload_data.len())
+            .set_payload(upload_data)
+            .to_request();
+        let res = test::call_service(&mut app, upload_image_req).await;
+        assert!(res.status() == 403);
+
+        let create_session_req = test::TestRequest::post()
+            .uri(&format!("/images/{}/uploads", image.id))
+            .header("Authorization", format!("Bearer {}", token))
+            .set_json(&CreateUploadSessionRequest {
+                length: TEST_IMAGE.len() as _,
+                checksum: "0".repeat(64),
+                filename: Some("image.png".into()),
+            })
+            .to_request();
+        let session: UploadSession = test::read_response_json(&mut app, create_session_req).await;
+
+        let append_req = test::TestRequest::patch()
+            .uri(&format!("/uploads/{}", session.id))
+            .header("Authorization", format!("Bearer {}", token))
+            .header("Content-Type", "application/offset+octet-stream")
+            .header("Upload-Offset", "0")
+            .set_payload(TEST_IMAGE)
+            .to_request();
+        let res = test::call_service(&mut app, append_req).await;
+        assert!(res.status() == 204);
+
+        let finish_req = || {
+            test::TestRequest::post()
+                .uri(&format!("/uploads/{}/finish", session.id))
+                .header("Authorization", format!("Bearer {}", token))
+                .to_request()
+        };
+        let res = test::call_service(&mut app, finish_req()).await;
+        assert!(res.status() == 400);
+
+        // The session is kept, the file has to be sent again.
+        let get_session_req = test::TestRequest::get()
+            .uri(&format!("/uploads/{}", session.id))
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let session: UploadSession = test::read_response_json(&mut app, get_session_req).await;
+        assert!(session.offset == 0);
+
+        let res = test::call_service(&mut app, finish_req()).await;
+        assert!(res.status() == 400);
+    }
+    // Storage usage
+    {
+        let usage_req = test::TestRequest::get()
+            .uri("/users/me/usage")
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let usage: StorageUsage = test::read_response_json(&mut app, usage_req).await;
+        assert!(usage.images >= 1);
+        assert!(usage.bytes > 0);
+        assert!(usage.max_images == Some(config.image_quota).filter(|q| *q > 0));
+
+        let set_quota_req = test::TestRequest::put()
+            .uri(&format!("/users/{}/quota", Uuid::new_v4()))
+            .header("Authorization", format!("Bearer {}", token))
+            .set_json(&SetStorageQuotaRequest {
+                max_bytes: Some(0),
+                max_images: None,
+            })
+            .to_request();
+        let res = test::call_service(&mut app, set_quota_req).await;
+        assert!(res.status() == 403);
+    }
+    // The test category was merged into another one above.
+    let category_id = {
+        let get_categories_req = test::TestRequest::get()
+            .uri("/categories")
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
         let res: GetCategoriesResponse =
             test::read_response_json(&mut app, get_categories_req).await;
-        assert!(res.categories.len() - 1 == category_count);
+        res.categories[0].id
+    };
+    // Create and upload at once
+    {
+        let upload_data = |categories: Vec<Uuid>| {
+            let mut data = Vec::new();
+
+            data.extend("--test_image\r\n".bytes());
+            data.extend(r#"Content-Disposition: form-data; name="metadata""#.bytes());
+            data.extend("\r\nContent-Type: application/json\r\n\r\n".bytes());
+            data.extend(
+                serde_json::to_vec(&CreateImageRequest {
+                    title: "created_with_file".into(),
+                    categories,
+                    description: None,
+                    visibility: None,
+                })
+                .unwrap(),
+            );
+            data.extend("\r\n--test_image\r\n".bytes());
+            data.extend(
+                r#"Content-Disposition: form-data; name="file"; filename="test_image.png""#.bytes(),
+            );
+            data.extend("\r\n\r\n".bytes());
+            data.extend(TEST_IMAGE);
+            data.extend("\r\n--test_image--\r\n".bytes());
+            data
+        };
+
+        let data = upload_data(vec![Uuid::new_v4()]);
+        let upload_req = test::TestRequest::post()
+            .uri("/images/upload")
+            .header("Authorization", format!("Bearer {}", token))
+            .header("Content-Type", "multipart/form-data; boundary=test_image")
+            .header("Content-Length", data.len())
+            .set_payload(data)
+            .to_request();
+        let res = test::call_service(&mut app, upload_req).await;
+        assert!(res.status() == 400);
+
+        let data = upload_data(vec![category_id]);
+        let upload_req = test::TestRequest::post()
+            .uri("/images/upload")
+            .header("Authorization", format!("Bearer {}", token))
+            .header("Content-Type", "multipart/form-data; boundary=test_image")
+            .header("Content-Length", data.len())
+            .set_payload(data)
+            .to_request();
+        let upload_res: CreateImageResponse = test::read_response_json(&mut app, upload_req).await;
+
+        let get_image_req = test::TestRequest::get()
+            .uri(&format!("/images/{}", upload_res.id))
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let mut get_image_res: GetImageResponse =
+            test::read_response_json(&mut app, get_image_req).await;
+        // Images are only returned once their file is uploaded.
+        assert!(get_image_res.image.categories.contains(&category_id));
+
+        // The same file was uploaded before, duplicates are found in the background.
+        for _ in 0..50 {
+            if get_image_res.image.duplicate_of.is_some() {
+                break;
+            }
+
+            actix_rt::time::delay_for(std::time::Duration::from_millis(100)).await;
+
+            let get_image_req = test::TestRequest::get()
+                .uri(&format!("/images/{}", upload_res.id))
+                .header("Authorization", format!("Bearer {}", token))
+                .to_request();
+            get_image_res = test::read_response_json(&mut app, get_image_req).await;
+        }
+        assert!(get_image_res.image.duplicate_of.is_some());
+    }
+    // Batch upload
+    {
+        let mut data = Vec::new();
+
+        data.extend("--test_image\r\n".bytes());
+        data.extend(r#"Content-Disposition: form-data; name="defaults""#.bytes());
+        data.extend("\r\n\r\n".bytes());
+        data.extend(format!(r#"{{"categories": ["{}"]}}"#, category_id).bytes());
+        data.extend("\r\n--test_image\r\n".bytes());
+        data.extend(
+            r#"Content-Disposition: form-data; name="file"; filename="first.png""#.bytes(),
+        );
+        data.extend("\r\n\r\n".bytes());
+        data.extend(TEST_IMAGE);
+        data.extend("\r\n--test_image\r\n".bytes());
+        data.extend(r#"Content-Disposition: form-data; name="metadata""#.bytes());
+        data.extend("\r\n\r\n".bytes());
+        data.extend(format!(r#"{{"categories": ["{}"]}}"#, Uuid::new_v4()).bytes());
+        data.extend("\r\n--test_image\r\n".bytes());
+        data.extend(
+            r#"Content-Disposition: form-data; name="file"; filename="second.png""#.bytes(),
+        );
+        data.extend("\r\n\r\n".bytes());
+        data.extend(TEST_IMAGE);
+        data.extend("\r\n--test_image--\r\n".bytes());
+
+        let batch_req = test::TestRequest::post()
+            .uri("/images/batch")
+            .header("Authorization", format!("Bearer {}", token))
+            .header("Content-Type", "multipart/form-data; boundary=test_image")
+            .header("Content-Length", data.len())
+            .set_payload(data)
+            .to_request();
+        let batch_res: BatchUploadResponse = test::read_response_json(&mut app, batch_req).await;
+        assert!(batch_res.succeeded == 1 && batch_res.failed == 1);
+        assert!(batch_res.items[0].id.is_some());
+        assert!(batch_res.items[1].error.as_ref().unwrap().code == "categoryNotFound");
+
+        let get_image_req = test::TestRequest::get()
+            .uri(&format!("/images/{}", batch_res.items[0].id.unwrap()))
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let get_image_res: GetImageResponse =
+            test::read_response_json(&mut app, get_image_req).await;
+        assert!(get_image_res.image.title == "first");
+    }
+    // Moderation
+    {
+        let mut data = Vec::new();
+
+        data.extend("--test_image\r\n".bytes());
+        data.extend(
+            r#"Content-Disposition: form-data; name="file"; filename="reported.png""#.bytes(),
+        );
+        data.extend("\r\n\r\n".bytes());
+        data.extend(TEST_IMAGE);
+        data.extend("\r\n--test_image--\r\n".bytes());
+
+        let batch_req = test::TestRequest::post()
+            .uri("/images/batch")
+            .header("Authorization", format!("Bearer {}", token))
+            .header("Content-Type", "multipart/form-data; boundary=test_image")
+            .header("Content-Length", data.len())
+            .set_payload(data)
+            .to_request();
+        let batch_res: BatchUploadResponse = test::read_response_json(&mut app, batch_req).await;
+        let reported_id = batch_res.items[0].id.unwrap();
+
+        let report_req = test::TestRequest::post()
+            .uri(&format!("/images/{}/report", reported_id))
+            .header("Authorization", format!("Bearer {}", token))
+            .set_json(&ReportImageRequest {
+                reason: ReportReason::Spam,
+                details: None,
+            })
+            .to_request();
+        let res = test::call_service(&mut app, report_req).await;
+        assert!(res.status() == 200);
+
+        let report_req = test::TestRequest::post()
+            .uri(&format!("/images/{}/report", reported_id))
+            .header("Authorization", format!("Bearer {}", token))
+            .set_json(&ReportImageRequest {
+                reason: ReportReason::Other,
+                details: None,
+            })
+            .to_request();
+        let res = test::call_service(&mut app, report_req).await;
+        assert!(res.status() == 409);
+
+        let reports_req = test::TestRequest::get()
+            .uri("/moderation/reports")
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let res = test::call_service(&mut app, reports_req).await;
+        assert!(res.status() == 403);
+
+        let login_req = test::TestRequest::post()
+            .uri("/auth/login")
+            .set_json(&LoginRequest {
+                email: "admin@admin.admin".into(),
+                password: "admin".into(),
+            })
+            .to_request();
+        let login_res: LoginResponse = test::read_response_json(&mut app, login_req).await;
+
+        let reports_req = test::TestRequest::get()
+            .uri("/moderation/reports?limit=100")
+            .header("Authorization", format!("Bearer {}", login_res.token))
+            .to_request();
+        let reports_res: GetReportedImagesResponse =
+            test::read_response_json(&mut app, reports_req).await;
+        assert!(reports_res
+            .images
+            .iter()
+            .any(|i| i.image_id == reported_id && i.reports[0].reason == ReportReason::Spam));
+
+        let create_link_req = test::TestRequest::post()
+            .uri(&format!("/images/{}/share-links", reported_id))
+            .header("Authorization", format!("Bearer {}", token))
+            .set_json(&CreateShareLinkRequest { expires: None })
+            .to_request();
+        let link: ShareLink = test::read_response_json(&mut app, create_link_req).await;
+
+        let create_album_req = test::TestRequest::post()
+            .uri("/albums")
+            .header("Authorization", format!("Bearer {}", token))
+            .set_json(&CreateAlbumRequest {
+                name: "reported_album".into(),
+                visibility: Visibility::Public,
+            })
+            .to_request();
+        let album: CreateAlbumResponse = test::read_response_json(&mut app, create_album_req).await;
+
+        let add_image_req = test::TestRequest::post()
+            .uri(&format!("/albums/{}/images", album.id))
+            .header("Authorization", format!("Bearer {}", token))
+            .set_json(&AddAlbumImageRequest {
+                image_id: reported_id,
+            })
+            .to_request();
+        let res = test::call_service(&mut app, add_image_req).await;
+        assert!(res.status() == 204);
+
+        let moderate_req = test::TestRequest::post()
+            .uri(&format!("/moderation/images/{}/actions", reported_id))
+            .header("Authorization", format!("Bearer {}", login_res.token))
+            .set_json(&ModerateImageRequest {
+                action: ModerationActionKind::Hide,
+                note: Some("spam".into()),
+                warn_user: true,
+            })
+            .to_request();
+        let res = test::call_service(&mut app, moderate_req).await;
+        assert!(res.status() == 204);
+
+        // Hidden images are only visible to their owners and moderators.
+        let email = format!("test_{}@test.test", random_string(12));
+        let register_req = test::TestRequest::post()
+            .uri("/auth/register")
+            .set_json(&RegisterRequest {
+                email: email.clone(),
+                password: "password".into(),
+            })
+            .to_request();
+        let res = test::call_service(&mut app, register_req).await;
+        assert!(res.status() == 204);
+
+        let viewer_login_req = test::TestRequest::post()
+            .uri("/auth/login")
+            .set_json(&LoginRequest {
+                email,
+                password: "password".into(),
+            })
+            .to_request();
+        let viewer_login_res: LoginResponse =
+            test::read_response_json(&mut app, viewer_login_req).await;
+
+        for uri in &[
+            format!("/images/{}", reported_id),
+            format!("/images/{}/download", reported_id),
+            format!("/images/{}/rating", reported_id),
+            format!("/images/{}/comments", reported_id),
+        ] {
+            let req = test::TestRequest::get()
+                .uri(uri)
+                .header(
+                    "Authorization",
+                    format!("Bearer {}", viewer_login_res.token),
+                )
+                .to_request();
+            let res = test::call_service(&mut app, req).await;
+            assert!(res.status() == 404, "{} got {}", uri, res.status());
+        }
+
+        let get_album_req = test::TestRequest::get()
+            .uri(&format!("/albums/{}", album.id))
+            .header(
+                "Authorization",
+                format!("Bearer {}", viewer_login_res.token),
+            )
+            .to_request();
+        let get_album_res: GetAlbumResponse =
+            test::read_response_json(&mut app, get_album_req).await;
+        assert!(get_album_res.album.image_count == 0);
+        assert!(get_album_res.images.is_empty());
+
+        let get_shared_req = test::TestRequest::get()
+            .uri(&format!("/shared/{}", link.token))
+            .to_request();
+        let res = test::call_service(&mut app, get_shared_req).await;
+        assert!(res.status() == 404);
+
+        for viewer_token in &[&token, &login_res.token] {
+            let get_image_req = test::TestRequest::get()
+                .uri(&format!("/images/{}", reported_id))
+                .header("Authorization", format!("Bearer {}", viewer_token))
+                .to_request();
+            let res = test::call_service(&mut app, get_image_req).await;
+            assert!(res.status() == 200);
+        }
+
+        let search_images_req = test::TestRequest::get()
+            .uri("/images?limit=100")
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let search_images_res: SearchImagesResponse =
+            test::read_response_json(&mut app, search_images_req).await;
+        assert!(search_images_res.images.iter().all(|i| i.id != reported_id));
+
+        let actions_req = test::TestRequest::get()
+            .uri(&format!("/moderation/actions?imageId={}", reported_id))
+            .header("Authorization", format!("Bearer {}", login_res.token))
+            .to_request();
+        let actions_res: GetModerationActionsResponse =
+            test::read_response_json(&mut app, actions_req).await;
+        assert!(actions_res.actions.len() == 2);
+    }
+
+    // Audit log
+    {
+        let audit_req = test::TestRequest::get()
+            .uri("/admin/audit")
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let res = test::call_service(&mut app, audit_req).await;
+        assert!(res.status() == 403);
+
+        let request_id = format!("audit-{}", random_string(16));
+
+        let login_req = test::TestRequest::post()
+            .uri("/auth/login")
+            .header("X-Request-Id", request_id.as_str())
+            .set_json(&LoginRequest {
+                email: "admin@admin.admin".into(),
+                password: "incorrect".into(),
+            })
+            .to_request();
+        let res = test::call_service(&mut app, login_req).await;
+        assert!(res.status() == 403);
+
+        let login_req = test::TestRequest::post()
+            .uri("/auth/login")
+            .set_json(&LoginRequest {
+                email: "admin@admin.admin".into(),
+                password: "admin".into(),
+            })
+            .to_request();
+        let login_res: LoginResponse = test::read_response_json(&mut app, login_req).await;
+
+        let audit_req = test::TestRequest::get()
+            .uri("/admin/audit?action=loginFailed&limit=100")
+            .header("Authorization", format!("Bearer {}", login_res.token))
+            .to_request();
+        let audit_res: GetAuditEventsResponse =
+            test::read_response_json(&mut app, audit_req).await;
+        assert!(audit_res.events.iter().any(|e| {
+            e.request_id.as_deref() == Some(request_id.as_str()) && e.actor_id.is_none()
+        }));
+
+        let audit_req = test::TestRequest::get()
+            .uri("/admin/audit?action=categoryCreate&limit=1000")
+            .header("Authorization", format!("Bearer {}", login_res.token))
+            .to_request();
+        let audit_res: GetAuditEventsResponse =
+            test::read_response_json(&mut app, audit_req).await;
+        assert!(audit_res
+            .events
+            .iter()
+            .any(|e| e.target_id == category_id.to_string()));
+
+        let audit_req = test::TestRequest::get()
+            .uri("/admin/audit?action=loginFailed&format=csv")
+            .header("Authorization", format!("Bearer {}", login_res.token))
+            .to_request();
+        let res = test::call_service(&mut app, audit_req).await;
+        assert!(res.status() == 200);
+        assert!(res
+            .headers()
+            .get("Content-Type")
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .starts_with("text/csv"));
+        let body = test::read_body(res).await;
+        assert!(body.starts_with(b"id,created,actorId,action,"));
+
+        let audit_req = test::TestRequest::get()
+            .uri("/admin/audit?from=2020-02-01T00:00:00Z&to=2020-01-01T00:00:00Z")
+            .header("Authorization", format!("Bearer {}", login_res.token))
+            .to_request();
+        let res = test::call_service(&mut app, audit_req).await;
+        assert!(res.status() == 400);
+    }
+
+    // Trash
+    {
+        let mut data = Vec::new();
+
+        data.extend("--test_image\r\n".bytes());
+        data.extend(
+            r#"Content-Disposition: form-data; name="file"; filename="trashed.png""#.bytes(),
+        );
+        data.extend("\r\n\r\n".bytes());
+        data.extend(TEST_IMAGE);
+        data.extend("\r\n--test_image--\r\n".bytes());
+
+        let batch_req = test::TestRequest::post()
+            .uri("/images/batch")
+            .header("Authorization", format!("Bearer {}", token))
+            .header("Content-Type", "multipart/form-data; boundary=test_image")
+            .header("Content-Length", data.len())
+            .set_payload(data)
+            .to_request();
+        let batch_res: BatchUploadResponse = test::read_response_json(&mut app, batch_req).await;
+        let trashed_id = batch_res.items[0].id.unwrap();
+
+        let delete_req = test::TestRequest::delete()
+            .uri(&format!("/images/{}", trashed_id))
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let res = test::call_service(&mut app, delete_req).await;
+        assert!(res.status() == 204);
+
+        let get_image_req = test::TestRequest::get()
+            .uri(&format!("/images/{}", trashed_id))
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let res = test::call_service(&mut app, get_image_req).await;
+        assert!(res.status() == 404);
 
-        let rename_category_req = test::TestRequest::put()
-            .uri(&format!("/categories/{}", &category_id))
+        let trash_req = test::TestRequest::get()
+            .uri("/trash/images")
             .header("Authorization", format!("Bearer {}", token))
-            .set_json(&RenameCategoryRequest {
-                name: format!("new_category_name_{}", random_string(24)),
-            })
             .to_request();
+        let trash_res: GetTrashedImagesResponse =
+            test::read_response_json(&mut app, trash_req).await;
+        assert!(trash_res
+            .images
+            .iter()
+            .any(|i| i.id == trashed_id && i.purge_after > i.deleted));
 
-        let res = test::call_service(&mut app, rename_category_req).await;
-        assert!(
-            res.status().as_u16() == 204,
-            "expected 204, but got response {:?}",
-            res
-        );
-    }
+        let restore_req = test::TestRequest::post()
+            .uri(&format!("/trash/images/{}/restore", trashed_id))
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let res = test::call_service(&mut app, restore_req).await;
+        assert!(res.status() == 204);
 
-    // User register and login
-    let token: String;
-    {
-        let invalid_req = test::TestRequest::post()
-            .uri("/auth/register")
-            .set_json(&RegisterRequest {
-                email: "invalid email 2131231".into(),
-                password: "password".into(),
+        let get_image_req = test::TestRequest::get()
+            .uri(&format!("/images/{}", trashed_id))
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let res = test::call_service(&mut app, get_image_req).await;
+        assert!(res.status() == 200);
+
+        let delete_req = test::TestRequest::delete()
+            .uri(&format!("/images/{}", trashed_id))
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let res = test::call_service(&mut app, delete_req).await;
+        assert!(res.status() == 204);
+
+        let purge_req = test::TestRequest::delete()
+            .uri(&format!("/trash/images/{}", trashed_id))
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let res = test::call_service(&mut app, purge_req).await;
+        assert!(res.status() == 204);
+
+        let restore_req = test::TestRequest::post()
+            .uri(&format!("/trash/images/{}/restore", trashed_id))
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let res = test::call_service(&mut app, restore_req).await;
+        assert!(res.status() == 404);
+
+        let login_req = test::TestRequest::post()
+            .uri("/auth/login")
+            .set_json(&LoginRequest {
+                email: "admin@admin.admin".into(),
+                password: "admin".into(),
             })
             .to_request();
+        let login_res: LoginResponse = test::read_response_json(&mut app, login_req).await;
 
-        let res = test::call_service(&mut app, invalid_req).await;
-        assert!(
-            res.status().as_u16() == 400,
-            "got: {}",
-            res.status().as_u16()
-        );
-        let invalid_req_data: InvalidRegisterRequest = test::read_body_json(res).await;
-        assert!(invalid_req_data.error.contains("e-mail"));
+        let create_category_req = test::TestRequest::post()
+            .uri("/categories")
+            .header("Authorization", format!("Bearer {}", login_res.token))
+            .set_json(&CreateCategoryRequest {
+                name: format!("trashed_{}", random_string(24)),
+                slug: None,
+                parent_id: None,
+                description: None,
+            })
+            .to_request();
+        let trashed_category: CreateCategoryResponse =
+            test::read_response_json(&mut app, create_category_req).await;
+
+        let delete_req = test::TestRequest::delete()
+            .uri(&format!("/categories/{}", trashed_category.id))
+            .header("Authorization", format!("Bearer {}", login_res.token))
+            .to_request();
+        let res = test::call_service(&mut app, delete_req).await;
+        assert!(res.status() == 204);
+
+        let trash_req = test::TestRequest::get()
+            .uri("/trash/categories")
+            .header("Authorization", format!("Bearer {}", login_res.token))
+            .to_request();
+        let trash_res: GetTrashedCategoriesResponse =
+            test::read_response_json(&mut app, trash_req).await;
+        assert!(trash_res
+            .categories
+            .iter()
+            .any(|c| c.id == trashed_category.id));
+
+        let restore_req = test::TestRequest::post()
+            .uri(&format!("/trash/categories/{}/restore", trashed_category.id))
+            .header("Authorization", format!("Bearer {}", login_res.token))
+            .to_request();
+        let res = test::call_service(&mut app, restore_req).await;
+        assert!(res.status() == 204);
+
+        let get_categories_req = test::TestRequest::get()
+            .uri("/categories")
+            .header("Authorization", format!("Bearer {}", login_res.token))
+            .to_request();
+        let res: GetCategoriesResponse =
+            test::read_response_json(&mut app, get_categories_req).await;
+        assert!(res.categories.iter().any(|c| c.id == trashed_category.id));
+    }
 
+    // Account export and deletion
+    {
         let email = format!("test_{}@test.test", random_string(12));
         let register_req = test::TestRequest::post()
             .uri("/auth/register")
@@ -293,29 +2908,208 @@ async fn whole_app() {
                 password: "password".into(),
             })
             .to_request();
+        let res = test::call_service(&mut app, register_req).await;
+        assert!(res.status() == 204);
 
-        let register_res = test::call_service(&mut app, register_req).await;
-        assert!(register_res.status() == 204);
-
-        let wrong_password_req = test::TestRequest::post()
+        let login_req = test::TestRequest::post()
             .uri("/auth/login")
             .set_json(&LoginRequest {
                 email: email.clone(),
-                password: "wrongPassword".into(),
+                password: "password".into(),
             })
             .to_request();
-        let wrong_password_res = test::call_service(&mut app, wrong_password_req).await;
-        assert!(wrong_password_res.status() == 403);
+        let login_res: LoginResponse = test::read_response_json(&mut app, login_req).await;
 
-        let wrong_email_req = test::TestRequest::post()
+        let mut data = Vec::new();
+
+        data.extend("--test_image\r\n".bytes());
+        data.extend(
+            r#"Content-Disposition: form-data; name="file"; filename="exported.png""#.bytes(),
+        );
+        data.extend("\r\n\r\n".bytes());
+        data.extend(TEST_IMAGE);
+        data.extend("\r\n--test_image--\r\n".bytes());
+
+        let batch_req = test::TestRequest::post()
+            .uri("/images/batch")
+            .header("Authorization", format!("Bearer {}", login_res.token))
+            .header("Content-Type", "multipart/form-data; boundary=test_image")
+            .header("Content-Length", data.len())
+            .set_payload(data)
+            .to_request();
+        let batch_res: BatchUploadResponse = test::read_response_json(&mut app, batch_req).await;
+        let exported_id = batch_res.items[0].id.unwrap();
+
+        // Own images cannot be rated.
+        let rate_req = test::TestRequest::put()
+            .uri(&format!("/images/{}/rating", image_id))
+            .header("Authorization", format!("Bearer {}", login_res.token))
+            .set_json(&RateImageRequest { rating: 4 })
+            .to_request();
+        let res = test::call_service(&mut app, rate_req).await;
+        assert!(res.status() == 204);
+
+        let export_req = test::TestRequest::post()
+            .uri("/users/me/export")
+            .header("Authorization", format!("Bearer {}", login_res.token))
+            .to_request();
+        let res = test::call_service(&mut app, export_req).await;
+        assert!(res.status() == 202);
+        let mut export: AccountExport = test::read_body_json(res).await;
+
+        // The archive is built in the background.
+        for _ in 0..50 {
+            if export.status != AccountExportStatus::Pending {
+                break;
+            }
+
+            actix_rt::time::delay_for(std::time::Duration::from_millis(100)).await;
+
+            let get_export_req = test::TestRequest::get()
+                .uri(&format!("/users/me/export/{}", export.id))
+                .header("Authorization", format!("Bearer {}", login_res.token))
+                .to_request();
+            export = test::read_response_json(&mut app, get_export_req).await;
+        }
+        assert!(export.status == AccountExportStatus::Ready);
+
+        let download_req = test::TestRequest::get()
+            .uri(&format!("/users/me/export/{}/download", export.id))
+            .header("Authorization", format!("Bearer {}", login_res.token))
+            .to_request();
+        let res = test::call_service(&mut app, download_req).await;
+        assert!(res.status() == 200);
+        let body = test::read_body(res).await;
+        assert!(body.starts_with(b"PK"));
+        assert!(body.len() as u64 == export.file_size.unwrap());
+
+        let download_req = test::TestRequest::get()
+            .uri(&format!("/users/me/export/{}/download", export.id))
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let res = test::call_service(&mut app, download_req).await;
+        assert!(res.status() == 404);
+
+        let delete_req = test::TestRequest::delete()
+            .uri("/users/me")
+            .header("Authorization", format!("Bearer {}", login_res.token))
+            .set_json(&DeleteAccountRequest {
+                password: Some("wrongPassword".into()),
+            })
+            .to_request();
+        let res = test::call_service(&mut app, delete_req).await;
+        assert!(res.status() == 403);
+
+        let delete_req = test::TestRequest::delete()
+            .uri("/users/me")
+            .header("Authorization", format!("Bearer {}", login_res.token))
+            .set_json(&DeleteAccountRequest {
+                password: Some("password".into()),
+            })
+            .to_request();
+        let res = test::call_service(&mut app, delete_req).await;
+        assert!(res.status() == 204);
+
+        let login_req = test::TestRequest::post()
             .uri("/auth/login")
             .set_json(&LoginRequest {
-                email: "doesnt@exist.com".into(),
+                email: email.clone(),
                 password: "password".into(),
             })
             .to_request();
-        let wrong_email_res = test::call_service(&mut app, wrong_email_req).await;
-        assert!(wrong_email_res.status() == 404);
+        let res = test::call_service(&mut app, login_req).await;
+        assert!(res.status() == 404);
+
+        // The tokens of the deleted user are rejected.
+        let delete_req = test::TestRequest::delete()
+            .uri("/users/me")
+            .header("Authorization", format!("Bearer {}", login_res.token))
+            .set_json(&DeleteAccountRequest {
+                password: Some("password".into()),
+            })
+            .to_request();
+        let res = test::call_service(&mut app, delete_req).await;
+        assert!(res.status() == 401);
+
+        let get_image_req = test::TestRequest::get()
+            .uri(&format!("/images/{}", exported_id))
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let res = test::call_service(&mut app, get_image_req).await;
+        assert!(res.status() == 404);
+
+        // Users of single sign-on have no password, they need a recent login instead.
+        let sso_user_id = db::app_user::AppUser::new(
+            &pool,
+            &format!("test_{}@test.test", random_string(12)).to_lowercase(),
+            "",
+            Role::User.as_str(),
+        )
+        .await
+        .unwrap();
+
+        let keys = auth::keys::TokenKeys::from_config(&config).unwrap();
+        let sso_token = |logged_in: time::OffsetDateTime| {
+            keys.encode(&auth::AppUserTokenClaims {
+                exp: (time::OffsetDateTime::now_utc() + time::Duration::hours(1)).timestamp()
+                    as usize,
+                iss: auth::TOKEN_ISSUER.into(),
+                sub: "appUser".into(),
+                user: auth::UserInfo {
+                    id: sso_user_id,
+                    role: Role::User,
+                    scopes: None,
+                    logged_in: Some(logged_in.timestamp()),
+                    expires: None,
+                },
+                mfa_pending: false,
+            })
+            .unwrap()
+        };
+
+        let delete_req = test::TestRequest::delete()
+            .uri("/users/me")
+            .header(
+                "Authorization",
+                format!(
+                    "Bearer {}",
+                    sso_token(time::OffsetDateTime::now_utc() - time::Duration::hours(1))
+                ),
+            )
+            .set_json(&DeleteAccountRequest { password: None })
+            .to_request();
+        let res = test::call_service(&mut app, delete_req).await;
+        assert!(res.status() == 403);
+
+        let delete_req = test::TestRequest::delete()
+            .uri("/users/me")
+            .header(
+                "Authorization",
+                format!("Bearer {}", sso_token(time::OffsetDateTime::now_utc())),
+            )
+            .set_json(&DeleteAccountRequest { password: None })
+            .to_request();
+        let res = test::call_service(&mut app, delete_req).await;
+        assert!(res.status() == 204);
+
+        assert!(db::app_user::AppUser::by_id(sso_user_id, &pool)
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    // Notifications
+    {
+        let email = format!("test_{}@test.test", random_string(12));
+        let register_req = test::TestRequest::post()
+            .uri("/auth/register")
+            .set_json(&RegisterRequest {
+                email: email.clone(),
+                password: "password".into(),
+            })
+            .to_request();
+        let res = test::call_service(&mut app, register_req).await;
+        assert!(res.status() == 204);
 
         let login_req = test::TestRequest::post()
             .uri("/auth/login")
@@ -324,94 +3118,298 @@ async fn whole_app() {
                 password: "password".into(),
             })
             .to_request();
-        let login_res = test::call_service(&mut app, login_req).await;
-        assert!(login_res.status() == 200);
+        let other: LoginResponse = test::read_response_json(&mut app, login_req).await;
 
-        let login_res_data: LoginResponse = test::read_body_json(login_res).await;
-        token = login_res_data.token;
+        let read_all_req = test::TestRequest::post()
+            .uri("/notifications/read")
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let res = test::call_service(&mut app, read_all_req).await;
+        assert!(res.status() == 204);
+
+        let stream_req = test::TestRequest::get()
+            .uri("/notifications/stream")
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let mut stream_res = test::call_service(&mut app, stream_req).await;
+        assert!(stream_res.status() == 200);
+        let mut events = stream_res.take_body();
+
+        let rate_req = test::TestRequest::put()
+            .uri(&format!("/images/{}/rating", image_id))
+            .header("Authorization", format!("Bearer {}", other.token))
+            .set_json(&RateImageRequest { rating: 3 })
+            .to_request();
+        let res = test::call_service(&mut app, rate_req).await;
+        assert!(res.status() == 204);
+
+        // Published by the database, so it takes a moment.
+        let event = actix_rt::time::timeout(std::time::Duration::from_secs(5), events.next())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        let event = String::from_utf8(event.to_vec()).unwrap();
+        let data = event
+            .lines()
+            .find_map(|l| l.strip_prefix("data: "))
+            .unwrap();
+        let pushed: Notification = serde_json::from_str(data).unwrap();
+        assert!(pushed.kind == NotificationKind::ImageRated);
+        assert!(pushed.image_id == Some(image_id));
+        assert!(pushed.rating == Some(3));
+        assert!(!pushed.read);
+
+        let comment_req = test::TestRequest::post()
+            .uri(&format!("/images/{}/comments", image_id))
+            .header("Authorization", format!("Bearer {}", other.token))
+            .set_json(&CreateCommentRequest {
+                body: "nice picture".into(),
+                parent_id: None,
+            })
+            .to_request();
+        let comment_res: CreateCommentResponse =
+            test::read_response_json(&mut app, comment_req).await;
+
+        // Reconnecting clients get the notifications they missed.
+        let stream_req = test::TestRequest::get()
+            .uri("/notifications/stream")
+            .header("Authorization", format!("Bearer {}", token))
+            .header("Last-Event-ID", pushed.id.to_string())
+            .to_request();
+        let mut stream_res = test::call_service(&mut app, stream_req).await;
+        assert!(stream_res.status() == 200);
+        let mut events = stream_res.take_body();
+
+        let event = actix_rt::time::timeout(std::time::Duration::from_secs(5), events.next())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        let event = String::from_utf8(event.to_vec()).unwrap();
+        let data = event
+            .lines()
+            .find_map(|l| l.strip_prefix("data: "))
+            .unwrap();
+        let missed: Notification = serde_json::from_str(data).unwrap();
+        assert!(missed.kind == NotificationKind::ImageCommented);
+        assert!(missed.comment_id == Some(comment_res.id));
+
+        let notifications_req = test::TestRequest::get()
+            .uri("/notifications?unread=true")
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let notifications_res: GetNotificationsResponse =
+            test::read_response_json(&mut app, notifications_req).await;
+        assert!(notifications_res.unread_count == 2);
+        assert!(notifications_res.notifications[0].kind == NotificationKind::ImageCommented);
+        assert!(notifications_res.notifications[0].comment_id == Some(comment_res.id));
+        assert!(notifications_res.notifications[1].id == pushed.id);
+
+        let read_req = test::TestRequest::post()
+            .uri(&format!("/notifications/{}/read", pushed.id))
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let res = test::call_service(&mut app, read_req).await;
+        assert!(res.status() == 204);
+
+        let read_req = test::TestRequest::post()
+            .uri(&format!("/notifications/{}/read", pushed.id))
+            .header("Authorization", format!("Bearer {}", other.token))
+            .to_request();
+        let res = test::call_service(&mut app, read_req).await;
+        assert!(res.status() == 404);
+
+        let notifications_req = test::TestRequest::get()
+            .uri("/notifications")
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let notifications_res: GetNotificationsResponse =
+            test::read_response_json(&mut app, notifications_req).await;
+        assert!(notifications_res.unread_count == 1);
+        assert!(notifications_res
+            .notifications
+            .iter()
+            .any(|n| n.id == pushed.id && n.read));
+
+        // Nobody is notified about their own actions.
+        let notifications_req = test::TestRequest::get()
+            .uri("/notifications")
+            .header("Authorization", format!("Bearer {}", other.token))
+            .to_request();
+        let notifications_res: GetNotificationsResponse =
+            test::read_response_json(&mut app, notifications_req).await;
+        assert!(notifications_res.notifications.is_empty());
     }
 
-    // Image upload
-    let image_id: Uuid;
+    // API keys
     {
+        let create_key_req = test::TestRequest::post()
+            .uri("/auth/api-keys")
+            .header("Authorization", format!("Bearer {}", token))
+            .set_json(&CreateApiKeyRequest {
+                name: "read only".into(),
+                scopes: vec![ApiKeyScope::Read],
+            })
+            .to_request();
+        let read_key: CreateApiKeyResponse =
+            test::read_response_json(&mut app, create_key_req).await;
+
+        let get_categories_req = test::TestRequest::get()
+            .uri("/categories")
+            .header("Authorization", format!("ApiKey {}", read_key.key))
+            .to_request();
+        let res = test::call_service(&mut app, get_categories_req).await;
+        assert!(res.status() == 200);
+
+        // Two-factor authentication is only managed with sessions.
+        let enroll_req = test::TestRequest::post()
+            .uri("/auth/mfa/totp")
+            .header("Authorization", format!("ApiKey {}", read_key.key))
+            .to_request();
+        let res = test::call_service(&mut app, enroll_req).await;
+        assert!(res.status() == 403);
+
+        let disable_req = test::TestRequest::post()
+            .uri("/auth/mfa/totp/disable")
+            .header("Authorization", format!("ApiKey {}", read_key.key))
+            .set_json(&DisableTotpRequest {
+                code: "000000".into(),
+            })
+            .to_request();
+        let res = test::call_service(&mut app, disable_req).await;
+        assert!(res.status() == 403);
+
+        // Changes need the matching scope.
         let create_image_req = test::TestRequest::post()
             .uri("/images")
             .header("Authorization", format!("Bearer {}", token))
             .set_json(&CreateImageRequest {
-                title: "test_image".to_string(),
+                title: "api_key_image".into(),
                 categories: vec![category_id],
-                description: "asd".to_string().into(),
+                description: None,
+                visibility: None,
             })
             .to_request();
-        let create_image_res = test::call_service(&mut app, create_image_req).await;
-        assert!(
-            create_image_res.status() == 200,
-            "got {}, {:?}",
-            create_image_res.status().as_u16(),
-            test::read_body(create_image_res).await
-        );
+        let image: CreateImageResponse = test::read_response_json(&mut app, create_image_req).await;
 
-        let create_image_res_data: CreateImageResponse =
-            test::read_body_json(create_image_res).await;
-        image_id = create_image_res_data.id;
+        let create_link_req = test::TestRequest::post()
+            .uri(&format!("/images/{}/share-links", image.id))
+            .header("Authorization", format!("ApiKey {}", read_key.key))
+            .set_json(&CreateShareLinkRequest { expires: None })
+            .to_request();
+        let res = test::call_service(&mut app, create_link_req).await;
+        assert!(res.status() == 403);
 
-        let mut image_upload_data = Vec::new();
+        let report_req = test::TestRequest::post()
+            .uri(&format!("/images/{}/report", image.id))
+            .header("Authorization", format!("ApiKey {}", read_key.key))
+            .set_json(&ReportImageRequest {
+                reason: ReportReason::Spam,
+                details: None,
+            })
+            .to_request();
+        let res = test::call_service(&mut app, report_req).await;
+        assert!(res.status() == 403);
 
-        image_upload_data.extend("--test_image\r\n".bytes());
-        image_upload_data.extend(
-            r#"Content-Disposition: form-data; name="image"; filename="test_image.png""#.bytes(),
-        );
-        image_upload_data.extend("\r\n\r\n".bytes());
-        image_upload_data.extend(TEST_IMAGE);
-        image_upload_data.extend("\r\n--test_image--\r\n".bytes());
+        let settings_req = test::TestRequest::put()
+            .uri("/users/me/settings")
+            .header("Authorization", format!("ApiKey {}", read_key.key))
+            .set_json(&UserSettings {
+                keep_image_location: true,
+            })
+            .to_request();
+        let res = test::call_service(&mut app, settings_req).await;
+        assert!(res.status() == 403);
 
-        let upload_image_req = test::TestRequest::post()
-            .uri(&format!("/images/{}", image_id))
+        let create_key_req = test::TestRequest::post()
+            .uri("/auth/api-keys")
             .header("Authorization", format!("Bearer {}", token))
-            .header("Content-Type", "multipart/form-data; boundary=test_image")
-            .header("Content-Length", image_upload_data.len())
-            .set_payload(image_upload_data)
+            .set_json(&CreateApiKeyRequest {
+                name: "uploads".into(),
+                scopes: vec![ApiKeyScope::Read, ApiKeyScope::Upload],
+            })
             .to_request();
-        let upload_image_res = test::call_service(&mut app, upload_image_req).await;
+        let upload_key: CreateApiKeyResponse =
+            test::read_response_json(&mut app, create_key_req).await;
 
-        assert!(
-            upload_image_res.status() == 204,
-            "got {}, {:?}",
-            upload_image_res.status().as_u16(),
-            test::read_body(upload_image_res).await
-        );
+        let create_link_req = test::TestRequest::post()
+            .uri(&format!("/images/{}/share-links", image.id))
+            .header("Authorization", format!("ApiKey {}", upload_key.key))
+            .set_json(&CreateShareLinkRequest { expires: None })
+            .to_request();
+        let link: ShareLink = test::read_response_json(&mut app, create_link_req).await;
 
-        // Image search
-        let search_images_req = test::TestRequest::get()
-            .uri("/images")
-            .header("Authorization", format!("Bearer {}", token))
+        let revoke_link_req = test::TestRequest::delete()
+            .uri(&format!("/share-links/{}", link.id))
+            .header("Authorization", format!("ApiKey {}", read_key.key))
             .to_request();
-        let search_images_res = test::call_service(&mut app, search_images_req).await;
-        assert!(
-            search_images_res.status() == 200,
-            "got {}, {:?}",
-            search_images_res.status().as_u16(),
-            test::read_body(search_images_res).await
-        );
-        let search_images_res_data: SearchImagesResponse =
-            test::read_body_json(search_images_res).await;
+        let res = test::call_service(&mut app, revoke_link_req).await;
+        assert!(res.status() == 403);
 
-        assert!(search_images_res_data.images.len() >= 1);
+        let revoke_link_req = test::TestRequest::delete()
+            .uri(&format!("/share-links/{}", link.id))
+            .header("Authorization", format!("ApiKey {}", upload_key.key))
+            .to_request();
+        let res = test::call_service(&mut app, revoke_link_req).await;
+        assert!(res.status() == 204);
 
-        // Finally image download
-        let download_image_req = test::TestRequest::get()
-            .uri(&format!("/images/{}", image_id))
-            .header("Authorization", format!("Bearer {}", token))
+        // Settings are only changed with sessions.
+        let settings_req = test::TestRequest::put()
+            .uri("/users/me/settings")
+            .header("Authorization", format!("ApiKey {}", upload_key.key))
+            .set_json(&UserSettings {
+                keep_image_location: true,
+            })
             .to_request();
-        let download_image_res = test::call_service(&mut app, download_image_req).await;
+        let res = test::call_service(&mut app, settings_req).await;
+        assert!(res.status() == 403);
 
-        assert!(
-            download_image_res.status() == 200,
-            "got {}",
-            download_image_res.status().as_u16(),
-        );
+        // Permissions of the user need the admin scope.
+        let login_req = test::TestRequest::post()
+            .uri("/auth/login")
+            .set_json(&LoginRequest {
+                email: "admin@admin.admin".into(),
+                password: "admin".into(),
+            })
+            .to_request();
+        let login_res: LoginResponse = test::read_response_json(&mut app, login_req).await;
 
-        // TODO: why did this break?
-        // assert!(test::read_body(download_image_res).await == TEST_IMAGE);
+        for (scopes, status) in vec![
+            (vec![ApiKeyScope::Read, ApiKeyScope::Upload], 403),
+            (vec![ApiKeyScope::Admin], 200),
+        ] {
+            let create_key_req = test::TestRequest::post()
+                .uri("/auth/api-keys")
+                .header("Authorization", format!("Bearer {}", login_res.token))
+                .set_json(&CreateApiKeyRequest {
+                    name: "admin".into(),
+                    scopes,
+                })
+                .to_request();
+            let admin_key: CreateApiKeyResponse =
+                test::read_response_json(&mut app, create_key_req).await;
+
+            let create_category_req = test::TestRequest::post()
+                .uri("/categories")
+                .header("Authorization", format!("ApiKey {}", admin_key.key))
+                .set_json(&CreateCategoryRequest {
+                    name: format!("api_key_{}", random_string(24)),
+                    slug: None,
+                    parent_id: None,
+                    description: None,
+                })
+                .to_request();
+            let res = test::call_service(&mut app, create_category_req).await;
+            assert!(res.status() == status);
+
+            // The admin scope includes reading.
+            let audit_req = test::TestRequest::get()
+                .uri("/admin/audit")
+                .header("Authorization", format!("ApiKey {}", admin_key.key))
+                .to_request();
+            let res = test::call_service(&mut app, audit_req).await;
+            assert!(res.status() == status);
+        }
     }
 }
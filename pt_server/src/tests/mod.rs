@@ -1 +1,2 @@
 mod integration;
+mod oidc;
@@ -0,0 +1,235 @@
+//! Tests the OpenID Connect flow against a mock identity provider.
+
+use crate::services::auth::oidc::{self, ProviderError};
+use actix_web::{client::Client, http::header, test, web, App, HttpRequest, HttpResponse};
+use jwt::{Algorithm, EncodingKey, Header};
+use serde::Deserialize;
+use serde_json::json;
+use std::sync::Mutex;
+use time::{prelude::*, OffsetDateTime};
+use url::Url;
+
+const TEST_KEY: &[u8] = include_bytes!("../../test_data/oidc_test_key.pem");
+const TEST_KEY_ID: &str = "test";
+/// Public components of the test key.
+const TEST_KEY_N: &str = "2Bx6nkyBkil3KyOQfIUtYpKpLV8MJ_RqiNC4BpAW8L_2jlUl_qJeAq00X22CG4lWMxnu-MQ3VZ6SBwOlq18wPoTKrUkzn0CKWUclWMWKxh99_zhbMAn8GIJ8DsAfaxgXbmZfESY5EE69LTfD8SbvBZGtOWvNlvDiWRWzTlzEdv2xc9MhLuRTrGPPX5_Cy2y_QQii1V6hI38OHzk7AePzKG3Dm1G1Cm80NRLA2j49nJOKBacqRuK0Ux7x72fGikP9vlvLdggKZMUmqGOipqtOqIGA9Yc_0u8orZ7URicBVp8U5fONmFisWJkep5tFXGy_uht0ZoAGs_-xG5biTM8ejw";
+const TEST_KEY_E: &str = "AQAB";
+
+const CLIENT_ID: &str = "pictureTeam";
+const AUTHORIZATION_CODE: &str = "test_code";
+
+#[derive(Default)]
+struct MockIdpState {
+    code_challenge: Option<String>,
+    nonce: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct AuthorizeQuery {
+    redirect_uri: String,
+    state: String,
+    nonce: String,
+    code_challenge: String,
+    code_challenge_method: String,
+}
+
+#[derive(Deserialize)]
+struct TokenForm {
+    grant_type: String,
+    code: String,
+    client_id: String,
+    code_verifier: String,
+}
+
+fn issuer(req: &HttpRequest) -> String {
+    let info = req.connection_info();
+    format!("{}://{}", info.scheme(), info.host())
+}
+
+async fn discovery(req: HttpRequest) -> HttpResponse {
+    let issuer = issuer(&req);
+
+    HttpResponse::Ok().json(json!({
+        "issuer": issuer,
+        "authorization_endpoint": format!("{}/authorize", issuer),
+        "token_endpoint": format!("{}/token", issuer),
+        "jwks_uri": format!("{}/jwks", issuer),
+    }))
+}
+
+async fn authorize(
+    query: web::Query<AuthorizeQuery>,
+    state: web::Data<Mutex<MockIdpState>>,
+) -> HttpResponse {
+    assert_eq!(query.code_challenge_method, "S256");
+
+    let mut state = state.lock().unwrap();
+    state.code_challenge = Some(query.code_challenge.clone());
+    state.nonce = Some(query.nonce.clone());
+
+    let mut redirect = Url::parse(&query.redirect_uri).unwrap();
+    redirect
+        .query_pairs_mut()
+        .append_pair("code", AUTHORIZATION_CODE)
+        .append_pair("state", &query.state);
+
+    HttpResponse::Found()
+        .header(header::LOCATION, redirect.as_str())
+        .finish()
+}
+
+async fn token(
+    req: HttpRequest,
+    form: web::Form<TokenForm>,
+    state: web::Data<Mutex<MockIdpState>>,
+) -> HttpResponse {
+    let state = state.lock().unwrap();
+
+    if form.grant_type != "authorization_code"
+        || form.code != AUTHORIZATION_CODE
+        || state.code_challenge.as_deref()
+            != Some(oidc::code_challenge(&form.code_verifier).as_str())
+    {
+        return HttpResponse::BadRequest().json(json!({ "error": "invalid_grant" }));
+    }
+
+    let claims = json!({
+        "iss": issuer(&req),
+        "sub": "mock-user",
+        "aud": form.client_id,
+        "exp": (OffsetDateTime::now_utc() + 5.minutes()).timestamp(),
+        "nonce": state.nonce,
+        "email": "Mock.User@example.com",
+        "email_verified": true,
+    });
+
+    let mut header = Header::new(Algorithm::RS256);
+    header.kid = Some(TEST_KEY_ID.into());
+
+    let id_token = jwt::encode(
+        &header,
+        &claims,
+        &EncodingKey::from_rsa_pem(TEST_KEY).unwrap(),
+    )
+    .unwrap();
+
+    HttpResponse::Ok().json(json!({
+        "access_token": "unused",
+        "token_type": "Bearer",
+        "id_token": id_token,
+    }))
+}
+
+async fn jwks() -> HttpResponse {
+    HttpResponse::Ok().json(json!({
+        "keys": [{
+            "kty": "RSA",
+            "kid": TEST_KEY_ID,
+            "alg": "RS256",
+            "use": "sig",
+            "n": TEST_KEY_N,
+            "e": TEST_KEY_E,
+        }]
+    }))
+}
+
+/// Goes through discovery, authorization, code exchange and ID token validation.
+#[actix_rt::test]
+async fn oidc_mock_idp() {
+    let idp_state = web::Data::new(Mutex::new(MockIdpState::default()));
+
+    let srv = test::start(move || {
+        App::new()
+            .app_data(idp_state.clone())
+            .route(
+                "/.well-known/openid-configuration",
+                web::get().to(discovery),
+            )
+            .route("/authorize", web::get().to(authorize))
+            .route("/token", web::post().to(token))
+            .route("/jwks", web::get().to(jwks))
+    });
+
+    let issuer = Url::parse(&srv.url("/")).unwrap();
+    let redirect_url = Url::parse("http://localhost/auth/oidc/callback").unwrap();
+    let code_verifier = "test_code_verifier_that_is_long_enough_for_pkce_0123456789";
+
+    let metadata = oidc::discover(&issuer).await.unwrap();
+
+    let authorization_url = oidc::authorization_url(
+        &metadata,
+        CLIENT_ID,
+        &redirect_url,
+        "test_state",
+        "test_nonce",
+        code_verifier,
+    );
+
+    // This is what the browser of the user would do.
+    let res = Client::default()
+        .get(authorization_url.as_str())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status().as_u16(), 302);
+
+    let callback_url = Url::parse(
+        res.headers()
+            .get(header::LOCATION)
+            .unwrap()
+            .to_str()
+            .unwrap(),
+    )
+    .unwrap();
+    let callback_params: Vec<(String, String)> = callback_url.query_pairs().into_owned().collect();
+    assert!(callback_params.contains(&("state".into(), "test_state".into())));
+    let code = callback_params
+        .iter()
+        .find(|(k, _)| k == "code")
+        .map(|(_, v)| v.clone())
+        .unwrap();
+
+    let wrong_verifier_res = oidc::exchange_code(
+        &metadata,
+        CLIENT_ID,
+        None,
+        &redirect_url,
+        &code,
+        "wrong_code_verifier",
+    )
+    .await;
+    assert!(matches!(
+        wrong_verifier_res,
+        Err(ProviderError::Status(400))
+    ));
+
+    let id_token = oidc::exchange_code(
+        &metadata,
+        CLIENT_ID,
+        None,
+        &redirect_url,
+        &code,
+        code_verifier,
+    )
+    .await
+    .unwrap();
+
+    let jwks = oidc::fetch_jwks(&metadata).await.unwrap();
+
+    let claims =
+        oidc::validate_id_token(&id_token, &jwks, &metadata, CLIENT_ID, "test_nonce").unwrap();
+    assert_eq!(claims.sub, "mock-user");
+    assert_eq!(claims.email.as_deref(), Some("Mock.User@example.com"));
+    assert_eq!(claims.email_verified, Some(true));
+
+    let wrong_nonce_res =
+        oidc::validate_id_token(&id_token, &jwks, &metadata, CLIENT_ID, "other_nonce");
+    assert!(matches!(wrong_nonce_res, Err(ProviderError::NonceMismatch)));
+
+    let wrong_audience_res =
+        oidc::validate_id_token(&id_token, &jwks, &metadata, "otherClient", "test_nonce");
+    assert!(matches!(
+        wrong_audience_res,
+        Err(ProviderError::InvalidIdToken(_))
+    ));
+}
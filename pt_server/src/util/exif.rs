@@ -0,0 +1,555 @@
+//! Reading and stripping the Exif metadata of uploaded photos.
+//!
+//! JPEG, PNG and WebP files are rewritten, TIFF and HEIF files are rejected
+//! as their metadata cannot be removed. Files in other formats have no Exif data
+//! and are stored as they were uploaded.
+
+use exif::{experimental::Writer, Context, Field, In, Reader, Tag, Value};
+use std::{
+    convert::{TryFrom, TryInto},
+    io::Cursor,
+};
+use time::{Date, OffsetDateTime, PrimitiveDateTime, Time, UtcOffset};
+
+const JPEG_SOI: &[u8] = &[0xff, 0xd8];
+const JPEG_APP1: u8 = 0xe1;
+const JPEG_SOS: u8 = 0xda;
+const JPEG_EOI: u8 = 0xd9;
+const JPEG_EXIF_HEADER: &[u8] = b"Exif\0\0";
+const JPEG_XMP_HEADER: &[u8] = b"http://ns.adobe.com/xap/1.0/\0";
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+const PNG_XMP_KEYWORD: &[u8] = b"XML:com.adobe.xmp\0";
+
+const RIFF_SIGNATURE: &[u8] = b"RIFF";
+const WEBP_SIGNATURE: &[u8] = b"WEBP";
+const WEBP_VP8X_EXIF: u8 = 0x08;
+const WEBP_VP8X_XMP: u8 = 0x04;
+
+const TIFF_SIGNATURES: &[&[u8]] = &[b"II*\0", b"MM\0*"];
+/// The box type at the start of ISO base media files, such as HEIF.
+const ISOBMFF_FTYP: &[u8] = b"ftyp";
+
+/// Tags that can identify the owner or the device,
+/// these are always removed from stored files.
+const SENSITIVE_TAGS: &[Tag] = &[
+    Tag::MakerNote,
+    Tag::UserComment,
+    Tag::ImageUniqueID,
+    Tag::CameraOwnerName,
+    Tag::BodySerialNumber,
+    Tag::LensSerialNumber,
+];
+
+/// The parts of the Exif data we keep track of.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ExifData {
+    pub camera_make: Option<String>,
+    pub camera_model: Option<String>,
+    pub lens_model: Option<String>,
+    /// Exposure time in seconds, e.g. `1/250`.
+    pub exposure_time: Option<String>,
+    pub f_number: Option<f64>,
+    pub iso: Option<i32>,
+    /// Focal length in millimetres.
+    pub focal_length: Option<f64>,
+    /// UTC is assumed if the camera did not record its time zone.
+    pub captured: Option<OffsetDateTime>,
+    pub orientation: Option<i16>,
+    /// GPS position in degrees as `(latitude, longitude)`.
+    pub location: Option<(f64, f64)>,
+}
+
+/// Reads the Exif data of a JPEG, PNG, TIFF, HEIF or WebP image,
+/// `None` is returned if there is nothing useful in it.
+pub fn parse(data: &[u8]) -> Option<ExifData> {
+    let exif = Reader::new()
+        .read_from_container(&mut Cursor::new(data))
+        .ok()?;
+
+    let field = |tag| exif.get_field(tag, In::PRIMARY);
+
+    let captured = match field(Tag::DateTimeOriginal) {
+        Some(f) => date_time(f, field(Tag::OffsetTimeOriginal)),
+        None => field(Tag::DateTime).and_then(|f| date_time(f, field(Tag::OffsetTime))),
+    };
+
+    let latitude = field(Tag::GPSLatitude)
+        .and_then(|f| coordinate(f, field(Tag::GPSLatitudeRef), b"S"))
+        .filter(|v| (-90.0..=90.0).contains(v));
+    let longitude = field(Tag::GPSLongitude)
+        .and_then(|f| coordinate(f, field(Tag::GPSLongitudeRef), b"W"))
+        .filter(|v| (-180.0..=180.0).contains(v));
+
+    let exif_data = ExifData {
+        camera_make: field(Tag::Make).and_then(ascii),
+        camera_model: field(Tag::Model).and_then(ascii),
+        lens_model: field(Tag::LensModel).and_then(ascii),
+        exposure_time: field(Tag::ExposureTime).and_then(exposure_time),
+        f_number: field(Tag::FNumber).and_then(rational),
+        iso: field(Tag::PhotographicSensitivity)
+            .and_then(|f| f.value.get_uint(0))
+            .map(|v| v as i32),
+        focal_length: field(Tag::FocalLength).and_then(rational),
+        captured,
+        orientation: field(Tag::Orientation)
+            .and_then(|f| f.value.get_uint(0))
+            .map(|v| v as i16),
+        location: latitude.zip(longitude),
+    };
+
+    if exif_data == ExifData::default() {
+        None
+    } else {
+        Some(exif_data)
+    }
+}
+
+/// Removes the sensitive tags from the Exif data of a JPEG, PNG or WebP image,
+/// GPS tags and XMP packets are only kept if `keep_location` is set.
+///
+/// Embedded thumbnails are always dropped, as they might
+/// show the uncropped original.
+///
+/// `None` is returned if the file cannot be parsed or its metadata cannot be removed,
+/// such files must not be stored. Other formats are returned unchanged.
+pub fn strip(data: &[u8], keep_location: bool) -> Option<Vec<u8>> {
+    if data.starts_with(JPEG_SOI) {
+        strip_jpeg(data, keep_location)
+    } else if data.starts_with(PNG_SIGNATURE) {
+        strip_png(data, keep_location)
+    } else if data.starts_with(RIFF_SIGNATURE) && data.get(8..12) == Some(WEBP_SIGNATURE) {
+        strip_webp(data, keep_location)
+    } else if TIFF_SIGNATURES.iter().any(|s| data.starts_with(s))
+        || data.get(4..8) == Some(ISOBMFF_FTYP)
+        || Reader::new()
+            .read_from_container(&mut Cursor::new(data))
+            .is_ok()
+    {
+        None
+    } else {
+        Some(data.to_vec())
+    }
+}
+
+fn strip_jpeg(data: &[u8], keep_location: bool) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(data.len());
+    out.extend_from_slice(JPEG_SOI);
+
+    let mut pos = JPEG_SOI.len();
+
+    loop {
+        if *data.get(pos)? != 0xff {
+            return None;
+        }
+
+        let marker = *data.get(pos + 1)?;
+
+        match marker {
+            // Fill byte before the marker.
+            0xff => {
+                pos += 1;
+                continue;
+            }
+            // Everything after the start of scan is image data.
+            JPEG_SOS | JPEG_EOI => {
+                out.extend_from_slice(&data[pos..]);
+                return Some(out);
+            }
+            // Markers without a segment.
+            0x01 | 0xd0..=0xd7 => {
+                out.extend_from_slice(&data[pos..pos + 2]);
+                pos += 2;
+                continue;
+            }
+            _ => {}
+        }
+
+        let len = u16::from_be_bytes([*data.get(pos + 2)?, *data.get(pos + 3)?]) as usize;
+
+        if len < 2 {
+            return None;
+        }
+
+        let segment = data.get(pos..pos + 2 + len)?;
+        let payload = &segment[4..];
+
+        if marker == JPEG_APP1 && payload.starts_with(JPEG_EXIF_HEADER) {
+            if let Some(tiff) = strip_tiff(&payload[JPEG_EXIF_HEADER.len()..], keep_location) {
+                let len = u16::try_from(2 + JPEG_EXIF_HEADER.len() + tiff.len()).ok()?;
+
+                out.extend_from_slice(&[0xff, JPEG_APP1]);
+                out.extend_from_slice(&len.to_be_bytes());
+                out.extend_from_slice(JPEG_EXIF_HEADER);
+                out.extend_from_slice(&tiff);
+            }
+        } else if !(marker == JPEG_APP1 && payload.starts_with(JPEG_XMP_HEADER) && !keep_location) {
+            out.extend_from_slice(segment);
+        }
+
+        pos += 2 + len;
+    }
+}
+
+fn strip_png(data: &[u8], keep_location: bool) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(data.len());
+    out.extend_from_slice(PNG_SIGNATURE);
+
+    let mut pos = PNG_SIGNATURE.len();
+
+    while pos < data.len() {
+        let len = u32::from_be_bytes(data.get(pos..pos + 4)?.try_into().ok()?) as usize;
+        let chunk = data.get(pos..pos + 12 + len)?;
+        let (kind, body) = (&chunk[4..8], &chunk[8..8 + len]);
+
+        if kind == b"eXIf" {
+            if let Some(tiff) = strip_tiff(body, keep_location) {
+                write_png_chunk(&mut out, b"eXIf", &tiff);
+            }
+        } else if !(kind == b"iTXt" && body.starts_with(PNG_XMP_KEYWORD) && !keep_location) {
+            out.extend_from_slice(chunk);
+        }
+
+        pos += 12 + len;
+    }
+
+    Some(out)
+}
+
+fn strip_webp(data: &[u8], keep_location: bool) -> Option<Vec<u8>> {
+    let mut chunks: Vec<(&[u8], Vec<u8>)> = Vec::new();
+
+    let mut pos = RIFF_SIGNATURE.len() + 4 + WEBP_SIGNATURE.len();
+
+    while pos < data.len() {
+        let kind = data.get(pos..pos + 4)?;
+        let len = u32::from_le_bytes(data.get(pos + 4..pos + 8)?.try_into().ok()?) as usize;
+        let body = data.get(pos + 8..pos + 8 + len)?;
+
+        if kind == b"EXIF" {
+            // Some writers keep the header of the JPEG segment.
+            let tiff = if body.starts_with(JPEG_EXIF_HEADER) {
+                &body[JPEG_EXIF_HEADER.len()..]
+            } else {
+                body
+            };
+
+            if let Some(tiff) = strip_tiff(tiff, keep_location) {
+                chunks.push((kind, tiff));
+            }
+        } else if !(kind == b"XMP " && !keep_location) {
+            chunks.push((kind, body.to_vec()));
+        }
+
+        // Chunks are padded to an even size.
+        pos += 8 + len + len % 2;
+    }
+
+    let has_exif = chunks.iter().any(|(kind, _)| *kind == b"EXIF");
+    let has_xmp = chunks.iter().any(|(kind, _)| *kind == b"XMP ");
+
+    // The extended header tells decoders which chunks to look for.
+    if let Some((_, header)) = chunks.iter_mut().find(|(kind, _)| *kind == b"VP8X") {
+        let flags = header.first_mut()?;
+        *flags &= !(WEBP_VP8X_EXIF | WEBP_VP8X_XMP);
+
+        if has_exif {
+            *flags |= WEBP_VP8X_EXIF;
+        }
+        if has_xmp {
+            *flags |= WEBP_VP8X_XMP;
+        }
+    }
+
+    let mut out = Vec::with_capacity(data.len());
+    out.extend_from_slice(RIFF_SIGNATURE);
+    out.extend_from_slice(&[0; 4]);
+    out.extend_from_slice(WEBP_SIGNATURE);
+
+    for (kind, body) in chunks {
+        out.extend_from_slice(kind);
+        out.extend_from_slice(&u32::try_from(body.len()).ok()?.to_le_bytes());
+        out.extend_from_slice(&body);
+
+        if body.len() % 2 == 1 {
+            out.push(0);
+        }
+    }
+
+    let riff_len = u32::try_from(out.len() - 8).ok()?;
+    out[4..8].copy_from_slice(&riff_len.to_le_bytes());
+
+    Some(out)
+}
+
+/// Rewrites the TIFF structure of the Exif data with only the allowed fields,
+/// `None` is returned if nothing is left of it.
+fn strip_tiff(tiff: &[u8], keep_location: bool) -> Option<Vec<u8>> {
+    let exif = Reader::new().read_raw(tiff.to_vec()).ok()?;
+
+    let mut writer = Writer::new();
+
+    for field in exif.fields() {
+        let allowed = field.ifd_num == In::PRIMARY
+            && !SENSITIVE_TAGS.contains(&field.tag)
+            && (keep_location || field.tag.context() != Context::Gps)
+            && !matches!(field.value, Value::Unknown(..));
+
+        if allowed {
+            writer.push_field(field);
+        }
+    }
+
+    // Fails if there are no fields left.
+    let mut out = Cursor::new(Vec::new());
+    writer.write(&mut out, exif.little_endian()).ok()?;
+
+    Some(out.into_inner())
+}
+
+fn write_png_chunk(out: &mut Vec<u8>, kind: &[u8], body: &[u8]) {
+    out.extend_from_slice(&(body.len() as u32).to_be_bytes());
+
+    let start = out.len();
+    out.extend_from_slice(kind);
+    out.extend_from_slice(body);
+
+    let crc = crc32(&out[start..]);
+    out.extend_from_slice(&crc.to_be_bytes());
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+
+    for &b in data {
+        crc ^= b as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+
+    !crc
+}
+
+fn ascii(field: &Field) -> Option<String> {
+    match &field.value {
+        Value::Ascii(v) => v
+            .first()
+            .map(|s| {
+                String::from_utf8_lossy(s)
+                    .trim_matches(|c: char| c == '\0' || c.is_whitespace())
+                    .to_string()
+            })
+            .filter(|s| !s.is_empty()),
+        _ => None,
+    }
+}
+
+fn rational(field: &Field) -> Option<f64> {
+    match &field.value {
+        Value::Rational(v) => v.first().filter(|r| r.denom != 0).map(|r| r.to_f64()),
+        _ => None,
+    }
+}
+
+fn exposure_time(field: &Field) -> Option<String> {
+    match &field.value {
+        Value::Rational(v) => v.first().filter(|r| r.denom != 0).map(|r| {
+            if r.num != 0 && r.num < r.denom && r.denom % r.num == 0 {
+                format!("1/{}", r.denom / r.num)
+            } else if r.num % r.denom == 0 {
+                format!("{}", r.num / r.denom)
+            } else {
+                format!("{}/{}", r.num, r.denom)
+            }
+        }),
+        _ => None,
+    }
+}
+
+/// Degrees, minutes and seconds, negated if the reference
+/// is the southern or western hemisphere.
+fn coordinate(field: &Field, reference: Option<&Field>, negative: &[u8]) -> Option<f64> {
+    let degrees = match &field.value {
+        Value::Rational(v) if v.len() == 3 && v.iter().all(|r| r.denom != 0) => {
+            v[0].to_f64() + v[1].to_f64() / 60.0 + v[2].to_f64() / 3600.0
+        }
+        _ => return None,
+    };
+
+    match reference.map(|f| &f.value) {
+        Some(Value::Ascii(v)) if v.first().map(|r| r.as_slice()) == Some(negative) => {
+            Some(-degrees)
+        }
+        _ => Some(degrees),
+    }
+}
+
+fn date_time(field: &Field, offset: Option<&Field>) -> Option<OffsetDateTime> {
+    let mut dt = match &field.value {
+        Value::Ascii(v) => exif::DateTime::from_ascii(v.first()?).ok()?,
+        _ => return None,
+    };
+
+    if let Some(Value::Ascii(v)) = offset.map(|f| &f.value) {
+        if let Some(o) = v.first() {
+            dt.parse_offset(o).ok();
+        }
+    }
+
+    let date = Date::try_from_ymd(dt.year as i32, dt.month, dt.day).ok()?;
+    let time = Time::try_from_hms(dt.hour, dt.minute, dt.second).ok()?;
+
+    Some(
+        PrimitiveDateTime::new(date, time)
+            .assume_offset(UtcOffset::minutes(dt.offset.unwrap_or(0))),
+    )
+}
+
+#[cfg(test)]
+fn test_jpeg(fields: &[Field]) -> Vec<u8> {
+    let mut writer = Writer::new();
+    for f in fields {
+        writer.push_field(f);
+    }
+    let mut tiff = Cursor::new(Vec::new());
+    writer.write(&mut tiff, false).unwrap();
+    let tiff = tiff.into_inner();
+
+    let mut jpeg = JPEG_SOI.to_vec();
+    jpeg.extend_from_slice(&[0xff, JPEG_APP1]);
+    jpeg.extend_from_slice(&((2 + JPEG_EXIF_HEADER.len() + tiff.len()) as u16).to_be_bytes());
+    jpeg.extend_from_slice(JPEG_EXIF_HEADER);
+    jpeg.extend_from_slice(&tiff);
+    jpeg.extend_from_slice(&[0xff, JPEG_SOS, 0x00, 0x02, 0x12, 0x34, 0xff, JPEG_EOI]);
+    jpeg
+}
+
+#[test]
+fn test_parse_and_strip() {
+    let ascii = |tag, s: &str| Field {
+        tag,
+        ifd_num: In::PRIMARY,
+        value: Value::Ascii(vec![s.as_bytes().to_vec()]),
+    };
+
+    let jpeg = test_jpeg(&[
+        ascii(Tag::Make, "Canon"),
+        ascii(Tag::DateTimeOriginal, "2020:11:14 10:20:30"),
+        ascii(Tag::OffsetTimeOriginal, "+01:00"),
+        ascii(Tag::BodySerialNumber, "123456"),
+        ascii(Tag::GPSLatitudeRef, "S"),
+        Field {
+            tag: Tag::GPSLatitude,
+            ifd_num: In::PRIMARY,
+            value: Value::Rational(vec![(47, 1).into(), (30, 1).into(), (0, 1).into()]),
+        },
+        ascii(Tag::GPSLongitudeRef, "E"),
+        Field {
+            tag: Tag::GPSLongitude,
+            ifd_num: In::PRIMARY,
+            value: Value::Rational(vec![(19, 1).into(), (3, 1).into(), (36, 1).into()]),
+        },
+    ]);
+
+    let data = parse(&jpeg).unwrap();
+    assert_eq!(data.camera_make.as_deref(), Some("Canon"));
+    assert_eq!(data.captured.unwrap().timestamp(), 1_605_345_630);
+    let (lat, lon) = data.location.unwrap();
+    assert!((lat + 47.5).abs() < 1e-9 && (lon - 19.06).abs() < 1e-9);
+
+    let read = |data: &[u8]| {
+        Reader::new()
+            .read_from_container(&mut Cursor::new(data))
+            .unwrap()
+    };
+
+    let stripped = read(&strip(&jpeg, false).unwrap());
+    assert!(stripped.get_field(Tag::Make, In::PRIMARY).is_some());
+    assert!(stripped
+        .get_field(Tag::BodySerialNumber, In::PRIMARY)
+        .is_none());
+    assert!(stripped
+        .get_field(Tag::GPSLatitudeRef, In::PRIMARY)
+        .is_none());
+
+    let kept = read(&strip(&jpeg, true).unwrap());
+    assert!(kept.get_field(Tag::GPSLatitudeRef, In::PRIMARY).is_some());
+
+    assert_eq!(strip(b"not an image", false).unwrap(), b"not an image");
+
+    // Files that cannot be cleaned are rejected.
+    assert!(strip(&jpeg[..jpeg.len() / 2], false).is_none());
+    assert!(strip(b"II*\0\x08\0\0\0", false).is_none());
+    assert!(strip(b"\0\0\0\x18ftypheic\0\0\0\0", false).is_none());
+}
+
+#[test]
+fn test_strip_webp() {
+    let ascii = |tag, s: &str| Field {
+        tag,
+        ifd_num: In::PRIMARY,
+        value: Value::Ascii(vec![s.as_bytes().to_vec()]),
+    };
+
+    let mut writer = Writer::new();
+    let (make, serial) = (
+        ascii(Tag::Make, "Canon"),
+        ascii(Tag::BodySerialNumber, "123456"),
+    );
+    writer.push_field(&make);
+    writer.push_field(&serial);
+    let mut tiff = Cursor::new(Vec::new());
+    writer.write(&mut tiff, true).unwrap();
+    let tiff = tiff.into_inner();
+
+    let chunk = |kind: &[u8], body: &[u8]| {
+        let mut c = kind.to_vec();
+        c.extend_from_slice(&(body.len() as u32).to_le_bytes());
+        c.extend_from_slice(body);
+        if body.len() % 2 == 1 {
+            c.push(0);
+        }
+        c
+    };
+
+    let mut body = WEBP_SIGNATURE.to_vec();
+    body.extend(chunk(
+        b"VP8X",
+        &[WEBP_VP8X_EXIF | WEBP_VP8X_XMP, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+    ));
+    body.extend(chunk(b"VP8 ", &[1, 2, 3]));
+    body.extend(chunk(b"EXIF", &tiff));
+    body.extend(chunk(b"XMP ", b"<x:xmpmeta/>"));
+
+    let mut webp = RIFF_SIGNATURE.to_vec();
+    webp.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    webp.extend(body);
+
+    let stripped = strip(&webp, false).unwrap();
+    assert_eq!(
+        u32::from_le_bytes(stripped[4..8].try_into().unwrap()) as usize,
+        stripped.len() - 8
+    );
+    // Only the Exif flag is left.
+    assert_eq!(stripped[20], WEBP_VP8X_EXIF);
+    assert!(!stripped.windows(4).any(|w| w == b"XMP "));
+
+    let exif = Reader::new()
+        .read_from_container(&mut Cursor::new(&stripped))
+        .unwrap();
+    assert!(exif.get_field(Tag::Make, In::PRIMARY).is_some());
+    assert!(exif.get_field(Tag::BodySerialNumber, In::PRIMARY).is_none());
+
+    // Truncated files are rejected.
+    assert!(strip(&webp[..webp.len() - 4], false).is_none());
+}
+
+#[test]
+fn test_crc32() {
+    assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+}
@@ -3,6 +3,10 @@ use regex::Regex;
 use serde::{de::Error, Deserializer, Deserialize, Serialize, Serializer};
 use time::OffsetDateTime;
 
+pub mod exif;
+pub mod phash;
+pub mod totp;
+
 pub const EMAIL_REGEX: &str = r#"^[a-zA-Z0-9_.+-]+@[a-zA-Z0-9-]+\.[a-zA-Z0-9-.]+$"#;
 
 pub fn validate_email(email: &str) -> bool {
@@ -15,6 +19,84 @@ fn test_validate_email() {
     assert!(validate_email("asd@gmail.com"));
 }
 
+pub const TAG_MAX_LENGTH: usize = 32;
+
+/// Lowercases the tag and joins its words with dashes,
+/// `None` is returned if nothing is left of it.
+pub fn normalize_tag(tag: &str) -> Option<String> {
+    let mut normalized = String::with_capacity(tag.len());
+
+    for c in tag.trim().trim_start_matches('#').chars() {
+        if c.is_alphanumeric() {
+            normalized.extend(c.to_lowercase());
+        } else if (c.is_whitespace() || c == '-' || c == '_')
+            && !normalized.is_empty()
+            && !normalized.ends_with('-')
+        {
+            normalized.push('-');
+        }
+    }
+
+    let normalized: String = normalized.chars().take(TAG_MAX_LENGTH).collect();
+    let normalized = normalized.trim_end_matches('-');
+
+    if normalized.is_empty() {
+        None
+    } else {
+        Some(normalized.into())
+    }
+}
+
+#[test]
+fn test_normalize_tag() {
+    assert_eq!(normalize_tag("#Street  Art").as_deref(), Some("street-art"));
+    assert_eq!(normalize_tag(" Balaton_nyár- ").as_deref(), Some("balaton-nyár"));
+    assert_eq!(normalize_tag("#!?"), None);
+}
+
+pub const SLUG_REGEX: &str = r#"^[a-z0-9]+(-[a-z0-9]+)*$"#;
+
+/// URL-safe form of the name, only ASCII letters and digits joined by dashes.
+pub fn slugify(name: &str) -> String {
+    let mut slug = String::with_capacity(name.len());
+
+    for c in name.chars() {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c.to_ascii_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+
+    slug.trim_end_matches('-').into()
+}
+
+#[test]
+fn test_slugify() {
+    assert_eq!(slugify("Nature"), "nature");
+    assert_eq!(slugify("  Birds & Bees! "), "birds-bees");
+    assert_eq!(slugify("Éjszaka"), "jszaka");
+    assert_eq!(slugify("!?"), "");
+    assert!(Regex::new(SLUG_REGEX).unwrap().is_match(&slugify("Schönherz Kollégium")));
+}
+
+/// Quotes the field if necessary, following RFC 4180.
+pub fn csv_field(value: &str) -> String {
+    if value.contains(&[',', '"', '\r', '\n'][..]) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.into()
+    }
+}
+
+#[test]
+fn test_csv_field() {
+    assert_eq!(csv_field("login"), "login");
+    assert_eq!(csv_field("a,b"), "\"a,b\"");
+    assert_eq!(csv_field(r#"{"name":"Birds"}"#), r#""{""name"":""Birds""}""#);
+    assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+}
+
 pub fn random_string(char_count: usize) -> String {
     thread_rng()
         .sample_iter(&Alphanumeric)
@@ -37,3 +119,22 @@ where
     Ok(OffsetDateTime::parse(s, time::Format::Rfc3339)
         .map_err(|e| D::Error::custom(&format!("invalid date: {}", e)))?)
 }
+
+pub fn serialize_rfc3339_opt<S>(date: &Option<OffsetDateTime>, ser: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    date.map(|d| d.format(time::Format::Rfc3339)).serialize(ser)
+}
+
+pub fn deserialize_rfc3339_opt<'de, D>(de: D) -> Result<Option<OffsetDateTime>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s: Option<String> = Option::deserialize(de)?;
+    s.map(|s| {
+        OffsetDateTime::parse(s, time::Format::Rfc3339)
+            .map_err(|e| D::Error::custom(&format!("invalid date: {}", e)))
+    })
+    .transpose()
+}
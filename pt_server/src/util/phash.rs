@@ -0,0 +1,62 @@
+//! Perceptual hashing for finding near-duplicate images.
+
+use image::imageops::{self, FilterType};
+
+/// Difference hash of an image, `None` if it cannot be decoded.
+///
+/// The image is shrunk to 9x8 grayscale pixels, and every bit tells whether
+/// a pixel is brighter than its right neighbour. The hash survives resizing,
+/// recompression and small colour changes.
+pub fn dhash(data: &[u8]) -> Option<i64> {
+    let img = image::load_from_memory(data).ok()?;
+    let small = imageops::resize(&imageops::grayscale(&img), 9, 8, FilterType::Triangle);
+
+    let mut hash = 0u64;
+
+    for y in 0..8 {
+        for x in 0..8 {
+            hash <<= 1;
+            if small.get_pixel(x, y)[0] > small.get_pixel(x + 1, y)[0] {
+                hash |= 1;
+            }
+        }
+    }
+
+    // Stored as BIGINT, only the bits matter.
+    Some(hash as i64)
+}
+
+/// The number of differing bits of two hashes.
+pub fn hamming_distance(a: i64, b: i64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+#[test]
+fn test_dhash() {
+    use image::{DynamicImage, ImageBuffer, ImageOutputFormat, Rgb};
+
+    let png = |size: u32| {
+        // Bright and dark bands, so that the hash has both set and unset bits.
+        let img = ImageBuffer::from_fn(size, size, |x, y| {
+            let v = if (x * 4 / size + y * 2 / size) % 2 == 0 {
+                220
+            } else {
+                30
+            };
+            Rgb([v, v, v / 2])
+        });
+        let mut data = Vec::new();
+        DynamicImage::ImageRgb8(img)
+            .write_to(&mut data, ImageOutputFormat::Png)
+            .unwrap();
+        data
+    };
+
+    let large = dhash(&png(128)).unwrap();
+    let small = dhash(&png(64)).unwrap();
+
+    assert_ne!(large, 0);
+    assert_ne!(large, -1);
+    assert!(hamming_distance(large, small) <= 4);
+    assert_eq!(dhash(b"not an image"), None);
+}
@@ -0,0 +1,123 @@
+//! Time-based one-time passwords as described in RFC 6238,
+//! using the defaults understood by authenticator apps (SHA-1, 6 digits, 30s steps).
+
+use hmac::{Hmac, Mac, NewMac};
+use rand::{thread_rng, RngCore};
+use sha1::Sha1;
+use url::Url;
+
+pub const TOTP_DIGITS: u32 = 6;
+pub const TOTP_STEP_SECONDS: u64 = 30;
+
+/// The amount of steps accepted before and after the current one
+/// to account for clock drift.
+pub const TOTP_ALLOWED_SKEW: u64 = 1;
+
+const SECRET_BYTES: usize = 20;
+
+/// Generates a new random base32 encoded secret.
+pub fn generate_secret() -> String {
+    let mut secret = [0u8; SECRET_BYTES];
+    thread_rng().fill_bytes(&mut secret);
+    base32::encode(base32::Alphabet::RFC4648 { padding: false }, &secret)
+}
+
+/// Builds an `otpauth://` URI that can be imported by authenticator apps.
+pub fn otpauth_uri(secret: &str, issuer: &str, account: &str) -> String {
+    let mut url = Url::parse("otpauth://totp/").unwrap();
+
+    url.set_path(&format!("{}:{}", issuer, account));
+    url.query_pairs_mut()
+        .append_pair("secret", secret)
+        .append_pair("issuer", issuer)
+        .append_pair("algorithm", "SHA1")
+        .append_pair("digits", &TOTP_DIGITS.to_string())
+        .append_pair("period", &TOTP_STEP_SECONDS.to_string());
+
+    url.to_string()
+}
+
+/// Calculates the code for the given base32 secret at the given step counter.
+///
+/// Returns `None` if the secret is not valid base32.
+pub fn code_at_step(secret: &str, step: u64) -> Option<u32> {
+    let key = base32::decode(base32::Alphabet::RFC4648 { padding: false }, secret)?;
+    Some(hotp(&key, step))
+}
+
+/// Checks the code against the given base32 secret at the given Unix timestamp.
+pub fn verify(secret: &str, code: &str, timestamp: u64) -> bool {
+    verify_step(secret, code, timestamp, None).is_some()
+}
+
+/// Like `verify`, but only accepts steps after the last used one,
+/// so that a code cannot be used twice.
+///
+/// Returns the step of the code, which becomes the last used step.
+pub fn verify_step(
+    secret: &str,
+    code: &str,
+    timestamp: u64,
+    last_step: Option<u64>,
+) -> Option<u64> {
+    let code = code.trim();
+
+    if !is_code(code) {
+        return None;
+    }
+
+    let code: u32 = code.parse().ok()?;
+    let step = timestamp / TOTP_STEP_SECONDS;
+
+    (step.saturating_sub(TOTP_ALLOWED_SKEW)..=step + TOTP_ALLOWED_SKEW)
+        .filter(|s| last_step.map_or(true, |last| *s > last))
+        .find(|s| code_at_step(secret, *s) == Some(code))
+}
+
+/// Whether the input looks like a TOTP code.
+pub fn is_code(code: &str) -> bool {
+    code.len() == TOTP_DIGITS as usize && code.bytes().all(|b| b.is_ascii_digit())
+}
+
+fn hotp(key: &[u8], counter: u64) -> u32 {
+    hotp_digits(key, counter, TOTP_DIGITS)
+}
+
+fn hotp_digits(key: &[u8], counter: u64, digits: u32) -> u32 {
+    let mut mac = Hmac::<Sha1>::new_varkey(key).expect("HMAC accepts keys of any size");
+    mac.update(&counter.to_be_bytes());
+    let hash = mac.finalize().into_bytes();
+
+    let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes([
+        hash[offset] & 0x7f,
+        hash[offset + 1],
+        hash[offset + 2],
+        hash[offset + 3],
+    ]);
+
+    binary % 10u32.pow(digits)
+}
+
+#[test]
+fn test_totp_rfc6238() {
+    let key = b"12345678901234567890";
+
+    assert_eq!(hotp_digits(key, 59 / TOTP_STEP_SECONDS, 8), 94287082);
+    assert_eq!(hotp_digits(key, 1111111109 / TOTP_STEP_SECONDS, 8), 7081804);
+    assert_eq!(
+        hotp_digits(key, 1234567890 / TOTP_STEP_SECONDS, 8),
+        89005924
+    );
+
+    let secret = base32::encode(base32::Alphabet::RFC4648 { padding: false }, key);
+    assert!(verify(&secret, "287082", 59));
+    assert!(verify(&secret, "287082", 59 + TOTP_STEP_SECONDS));
+    assert!(!verify(&secret, "287082", 59 + 3 * TOTP_STEP_SECONDS));
+    assert!(!verify(&secret, "abcdef", 59));
+    assert!(!verify(&secret, "+28708", 59));
+
+    assert_eq!(verify_step(&secret, "287082", 59, None), Some(1));
+    assert_eq!(verify_step(&secret, "287082", 59, Some(0)), Some(1));
+    assert_eq!(verify_step(&secret, "287082", 59, Some(1)), None);
+}